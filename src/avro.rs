@@ -0,0 +1,469 @@
+use crate::{
+    Data, DataCollection, DataSignature, DataType, DataValue, DataValueCollection,
+    EncryptedDataValue, KeyName, UnencryptedDataValue,
+};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+macro_rules! data_schema {
+    () => {
+        r#"{"type":"record","name":"Data","namespace":"redact.data","fields":[{"name":"path","type":"string"},{"name":"value","type":{"type":"array","items":[{"type":"record","name":"EncryptedDataValue","fields":[{"name":"value","type":"bytes"},{"name":"datatype","type":{"type":"enum","name":"DataType","symbols":["Bool","U64","I64","F64","String","Bytes"]}},{"name":"keyname","type":"string"},{"name":"nonce","type":["null","bytes"],"default":null},{"name":"aad","type":["null","bytes"],"default":null},{"name":"algorithm","type":["null","string"],"default":null}]},"boolean",{"type":"fixed","name":"U64","size":8},"long","double","string","bytes"]}},{"name":"integrity","type":["null","string"],"default":null},{"name":"signature","type":["null",{"type":"record","name":"DataSignature","fields":[{"name":"algorithm","type":"string"},{"name":"keyid","type":"string"},{"name":"signature","type":"bytes"}]}],"default":null},{"name":"written_at","type":["null","long"],"default":null},{"name":"encryptedby","type":{"type":"array","items":"string"},"default":[]},{"name":"labels","type":{"type":"map","values":"string"},"default":{}},{"name":"expires_at","type":["null","long"],"default":null},{"name":"recipients","type":{"type":"map","values":"bytes"},"default":{}},{"name":"links","type":{"type":"array","items":{"type":"record","name":"DataLink","fields":[{"name":"link_type","type":"string"},{"name":"target","type":"string"}]}},"default":[]}]}"#
+    };
+}
+
+/// The Avro schema `Data` is encoded with. Each value is a union of an
+/// `EncryptedDataValue` record and the unencrypted types, in which `u64`
+/// values are big-endian `U64` fixeds since Avro longs are signed.
+pub const DATA_SCHEMA: &str = data_schema!();
+
+/// The Avro schema `DataCollection` is encoded with, embedding `DATA_SCHEMA`
+pub const DATA_COLLECTION_SCHEMA: &str = concat!(
+    r#"{"type":"record","name":"DataCollection","namespace":"redact.data","fields":[{"name":"data","type":{"type":"array","items":"#,
+    data_schema!(),
+    r#"}},{"name":"next_cursor","type":["null","string"],"default":null}]}"#
+);
+
+const DATA_TYPES: [DataType; 6] = [
+    DataType::Bool,
+    DataType::U64,
+    DataType::I64,
+    DataType::F64,
+    DataType::String,
+    DataType::Bytes,
+];
+
+/// Returned when bytes are not a valid Avro encoding of the expected schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvroError {
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl Error for AvroError {}
+
+impl Display for AvroError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid Avro at byte {}: {}", self.position, self.reason)
+    }
+}
+
+/// Encodes data with `DATA_SCHEMA` in the Avro binary encoding
+pub fn to_avro(data: &Data) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_data(&mut out, data);
+    out
+}
+
+/// Decodes data encoded with `DATA_SCHEMA` in the Avro binary encoding
+pub fn from_avro(bytes: &[u8]) -> Result<Data, AvroError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let data = reader.data()?;
+    reader.finish()?;
+    Ok(data)
+}
+
+/// Encodes a collection with `DATA_COLLECTION_SCHEMA` in the Avro binary encoding
+pub fn collection_to_avro(collection: &DataCollection) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_array(&mut out, &collection.data, write_data);
+    write_optional(&mut out, collection.next_cursor.as_ref(), |out, cursor| {
+        write_string(out, cursor.as_str())
+    });
+    out
+}
+
+/// Decodes a collection encoded with `DATA_COLLECTION_SCHEMA` in the Avro
+/// binary encoding
+pub fn collection_from_avro(bytes: &[u8]) -> Result<DataCollection, AvroError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let data = reader.array(Reader::data)?;
+    let next_cursor = reader
+        .optional(Reader::string)?
+        .map(|cursor| crate::Cursor::new(&cursor));
+    reader.finish()?;
+    Ok(DataCollection { data, next_cursor })
+}
+
+fn write_long(out: &mut Vec<u8>, n: i64) {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+    while zigzag >= 0x80 {
+        out.push((zigzag as u8) | 0x80);
+        zigzag >>= 7;
+    }
+    out.push(zigzag as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    write_bytes(out, s.as_bytes());
+}
+
+fn write_array<T>(out: &mut Vec<u8>, items: &[T], write: fn(&mut Vec<u8>, &T)) {
+    if !items.is_empty() {
+        write_long(out, items.len() as i64);
+        items.iter().for_each(|item| write(out, item));
+    }
+    write_long(out, 0);
+}
+
+fn write_optional<T: ?Sized, F: Fn(&mut Vec<u8>, &T)>(
+    out: &mut Vec<u8>,
+    value: Option<&T>,
+    write: F,
+) {
+    match value {
+        Some(value) => {
+            write_long(out, 1);
+            write(out, value);
+        }
+        None => write_long(out, 0),
+    }
+}
+
+fn write_data(out: &mut Vec<u8>, data: &Data) {
+    write_string(out, &data.path());
+    write_array(out, &data.value().0, write_value);
+    write_optional(out, data.integrity(), write_string);
+    write_optional(out, data.signature(), |out, signature| {
+        write_string(out, &signature.algorithm);
+        write_string(out, &signature.keyid);
+        write_bytes(out, &signature.signature);
+    });
+    write_optional(out, data.written_at().as_ref(), |out, at| {
+        write_long(out, *at as i64)
+    });
+    write_array(out, data.requested_keys(), |out, keyname| {
+        write_string(out, keyname.as_str())
+    });
+    let labels: Vec<_> = data.labels().iter().collect();
+    write_array(out, &labels, |out, (key, value)| {
+        write_string(out, key);
+        write_string(out, value);
+    });
+    write_optional(out, data.expires_at().as_ref(), |out, at| {
+        write_long(out, *at as i64)
+    });
+    let recipients: Vec<_> = data.recipients().iter().collect();
+    write_array(out, &recipients, |out, (keyname, wrapped_key)| {
+        write_string(out, keyname.as_str());
+        write_bytes(out, wrapped_key);
+    });
+    write_array(out, data.links(), |out, link| {
+        write_string(out, &link.link_type);
+        write_string(out, link.target.as_str());
+    });
+}
+
+fn write_value(out: &mut Vec<u8>, value: &DataValue) {
+    match *value {
+        DataValue::Encrypted(ref e) => {
+            write_long(out, 0);
+            write_bytes(out, e.value());
+            let datatype = value.datatype();
+            write_long(
+                out,
+                DATA_TYPES.iter().position(|t| *t == datatype).unwrap_or(0) as i64,
+            );
+            write_string(out, e.keyname());
+            write_optional(out, e.nonce(), write_bytes);
+            write_optional(out, e.aad(), write_bytes);
+            write_optional(out, e.algorithm(), write_string);
+        }
+        DataValue::Unencrypted(ref u) => match *u {
+            UnencryptedDataValue::Bool(b) => {
+                write_long(out, 1);
+                out.push(b as u8);
+            }
+            UnencryptedDataValue::U64(n) => {
+                write_long(out, 2);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+            UnencryptedDataValue::I64(n) => {
+                write_long(out, 3);
+                write_long(out, n);
+            }
+            UnencryptedDataValue::F64(n) => {
+                write_long(out, 4);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            UnencryptedDataValue::String(ref s) => {
+                write_long(out, 5);
+                write_string(out, s);
+            }
+            UnencryptedDataValue::Bytes(ref b) => {
+                write_long(out, 6);
+                write_bytes(out, b);
+            }
+        },
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, reason: &'static str) -> AvroError {
+        AvroError {
+            position: self.position,
+            reason,
+        }
+    }
+
+    fn finish(&self) -> Result<(), AvroError> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(self.error("trailing bytes")),
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], AvroError> {
+        let end = self
+            .position
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn long(&mut self) -> Result<i64, AvroError> {
+        let mut zigzag = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            zigzag |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+            }
+        }
+        Err(self.error("long is too long"))
+    }
+
+    fn length(&mut self) -> Result<usize, AvroError> {
+        let n = self.long()?;
+        usize::try_from(n).map_err(|_| self.error("negative length"))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, AvroError> {
+        let n = self.length()?;
+        Ok(self.take(n)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, AvroError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes).map_err(|_| self.error("string is not utf-8"))
+    }
+
+    fn array<T>(
+        &mut self,
+        read: fn(&mut Self) -> Result<T, AvroError>,
+    ) -> Result<Vec<T>, AvroError> {
+        let mut items = Vec::new();
+        loop {
+            let count = match self.long()? {
+                0 => return Ok(items),
+                // A negative count is followed by the size of the block in bytes
+                count if count < 0 => {
+                    self.long()?;
+                    count.unsigned_abs()
+                }
+                count => count as u64,
+            };
+            for _ in 0..count {
+                items.push(read(self)?);
+            }
+        }
+    }
+
+    fn optional<T>(
+        &mut self,
+        read: fn(&mut Self) -> Result<T, AvroError>,
+    ) -> Result<Option<T>, AvroError> {
+        match self.long()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(self.error("invalid union branch")),
+        }
+    }
+
+    fn data(&mut self) -> Result<Data, AvroError> {
+        let path = self.string()?;
+        let values = self.array(Reader::value)?;
+        let mut data = Data::with_values(&path, DataValueCollection::from(values));
+        data.set_integrity(self.optional(Reader::string)?);
+        data.set_signature(self.optional(|reader| {
+            Ok(DataSignature {
+                algorithm: reader.string()?,
+                keyid: reader.string()?,
+                signature: reader.bytes()?,
+            })
+        })?);
+        data.set_written_at(self.optional(Reader::long)?.map(|at| at as u64));
+        for keyname in self.array(|reader| {
+            KeyName::new(&reader.string()?).map_err(|_| reader.error("invalid key name"))
+        })? {
+            data.add_encrypted_by(keyname);
+        }
+        for (key, value) in self.array(|reader| Ok((reader.string()?, reader.string()?)))? {
+            data.set_label(&key, &value);
+        }
+        data.set_expires_at(self.optional(Reader::long)?.map(|at| at as u64));
+        for (keyname, wrapped_key) in self.array(|reader| {
+            let keyname =
+                KeyName::new(&reader.string()?).map_err(|_| reader.error("invalid key name"))?;
+            Ok((keyname, reader.bytes()?))
+        })? {
+            data.add_recipient(keyname, wrapped_key);
+        }
+        for (link_type, target) in self.array(|reader| Ok((reader.string()?, reader.string()?)))? {
+            data.add_link(&link_type, &target);
+        }
+        Ok(data)
+    }
+
+    fn value(&mut self) -> Result<DataValue, AvroError> {
+        let unencrypted = match self.long()? {
+            0 => {
+                let value = self.bytes()?;
+                let datatype = usize::try_from(self.long()?)
+                    .ok()
+                    .and_then(|i| DATA_TYPES.get(i))
+                    .cloned()
+                    .ok_or_else(|| self.error("invalid data type"))?;
+                let keyname = self.string()?;
+                let mut encrypted = EncryptedDataValue::new(value, datatype, &keyname);
+                if let Some(nonce) = self.optional(Reader::bytes)? {
+                    encrypted = encrypted.with_nonce(nonce);
+                }
+                if let Some(aad) = self.optional(Reader::bytes)? {
+                    encrypted = encrypted.with_aad(aad);
+                }
+                if let Some(algorithm) = self.optional(Reader::string)? {
+                    encrypted = encrypted.with_algorithm(&algorithm);
+                }
+                return Ok(DataValue::Encrypted(encrypted));
+            }
+            1 => match self.take(1)?[0] {
+                0 => UnencryptedDataValue::Bool(false),
+                1 => UnencryptedDataValue::Bool(true),
+                _ => return Err(self.error("invalid boolean")),
+            },
+            2 => {
+                let mut n = [0; 8];
+                n.copy_from_slice(self.take(8)?);
+                UnencryptedDataValue::U64(u64::from_be_bytes(n))
+            }
+            3 => UnencryptedDataValue::I64(self.long()?),
+            4 => {
+                let mut n = [0; 8];
+                n.copy_from_slice(self.take(8)?);
+                UnencryptedDataValue::F64(f64::from_le_bytes(n))
+            }
+            5 => UnencryptedDataValue::String(self.string()?),
+            6 => UnencryptedDataValue::Bytes(self.bytes()?),
+            _ => return Err(self.error("invalid union branch")),
+        };
+        Ok(DataValue::Unencrypted(unencrypted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collection_from_avro, collection_to_avro, from_avro, to_avro, AvroError,
+        DATA_COLLECTION_SCHEMA, DATA_SCHEMA,
+    };
+    use crate::{
+        Cursor, Data, DataCollection, DataSignature, DataType, DataValue, DataValueCollection,
+        EncryptedDataValue, KeyName,
+    };
+
+    #[test]
+    fn test_schemas_are_json() {
+        for schema in [DATA_SCHEMA, DATA_COLLECTION_SCHEMA] {
+            assert!(serde_json::from_str::<serde_json::Value>(schema).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_encodes_with_avro_binary_encoding() {
+        let data = Data::new(".a.", DataValue::from(-1i64));
+        // path ".a." (length 3), one value of branch 3 (long) -1, end of
+        // array, three null unions, two empty arrays, a null union, an empty
+        // map, and an empty array
+        assert_eq!(
+            vec![6, b'.', b'a', b'.', 2, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            to_avro(&data)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from(true),
+                DataValue::from(u64::MAX),
+                DataValue::from(i64::MIN),
+                DataValue::from(1.5f64),
+                DataValue::from("x"),
+                DataValue::from(vec![1u8, 2]),
+                DataValue::Encrypted(EncryptedDataValue::new(vec![3], DataType::F64, "k")),
+                DataValue::Encrypted(
+                    EncryptedDataValue::new(vec![4], DataType::String, "k")
+                        .with_nonce(vec![5; 12])
+                        .with_aad(b"ctx".to_vec())
+                        .with_algorithm("AES-256-GCM"),
+                ),
+            ]),
+        );
+        data.seal_integrity();
+        data.set_written_at(Some(42));
+        data.add_encrypted_by(KeyName::new("k1").unwrap());
+        data.add_encrypted_by(KeyName::new("k2").unwrap());
+        data.set_label("team", "billing");
+        data.set_label("tier", "gold");
+        data.set_expires_at(Some(43));
+        data.add_recipient(KeyName::new("k3").unwrap(), vec![8; 40]);
+        data.add_link("derived_from", ".b.");
+        data.set_signature(Some(DataSignature {
+            algorithm: "hmac-sha256".to_owned(),
+            keyid: "k1".to_owned(),
+            signature: vec![9; 32],
+        }));
+        assert_eq!(data, from_avro(&to_avro(&data)).unwrap());
+
+        let collection = DataCollection {
+            data: vec![data, Data::new(".b.", DataValue::from(1u64))],
+            next_cursor: Some(Cursor::new("2")),
+        };
+        assert_eq!(
+            collection,
+            collection_from_avro(&collection_to_avro(&collection)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        let bytes = to_avro(&Data::new(".a.", DataValue::from("x")));
+        assert_eq!(
+            Err(AvroError {
+                position: bytes.len() - 4,
+                reason: "unexpected end of input"
+            }),
+            from_avro(&bytes[..bytes.len() - 4])
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(from_avro(&trailing).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use crate::data::binary;
+use crate::{
+    Data, DataLink, DataSignature, DataType, DataValue, DataValueCollection, EncryptedDataValue,
+    KeyName, UnencryptedDataValue,
+};
+use serde::{
+    de::{SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+/// A `Data` borrowing its path, strings, and bytes from the buffer it was
+/// deserialized from, for reading hot entries without allocating. Strings are
+/// only borrowed when they need no unescaping, and bytes when the deserializer
+/// hands them over as a slice rather than a sequence of numbers, as MessagePack
+/// does; anything else, including key names and labels, is owned. It
+/// serializes exactly as `Data` does. CBOR cannot be borrowed from, see
+/// `DataCodec::decode_borrowed`.
+///
+/// Unlike `Data`, it decodes only the current schema, and keeps the path as
+/// written instead of normalizing it, see `into_owned`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DataRef<'a> {
+    #[serde(borrow)]
+    path: Cow<'a, str>,
+    #[serde(borrow, alias = "values")]
+    value: Vec<DataValueRef<'a>>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    integrity: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<DataSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    written_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    encryptedby: Vec<KeyName>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        with = "binary::map"
+    )]
+    recipients: BTreeMap<KeyName, Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<DataLink>,
+}
+
+/// A `DataValue` borrowing from the buffer it was deserialized from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DataValueRef<'a> {
+    #[serde(borrow)]
+    Encrypted(EncryptedDataValueRef<'a>),
+    #[serde(borrow)]
+    Unencrypted(UnencryptedDataValueRef<'a>),
+}
+
+/// An `UnencryptedDataValue` borrowing from the buffer it was deserialized from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum UnencryptedDataValueRef<'a> {
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(#[serde(borrow)] Cow<'a, str>),
+    Bytes(
+        #[serde(
+            borrow,
+            serialize_with = "binary::serialize",
+            deserialize_with = "borrowed_bytes"
+        )]
+        Cow<'a, [u8]>,
+    ),
+}
+
+/// An `EncryptedDataValue` borrowing from the buffer it was deserialized from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EncryptedDataValueRef<'a> {
+    #[serde(
+        borrow,
+        serialize_with = "binary::serialize",
+        deserialize_with = "borrowed_bytes"
+    )]
+    value: Cow<'a, [u8]>,
+    datatype: DataType,
+    #[serde(borrow)]
+    keyname: Cow<'a, str>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "binary::option"
+    )]
+    nonce: Option<Vec<u8>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "binary::option"
+    )]
+    aad: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    algorithm: Option<String>,
+}
+
+impl<'a> DataRef<'a> {
+    /// Returns the path as it was written
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the values held by the data
+    pub fn value(&self) -> &[DataValueRef<'a>] {
+        &self.value
+    }
+
+    /// Returns the stored integrity checksum, if any
+    pub fn integrity(&self) -> Option<&str> {
+        self.integrity.as_deref()
+    }
+
+    /// Returns the detached signature over the data, if any
+    pub fn signature(&self) -> Option<&DataSignature> {
+        self.signature.as_ref()
+    }
+
+    /// Returns when the data was written, in milliseconds since the unix epoch,
+    /// if the storer it was read from records it
+    pub fn written_at(&self) -> Option<u64> {
+        self.written_at
+    }
+
+    /// Copies everything borrowed into an owned `Data`, normalizing the path
+    pub fn into_owned(self) -> Data {
+        let mut data = Data::with_values(
+            &self.path,
+            DataValueCollection(self.value.into_iter().map(DataValue::from).collect()),
+        );
+        data.set_integrity(self.integrity.map(Cow::into_owned));
+        data.set_signature(self.signature);
+        data.set_written_at(self.written_at);
+        for keyname in self.encryptedby {
+            data.add_encrypted_by(keyname);
+        }
+        for (key, value) in self.labels {
+            data.set_label(&key, &value);
+        }
+        data.set_expires_at(self.expires_at);
+        for (keyname, wrapped_key) in self.recipients {
+            data.add_recipient(keyname, wrapped_key);
+        }
+        for link in self.links {
+            data.add_link(&link.link_type, link.target.as_str());
+        }
+        data
+    }
+}
+
+impl<'a> DataValueRef<'a> {
+    /// Returns the type of the value, or of the plaintext for encrypted values
+    pub fn datatype(&self) -> DataType {
+        match *self {
+            DataValueRef::Encrypted(ref e) => e.datatype.clone(),
+            DataValueRef::Unencrypted(ref u) => match *u {
+                UnencryptedDataValueRef::Bool(_) => DataType::Bool,
+                UnencryptedDataValueRef::U64(_) => DataType::U64,
+                UnencryptedDataValueRef::I64(_) => DataType::I64,
+                UnencryptedDataValueRef::F64(_) => DataType::F64,
+                UnencryptedDataValueRef::String(_) => DataType::String,
+                UnencryptedDataValueRef::Bytes(_) => DataType::Bytes,
+            },
+        }
+    }
+}
+
+impl<'a> EncryptedDataValueRef<'a> {
+    /// Returns the ciphertext
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// Returns the name of the key the value was encrypted by
+    pub fn keyname(&self) -> &str {
+        &self.keyname
+    }
+
+    /// Returns the nonce, or IV, the value was encrypted with, if recorded
+    pub fn nonce(&self) -> Option<&[u8]> {
+        self.nonce.as_deref()
+    }
+
+    /// Returns the additional authenticated data the value was encrypted
+    /// with, if recorded
+    pub fn aad(&self) -> Option<&[u8]> {
+        self.aad.as_deref()
+    }
+
+    /// Returns the identifier of the algorithm the value was encrypted with,
+    /// if recorded
+    pub fn algorithm(&self) -> Option<&str> {
+        self.algorithm.as_deref()
+    }
+}
+
+impl<'a> From<DataValueRef<'a>> for DataValue {
+    fn from(value: DataValueRef<'a>) -> Self {
+        match value {
+            DataValueRef::Encrypted(e) => {
+                let mut encrypted =
+                    EncryptedDataValue::new(e.value.into_owned(), e.datatype, &e.keyname);
+                if let Some(nonce) = e.nonce {
+                    encrypted = encrypted.with_nonce(nonce);
+                }
+                if let Some(aad) = e.aad {
+                    encrypted = encrypted.with_aad(aad);
+                }
+                if let Some(algorithm) = e.algorithm {
+                    encrypted = encrypted.with_algorithm(&algorithm);
+                }
+                DataValue::Encrypted(encrypted)
+            }
+            DataValueRef::Unencrypted(u) => DataValue::Unencrypted(match u {
+                UnencryptedDataValueRef::Bool(b) => UnencryptedDataValue::Bool(b),
+                UnencryptedDataValueRef::U64(n) => UnencryptedDataValue::U64(n),
+                UnencryptedDataValueRef::I64(n) => UnencryptedDataValue::I64(n),
+                UnencryptedDataValueRef::F64(n) => UnencryptedDataValue::F64(n),
+                UnencryptedDataValueRef::String(s) => UnencryptedDataValue::String(s.into_owned()),
+                UnencryptedDataValueRef::Bytes(b) => UnencryptedDataValue::Bytes(b.into_owned()),
+            }),
+        }
+    }
+}
+
+impl<'a> From<DataRef<'a>> for Data {
+    fn from(data: DataRef<'a>) -> Self {
+        data.into_owned()
+    }
+}
+
+/// Deserializes an optional string, borrowing it where possible, which serde
+/// only does for a `Cow` outside of an `Option`
+fn borrowed_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'de, str>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+
+    Ok(Option::<Borrowed>::deserialize(deserializer)?.map(|borrowed| borrowed.0))
+}
+
+/// Deserializes bytes, borrowing them if the deserializer hands over a slice
+/// of its input, and accepting the sequence of numbers serde encodes `Vec<u8>`
+/// as in self-describing formats
+fn borrowed_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'de, [u8]>, D::Error> {
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Cow<'de, [u8]>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("bytes")
+        }
+
+        fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+            Ok(Cow::Borrowed(v))
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(Cow::Owned(v.to_vec()))
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(Cow::Owned(v))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            Ok(Cow::Owned(bytes))
+        }
+    }
+
+    deserializer.deserialize_bytes(BytesVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataRef, DataValueRef, UnencryptedDataValueRef};
+    use crate::{
+        Data, DataCodec, DataType, DataValue, DataValueCollection, EncryptedDataValue, KeyName,
+    };
+    use std::borrow::Cow;
+
+    fn data() -> Data {
+        let mut data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from("plain"),
+                DataValue::from("esc\"aped"),
+                DataValue::from(vec![1u8, 2]),
+                DataValue::Encrypted(EncryptedDataValue::new(vec![3], DataType::U64, "k")),
+                DataValue::Encrypted(
+                    EncryptedDataValue::new(vec![4], DataType::U64, "k")
+                        .with_nonce(vec![5, 6])
+                        .with_algorithm("AES-256-GCM"),
+                ),
+            ]),
+        );
+        data.set_written_at(Some(5));
+        data.set_label("team", "billing");
+        data.add_recipient(KeyName::new("k").unwrap(), vec![7]);
+        data.add_link("derived_from", ".b.");
+        data.seal_integrity();
+        data
+    }
+
+    #[test]
+    fn test_borrows_from_json() {
+        let json = serde_json::to_vec(&data()).unwrap();
+        let data_ref: DataRef = serde_json::from_slice(&json).unwrap();
+        assert!(matches!(data_ref.path, Cow::Borrowed(".a.")));
+        assert!(matches!(data_ref.integrity, Some(Cow::Borrowed(_))));
+        assert!(matches!(
+            data_ref.value()[0],
+            DataValueRef::Unencrypted(UnencryptedDataValueRef::String(Cow::Borrowed("plain")))
+        ));
+        assert!(matches!(
+            data_ref.value()[1],
+            DataValueRef::Unencrypted(UnencryptedDataValueRef::String(Cow::Owned(_)))
+        ));
+        assert_eq!(DataType::Bytes, data_ref.value()[2].datatype());
+        assert_eq!(json, serde_json::to_vec(&data_ref).unwrap());
+        assert_eq!(data(), data_ref.into_owned());
+    }
+
+    #[test]
+    fn test_decodes_borrowing_codecs() {
+        for codec in [DataCodec::Json, DataCodec::MessagePack] {
+            let bytes = codec.encode(&data()).unwrap();
+            let data_ref: DataRef = codec.decode_borrowed(&bytes).unwrap();
+            assert_eq!(bytes, codec.encode(&data_ref).unwrap());
+            assert_eq!(data(), Data::from(data_ref));
+        }
+        let bytes = DataCodec::MessagePack.encode(&data()).unwrap();
+        let data_ref: DataRef = DataCodec::MessagePack.decode_borrowed(&bytes).unwrap();
+        assert!(matches!(
+            data_ref.value()[2],
+            DataValueRef::Unencrypted(UnencryptedDataValueRef::Bytes(Cow::Borrowed(_)))
+        ));
+
+        let bytes = DataCodec::Cbor.encode(&data()).unwrap();
+        assert!(DataCodec::Cbor.decode_borrowed::<DataRef>(&bytes).is_err());
+        assert_eq!(data(), DataCodec::Cbor.decode::<Data>(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_into_owned_normalizes_path() {
+        let data_ref: DataRef =
+            serde_json::from_str(r#"{"path":"a","value":[{"Unencrypted":{"U64":1}}]}"#).unwrap();
+        assert_eq!("a", data_ref.path());
+        assert_eq!(
+            Data::new(".a.", DataValue::from(1u64)),
+            data_ref.into_owned()
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{Data, DataValue, DataValueCollection, InvalidKeyName, KeyName};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Returned by `DataBuilder::build` when the data being built is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataBuilderError {
+    /// No path was given
+    MissingPath,
+    /// The path has an empty segment, e.g. `.a..b.`
+    InvalidPath { path: String },
+    /// No value was given
+    MissingValue,
+    /// A key name was empty or held whitespace or control characters
+    InvalidKeyName { keyname: String },
+    /// A label key was empty
+    InvalidLabel { key: String },
+    /// The data would expire immediately, or past the end of time
+    InvalidExpiry { expires_in: Duration },
+}
+
+impl Error for DataBuilderError {}
+
+impl Display for DataBuilderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            DataBuilderError::MissingPath => write!(f, "Data has no path"),
+            DataBuilderError::InvalidPath { ref path } => {
+                write!(f, "Data path {} has an empty segment", path)
+            }
+            DataBuilderError::MissingValue => write!(f, "Data has no value"),
+            DataBuilderError::InvalidKeyName { ref keyname } => {
+                write!(f, "Key name {:?} is invalid", keyname)
+            }
+            DataBuilderError::InvalidLabel { ref key } => {
+                write!(f, "Label key {:?} is invalid", key)
+            }
+            DataBuilderError::InvalidExpiry { expires_in } => {
+                write!(f, "Data cannot expire in {:?}", expires_in)
+            }
+        }
+    }
+}
+
+/// Builds `Data` field by field, see `Data::builder`. Nothing is validated
+/// until `build`, which reports the first invalid field.
+#[derive(Debug, Clone, Default)]
+pub struct DataBuilder {
+    path: Option<String>,
+    values: Vec<DataValue>,
+    encryptedby: Vec<String>,
+    recipients: Vec<(String, Vec<u8>)>,
+    labels: Vec<(String, String)>,
+    expires_in: Option<Duration>,
+}
+
+impl DataBuilder {
+    /// Sets the path of the data, which is normalized as `Data::new` does
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_owned());
+        self
+    }
+
+    /// Adds a value to the data
+    pub fn value<V: Into<DataValue>>(mut self, value: V) -> Self {
+        self.values.push(value.into());
+        self
+    }
+
+    /// Adds the name of a key the data is to be encrypted by
+    pub fn encrypted_by(mut self, keyname: &str) -> Self {
+        self.encryptedby.push(keyname.to_owned());
+        self
+    }
+
+    /// Adds a recipient key along with the data-encryption key wrapped by it,
+    /// replacing any wrapped key the recipient already had
+    pub fn recipient(mut self, keyname: &str, wrapped_key: Vec<u8>) -> Self {
+        self.recipients.push((keyname.to_owned(), wrapped_key));
+        self
+    }
+
+    /// Attaches a label, replacing any label with the same key
+    pub fn label(mut self, key: &str, value: &str) -> Self {
+        self.labels.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Makes the data expire once the given time has passed from now
+    pub fn expires_in(mut self, expires_in: Duration) -> Self {
+        self.expires_in = Some(expires_in);
+        self
+    }
+
+    /// Validates the fields and builds the data
+    pub fn build(self) -> Result<Data, DataBuilderError> {
+        let path = self.path.ok_or(DataBuilderError::MissingPath)?;
+        if path.is_empty() {
+            return Err(DataBuilderError::MissingPath);
+        }
+        if path.contains("..") {
+            return Err(DataBuilderError::InvalidPath { path });
+        }
+        if self.values.is_empty() {
+            return Err(DataBuilderError::MissingValue);
+        }
+        let encryptedby = self
+            .encryptedby
+            .into_iter()
+            .map(KeyName::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DataBuilderError::InvalidKeyName { keyname: e.keyname })?;
+        let recipients = self
+            .recipients
+            .into_iter()
+            .map(|(keyname, wrapped_key)| Ok((KeyName::try_from(keyname)?, wrapped_key)))
+            .collect::<Result<Vec<_>, InvalidKeyName>>()
+            .map_err(|e| DataBuilderError::InvalidKeyName { keyname: e.keyname })?;
+        if let Some((key, _)) = self.labels.iter().find(|(key, _)| key.is_empty()) {
+            return Err(DataBuilderError::InvalidLabel { key: key.clone() });
+        }
+        let expires_at = self.expires_in.map(expires_at).transpose()?;
+
+        let mut data = Data::with_values(&path, DataValueCollection::from(self.values));
+        for keyname in encryptedby {
+            data.add_encrypted_by(keyname);
+        }
+        for (keyname, wrapped_key) in recipients {
+            data.add_recipient(keyname, wrapped_key);
+        }
+        for (key, value) in self.labels {
+            data.set_label(&key, &value);
+        }
+        data.set_expires_at(expires_at);
+        Ok(data)
+    }
+}
+
+/// Returns the time in milliseconds since the unix epoch the given time from
+/// now
+fn expires_at(expires_in: Duration) -> Result<u64, DataBuilderError> {
+    let invalid = DataBuilderError::InvalidExpiry { expires_in };
+    if expires_in.is_zero() {
+        return Err(invalid);
+    }
+    SystemTime::now()
+        .checked_add(expires_in)
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .and_then(|at| u64::try_from(at.as_millis()).ok())
+        .ok_or(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DataBuilderError;
+    use crate::{Data, DataValue, DataValueCollection};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_builds_data() {
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let data = Data::builder()
+            .path("users.1")
+            .value(1u64)
+            .value("a")
+            .encrypted_by("k1")
+            .encrypted_by("k1")
+            .recipient("k2", vec![1, 2])
+            .label("team", "billing")
+            .expires_in(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        assert_eq!(".users.1.", data.path());
+        assert_eq!(
+            &DataValueCollection::from(vec![DataValue::from(1u64), DataValue::from("a")]),
+            data.value()
+        );
+        assert_eq!(vec!["k1"], data.encryptedby());
+        assert_eq!(Some(&[1, 2][..]), data.wrapped_key("k2"));
+        assert_eq!(Some("billing"), data.label("team"));
+        let expires_at = data.expires_at().unwrap();
+        assert!(expires_at >= before + 60_000 && expires_at < before + 70_000);
+    }
+
+    #[test]
+    fn test_build_validates() {
+        assert_eq!(
+            Err(DataBuilderError::MissingPath),
+            Data::builder().value(1u64).build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::InvalidPath {
+                path: ".a..b.".to_owned()
+            }),
+            Data::builder().path(".a..b.").value(1u64).build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::MissingValue),
+            Data::builder().path(".a.").build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::InvalidKeyName {
+                keyname: "k 1".to_owned()
+            }),
+            Data::builder()
+                .path(".a.")
+                .value(1u64)
+                .encrypted_by("k 1")
+                .build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::InvalidKeyName {
+                keyname: "k 2".to_owned()
+            }),
+            Data::builder()
+                .path(".a.")
+                .value(1u64)
+                .recipient("k 2", vec![1])
+                .build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::InvalidLabel { key: String::new() }),
+            Data::builder()
+                .path(".a.")
+                .value(1u64)
+                .label("", "x")
+                .build()
+        );
+        assert_eq!(
+            Err(DataBuilderError::InvalidExpiry {
+                expires_in: Duration::from_secs(0)
+            }),
+            Data::builder()
+                .path(".a.")
+                .value(1u64)
+                .expires_in(Duration::from_secs(0))
+                .build()
+        );
+    }
+
+    #[test]
+    fn test_to_string_invalid_key_name() {
+        let s = DataBuilderError::InvalidKeyName {
+            keyname: "k 1".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Key name \"k 1\" is invalid");
+    }
+
+    #[test]
+    fn test_new_data_has_no_metadata() {
+        let data = Data::new(".a.", DataValue::from(1u64));
+        assert!(data.labels().is_empty());
+        assert_eq!(None, data.expires_at());
+        assert_eq!(
+            r#"{"path":".a.","value":[{"Unencrypted":{"U64":1}}]}"#,
+            serde_json::to_string(&data).unwrap()
+        );
+    }
+}
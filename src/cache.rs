@@ -1,5 +1,12 @@
+pub mod bloom;
+pub mod boxed;
+pub mod collection;
+pub mod encryption;
 pub mod error;
+pub mod hashed;
+pub mod memory;
 pub mod redis;
+pub mod slow_log;
 
 use async_trait::async_trait;
 use error::CacheError;
@@ -20,6 +27,25 @@ pub trait DataCacher: Clone + Send + Sync {
     /// sets the cache entry's expiration in seconds
     async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError>;
 
+    /// retrieves a cached value and resets its expiration in seconds, or
+    /// returns `None` if there is no entry with the key. The default
+    /// implementation issues `exists`, `expire`, and `get` in turn; cachers
+    /// able to do it in a single round trip should override it.
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        if !self.exists(key).await? {
+            return Ok(None);
+        }
+        self.expire(key, seconds).await?;
+        self.get(key).await.map(Some)
+    }
+
+    /// removes the entry with the given key, returning whether one existed
+    async fn delete(&self, key: &str) -> Result<bool, CacheError>;
+
+    /// removes every entry whose key begins with the given prefix, returning
+    /// the number of entries removed
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError>;
+
     fn get_default_key_expiration_seconds(&self) -> usize;
 }
 
@@ -46,30 +72,19 @@ impl<U> DataCacher for Arc<U>
         self.deref().expire(key, seconds).await
     }
 
-    fn get_default_key_expiration_seconds(&self) -> usize {
-        self.deref().get_default_key_expiration_seconds()
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        self.deref().get_and_expire(key, seconds).await
     }
-}
 
-pub mod tests {
-    use crate::{DataCacher, CacheError, Data};
-    use async_trait::async_trait;
-    use mockall::predicate::*;
-    use mockall::*;
-
-    mock! {
-    pub DataCacher {}
-    #[async_trait]
-    impl DataCacher for DataCacher {
-        async fn set(&self, key: &str, value: Data) -> Result<(), CacheError>;
-        async fn get(&self, key: &str) -> Result<Data, CacheError>;
-        async fn exists(&self, key: &str) -> Result<bool, CacheError>;
-        async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError>;
-        fn get_default_key_expiration_seconds(&self) -> usize;
-    }
-    impl Clone for DataCacher {
-        fn clone(&self) -> Self;
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        self.deref().delete(key).await
     }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        self.deref().delete_prefix(prefix).await
     }
 
-}
\ No newline at end of file
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.deref().get_default_key_expiration_seconds()
+    }
+}
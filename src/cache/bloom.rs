@@ -0,0 +1,200 @@
+use crate::cache::{error::CacheError, DataCacher};
+use crate::Data;
+use async_trait::async_trait;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// A Bloom filter over strings, which answers whether a string might have
+/// been inserted, with no false negatives and false positives at a rate set
+/// by its size. Insertions are lock-free.
+#[derive(Debug)]
+pub struct BloomFilter {
+    bits: Vec<AtomicU64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Builds a filter sized to answer with the given rate of false positives
+    /// once `expected_items` strings have been inserted; the rate rises as
+    /// more are inserted
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * p.ln() / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let hashes = ((bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        BloomFilter {
+            bits: (0..bits.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            hashes,
+        }
+    }
+
+    /// Records a string in the filter
+    pub fn insert(&self, item: &str) {
+        for bit in self.bit_indexes(item) {
+            self.bits[bit / 64].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns whether the string might have been inserted; `false` means it
+    /// certainly was not
+    pub fn might_contain(&self, item: &str) -> bool {
+        self.bit_indexes(item)
+            .all(|bit| self.bits[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0)
+    }
+
+    /// Forgets every inserted string
+    pub fn clear(&self) {
+        for word in &self.bits {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Derives the bits of a string from two hashes of it, by double hashing
+    fn bit_indexes(&self, item: &str) -> impl Iterator<Item = usize> {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            item.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (h1, h2) = (hash(0), hash(1) | 1);
+        let len = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+/// Wraps a cacher to track the keys set through it in a Bloom filter, which
+/// is consulted before asking the cacher whether a key exists, or for its
+/// entry. Lookups of keys which were never cached are then answered in
+/// process instead of with a round trip to e.g. redis.
+///
+/// Only keys set through this cacher, or its clones, are known to the
+/// filter, so entries set by other processes sharing the cache read as
+/// misses until set through it. Deleted and expired keys remain in the filter
+/// and are looked up in the cacher as before.
+#[derive(Clone)]
+pub struct BloomDataCacher<C: DataCacher> {
+    cacher: C,
+    filter: Arc<BloomFilter>,
+}
+
+impl<C: DataCacher> BloomDataCacher<C> {
+    /// Instantiates a cacher tracking up to 100,000 keys with a 1% rate of
+    /// false positives in front of an existing cacher
+    pub fn new(cacher: C) -> Self {
+        BloomDataCacher {
+            cacher,
+            filter: Arc::new(BloomFilter::new(100_000, 0.01)),
+        }
+    }
+
+    /// Replaces the filter with one sized for the given number of keys and
+    /// rate of false positives
+    pub fn with_capacity(mut self, expected_keys: usize, false_positive_rate: f64) -> Self {
+        self.filter = Arc::new(BloomFilter::new(expected_keys, false_positive_rate));
+        self
+    }
+
+    /// Returns the filter of the keys set through the cacher
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
+
+#[async_trait]
+impl<C: DataCacher> DataCacher for BloomDataCacher<C> {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        self.cacher.set(key, value).await?;
+        self.filter.insert(key);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        if !self.filter.might_contain(key) {
+            return Err(CacheError::NotFound);
+        }
+        self.cacher.get(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        if !self.filter.might_contain(key) {
+            return Ok(false);
+        }
+        self.cacher.exists(key).await
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        if !self.filter.might_contain(key) {
+            return Ok(false);
+        }
+        self.cacher.expire(key, seconds).await
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        if !self.filter.might_contain(key) {
+            return Ok(None);
+        }
+        self.cacher.get_and_expire(key, seconds).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        self.cacher.delete(key).await
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        self.cacher.delete_prefix(prefix).await
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.cacher.get_default_key_expiration_seconds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BloomDataCacher, BloomFilter};
+    use crate::mocks::MockDataCacher;
+    use crate::{Data, DataCacher, DataValue};
+
+    #[test]
+    fn test_filter_has_no_false_negatives() {
+        let filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&format!(".keys.{}.", i));
+        }
+        assert!((0..1000).all(|i| filter.might_contain(&format!(".keys.{}.", i))));
+        let false_positives = (1000..11000)
+            .filter(|i| filter.might_contain(&format!(".keys.{}.", i)))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+        filter.clear();
+        assert!(!filter.might_contain(".keys.1."));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_keys_skip_the_cacher() {
+        let mut inner = MockDataCacher::new();
+        inner.expect_set().times(1).returning(|_, _| Ok(()));
+        inner
+            .expect_exists()
+            .withf(|key| key == ".known.")
+            .times(1)
+            .returning(|_| Ok(true));
+        let cacher = BloomDataCacher::new(inner).with_capacity(100, 0.001);
+
+        assert!(!cacher.exists(".unknown.").await.unwrap());
+        assert!(cacher.get(".unknown.").await.is_err());
+        cacher
+            .set(".known.", Data::new(".known.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert!(cacher.exists(".known.").await.unwrap());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::cache::{error::CacheError, DataCacher};
+use crate::Data;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// The object-safe form of `DataCacher` which `BoxedDataCacher` dispatches
+/// through, implemented for every cacher
+#[async_trait]
+trait DynDataCacher: Send + Sync {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError>;
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError>;
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError>;
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError>;
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError>;
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError>;
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError>;
+
+    fn get_default_key_expiration_seconds(&self) -> usize;
+}
+
+#[async_trait]
+impl<C: DataCacher + 'static> DynDataCacher for C {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        DataCacher::set(self, key, value).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        DataCacher::get(self, key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        DataCacher::exists(self, key).await
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        DataCacher::expire(self, key, seconds).await
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        DataCacher::get_and_expire(self, key, seconds).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        DataCacher::delete(self, key).await
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        DataCacher::delete_prefix(self, prefix).await
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        DataCacher::get_default_key_expiration_seconds(self)
+    }
+}
+
+/// A cacher of any type behind a pointer, in the same way as
+/// `BoxedDataStorer`
+#[derive(Clone)]
+pub struct BoxedDataCacher {
+    cacher: Arc<dyn DynDataCacher>,
+}
+
+impl BoxedDataCacher {
+    pub fn new<C: DataCacher + 'static>(cacher: C) -> Self {
+        BoxedDataCacher {
+            cacher: Arc::new(cacher),
+        }
+    }
+}
+
+#[async_trait]
+impl DataCacher for BoxedDataCacher {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        self.cacher.set(key, value).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        self.cacher.get(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        self.cacher.exists(key).await
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        self.cacher.expire(key, seconds).await
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        self.cacher.get_and_expire(key, seconds).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        self.cacher.delete(key).await
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        self.cacher.delete_prefix(prefix).await
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.cacher.get_default_key_expiration_seconds()
+    }
+}
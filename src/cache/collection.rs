@@ -0,0 +1,207 @@
+use crate::cache::error::CacheError;
+use crate::{DataCollection, Sort};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Identifies a page of the collection under a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageKey {
+    pub path: String,
+    pub skip: i64,
+    pub page_size: i64,
+    pub sort: Option<Sort>,
+}
+
+impl PageKey {
+    pub fn new(path: &str, skip: i64, page_size: i64, sort: Option<Sort>) -> Self {
+        PageKey {
+            path: path.to_owned(),
+            skip,
+            page_size,
+            sort,
+        }
+    }
+
+    /// Returns the parameters distinguishing the page from the other pages of
+    /// its path
+    pub fn params(&self) -> String {
+        let mut params = format!("skip={}&page_size={}", self.skip, self.page_size);
+        if let Some(ref sort) = self.sort {
+            params.push('&');
+            params.push_str(&sort.to_query_params());
+        }
+        params
+    }
+
+    /// Returns the composite key the page is cached under
+    pub fn key(&self) -> String {
+        format!("page:{}?{}", self.path, self.params())
+    }
+}
+
+/// Returns the path and each of its ancestors, from the root down, e.g. `.`,
+/// `.a.`, and `.a.b.` for `.a.b.`
+pub fn ancestors(path: &str) -> Vec<&str> {
+    let mut ancestors = vec!["."];
+    ancestors.extend(
+        path.match_indices('.')
+            .map(|(i, _)| &path[..=i])
+            .filter(|ancestor| ancestor.len() > 1),
+    );
+    ancestors
+}
+
+/// The operations a cache of collection pages must be able to fulfill. Pages
+/// are invalidated by prefix rather than one by one, as a write to a path
+/// changes every page listing it. Prefixes are paths as normalized by `Data`,
+/// e.g. `.a.b.`, and cover the pages of that path and of every path below it.
+#[async_trait]
+pub trait CollectionCacher: Clone + Send + Sync {
+    /// retrieves a cached page, if any
+    async fn get_page(&self, key: &PageKey) -> Result<Option<DataCollection>, CacheError>;
+
+    /// caches a page
+    async fn set_page(&self, key: &PageKey, page: DataCollection) -> Result<(), CacheError>;
+
+    /// drops every cached page of the prefix and of the paths below it
+    async fn invalidate(&self, prefix: &str) -> Result<(), CacheError>;
+}
+
+/// Stores an in-process cache of collection pages, holding at most
+/// `max_pages` pages at once. Pages beyond that are not cached until others
+/// are invalidated. Clones share the same pages.
+#[derive(Clone)]
+pub struct MemoryCollectionCacher {
+    /// Pages by path, then by their parameters
+    pages: Arc<Mutex<BTreeMap<String, HashMap<String, DataCollection>>>>,
+    max_pages: usize,
+}
+
+impl MemoryCollectionCacher {
+    pub fn new(max_pages: usize) -> Self {
+        MemoryCollectionCacher {
+            pages: Arc::new(Mutex::new(BTreeMap::new())),
+            max_pages,
+        }
+    }
+
+    /// Returns the number of pages currently held
+    pub fn page_count(&self) -> usize {
+        self.pages.lock().unwrap().values().map(HashMap::len).sum()
+    }
+}
+
+#[async_trait]
+impl CollectionCacher for MemoryCollectionCacher {
+    async fn get_page(&self, key: &PageKey) -> Result<Option<DataCollection>, CacheError> {
+        Ok(self
+            .pages
+            .lock()
+            .unwrap()
+            .get(&key.path)
+            .and_then(|pages| pages.get(&key.params()))
+            .cloned())
+    }
+
+    async fn set_page(&self, key: &PageKey, page: DataCollection) -> Result<(), CacheError> {
+        let mut pages = self.pages.lock().unwrap();
+        let params = key.params();
+        let cached = pages
+            .get(&key.path)
+            .is_some_and(|path_pages| path_pages.contains_key(&params));
+        if !cached && pages.values().map(HashMap::len).sum::<usize>() >= self.max_pages {
+            return Ok(());
+        }
+        pages
+            .entry(key.path.clone())
+            .or_default()
+            .insert(params, page);
+        Ok(())
+    }
+
+    async fn invalidate(&self, prefix: &str) -> Result<(), CacheError> {
+        self.pages
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(prefix));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ancestors, CollectionCacher, MemoryCollectionCacher, PageKey};
+    use crate::{Data, DataCollection, DataValue, Sort, SortBy, SortOrder};
+
+    fn page(path: &str) -> DataCollection {
+        DataCollection::new(vec![Data::new(path, DataValue::from(1u64))])
+    }
+
+    #[test]
+    fn test_ancestors() {
+        assert_eq!(vec!["."], ancestors("."));
+        assert_eq!(vec![".", ".a.", ".a.b."], ancestors(".a.b."));
+    }
+
+    #[test]
+    fn test_page_keys_are_distinct() {
+        let sorted = PageKey::new(
+            ".a.",
+            0,
+            10,
+            Some(Sort {
+                by: SortBy::Path,
+                order: SortOrder::Ascending,
+            }),
+        );
+        assert_eq!(
+            "page:.a.?skip=0&page_size=10",
+            PageKey::new(".a.", 0, 10, None).key()
+        );
+        assert_ne!(PageKey::new(".a.", 0, 10, None).key(), sorted.key());
+        assert_ne!(
+            PageKey::new(".a.", 0, 10, None).key(),
+            PageKey::new(".a.", 10, 10, None).key()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalidates_pages_under_prefix() {
+        let cacher = MemoryCollectionCacher::new(10);
+        for (path, skip) in [(".a.", 0), (".a.", 10), (".a.b.", 0), (".c.", 0)] {
+            let key = PageKey::new(path, skip, 10, None);
+            cacher.set_page(&key, page(path)).await.unwrap();
+        }
+        assert_eq!(4, cacher.page_count());
+
+        cacher.invalidate(".a.").await.unwrap();
+        assert_eq!(1, cacher.page_count());
+        assert!(cacher
+            .get_page(&PageKey::new(".a.", 0, 10, None))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            Some(page(".c.")),
+            cacher
+                .get_page(&PageKey::new(".c.", 0, 10, None))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_holds_at_most_max_pages() {
+        let cacher = MemoryCollectionCacher::new(1);
+        let first = PageKey::new(".a.", 0, 10, None);
+        cacher.set_page(&first, page(".a.")).await.unwrap();
+        cacher
+            .set_page(&PageKey::new(".b.", 0, 10, None), page(".b."))
+            .await
+            .unwrap();
+        cacher.set_page(&first, page(".c.")).await.unwrap();
+        assert_eq!(1, cacher.page_count());
+        assert_eq!(Some(page(".c.")), cacher.get_page(&first).await.unwrap());
+    }
+}
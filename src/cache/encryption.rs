@@ -0,0 +1,100 @@
+use crate::cache::error::CacheError;
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+
+/// Length in bytes of the nonce prefixed to every sealed entry
+const NONCE_LEN: usize = 12;
+
+/// Encrypts cache entries before they are written to a cache outside of the
+/// security boundary of the primary datastore, and decrypts them when read.
+/// Implementations should bind each entry to its key, so that entries cannot
+/// be swapped between keys without detection.
+pub trait CacheEncryptor: Send + Sync {
+    fn encrypt(&self, key: &str, plaintext: &[u8]) -> Result<Vec<u8>, CacheError>;
+
+    fn decrypt(&self, key: &str, ciphertext: &[u8]) -> Result<Vec<u8>, CacheError>;
+}
+
+/// Seals cache entries with AES-256-GCM as `nonce || ciphertext`, with the
+/// cache key as associated data
+#[derive(Clone)]
+pub struct AesGcmCacheEncryptor {
+    cipher: Aes256Gcm,
+}
+
+impl AesGcmCacheEncryptor {
+    /// Instantiates an encryptor using a supplied 256-bit key
+    pub fn new(key: [u8; 32]) -> Self {
+        AesGcmCacheEncryptor {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+}
+
+impl CacheEncryptor for AesGcmCacheEncryptor {
+    fn encrypt(&self, key: &str, plaintext: &[u8]) -> Result<Vec<u8>, CacheError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| cipher_error("Could not encrypt the cache entry"))?;
+        let mut entry = nonce.to_vec();
+        entry.extend_from_slice(&ciphertext);
+        Ok(entry)
+    }
+
+    fn decrypt(&self, key: &str, ciphertext: &[u8]) -> Result<Vec<u8>, CacheError> {
+        if ciphertext.len() < NONCE_LEN {
+            return Err(cipher_error("Encrypted cache entry is truncated"));
+        }
+        let (nonce, ciphertext) = ciphertext.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                cipher_error(
+                    "Could not decrypt the cache entry; the key is wrong or it was tampered with",
+                )
+            })
+    }
+}
+
+fn cipher_error(message: &str) -> CacheError {
+    CacheError::InternalError {
+        source: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AesGcmCacheEncryptor, CacheEncryptor};
+
+    #[test]
+    fn test_round_trips_and_binds_entries_to_keys() {
+        let encryptor = AesGcmCacheEncryptor::new([7; 32]);
+        let sealed = encryptor.encrypt(".a.", b"plaintext").unwrap();
+        assert!(!sealed.windows(9).any(|w| w == b"plaintext"));
+        assert_eq!(
+            b"plaintext".to_vec(),
+            encryptor.decrypt(".a.", &sealed).unwrap()
+        );
+        assert!(encryptor.decrypt(".b.", &sealed).is_err());
+        assert!(AesGcmCacheEncryptor::new([8; 32])
+            .decrypt(".a.", &sealed)
+            .is_err());
+        assert!(encryptor.decrypt(".a.", &sealed[..4]).is_err());
+    }
+}
@@ -29,7 +29,7 @@ impl Display for CacheError {
             CacheError::InternalError { .. } => {
                 write!(f, "Internal error occurred")
             }
-            CacheError::NotFound { .. } => {
+            CacheError::NotFound => {
                 write!(f, "Cache entry not found")
             }
         }
@@ -0,0 +1,163 @@
+use crate::cache::{error::CacheError, DataCacher};
+use crate::Data;
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+/// Separates the retained head of a long key from the digest of the key
+const DIGEST_SEPARATOR: char = '#';
+
+/// Hex characters of the SHA-256 digest kept in hashed keys, 128 bits
+const DIGEST_LEN: usize = 32;
+
+/// Wraps a cacher to shorten keys longer than a maximum length, which deeply
+/// nested paths produce and which inflate the cache's memory and slow its key
+/// comparisons. A long key is replaced by its first `max_key_len` bytes
+/// followed by a digest of the whole key, e.g. `.a.b.c#9f86d081...`, so that
+/// hashed keys never collide with unhashed ones and still share the prefix
+/// of the path they were hashed from. The original path remains readable in
+/// the cached `Data` itself.
+///
+/// Invalidating a prefix longer than `max_key_len` invalidates every key
+/// sharing its first `max_key_len` bytes, which may include entries outside
+/// of the prefix.
+#[derive(Clone)]
+pub struct HashedKeyDataCacher<C: DataCacher> {
+    cacher: C,
+    max_key_len: usize,
+}
+
+impl<C: DataCacher> HashedKeyDataCacher<C> {
+    /// Instantiates a cacher hashing keys longer than 128 bytes before
+    /// passing them to an existing cacher
+    pub fn new(cacher: C) -> Self {
+        HashedKeyDataCacher {
+            cacher,
+            max_key_len: 128,
+        }
+    }
+
+    /// Sets the length in bytes above which keys are hashed
+    pub fn with_max_key_len(mut self, max_key_len: usize) -> Self {
+        self.max_key_len = max_key_len;
+        self
+    }
+
+    /// Returns the key the wrapped cacher stores an entry under
+    pub fn hashed_key(&self, key: &str) -> String {
+        if key.len() <= self.max_key_len {
+            return key.to_owned();
+        }
+        let digest: String = Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!(
+            "{}{}{}",
+            self.head(key),
+            DIGEST_SEPARATOR,
+            &digest[..DIGEST_LEN]
+        )
+    }
+
+    /// Returns the longest start of the key within the maximum length
+    fn head<'a>(&self, key: &'a str) -> &'a str {
+        let mut end = self.max_key_len.min(key.len());
+        while !key.is_char_boundary(end) {
+            end -= 1;
+        }
+        &key[..end]
+    }
+}
+
+#[async_trait]
+impl<C: DataCacher> DataCacher for HashedKeyDataCacher<C> {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        self.cacher.set(&self.hashed_key(key), value).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        self.cacher.get(&self.hashed_key(key)).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        self.cacher.exists(&self.hashed_key(key)).await
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        self.cacher.expire(&self.hashed_key(key), seconds).await
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        self.cacher
+            .get_and_expire(&self.hashed_key(key), seconds)
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        self.cacher.delete(&self.hashed_key(key)).await
+    }
+
+    /// Deletes the keys starting with the prefix, hashed or not, since a
+    /// hashed key starts with the first `max_key_len` bytes of its original
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        self.cacher.delete_prefix(self.head(prefix)).await
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.cacher.get_default_key_expiration_seconds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashedKeyDataCacher;
+    use crate::{Data, DataCacher, DataValue, MemoryCacheConfig, MemoryDataCacher};
+
+    fn cacher() -> HashedKeyDataCacher<MemoryDataCacher> {
+        HashedKeyDataCacher::new(MemoryDataCacher::new(MemoryCacheConfig {
+            max_entries: 100,
+            max_total_bytes: usize::MAX,
+            default_key_expiration_seconds: 60,
+        }))
+        .with_max_key_len(8)
+    }
+
+    #[test]
+    fn test_hashes_only_long_keys() {
+        let cacher = cacher();
+        assert_eq!(".a.b.", cacher.hashed_key(".a.b."));
+        let hashed = cacher.hashed_key(".a.b.c.d.e.");
+        assert_eq!(".a.b.c.d#".len() + 32, hashed.len());
+        assert!(hashed.starts_with(".a.b.c.d#"));
+        assert_ne!(hashed, cacher.hashed_key(".a.b.c.d.f."));
+        assert!(cacher.hashed_key(".ü.ü.ü.ü.").starts_with(".ü.ü.#"));
+    }
+
+    #[tokio::test]
+    async fn test_stores_long_keys_hashed() {
+        let cacher = cacher();
+        let data = Data::new(".a.b.c.d.e.", DataValue::from(1u64));
+        cacher.set(".a.b.c.d.e.", data.clone()).await.unwrap();
+        assert_eq!(data, cacher.get(".a.b.c.d.e.").await.unwrap());
+        assert!(!cacher.cacher.exists(".a.b.c.d.e.").await.unwrap());
+        assert!(cacher
+            .cacher
+            .exists(&cacher.hashed_key(".a.b.c.d.e."))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deletes_hashed_keys_by_prefix() {
+        let cacher = cacher();
+        for path in &[".a.", ".a.b.c.d.e.", ".b.c.d.e.f."] {
+            cacher
+                .set(path, Data::new(path, DataValue::from(1u64)))
+                .await
+                .unwrap();
+        }
+        assert_eq!(2, cacher.delete_prefix(".a.").await.unwrap());
+        assert!(cacher.exists(".b.c.d.e.f.").await.unwrap());
+        assert_eq!(1, cacher.delete_prefix(".b.c.d.e.f.").await.unwrap());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::cache::{DataCacher, error::CacheError};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::Data;
+
+/// Returns the weight of a cached value: the size of its JSON serialization in bytes
+pub fn serialized_size(data: &Data) -> usize {
+    serde_json::to_vec(data).map(|bytes| bytes.len()).unwrap_or(usize::MAX)
+}
+
+/// Stores the configuration values used to construct a MemoryDataCacher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryCacheConfig {
+    /// The maximum number of entries held at once
+    pub max_entries: usize,
+    /// The maximum total weight of the entries held at once
+    pub max_total_bytes: usize,
+    pub default_key_expiration_seconds: usize,
+}
+
+struct CacheEntry {
+    data: Data,
+    weight: usize,
+    expires_at: Option<Instant>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// Keys by the tick at which they were last used, least recent first
+    recency: BTreeMap<u64, String>,
+    total_bytes: usize,
+    tick: u64,
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        self.total_bytes -= entry.weight;
+        Some(entry)
+    }
+
+    /// Returns the live entry with the key, dropping it if it has expired
+    fn live(&mut self, key: &str) -> Option<&mut CacheEntry> {
+        let expired = self.entries.get(key)?.expires_at.is_some_and(|at| at <= Instant::now());
+        if expired {
+            self.remove(key);
+            return None;
+        }
+        self.entries.get_mut(key)
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.recency.insert(tick, key.to_owned());
+        }
+    }
+}
+
+/// Stores an instance of an in-process cache bounded both in entries and in
+/// total weight, as measured by a weigher which defaults to `serialized_size`.
+/// The least recently used entries are evicted once either bound is exceeded,
+/// and values heavier than the whole cache are never cached, so a burst of
+/// large values cannot exhaust the host's memory. Clones share the same entries.
+#[derive(Clone)]
+pub struct MemoryDataCacher {
+    state: Arc<Mutex<CacheState>>,
+    config: MemoryCacheConfig,
+    weigher: fn(&Data) -> usize,
+}
+
+impl MemoryDataCacher {
+    pub fn new(config: MemoryCacheConfig) -> MemoryDataCacher {
+        MemoryDataCacher {
+            state: Arc::new(Mutex::new(CacheState::default())),
+            config,
+            weigher: serialized_size,
+        }
+    }
+
+    /// Weighs entries with the given function instead of their serialized size
+    pub fn with_weigher(mut self, weigher: fn(&Data) -> usize) -> Self {
+        self.weigher = weigher;
+        self
+    }
+
+    /// Returns the number of entries currently held, including expired
+    /// entries which have not been dropped yet
+    pub fn entry_count(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns the total weight of the entries currently held
+    pub fn total_bytes(&self) -> usize {
+        self.state.lock().unwrap().total_bytes
+    }
+}
+
+#[async_trait]
+impl DataCacher for MemoryDataCacher {
+
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        let weight = (self.weigher)(&value);
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+        if weight > self.config.max_total_bytes || self.config.max_entries == 0 {
+            return Ok(());
+        }
+
+        let expires_at = Instant::now()
+            .checked_add(Duration::from_secs(self.config.default_key_expiration_seconds as u64));
+        state.entries.insert(key.to_owned(), CacheEntry { data: value, weight, expires_at, last_used: 0 });
+        state.total_bytes += weight;
+        state.touch(key);
+        while state.entries.len() > self.config.max_entries || state.total_bytes > self.config.max_total_bytes {
+            let lru = match state.recency.values().next() {
+                Some(lru) => lru.clone(),
+                None => break,
+            };
+            state.remove(&lru);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        let data = state.live(key).map(|entry| entry.data.clone()).ok_or(CacheError::NotFound)?;
+        state.touch(key);
+        Ok(data)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        Ok(self.state.lock().unwrap().live(key).is_some())
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        match state.live(key) {
+            Some(entry) => {
+                entry.expires_at = Instant::now().checked_add(Duration::from_secs(seconds as u64));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        let data = match state.live(key) {
+            Some(entry) => {
+                entry.expires_at = Instant::now().checked_add(Duration::from_secs(seconds as u64));
+                entry.data.clone()
+            }
+            None => return Ok(None),
+        };
+        state.touch(key);
+        Ok(Some(data))
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        Ok(self.state.lock().unwrap().remove(key).is_some())
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<String> = state.entries.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+        for key in &keys {
+            state.remove(key);
+        }
+        Ok(keys.len() as u64)
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.config.default_key_expiration_seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryCacheConfig, MemoryDataCacher, serialized_size};
+    use crate::{Data, DataCacher, DataValue};
+
+    fn cacher(max_entries: usize, max_total_bytes: usize) -> MemoryDataCacher {
+        MemoryDataCacher::new(MemoryCacheConfig {
+            max_entries,
+            max_total_bytes,
+            default_key_expiration_seconds: 60,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let cacher = cacher(2, usize::MAX);
+        cacher.set("a", Data::new(".a.", DataValue::from(1u64))).await.unwrap();
+        cacher.set("b", Data::new(".b.", DataValue::from(2u64))).await.unwrap();
+        cacher.get("a").await.unwrap();
+        cacher.set("c", Data::new(".c.", DataValue::from(3u64))).await.unwrap();
+
+        assert!(cacher.exists("a").await.unwrap());
+        assert!(!cacher.exists("b").await.unwrap());
+        assert!(cacher.exists("c").await.unwrap());
+        assert_eq!(2, cacher.entry_count());
+    }
+
+    #[tokio::test]
+    async fn test_bounds_total_weight() {
+        let small = Data::new(".a.", DataValue::from(1u64));
+        let weight = serialized_size(&small);
+        let cacher = cacher(100, weight * 2);
+        cacher.set("a", small.clone()).await.unwrap();
+        cacher.set("b", small.clone()).await.unwrap();
+        cacher.set("c", small).await.unwrap();
+        assert_eq!(2, cacher.entry_count());
+        assert_eq!(weight * 2, cacher.total_bytes());
+        assert!(!cacher.exists("a").await.unwrap());
+
+        let large = Data::new(".large.", DataValue::from("x".repeat(weight * 2)));
+        cacher.set("large", large).await.unwrap();
+        assert!(!cacher.exists("large").await.unwrap());
+        assert_eq!(2, cacher.entry_count());
+    }
+
+    #[tokio::test]
+    async fn test_expire_and_delete_prefix() {
+        let cacher = cacher(10, usize::MAX).with_weigher(|_| 1);
+        cacher.set(".a.1.", Data::new(".a.1.", DataValue::from(1u64))).await.unwrap();
+        cacher.set(".a.2.", Data::new(".a.2.", DataValue::from(2u64))).await.unwrap();
+        cacher.set(".b.", Data::new(".b.", DataValue::from(3u64))).await.unwrap();
+        assert_eq!(3, cacher.total_bytes());
+
+        assert!(cacher.expire(".b.", 0).await.unwrap());
+        assert!(cacher.get(".b.").await.is_err());
+        assert_eq!(2, cacher.delete_prefix(".a.").await.unwrap());
+        assert_eq!(0, cacher.entry_count());
+    }
+
+    #[tokio::test]
+    async fn test_get_and_expire() {
+        let cacher = cacher(10, usize::MAX);
+        let data = Data::new(".a.", DataValue::from(1u64));
+        cacher.set(".a.", data.clone()).await.unwrap();
+
+        assert_eq!(Some(data), cacher.get_and_expire(".a.", 0).await.unwrap());
+        assert_eq!(None, cacher.get_and_expire(".a.", 60).await.unwrap());
+        assert_eq!(None, cacher.get_and_expire(".missing.", 60).await.unwrap());
+    }
+}
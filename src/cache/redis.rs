@@ -1,29 +1,43 @@
-use crate::cache::{DataCacher, error::CacheError};
+use crate::cache::{DataCacher, collection::{ancestors, CollectionCacher, PageKey}, encryption::CacheEncryptor, error::CacheError};
 use async_trait::async_trait;
-use std::time::Duration;
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, time::Duration};
 use mobc_redis::{redis, RedisConnectionManager};
 use mobc::{Connection, Pool};
 use mobc_redis::redis::{AsyncCommands, ToRedisArgs, FromRedisValue, RedisWrite, RedisResult, Value, from_redis_value, ErrorKind};
-use crate::Data;
+use crate::{Data, DataCodec, DataCollection};
+use serde::{de::DeserializeOwned, Serialize};
 
 pub type MobcPool = Pool<RedisConnectionManager>;
 pub type MobcCon = Connection<RedisConnectionManager>;
 
-/// Stores an instance of a redis-backed cache
+/// Stores an instance of a redis-backed cache. Entries are written with the
+/// cacher's codec, JSON by default, and read with whichever codec wrote them.
+///
+/// Commands added in redis 6.2, `GETEX`, and 6.0, `SET KEEPTTL`, are used
+/// until the server rejects them, after which the cacher falls back to the
+/// older equivalent of the rejected one for the rest of its life.
+///
+/// Entries and pages can be encrypted before they are written, see
+/// `with_encryptor`, for caches outside of the security boundary of the
+/// primary datastore.
 #[derive(Clone)]
 pub struct RedisDataCacher {
     pool: MobcPool,
-    cache_default_key_espiration_seconds: u64
+    cache_default_key_espiration_seconds: u64,
+    codec: DataCodec,
+    getex_unsupported: Arc<AtomicBool>,
+    keepttl_unsupported: Arc<AtomicBool>,
+    encryptor: Option<Arc<dyn CacheEncryptor>>
 }
 
 /// Stores the configuration values used to construct a RedisDataCacher
 pub struct RedisCacheConfig<'a> {
-    connection_string: &'a str,
-    cache_pool_timeout_seconds: u64,
-    cache_pool_max_open: u64,
-    cache_pool_max_idle: u64,
-    cache_pool_expire_seconds: u64,
-    cache_default_key_expiration_seconds: u64
+    pub connection_string: &'a str,
+    pub cache_pool_timeout_seconds: u64,
+    pub cache_pool_max_open: u64,
+    pub cache_pool_max_idle: u64,
+    pub cache_pool_expire_seconds: u64,
+    pub cache_default_key_expiration_seconds: u64
 }
 
 impl RedisDataCacher {
@@ -38,17 +52,109 @@ impl RedisDataCacher {
             .build(manager);
         Ok(RedisDataCacher {
             pool,
-            cache_default_key_espiration_seconds: config.cache_default_key_expiration_seconds
+            cache_default_key_espiration_seconds: config.cache_default_key_expiration_seconds,
+            codec: DataCodec::default(),
+            getex_unsupported: Arc::new(AtomicBool::new(false)),
+            keepttl_unsupported: Arc::new(AtomicBool::new(false)),
+            encryptor: None
         })
     }
 
+    /// Writes entries with the given codec instead of JSON
+    pub fn with_codec(mut self, codec: DataCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Encrypts every entry and page with the given encryptor before writing
+    /// it, and decrypts it when read. Entries written without the encryptor
+    /// can no longer be read.
+    pub fn with_encryptor<E: CacheEncryptor + 'static>(mut self, encryptor: E) -> Self {
+        self.encryptor = Some(Arc::new(encryptor));
+        self
+    }
+
+    /// Encodes a value with the cacher's codec and encrypts it, if the cacher
+    /// has an encryptor
+    fn seal<T: Serialize>(&self, key: &str, value: &T) -> Result<Vec<u8>, CacheError> {
+        let bytes = self.codec.encode(value).map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+        match self.encryptor {
+            Some(ref encryptor) => encryptor.encrypt(key, &bytes),
+            None => Ok(bytes),
+        }
+    }
+
+    /// Decrypts a value, if the cacher has an encryptor, and decodes it with
+    /// whichever codec wrote it
+    fn open<T: DeserializeOwned>(&self, key: &str, bytes: Vec<u8>) -> Result<T, CacheError> {
+        let bytes = match self.encryptor {
+            Some(ref encryptor) => encryptor.decrypt(key, &bytes)?,
+            None => bytes,
+        };
+        DataCodec::detect(&bytes).decode(&bytes).map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
+
     async fn get_con(pool: &MobcPool) -> Result<MobcCon, CacheError> {
         pool.get().await.map_err(|e| {
             CacheError::InternalError { source: Box::new(e), }
         })
     }
+
+    /// Overwrites an entry while keeping the time it has left to live, or
+    /// writes it without an expiration if there was no entry with the key
+    pub async fn set_keep_ttl(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        let value = self.seal(key, &value)?;
+        if !self.keepttl_unsupported.load(Ordering::Relaxed) {
+            let set = redis::cmd("SET")
+                .arg(key)
+                .arg(value.as_slice())
+                .arg("KEEPTTL")
+                .query_async::<_, ()>(&mut *con)
+                .await;
+            match set {
+                Err(e) if is_syntax_error(&e) => self.keepttl_unsupported.store(true, Ordering::Relaxed),
+                set => return set.map_err(|e| CacheError::InternalError { source: Box::new(e), }),
+            }
+        }
+        redis::Script::new(KEEP_TTL_SCRIPT)
+            .key(key)
+            .arg(value)
+            .invoke_async::<_, ()>(&mut *con)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
 }
 
+/// Returns whether the server rejected the named command as one it does not
+/// know, as servers predating it do
+fn is_unknown_command(e: &redis::RedisError, command: &str) -> bool {
+    e.kind() == ErrorKind::ResponseError
+        && e.detail().is_some_and(|detail| {
+            let detail = detail.to_lowercase();
+            detail.contains("unknown command") && detail.contains(&command.to_lowercase())
+        })
+}
+
+/// Returns whether the server rejected a command's arguments, as servers
+/// predating one of its options do. Only meaningful for commands whose other
+/// arguments are always valid, such as `SET` with `KEEPTTL`.
+fn is_syntax_error(e: &redis::RedisError) -> bool {
+    e.kind() == ErrorKind::ResponseError
+        && e.detail().is_some_and(|detail| detail.to_lowercase().starts_with("syntax error"))
+}
+
+/// Overwrites `KEYS[1]` with `ARGV[1]`, keeping its expiration, for servers
+/// older than redis 6.0
+const KEEP_TTL_SCRIPT: &str = r"
+local ttl = redis.call('PTTL', KEYS[1])
+if ttl > 0 then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ttl)
+else
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+";
+
 impl ToRedisArgs for Data {
     fn write_redis_args<W>(&self, out: &mut W)
         where
@@ -61,17 +167,29 @@ impl ToRedisArgs for Data {
 
 impl FromRedisValue for Data {
     fn from_redis_value(v: &Value) -> RedisResult<Data> {
-        let s: String = from_redis_value(v)?;
-        let d: Data = serde_json::from_str(&s).map_err(|_e| (ErrorKind::TypeError, "deserialization error!"))?;
+        let bytes: Vec<u8> = from_redis_value(v)?;
+        let d: Data = DataCodec::detect(&bytes).decode(&bytes).map_err(|_e| (ErrorKind::TypeError, "deserialization error!"))?;
         Ok(d)
     }
 }
 
+/// Escapes the characters redis treats specially in `SCAN MATCH` patterns
+fn escape_glob(s: &str) -> String {
+    s.chars().fold(String::with_capacity(s.len()), |mut escaped, c| {
+        if "\\*?[]^".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
 #[async_trait]
 impl DataCacher for RedisDataCacher {
 
     async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
         let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        let value = self.seal(key, &value)?;
         con.set_ex(key, value, self.get_default_key_expiration_seconds())
             .await
             .map_err(|e| CacheError::InternalError { source: Box::new(e), })
@@ -79,7 +197,10 @@ impl DataCacher for RedisDataCacher {
 
     async fn get(&self, key: &str) -> Result<Data, CacheError> {
         let mut con = RedisDataCacher::get_con(&self.pool).await?;
-        con.get(key).await.map_err(|e| CacheError::InternalError { source: Box::new(e), })
+        let bytes: Option<Vec<u8>> = con.get(key)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+        self.open(key, bytes.ok_or(CacheError::NotFound)?)
     }
 
     async fn exists(&self, key: &str) -> Result<bool, CacheError> {
@@ -92,7 +213,141 @@ impl DataCacher for RedisDataCacher {
         con.expire(key, seconds).await.map_err(|e| CacheError::InternalError { source: Box::new(e), })
     }
 
+    /// Gets the entry and resets its expiration with `GETEX`, or with an
+    /// atomic pipeline of `GET` and `EXPIRE` on servers older than 6.2, so a
+    /// cache hit costs one round trip
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        if !self.getex_unsupported.load(Ordering::Relaxed) {
+            let get = redis::cmd("GETEX")
+                .arg(key)
+                .arg("EX")
+                .arg(seconds)
+                .query_async::<_, Option<Vec<u8>>>(&mut *con)
+                .await;
+            match get {
+                Err(e) if is_unknown_command(&e, "GETEX") => self.getex_unsupported.store(true, Ordering::Relaxed),
+                get => {
+                    let bytes = get.map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+                    return bytes.map(|bytes| self.open(key, bytes)).transpose();
+                }
+            }
+        }
+        let (bytes, _): (Option<Vec<u8>>, bool) = redis::pipe()
+            .atomic()
+            .get(key)
+            .expire(key, seconds)
+            .query_async(&mut *con)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+        bytes.map(|bytes| self.open(key, bytes)).transpose()
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        con.del(key).await.map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        let pattern = format!("{}*", escape_glob(prefix));
+        let mut keys: Vec<String> = Vec::new();
+        {
+            let mut iter = con.scan_match::<_, String>(pattern)
+                .await
+                .map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+        }
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        con.del(keys).await.map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
+
     fn get_default_key_expiration_seconds(&self) -> usize {
         self.cache_default_key_espiration_seconds as usize
     }
 }
+
+/// Returns the key of the set tagging every cached page under a prefix
+fn tag_key(prefix: &str) -> String {
+    format!("page-tag:{}", prefix)
+}
+
+/// Caches pages under their composite key, and adds each page's key to a tag
+/// set for its path and every ancestor of it, so that invalidating a prefix
+/// only has to delete the members of the prefix's tag set. Tag sets expire
+/// along with the last page added to them.
+#[async_trait]
+impl CollectionCacher for RedisDataCacher {
+
+    async fn get_page(&self, key: &PageKey) -> Result<Option<DataCollection>, CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        let page_key = key.key();
+        let bytes: Option<Vec<u8>> = con.get(&page_key)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })?;
+        bytes.map(|bytes| self.open(&page_key, bytes)).transpose()
+    }
+
+    async fn set_page(&self, key: &PageKey, page: DataCollection) -> Result<(), CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        let page_key = key.key();
+        let value = self.seal(&page_key, &page)?;
+        let seconds = self.get_default_key_expiration_seconds();
+        let mut pipe = redis::pipe();
+        pipe.atomic().set_ex(&page_key, value, seconds).ignore();
+        for ancestor in ancestors(&key.path) {
+            let tag = tag_key(ancestor);
+            pipe.sadd(&tag, &page_key).ignore().expire(&tag, seconds).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut *con)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
+
+    async fn invalidate(&self, prefix: &str) -> Result<(), CacheError> {
+        let mut con = RedisDataCacher::get_con(&self.pool).await?;
+        redis::Script::new(INVALIDATE_SCRIPT)
+            .key(tag_key(prefix))
+            .invoke_async::<_, ()>(&mut *con)
+            .await
+            .map_err(|e| CacheError::InternalError { source: Box::new(e), })
+    }
+}
+
+/// Deletes the pages tagged with `KEYS[1]` and the tag set itself, atomically
+/// so that no page is tagged while the set is being emptied
+const INVALIDATE_SCRIPT: &str = r"
+for _, key in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    redis.call('DEL', key)
+end
+redis.call('DEL', KEYS[1])
+";
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_glob, is_syntax_error, is_unknown_command};
+    use mobc_redis::redis::{ErrorKind, RedisError};
+
+    #[test]
+    fn test_escape_glob() {
+        assert_eq!(".a\\*.b\\?.", escape_glob(".a*.b?."));
+    }
+
+    #[test]
+    fn test_is_unsupported() {
+        let unknown = RedisError::from((ErrorKind::ResponseError, "An error was signalled by the server", "unknown command `GETEX`, with args beginning with: `k`".to_owned()));
+        let other_unknown = RedisError::from((ErrorKind::ResponseError, "An error was signalled by the server", "unknown command 'FOO'".to_owned()));
+        let syntax = RedisError::from((ErrorKind::ResponseError, "An error was signalled by the server", "syntax error".to_owned()));
+        let wrong_type = RedisError::from((ErrorKind::TypeError, "Response was of incompatible type", "unknown command GETEX".to_owned()));
+        assert!(is_unknown_command(&unknown, "GETEX"));
+        assert!(!is_unknown_command(&other_unknown, "GETEX"));
+        assert!(!is_unknown_command(&syntax, "GETEX"));
+        assert!(!is_unknown_command(&wrong_type, "GETEX"));
+        assert!(is_syntax_error(&syntax));
+        assert!(!is_syntax_error(&unknown));
+    }
+}
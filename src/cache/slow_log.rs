@@ -0,0 +1,99 @@
+use crate::cache::{error::CacheError, DataCacher};
+use crate::storage::slow_log::log_if_slow;
+use crate::Data;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Wraps a cacher to log every operation taking longer than a threshold, in
+/// the same way as `SlowLogDataStorer`
+#[derive(Clone)]
+pub struct SlowLogDataCacher<C: DataCacher> {
+    cacher: C,
+    backend: String,
+    threshold: Duration,
+}
+
+impl<C: DataCacher> SlowLogDataCacher<C> {
+    /// Instantiates a cacher logging the operations of an existing cacher
+    /// which take longer than `threshold`
+    pub fn new(cacher: C, backend: &str, threshold: Duration) -> Self {
+        SlowLogDataCacher {
+            cacher,
+            backend: backend.to_owned(),
+            threshold,
+        }
+    }
+}
+
+#[async_trait]
+impl<C: DataCacher> DataCacher for SlowLogDataCacher<C> {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        log_if_slow(
+            &self.backend,
+            "set",
+            key,
+            self.threshold,
+            self.cacher.set(key, value),
+        )
+        .await
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        log_if_slow(
+            &self.backend,
+            "get",
+            key,
+            self.threshold,
+            self.cacher.get(key),
+        )
+        .await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        log_if_slow(
+            &self.backend,
+            "exists",
+            key,
+            self.threshold,
+            self.cacher.exists(key),
+        )
+        .await
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        let expire = self.cacher.expire(key, seconds);
+        log_if_slow(&self.backend, "expire", key, self.threshold, expire).await
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        let get = self.cacher.get_and_expire(key, seconds);
+        log_if_slow(&self.backend, "get_and_expire", key, self.threshold, get).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        log_if_slow(
+            &self.backend,
+            "delete",
+            key,
+            self.threshold,
+            self.cacher.delete(key),
+        )
+        .await
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        let delete = self.cacher.delete_prefix(prefix);
+        log_if_slow(
+            &self.backend,
+            "delete_prefix",
+            prefix,
+            self.threshold,
+            delete,
+        )
+        .await
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        self.cacher.get_default_key_expiration_seconds()
+    }
+}
@@ -0,0 +1,301 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// The encodings `Data` can be cached and transported with. CBOR and
+/// MessagePack are only supported with the `codec` feature, enabled by
+/// default.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DataCodec {
+    #[default]
+    Json,
+    Cbor,
+    MessagePack,
+}
+
+/// Returned when a value cannot be encoded or decoded with a codec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecError {
+    pub codec: DataCodec,
+    pub reason: String,
+}
+
+impl Error for CodecError {}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.codec.content_type(), self.reason)
+    }
+}
+
+impl DataCodec {
+    /// Returns the media type of the encoding, for HTTP content negotiation
+    pub fn content_type(self) -> &'static str {
+        match self {
+            DataCodec::Json => "application/json",
+            DataCodec::Cbor => "application/cbor",
+            DataCodec::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Returns the codec of a media type, ignoring any parameters such as the
+    /// charset, or `None` if it is not one of the supported encodings
+    pub fn from_content_type(content_type: &str) -> Option<DataCodec> {
+        let media_type = content_type.split(';').next()?.trim();
+        [DataCodec::Json, DataCodec::Cbor, DataCodec::MessagePack]
+            .iter()
+            .copied()
+            .find(|codec| media_type.eq_ignore_ascii_case(codec.content_type()))
+    }
+
+    /// Returns the codec which most likely encoded the given bytes, judging by
+    /// their first byte: JSON values encoded by this crate are objects, while
+    /// CBOR and MessagePack values begin with a map head, whose ranges do not
+    /// overlap each other or `{`.
+    pub fn detect(bytes: &[u8]) -> DataCodec {
+        match bytes.first() {
+            Some(b) if b >> 5 == 5 => DataCodec::Cbor,
+            Some(0x80..=0x8f) | Some(0xde) | Some(0xdf) => DataCodec::MessagePack,
+            _ => DataCodec::Json,
+        }
+    }
+
+    /// Encodes a value
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            DataCodec::Json => serde_json::to_vec(value).map_err(|e| self.error(e)),
+            DataCodec::Cbor => encode_cbor(value),
+            DataCodec::MessagePack => encode_msgpack(value),
+        }
+    }
+
+    /// Decodes a value
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            DataCodec::Cbor => decode_cbor(bytes),
+            _ => self.decode_borrowed(bytes),
+        }
+    }
+
+    /// Decodes a value which may borrow from the bytes, such as a `DataRef`.
+    /// JSON and MessagePack hand strings and bytes over as slices of the
+    /// input; CBOR cannot, and is only decoded with `decode`.
+    pub fn decode_borrowed<'de, T: Deserialize<'de>>(
+        self,
+        bytes: &'de [u8],
+    ) -> Result<T, CodecError> {
+        match self {
+            DataCodec::Json => serde_json::from_slice(bytes).map_err(|e| self.error(e)),
+            DataCodec::Cbor => Err(self.error("values cannot borrow from CBOR; use `decode`")),
+            DataCodec::MessagePack => decode_msgpack(bytes),
+        }
+    }
+
+    fn error<E: Display>(self, e: E) -> CodecError {
+        CodecError {
+            codec: self,
+            reason: e.to_string(),
+        }
+    }
+
+    #[cfg(not(feature = "codec"))]
+    fn disabled(self) -> CodecError {
+        self.error("support is disabled; enable the `codec` feature")
+    }
+}
+
+#[cfg(feature = "codec")]
+fn encode_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(value, &mut bytes).map_err(|e| DataCodec::Cbor.error(e))?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "codec")]
+fn decode_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+    ciborium::de::from_reader(bytes).map_err(|e| DataCodec::Cbor.error(e))
+}
+
+#[cfg(not(feature = "codec"))]
+fn encode_cbor<T: Serialize>(_value: &T) -> Result<Vec<u8>, CodecError> {
+    Err(DataCodec::Cbor.disabled())
+}
+
+#[cfg(not(feature = "codec"))]
+fn decode_cbor<T: DeserializeOwned>(_bytes: &[u8]) -> Result<T, CodecError> {
+    Err(DataCodec::Cbor.disabled())
+}
+
+/// Encodes MessagePack with struct fields named, as they are in JSON and CBOR,
+/// and bytes as `bin`, the counterpart of CBOR byte strings
+#[cfg(feature = "codec")]
+fn encode_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+    rmp_serde::to_vec_named(value).map_err(|e| DataCodec::MessagePack.error(e))
+}
+
+#[cfg(feature = "codec")]
+fn decode_msgpack<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, CodecError> {
+    rmp_serde::from_slice(bytes).map_err(|e| DataCodec::MessagePack.error(e))
+}
+
+#[cfg(not(feature = "codec"))]
+fn encode_msgpack<T: Serialize>(_value: &T) -> Result<Vec<u8>, CodecError> {
+    Err(DataCodec::MessagePack.disabled())
+}
+
+#[cfg(not(feature = "codec"))]
+fn decode_msgpack<'de, T: Deserialize<'de>>(_bytes: &'de [u8]) -> Result<T, CodecError> {
+    Err(DataCodec::MessagePack.disabled())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DataCodec;
+    use crate::{Data, DataType, DataValue, DataValueCollection, EncryptedDataValue};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_content_types() {
+        assert_eq!(
+            Some(DataCodec::Json),
+            DataCodec::from_content_type("application/json; charset=utf-8")
+        );
+        assert_eq!(
+            Some(DataCodec::Cbor),
+            DataCodec::from_content_type("Application/CBOR")
+        );
+        assert_eq!(
+            Some(DataCodec::MessagePack),
+            DataCodec::from_content_type("application/msgpack")
+        );
+        assert_eq!(None, DataCodec::from_content_type("text/plain"));
+    }
+
+    #[test]
+    fn test_encodes_cbor_rfc_examples() {
+        for (value, expected) in [
+            (json!(0), vec![0x00]),
+            (json!(24), vec![0x18, 0x18]),
+            (json!(1000), vec![0x19, 0x03, 0xe8]),
+            (json!(-1000), vec![0x39, 0x03, 0xe7]),
+            (
+                json!(1.1),
+                vec![0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a],
+            ),
+            (json!(true), vec![0xf5]),
+            (json!("a"), vec![0x61, 0x61]),
+            (json!([1, [2]]), vec![0x82, 0x01, 0x81, 0x02]),
+            (json!({"a": 1}), vec![0xa1, 0x61, 0x61, 0x01]),
+        ] {
+            assert_eq!(expected, DataCodec::Cbor.encode(&value).unwrap());
+            assert_eq!(value, DataCodec::Cbor.decode::<Value>(&expected).unwrap());
+        }
+        // An indefinite-length array holding a half float
+        let bytes = [0x9f, 0xf9, 0x3e, 0x00, 0x01, 0xff];
+        assert_eq!(
+            json!([1.5, 1]),
+            DataCodec::Cbor.decode::<Value>(&bytes).unwrap()
+        );
+        assert!(DataCodec::Cbor.decode::<Value>(&[0x82, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_encodes_msgpack_spec_examples() {
+        for (value, expected) in [
+            (json!(127), vec![0x7f]),
+            (json!(128), vec![0xcc, 0x80]),
+            (json!(-33), vec![0xd0, 0xdf]),
+            (json!(null), vec![0xc0]),
+            (json!("a"), vec![0xa1, 0x61]),
+            (json!([1, [false]]), vec![0x92, 0x01, 0x91, 0xc2]),
+            (json!({"a": 1}), vec![0x81, 0xa1, 0x61, 0x01]),
+        ] {
+            assert_eq!(expected, DataCodec::MessagePack.encode(&value).unwrap());
+            assert_eq!(
+                value,
+                DataCodec::MessagePack.decode::<Value>(&expected).unwrap()
+            );
+        }
+        assert!(DataCodec::MessagePack
+            .decode::<Value>(&[0x92, 0x01])
+            .is_err());
+    }
+
+    #[test]
+    fn test_round_trips_data() {
+        let data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from(u64::MAX),
+                DataValue::from(i64::MIN),
+                DataValue::from(0.5),
+                DataValue::from(vec![0u8, 255]),
+            ]),
+        );
+        for codec in [DataCodec::Cbor, DataCodec::MessagePack] {
+            let bytes = codec.encode(&data).unwrap();
+            assert!(bytes.len() < serde_json::to_vec(&data).unwrap().len());
+            assert_eq!(data, codec.decode::<Data>(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_encodes_bytes_as_byte_strings() {
+        let data = Data::new(
+            ".a.",
+            DataValue::Encrypted(EncryptedDataValue::new(
+                vec![0xff; 256],
+                DataType::Bytes,
+                "k",
+            )),
+        );
+        for (codec, head) in [
+            (DataCodec::Cbor, [0x59, 0x01, 0x00]),
+            (DataCodec::MessagePack, [0xc5, 0x01, 0x00]),
+        ] {
+            let bytes = codec.encode(&data).unwrap();
+            assert!(bytes.len() < 384);
+            assert!(bytes.windows(3).any(|window| window == head));
+            assert_eq!(data, codec.decode::<Data>(&bytes).unwrap());
+        }
+        assert!(serde_json::to_string(&data).unwrap().contains("[255,255,"));
+    }
+
+    #[test]
+    fn test_decodes_bytes_encoded_as_arrays() {
+        let value = json!({"path": ".a.", "value": [{"Unencrypted": {"Bytes": [0, 255]}}]});
+        let data = Data::new(".a.", DataValue::from(vec![0u8, 255]));
+        for codec in [DataCodec::Cbor, DataCodec::MessagePack] {
+            let bytes = codec.encode(&value).unwrap();
+            assert_eq!(data, codec.decode::<Data>(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_round_trips_floats_json_cannot_represent() {
+        let data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from(f64::NAN),
+                DataValue::from(f64::INFINITY),
+                DataValue::from(f64::NEG_INFINITY),
+            ]),
+        );
+        for codec in [DataCodec::Cbor, DataCodec::MessagePack] {
+            let bytes = codec.encode(&data).unwrap();
+            assert_eq!(data, codec.decode::<Data>(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let data = Data::new(".a.", DataValue::from(1u64));
+        for codec in [DataCodec::Json, DataCodec::Cbor, DataCodec::MessagePack] {
+            let bytes = codec.encode(&data).unwrap();
+            assert_eq!(codec, DataCodec::detect(&bytes));
+            assert_eq!(data, codec.decode::<Data>(&bytes).unwrap());
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    Data, DataStorerError, DataType, DataValue, DataValueCollection, EncryptedDataValue,
+    UnencryptedDataValue,
+};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use smallvec::{smallvec, SmallVec};
+
+/// Header through which a client advertises the wire version it speaks
+pub const WIRE_VERSION_HEADER: &str = "X-Redact-Wire-Version";
+
+/// The versions of the schema `Data` is exchanged with between redact services.
+/// Decoding is tolerant of every version; encoding for an older version drops
+/// or rejects what it cannot represent, see `to_wire`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WireVersion {
+    /// The original schema, holding only the path and values of data, with
+    /// no bytes values
+    V1,
+    /// Adds integrity checksums, signatures, write times, key names, labels,
+    /// expiry times, recipients' wrapped keys, links, and bytes values
+    V2,
+}
+
+impl WireVersion {
+    /// The version this crate writes by default
+    pub const CURRENT: WireVersion = WireVersion::V2;
+
+    /// Returns the number identifying the version on the wire
+    pub fn number(self) -> u32 {
+        match self {
+            WireVersion::V1 => 1,
+            WireVersion::V2 => 2,
+        }
+    }
+
+    /// Returns the newest version spoken both by this crate and by a peer
+    /// speaking versions up to the given number, or `None` if there is none
+    pub fn negotiate(peer: u32) -> Option<WireVersion> {
+        match peer {
+            0 => None,
+            1 => Some(WireVersion::V1),
+            _ => Some(WireVersion::CURRENT),
+        }
+    }
+}
+
+/// Encodes data as a peer speaking the given wire version expects it. Older
+/// versions cannot carry integrity checksums, signatures, write times, key
+/// names, labels, expiry times, recipients' wrapped keys, or links, which are
+/// dropped, nor bytes values, which fail with `WireIncompatible`.
+pub fn to_wire(data: &Data, version: WireVersion) -> Result<Value, DataStorerError> {
+    let encode_error = |e: serde_json::Error| DataStorerError::WireIncompatible {
+        path: data.path(),
+        reason: e.to_string(),
+    };
+    match version {
+        WireVersion::V2 => serde_json::to_value(data).map_err(encode_error),
+        WireVersion::V1 => {
+            if data
+                .value()
+                .0
+                .iter()
+                .any(|v| v.datatype() == DataType::Bytes)
+            {
+                return Err(DataStorerError::WireIncompatible {
+                    path: data.path(),
+                    reason: "bytes values require wire version 2".to_owned(),
+                });
+            }
+            let value = serde_json::to_value(data.value()).map_err(encode_error)?;
+            Ok(json!({ "path": data.path(), "value": value }))
+        }
+    }
+}
+
+/// The externally tagged encoding `DataValue` is serialized with
+#[derive(Deserialize)]
+enum TaggedDataValue {
+    Encrypted(EncryptedDataValue),
+    Unencrypted(UnencryptedDataValue),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WireDataValue {
+    Tagged(TaggedDataValue),
+    /// An unencrypted value missing its `Unencrypted` tag
+    Bare(UnencryptedDataValue),
+    /// A plain JSON scalar, typed as `From<Value>` does
+    Plain(Value),
+}
+
+impl<'de> Deserialize<'de> for DataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match WireDataValue::deserialize(deserializer)? {
+            WireDataValue::Tagged(TaggedDataValue::Encrypted(e)) => Ok(DataValue::Encrypted(e)),
+            WireDataValue::Tagged(TaggedDataValue::Unencrypted(u)) | WireDataValue::Bare(u) => {
+                Ok(DataValue::Unencrypted(u))
+            }
+            WireDataValue::Plain(v @ Value::Bool(_))
+            | WireDataValue::Plain(v @ Value::Number(_))
+            | WireDataValue::Plain(v @ Value::String(_)) => Ok(v.into()),
+            WireDataValue::Plain(v) => Err(D::Error::custom(format!(
+                "expected a data value, found {}",
+                v
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WireDataValueCollection {
+    Many(SmallVec<[DataValue; 1]>),
+    /// A single value, as held by data before values became collections
+    One(DataValue),
+}
+
+impl<'de> Deserialize<'de> for DataValueCollection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match WireDataValueCollection::deserialize(deserializer)? {
+            WireDataValueCollection::Many(values) => DataValueCollection(values),
+            WireDataValueCollection::One(value) => DataValueCollection(smallvec![value]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_wire, WireVersion};
+    use crate::{
+        Data, DataStorerError, DataType, DataValue, DataValueCollection, EncryptedDataValue,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_decodes_current_schema() {
+        let mut data = Data::new(".a.", DataValue::from(1u64));
+        data.set_written_at(Some(5));
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(data, serde_json::from_str::<Data>(&json).unwrap());
+        let encrypted = DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, "k"));
+        let json = serde_json::to_string(&encrypted).unwrap();
+        assert_eq!(encrypted, serde_json::from_str::<DataValue>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_decodes_previous_schemas_tolerantly() {
+        let single: Data = serde_json::from_value(json!({
+            "path": "a",
+            "value": {"Unencrypted": {"U64": 1}},
+        }))
+        .unwrap();
+        assert_eq!(Data::new(".a.", DataValue::from(1u64)), single);
+
+        let values: DataValueCollection = serde_json::from_value(json!([
+            {"String": "x"},
+            true,
+            -2,
+            {"Encrypted": {"value": [1], "datatype": "u64", "keyname": "k"}},
+        ]))
+        .unwrap();
+        assert_eq!(
+            vec![
+                DataValue::from("x"),
+                DataValue::from(true),
+                DataValue::from(-2i64),
+                DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, "k")),
+            ],
+            values.0.to_vec()
+        );
+        assert!(serde_json::from_value::<DataValue>(json!({"a": 1})).is_err());
+        assert!(serde_json::from_value::<DataValue>(json!(null)).is_err());
+    }
+
+    #[test]
+    fn test_to_wire_v1() {
+        let mut data = Data::new(".a.", DataValue::from(1u64));
+        data.set_written_at(Some(5));
+        data.seal_integrity();
+        assert_eq!(
+            json!({"path": ".a.", "value": [{"Unencrypted": {"U64": 1}}]}),
+            to_wire(&data, WireVersion::V1).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&data).unwrap(),
+            to_wire(&data, WireVersion::V2).unwrap()
+        );
+
+        let bytes = Data::new(".b.", DataValue::from(vec![1u8]));
+        assert!(matches!(
+            to_wire(&bytes, WireVersion::V1),
+            Err(DataStorerError::WireIncompatible { .. })
+        ));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(None, WireVersion::negotiate(0));
+        assert_eq!(Some(WireVersion::V1), WireVersion::negotiate(1));
+        assert_eq!(Some(WireVersion::V2), WireVersion::negotiate(7));
+    }
+}
@@ -0,0 +1,515 @@
+pub mod error;
+pub mod factory;
+pub mod registry;
+
+use crate::cache::redis::{RedisCacheConfig, RedisDataCacher};
+use crate::{
+    AnyDataStorer, BoxedDataCacher, CacheError, Data, DataCacher, FileDataStorer,
+    MemoryCacheConfig, MemoryDataCacher, MemoryDataStorer, MongoDataStorer, RedactDataStorer,
+};
+use async_trait::async_trait;
+use error::ConfigError;
+use registry::BackendRegistry;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Number, Value};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+
+/// Configures which storer backend to use, selected by its `backend` field:
+///
+/// ```toml
+/// [storage]
+/// backend = "mongodb"
+/// url = "mongodb://localhost:27017"
+/// db_name = "redact"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StorageConfig {
+    Memory,
+    File {
+        root: PathBuf,
+    },
+    Mongodb {
+        url: String,
+        db_name: String,
+        #[serde(default)]
+        collection_name: Option<String>,
+    },
+    Redact {
+        url: String,
+        #[serde(default)]
+        streamed_collections: bool,
+    },
+    /// A backend registered with the `BackendRegistry` under the URI's scheme
+    Plugin {
+        uri: String,
+    },
+}
+
+/// Configures which cacher backend to use, selected by its `backend` field
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case", deny_unknown_fields)]
+pub enum CacheConfig {
+    Memory {
+        max_entries: usize,
+        max_total_bytes: usize,
+        default_key_expiration_seconds: usize,
+    },
+    Redis {
+        connection_string: String,
+        #[serde(default = "default_pool_timeout_seconds")]
+        pool_timeout_seconds: u64,
+        #[serde(default = "default_pool_max_open")]
+        pool_max_open: u64,
+        #[serde(default = "default_pool_max_idle")]
+        pool_max_idle: u64,
+        #[serde(default = "default_pool_expire_seconds")]
+        pool_expire_seconds: u64,
+        default_key_expiration_seconds: u64,
+    },
+    /// A backend registered with the `BackendRegistry` under the URI's scheme
+    Plugin { uri: String },
+}
+
+fn default_pool_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_pool_max_open() -> u64 {
+    16
+}
+
+fn default_pool_max_idle() -> u64 {
+    8
+}
+
+fn default_pool_expire_seconds() -> u64 {
+    300
+}
+
+/// The configuration of an application's storer and, optionally, cacher, as
+/// loaded from a file with a `[storage]` table and an optional `[cache]`
+/// table
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactConfig {
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+}
+
+impl RedactConfig {
+    /// Loads the configuration from a `.toml` or `.json` file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        from_file(path.as_ref())
+    }
+
+    /// Parses the configuration from a TOML document
+    pub fn from_toml_str(toml: &str) -> Result<Self, ConfigError> {
+        from_value(toml_to_json(toml)?)
+    }
+}
+
+impl StorageConfig {
+    /// Loads the configuration from the environment variables starting with
+    /// `prefix` and an underscore, e.g. `REDACT_STORAGE_BACKEND` and
+    /// `REDACT_STORAGE_URL` for the prefix `REDACT_STORAGE`. Values which
+    /// parse as booleans or numbers are read as such.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        from_vars(prefix, std::env::vars())
+    }
+}
+
+impl CacheConfig {
+    /// Loads the configuration from the environment variables starting with
+    /// `prefix` and an underscore, in the same way as `StorageConfig::from_env`
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        from_vars(prefix, std::env::vars())
+    }
+}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ConfigError> {
+    serde_json::from_value(value).map_err(|source| ConfigError::Invalid { source })
+}
+
+fn from_file<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io { source })?;
+    let value = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml_to_json(&contents)?,
+        Some("json") => serde_json::from_str(&contents).map_err(|e| ConfigError::Parse {
+            reason: e.to_string(),
+        })?,
+        _ => {
+            return Err(ConfigError::UnsupportedFormat {
+                path: path.display().to_string(),
+            })
+        }
+    };
+    from_value(value)
+}
+
+fn from_vars<T: DeserializeOwned>(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<T, ConfigError> {
+    let prefix = format!("{}_", prefix);
+    let fields: Map<String, Value> = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let field = name.strip_prefix(&prefix)?.to_lowercase();
+            Some((field, env_value(value)))
+        })
+        .collect();
+    from_value(Value::Object(fields))
+}
+
+/// Reads an environment variable's value as a boolean or number if it
+/// parses as one, and as a string otherwise
+fn env_value(value: String) -> Value {
+    if let Ok(b) = value.parse::<bool>() {
+        Value::Bool(b)
+    } else if let Ok(n) = value.parse::<u64>() {
+        Value::from(n)
+    } else if let Ok(n) = value.parse::<i64>() {
+        Value::from(n)
+    } else {
+        Value::String(value)
+    }
+}
+
+fn toml_to_json(toml: &str) -> Result<Value, ConfigError> {
+    let document: DocumentMut =
+        toml.parse()
+            .map_err(|e: toml_edit::TomlError| ConfigError::Parse {
+                reason: e.message().to_owned(),
+            })?;
+    Ok(item_to_json(document.as_item()))
+}
+
+fn item_to_json(item: &Item) -> Value {
+    match item {
+        Item::None => Value::Null,
+        Item::Value(value) => value_to_json(value),
+        Item::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, item)| (key.to_owned(), item_to_json(item)))
+                .collect(),
+        ),
+        Item::ArrayOfTables(tables) => Value::Array(
+            tables
+                .iter()
+                .map(|table| item_to_json(&Item::Table(table.clone())))
+                .collect(),
+        ),
+    }
+}
+
+fn value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::String(s.value().to_owned()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Number::from_f64(*f.value())
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        toml_edit::Value::Datetime(d) => Value::String(d.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(value_to_json).collect()),
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_owned(), value_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Builds the storer a configuration describes, holding storers of plugins
+/// as `AnyDataStorer::Boxed`
+pub async fn build_storer(config: &StorageConfig) -> Result<AnyDataStorer, ConfigError> {
+    Ok(match config {
+        StorageConfig::Memory => AnyDataStorer::Memory(MemoryDataStorer::new()),
+        StorageConfig::File { root } => AnyDataStorer::File(Box::new(
+            FileDataStorer::new(root)
+                .await
+                .map_err(|source| ConfigError::Storer { source })?,
+        )),
+        StorageConfig::Mongodb {
+            url,
+            db_name,
+            collection_name,
+        } => {
+            let storer = MongoDataStorer::new(url, db_name).await;
+            AnyDataStorer::Mongodb(match collection_name {
+                Some(collection_name) => storer.with_collection_name(collection_name),
+                None => storer,
+            })
+        }
+        StorageConfig::Redact {
+            url,
+            streamed_collections,
+        } => AnyDataStorer::Redact(
+            RedactDataStorer::new(url).with_streamed_collections(*streamed_collections),
+        ),
+        StorageConfig::Plugin { uri } => {
+            AnyDataStorer::Boxed(BackendRegistry::global().build_storer(uri).await?)
+        }
+    })
+}
+
+/// A cacher of whichever backend was configured, see `build_cacher`
+#[derive(Clone)]
+pub enum ConfiguredDataCacher {
+    Memory(MemoryDataCacher),
+    Redis(RedisDataCacher),
+    Plugin(BoxedDataCacher),
+}
+
+/// Builds the cacher a configuration describes
+pub fn build_cacher(config: &CacheConfig) -> Result<ConfiguredDataCacher, ConfigError> {
+    Ok(match config {
+        CacheConfig::Memory {
+            max_entries,
+            max_total_bytes,
+            default_key_expiration_seconds,
+        } => ConfiguredDataCacher::Memory(MemoryDataCacher::new(MemoryCacheConfig {
+            max_entries: *max_entries,
+            max_total_bytes: *max_total_bytes,
+            default_key_expiration_seconds: *default_key_expiration_seconds,
+        })),
+        CacheConfig::Redis {
+            connection_string,
+            pool_timeout_seconds,
+            pool_max_open,
+            pool_max_idle,
+            pool_expire_seconds,
+            default_key_expiration_seconds,
+        } => ConfiguredDataCacher::Redis(
+            RedisDataCacher::new(RedisCacheConfig {
+                connection_string,
+                cache_pool_timeout_seconds: *pool_timeout_seconds,
+                cache_pool_max_open: *pool_max_open,
+                cache_pool_max_idle: *pool_max_idle,
+                cache_pool_expire_seconds: *pool_expire_seconds,
+                cache_default_key_expiration_seconds: *default_key_expiration_seconds,
+            })
+            .map_err(|source| ConfigError::Cacher { source })?,
+        ),
+        CacheConfig::Plugin { uri } => {
+            ConfiguredDataCacher::Plugin(BackendRegistry::global().build_cacher(uri)?)
+        }
+    })
+}
+
+#[async_trait]
+impl DataCacher for ConfiguredDataCacher {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.set(key, value).await,
+            ConfiguredDataCacher::Redis(c) => c.set(key, value).await,
+            ConfiguredDataCacher::Plugin(c) => c.set(key, value).await,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Data, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.get(key).await,
+            ConfiguredDataCacher::Redis(c) => c.get(key).await,
+            ConfiguredDataCacher::Plugin(c) => c.get(key).await,
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.exists(key).await,
+            ConfiguredDataCacher::Redis(c) => c.exists(key).await,
+            ConfiguredDataCacher::Plugin(c) => c.exists(key).await,
+        }
+    }
+
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.expire(key, seconds).await,
+            ConfiguredDataCacher::Redis(c) => c.expire(key, seconds).await,
+            ConfiguredDataCacher::Plugin(c) => c.expire(key, seconds).await,
+        }
+    }
+
+    async fn get_and_expire(&self, key: &str, seconds: usize) -> Result<Option<Data>, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.get_and_expire(key, seconds).await,
+            ConfiguredDataCacher::Redis(c) => c.get_and_expire(key, seconds).await,
+            ConfiguredDataCacher::Plugin(c) => c.get_and_expire(key, seconds).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.delete(key).await,
+            ConfiguredDataCacher::Redis(c) => c.delete(key).await,
+            ConfiguredDataCacher::Plugin(c) => c.delete(key).await,
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError> {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.delete_prefix(prefix).await,
+            ConfiguredDataCacher::Redis(c) => c.delete_prefix(prefix).await,
+            ConfiguredDataCacher::Plugin(c) => c.delete_prefix(prefix).await,
+        }
+    }
+
+    fn get_default_key_expiration_seconds(&self) -> usize {
+        match self {
+            ConfiguredDataCacher::Memory(c) => c.get_default_key_expiration_seconds(),
+            ConfiguredDataCacher::Redis(c) => c.get_default_key_expiration_seconds(),
+            ConfiguredDataCacher::Plugin(c) => c.get_default_key_expiration_seconds(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_cacher, build_storer, error::ConfigError, from_vars, AnyDataStorer, CacheConfig,
+        ConfiguredDataCacher, RedactConfig, StorageConfig,
+    };
+    use crate::{Data, DataCacher, DataStorer, DataValue};
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parses_toml() {
+        let config = RedactConfig::from_toml_str(
+            r#"
+            [storage]
+            backend = "redact"
+            url = "http://redact-store"
+            streamed_collections = true
+
+            [cache]
+            backend = "redis"
+            connection_string = "redis://localhost"
+            default_key_expiration_seconds = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            StorageConfig::Redact {
+                url: "http://redact-store".to_owned(),
+                streamed_collections: true,
+            },
+            config.storage
+        );
+        assert_eq!(
+            Some(CacheConfig::Redis {
+                connection_string: "redis://localhost".to_owned(),
+                pool_timeout_seconds: 30,
+                pool_max_open: 16,
+                pool_max_idle: 8,
+                pool_expire_seconds: 300,
+                default_key_expiration_seconds: 60,
+            }),
+            config.cache
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_and_invalid_toml() {
+        assert!(matches!(
+            RedactConfig::from_toml_str("[storage"),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            RedactConfig::from_toml_str("[storage]\nbackend = \"cassandra\""),
+            Err(ConfigError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_loads_from_vars() {
+        let config: StorageConfig = from_vars(
+            "REDACT_STORAGE",
+            vars(&[
+                ("REDACT_STORAGE_BACKEND", "mongodb"),
+                ("REDACT_STORAGE_URL", "mongodb://localhost:27017"),
+                ("REDACT_STORAGE_DB_NAME", "redact"),
+                ("REDACT_CACHE_BACKEND", "redis"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            StorageConfig::Mongodb {
+                url: "mongodb://localhost:27017".to_owned(),
+                db_name: "redact".to_owned(),
+                collection_name: None,
+            },
+            config
+        );
+
+        let config: CacheConfig = from_vars(
+            "REDACT_CACHE",
+            vars(&[
+                ("REDACT_CACHE_BACKEND", "memory"),
+                ("REDACT_CACHE_MAX_ENTRIES", "100"),
+                ("REDACT_CACHE_MAX_TOTAL_BYTES", "1048576"),
+                ("REDACT_CACHE_DEFAULT_KEY_EXPIRATION_SECONDS", "60"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            CacheConfig::Memory {
+                max_entries: 100,
+                max_total_bytes: 1048576,
+                default_key_expiration_seconds: 60,
+            },
+            config
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loads_file_and_builds() {
+        let dir = std::env::temp_dir().join(format!("redact-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("redact.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "storage": { "backend": "memory" },
+                "cache": {
+                    "backend": "memory",
+                    "max_entries": 10,
+                    "max_total_bytes": 1024,
+                    "default_key_expiration_seconds": 60
+                }
+            }"#,
+        )
+        .unwrap();
+        let config = RedactConfig::from_file(&path).unwrap();
+        assert!(matches!(
+            RedactConfig::from_file(dir.join("redact.yaml")),
+            Err(ConfigError::Io { .. })
+        ));
+        std::fs::write(dir.join("redact.yaml"), "storage: {}").unwrap();
+        assert!(matches!(
+            RedactConfig::from_file(dir.join("redact.yaml")),
+            Err(ConfigError::UnsupportedFormat { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let storer = build_storer(&config.storage).await.unwrap();
+        assert!(matches!(storer, AnyDataStorer::Memory(_)));
+        let data = Data::new(".a.", DataValue::from(1u64));
+        storer.create(data.clone()).await.unwrap();
+        assert_eq!(data, storer.get(".a.").await.unwrap());
+
+        let cacher = build_cacher(&config.cache.unwrap()).unwrap();
+        assert!(matches!(cacher, ConfiguredDataCacher::Memory(_)));
+        assert_eq!(60, cacher.get_default_key_expiration_seconds());
+    }
+}
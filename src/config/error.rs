@@ -0,0 +1,121 @@
+use crate::{CacheError, DataStorerError};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Errors occurring while loading configuration or building what it
+/// configures
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Represents an error which occurred while reading a configuration file
+    Io { source: std::io::Error },
+
+    /// Indicates a configuration file's extension was neither `.toml` nor
+    /// `.json`
+    UnsupportedFormat { path: String },
+
+    /// Indicates a storer URI was malformed or lacked something its backend
+    /// requires
+    InvalidUri { uri: String, reason: String },
+
+    /// Indicates a storer URI's scheme matched no backend
+    UnsupportedScheme { scheme: String },
+
+    /// Indicates a configuration file was not well-formed
+    Parse { reason: String },
+
+    /// Indicates the configuration was well-formed but did not describe a
+    /// valid storer or cacher
+    Invalid { source: serde_json::Error },
+
+    /// Represents an error which occurred while building a configured storer
+    Storer { source: DataStorerError },
+
+    /// Represents an error which occurred while building a configured cacher
+    Cacher { source: CacheError },
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ConfigError::Io { ref source } => Some(source),
+            ConfigError::UnsupportedFormat { .. } => None,
+            ConfigError::InvalidUri { .. } => None,
+            ConfigError::UnsupportedScheme { .. } => None,
+            ConfigError::Parse { .. } => None,
+            ConfigError::Invalid { ref source } => Some(source),
+            ConfigError::Storer { ref source } => Some(source),
+            ConfigError::Cacher { ref source } => Some(source),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            ConfigError::Io { .. } => write!(f, "Failed to read configuration"),
+            ConfigError::UnsupportedFormat { ref path } => {
+                write!(f, "Unsupported configuration format: {}", path)
+            }
+            ConfigError::InvalidUri {
+                ref uri,
+                ref reason,
+            } => write!(f, "Invalid storer URI {}: {}", uri, reason),
+            ConfigError::UnsupportedScheme { ref scheme } => {
+                write!(f, "No storer backend for scheme {}", scheme)
+            }
+            ConfigError::Parse { ref reason } => {
+                write!(f, "Malformed configuration: {}", reason)
+            }
+            ConfigError::Invalid { ref source } => write!(f, "Invalid configuration: {}", source),
+            ConfigError::Storer { .. } => write!(f, "Failed to build the configured storer"),
+            ConfigError::Cacher { .. } => write!(f, "Failed to build the configured cacher"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigError;
+
+    #[test]
+    fn test_to_string_unsupported_format() {
+        let s = ConfigError::UnsupportedFormat {
+            path: "redact.yaml".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Unsupported configuration format: redact.yaml");
+    }
+
+    #[test]
+    fn test_to_string_invalid_uri() {
+        let s = ConfigError::InvalidUri {
+            uri: "mongodb://localhost".to_owned(),
+            reason: "missing database name".to_owned(),
+        }
+        .to_string();
+        assert_eq!(
+            s,
+            "Invalid storer URI mongodb://localhost: missing database name"
+        );
+    }
+
+    #[test]
+    fn test_to_string_unsupported_scheme() {
+        let s = ConfigError::UnsupportedScheme {
+            scheme: "cassandra".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "No storer backend for scheme cassandra");
+    }
+
+    #[test]
+    fn test_to_string_parse() {
+        let s = ConfigError::Parse {
+            reason: "expected `=`".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Malformed configuration: expected `=`");
+    }
+}
@@ -0,0 +1,191 @@
+use super::{build_storer, error::ConfigError, registry::BackendRegistry, StorageConfig};
+use crate::BoxedDataStorer;
+use std::path::PathBuf;
+
+/// Options of the backends a URI can select which the URI itself does not
+/// carry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorerOptions {
+    /// The mongodb database, if the URI does not name one in its path
+    pub db_name: Option<String>,
+    /// The mongodb collection, if not the default one
+    pub collection_name: Option<String>,
+    /// Whether a redact-store server streams collections, see
+    /// `RedactDataStorer::with_streamed_collections`
+    pub streamed_collections: bool,
+}
+
+/// Builds storers from URIs, so that the deployment environment alone selects
+/// the backend:
+///
+/// - `memory://`: an in-memory storer
+/// - `file:///var/lib/redact`: a file storer rooted at the URI's path
+/// - `mongodb://host/db` or `mongodb+srv://host/db`: a mongodb storer
+/// - `http://host` or `https://host`: a redact-store client
+///
+/// along with any scheme registered with the `BackendRegistry`.
+pub struct DataStorerFactory;
+
+impl DataStorerFactory {
+    /// Builds the storer the URI selects
+    pub async fn from_uri(
+        uri: &str,
+        options: &StorerOptions,
+    ) -> Result<BoxedDataStorer, ConfigError> {
+        let storer = build_storer(&StorageConfig::from_uri(uri, options)?).await?;
+        Ok(BoxedDataStorer::new(storer))
+    }
+}
+
+impl StorageConfig {
+    /// Describes the storer a URI selects, see `DataStorerFactory`
+    pub fn from_uri(uri: &str, options: &StorerOptions) -> Result<Self, ConfigError> {
+        let (scheme, rest) = uri
+            .split_once("://")
+            .ok_or_else(|| ConfigError::InvalidUri {
+                uri: uri.to_owned(),
+                reason: "missing scheme".to_owned(),
+            })?;
+        match scheme {
+            "memory" => Ok(StorageConfig::Memory),
+            "file" if !rest.is_empty() => Ok(StorageConfig::File {
+                root: PathBuf::from(rest),
+            }),
+            "file" => Err(ConfigError::InvalidUri {
+                uri: uri.to_owned(),
+                reason: "missing path".to_owned(),
+            }),
+            "mongodb" | "mongodb+srv" => {
+                let db_name = options
+                    .db_name
+                    .clone()
+                    .or_else(|| mongodb_database(rest))
+                    .ok_or_else(|| ConfigError::InvalidUri {
+                        uri: uri.to_owned(),
+                        reason: "missing database name".to_owned(),
+                    })?;
+                Ok(StorageConfig::Mongodb {
+                    url: uri.to_owned(),
+                    db_name,
+                    collection_name: options.collection_name.clone(),
+                })
+            }
+            "http" | "https" => Ok(StorageConfig::Redact {
+                url: uri.to_owned(),
+                streamed_collections: options.streamed_collections,
+            }),
+            _ if BackendRegistry::global().has_storer(scheme) => Ok(StorageConfig::Plugin {
+                uri: uri.to_owned(),
+            }),
+            _ => Err(ConfigError::UnsupportedScheme {
+                scheme: scheme.to_owned(),
+            }),
+        }
+    }
+}
+
+/// Returns the database named by the path of a mongodb URI, if any
+fn mongodb_database(rest: &str) -> Option<String> {
+    let (_, path) = rest.split_once('/')?;
+    let db_name = path.split('?').next().unwrap_or_default();
+    match db_name.is_empty() {
+        true => None,
+        false => Some(db_name.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataStorerFactory, StorerOptions};
+    use crate::{
+        BackendRegistry, BoxedDataStorer, ConfigError, Data, DataStorer, DataValue,
+        MemoryDataStorer, StorageConfig,
+    };
+
+    #[test]
+    fn test_selects_backend_by_scheme() {
+        let options = StorerOptions::default();
+        assert_eq!(
+            StorageConfig::Memory,
+            StorageConfig::from_uri("memory://", &options).unwrap()
+        );
+        assert_eq!(
+            StorageConfig::File {
+                root: "/var/lib/redact".into()
+            },
+            StorageConfig::from_uri("file:///var/lib/redact", &options).unwrap()
+        );
+        assert_eq!(
+            StorageConfig::Mongodb {
+                url: "mongodb://localhost:27017/redact?retryWrites=true".to_owned(),
+                db_name: "redact".to_owned(),
+                collection_name: None,
+            },
+            StorageConfig::from_uri(
+                "mongodb://localhost:27017/redact?retryWrites=true",
+                &options
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            StorageConfig::Redact {
+                url: "https://redact-store".to_owned(),
+                streamed_collections: false,
+            },
+            StorageConfig::from_uri("https://redact-store", &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_incomplete_and_unknown_uris() {
+        let options = StorerOptions::default();
+        assert!(matches!(
+            StorageConfig::from_uri("mongodb://localhost:27017", &options),
+            Err(ConfigError::InvalidUri { .. })
+        ));
+        assert!(matches!(
+            StorageConfig::from_uri("localhost", &options),
+            Err(ConfigError::InvalidUri { .. })
+        ));
+        assert!(matches!(
+            StorageConfig::from_uri("cassandra://localhost", &options),
+            Err(ConfigError::UnsupportedScheme { .. })
+        ));
+
+        let options = StorerOptions {
+            db_name: Some("redact".to_owned()),
+            ..StorerOptions::default()
+        };
+        assert!(StorageConfig::from_uri("mongodb://localhost:27017", &options).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_builds_storer_from_uri() {
+        let storer = DataStorerFactory::from_uri("memory://", &StorerOptions::default())
+            .await
+            .unwrap();
+        let data = Data::new(".a.", DataValue::from(1u64));
+        storer.create(data.clone()).await.unwrap();
+        assert_eq!(data, storer.get(".a.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_builds_registered_scheme() {
+        BackendRegistry::global().register_storer("factory-test", |_| {
+            Box::pin(async { Ok(BoxedDataStorer::new(MemoryDataStorer::new())) })
+        });
+        assert_eq!(
+            StorageConfig::Plugin {
+                uri: "factory-test://".to_owned()
+            },
+            StorageConfig::from_uri("factory-test://", &StorerOptions::default()).unwrap()
+        );
+        let storer = DataStorerFactory::from_uri("factory-test://", &StorerOptions::default())
+            .await
+            .unwrap();
+        assert!(storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,153 @@
+use super::error::ConfigError;
+use crate::{BoxedDataCacher, BoxedDataStorer};
+use futures::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// Builds a storer from a URI with a registered scheme
+pub type StorerFactoryFn =
+    dyn Fn(&str) -> BoxFuture<'static, Result<BoxedDataStorer, ConfigError>> + Send + Sync;
+
+/// Builds a cacher from a URI with a registered scheme
+pub type CacherFactoryFn = dyn Fn(&str) -> Result<BoxedDataCacher, ConfigError> + Send + Sync;
+
+/// Factories of storers and cachers registered by other crates under a URI
+/// scheme, so that their backends can be selected by `DataStorerFactory` and
+/// by configuration, as `backend = "plugin"` with a `uri`, like the built-in
+/// ones. Schemes of built-in backends are never looked up here.
+///
+/// ```
+/// # use redact_data::{BackendRegistry, BoxedDataStorer, MemoryDataStorer};
+/// BackendRegistry::global().register_storer("acme", |_uri| {
+///     Box::pin(async { Ok(BoxedDataStorer::new(MemoryDataStorer::new())) })
+/// });
+/// ```
+#[derive(Default)]
+pub struct BackendRegistry {
+    storers: RwLock<HashMap<String, Arc<StorerFactoryFn>>>,
+    cachers: RwLock<HashMap<String, Arc<CacherFactoryFn>>>,
+}
+
+impl BackendRegistry {
+    /// Returns the registry consulted by `DataStorerFactory`, `build_storer`,
+    /// and `build_cacher`
+    pub fn global() -> &'static BackendRegistry {
+        static GLOBAL: OnceLock<BackendRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(BackendRegistry::default)
+    }
+
+    /// Registers a storer factory under a scheme, replacing any registered
+    /// before it
+    pub fn register_storer<F>(&self, scheme: &str, factory: F)
+    where
+        F: Fn(&str) -> BoxFuture<'static, Result<BoxedDataStorer, ConfigError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.storers
+            .write()
+            .unwrap()
+            .insert(scheme.to_owned(), Arc::new(factory));
+    }
+
+    /// Registers a cacher factory under a scheme, replacing any registered
+    /// before it
+    pub fn register_cacher<F>(&self, scheme: &str, factory: F)
+    where
+        F: Fn(&str) -> Result<BoxedDataCacher, ConfigError> + Send + Sync + 'static,
+    {
+        self.cachers
+            .write()
+            .unwrap()
+            .insert(scheme.to_owned(), Arc::new(factory));
+    }
+
+    /// Returns whether a storer factory is registered under the scheme
+    pub fn has_storer(&self, scheme: &str) -> bool {
+        self.storers.read().unwrap().contains_key(scheme)
+    }
+
+    /// Builds a storer with the factory registered under the URI's scheme
+    pub async fn build_storer(&self, uri: &str) -> Result<BoxedDataStorer, ConfigError> {
+        let factory = self.storers.read().unwrap().get(scheme(uri)?).cloned();
+        match factory {
+            Some(factory) => factory(uri).await,
+            None => Err(ConfigError::UnsupportedScheme {
+                scheme: scheme(uri)?.to_owned(),
+            }),
+        }
+    }
+
+    /// Builds a cacher with the factory registered under the URI's scheme
+    pub fn build_cacher(&self, uri: &str) -> Result<BoxedDataCacher, ConfigError> {
+        let factory = self.cachers.read().unwrap().get(scheme(uri)?).cloned();
+        match factory {
+            Some(factory) => factory(uri),
+            None => Err(ConfigError::UnsupportedScheme {
+                scheme: scheme(uri)?.to_owned(),
+            }),
+        }
+    }
+}
+
+/// Returns the scheme of a URI
+pub(crate) fn scheme(uri: &str) -> Result<&str, ConfigError> {
+    uri.split_once("://")
+        .map(|(scheme, _)| scheme)
+        .ok_or_else(|| ConfigError::InvalidUri {
+            uri: uri.to_owned(),
+            reason: "missing scheme".to_owned(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackendRegistry;
+    use crate::{
+        BoxedDataCacher, BoxedDataStorer, ConfigError, Data, DataCacher, DataStorer, DataValue,
+        MemoryCacheConfig, MemoryDataCacher, MemoryDataStorer,
+    };
+
+    #[tokio::test]
+    async fn test_builds_registered_backends() {
+        let registry = BackendRegistry::default();
+        registry.register_storer("acme", |uri| {
+            let path = format!(".{}.", uri.trim_start_matches("acme://"));
+            Box::pin(async move {
+                let storer = MemoryDataStorer::new();
+                storer
+                    .create(Data::new(&path, DataValue::from(1u64)))
+                    .await
+                    .map_err(|source| ConfigError::Storer { source })?;
+                Ok(BoxedDataStorer::new(storer))
+            })
+        });
+        registry.register_cacher("acme", |_| {
+            Ok(BoxedDataCacher::new(MemoryDataCacher::new(
+                MemoryCacheConfig {
+                    max_entries: 10,
+                    max_total_bytes: usize::MAX,
+                    default_key_expiration_seconds: 42,
+                },
+            )))
+        });
+
+        assert!(registry.has_storer("acme"));
+        let storer = registry.build_storer("acme://seeded").await.unwrap();
+        assert!(storer.get(".seeded.").await.is_ok());
+        let cacher = registry.build_cacher("acme://").unwrap();
+        assert_eq!(42, cacher.get_default_key_expiration_seconds());
+
+        assert!(matches!(
+            registry.build_storer("other://").await,
+            Err(ConfigError::UnsupportedScheme { .. })
+        ));
+        assert!(matches!(
+            registry.build_cacher("acme"),
+            Err(ConfigError::InvalidUri { .. })
+        ));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    diff, Data, DataPatch, DataPath, DataStorer, DataStorerError, DataValue, DataValueCollection,
+    UnencryptedDataValue,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+
+/// How writing a JSON document under a prefix treats the data already there
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Removes every existing entry under the prefix the document does not hold
+    Replace,
+    /// Keeps existing entries the document does not mention, as in JSON Merge
+    /// Patch: a `null` member removes everything stored at and below it, and
+    /// a written leaf displaces any entry stored at its ancestors or below it
+    DeepMerge,
+}
+
+/// Splits a JSON document into one `Data` per leaf, each stored at the path of
+/// the leaf below `root`. Object members become path segments and array items
+/// are addressed by their index, so `{"a": [true]}` flattened at `.doc.` gives
+/// `.doc.a.0.` holding `true`. Numbers map to `U64`, `I64`, or `F64` in that
+/// order of preference. Nulls, empty objects, and empty arrays carry no value
+/// and are left out.
+pub fn flatten(value: Value, root: &DataPath) -> Vec<Data> {
+    let mut data = Vec::new();
+    flatten_into(value, root.to_string(), &mut data);
+    data
+}
+
+fn flatten_into(value: Value, path: String, data: &mut Vec<Data>) {
+    match value {
+        Value::Null => (),
+        Value::Array(items) => {
+            for (i, item) in items.into_iter().enumerate() {
+                flatten_into(item, format!("{}{}.", path, i), data);
+            }
+        }
+        Value::Object(members) => {
+            for (name, member) in members {
+                flatten_into(member, format!("{}{}.", path, name), data);
+            }
+        }
+        leaf => data.push(Data::new(&path, DataValue::from(leaf))),
+    }
+}
+
+/// Rebuilds a JSON document from a set of `Data`, reversing `flatten` taken at
+/// the root path. Objects whose members are exactly the indices `0..n` become
+/// arrays. A `Data` holding several values becomes an array of them, and
+/// encrypted values keep their serialized form. When a path holds both a value
+/// and children, the children win.
+pub fn unflatten(data: Vec<Data>) -> Value {
+    let mut document = Value::Null;
+    for d in data {
+        let path = d.path();
+        let segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+        insert(&mut document, &segments, collection_to_json(d.value()));
+    }
+    into_arrays(document)
+}
+
+fn insert(node: &mut Value, segments: &[&str], leaf: Value) {
+    match segments.split_first() {
+        None => {
+            if !node.is_object() {
+                *node = leaf;
+            }
+        }
+        Some((segment, rest)) => {
+            if !node.is_object() {
+                *node = Value::Object(Map::new());
+            }
+            if let Value::Object(ref mut members) = *node {
+                let child = members.entry(*segment).or_insert(Value::Null);
+                insert(child, rest, leaf);
+            }
+        }
+    }
+}
+
+/// Converts every object keyed by the contiguous indices `0..n` into an array
+fn into_arrays(value: Value) -> Value {
+    match value {
+        Value::Object(members) => {
+            let is_array = !members.is_empty()
+                && (0..members.len()).all(|i| members.contains_key(&i.to_string()));
+            let mut members: Map<String, Value> = members
+                .into_iter()
+                .map(|(name, member)| (name, into_arrays(member)))
+                .collect();
+            if is_array {
+                Value::Array(
+                    (0..members.len())
+                        .map(|i| members.remove(&i.to_string()).unwrap_or(Value::Null))
+                        .collect(),
+                )
+            } else {
+                Value::Object(members)
+            }
+        }
+        value => value,
+    }
+}
+
+fn collection_to_json(values: &DataValueCollection) -> Value {
+    match values.0.as_slice() {
+        [value] => value_to_json(value),
+        values => Value::Array(values.iter().map(value_to_json).collect()),
+    }
+}
+
+fn value_to_json(value: &DataValue) -> Value {
+    match *value {
+        DataValue::Unencrypted(UnencryptedDataValue::Bool(b)) => Value::Bool(b),
+        DataValue::Unencrypted(UnencryptedDataValue::U64(n)) => Value::from(n),
+        DataValue::Unencrypted(UnencryptedDataValue::I64(n)) => Value::from(n),
+        DataValue::Unencrypted(UnencryptedDataValue::F64(n)) => {
+            Number::from_f64(n).map_or(Value::Null, Value::Number)
+        }
+        DataValue::Unencrypted(UnencryptedDataValue::String(ref s)) => Value::from(s.as_str()),
+        DataValue::Unencrypted(UnencryptedDataValue::Bytes(ref b)) => Value::from(b.clone()),
+        DataValue::Encrypted(_) => serde_json::to_value(value).unwrap_or(Value::Null),
+    }
+}
+
+/// Collects the paths of every `null` within a document
+fn null_paths(value: &Value, path: String, nulls: &mut Vec<String>) {
+    match *value {
+        Value::Null => nulls.push(path),
+        Value::Array(ref items) => {
+            for (i, item) in items.iter().enumerate() {
+                null_paths(item, format!("{}{}.", path, i), nulls);
+            }
+        }
+        Value::Object(ref members) => {
+            for (name, member) in members {
+                null_paths(member, format!("{}{}.", path, name), nulls);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Writes a JSON document under a prefix according to the merge strategy,
+/// applying the changes as a single patch and returning it
+pub(crate) async fn put_subtree<S: DataStorer>(
+    storer: &S,
+    prefix: &str,
+    value: Value,
+    strategy: MergeStrategy,
+) -> Result<DataPatch, DataStorerError> {
+    let root = DataPath::from(prefix);
+    let prefix = root.to_string();
+    let mut existing = Vec::new();
+    let mut entries = storer.iter_collection(&prefix);
+    while let Some(entry) = entries.next().await {
+        existing.push(entry?);
+    }
+    drop(entries);
+
+    let updated = match strategy {
+        MergeStrategy::Replace => flatten(value, &root),
+        MergeStrategy::DeepMerge => {
+            let mut nulls = Vec::new();
+            null_paths(&value, prefix, &mut nulls);
+            let mut merged: BTreeMap<String, Data> = existing
+                .iter()
+                .map(|d| (d.path(), d.clone()))
+                .filter(|(path, _)| !nulls.iter().any(|null| path.starts_with(null)))
+                .collect();
+            for d in flatten(value, &root) {
+                let path = d.path();
+                merged.retain(|p, _| !p.starts_with(&path) && !path.starts_with(p.as_str()));
+                merged.insert(path, d);
+            }
+            merged.into_values().collect()
+        }
+    };
+    let patch = diff(&existing, &updated);
+    storer.apply_patch(".", &patch).await?;
+    Ok(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flatten, unflatten};
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataPath, DataStorer, DataValue, DataValueCollection, MergeStrategy};
+    use serde_json::json;
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn test_flatten() {
+        let document = json!({
+            "name": "alice",
+            "age": 30,
+            "balance": -2,
+            "score": 1.5,
+            "admin": false,
+            "tags": ["a", "b"],
+            "address": { "city": "ghent" },
+            "nothing": null,
+            "empty": {}
+        });
+        let mut data = flatten(document, &DataPath::new(".users.1."));
+        data.sort_by_key(Data::path);
+        assert_eq!(
+            vec![
+                Data::new(".users.1.address.city.", DataValue::from("ghent")),
+                Data::new(".users.1.admin.", DataValue::from(false)),
+                Data::new(".users.1.age.", DataValue::from(30u64)),
+                Data::new(".users.1.balance.", DataValue::from(-2i64)),
+                Data::new(".users.1.name.", DataValue::from("alice")),
+                Data::new(".users.1.score.", DataValue::from(1.5)),
+                Data::new(".users.1.tags.0.", DataValue::from("a")),
+                Data::new(".users.1.tags.1.", DataValue::from("b")),
+            ],
+            data
+        );
+    }
+
+    #[test]
+    fn test_flatten_scalar_at_root() {
+        assert_eq!(
+            vec![Data::new(".count.", DataValue::from(3u64))],
+            flatten(json!(3), &DataPath::new(".count."))
+        );
+    }
+
+    #[test]
+    fn test_unflatten_round_trip() {
+        let document = json!({
+            "users": [
+                { "name": "alice", "tags": ["a", "b"] },
+                { "name": "bob", "admin": true }
+            ],
+            "count": 2,
+            "ratio": 0.5,
+            "sparse": { "0": "x", "2": "y" }
+        });
+        assert_eq!(
+            document,
+            unflatten(flatten(document.clone(), &DataPath::new(".")))
+        );
+    }
+
+    #[test]
+    fn test_unflatten_multiple_values_and_conflicts() {
+        let data = vec![
+            Data::with_values(
+                ".a.",
+                DataValueCollection::from(vec![DataValue::from(1u64), DataValue::from(2u64)]),
+            ),
+            Data::new(".b.c.", DataValue::from("child")),
+            Data::new(".b.", DataValue::from("parent")),
+        ];
+        assert_eq!(
+            json!({ "a": [1, 2], "b": { "c": "child" } }),
+            unflatten(data)
+        );
+    }
+
+    fn stored(entries: &Arc<Mutex<BTreeMap<String, Data>>>) -> serde_json::Value {
+        unflatten(entries.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn seeded() -> (
+        crate::storage::tests::MockDataStorer,
+        Arc<Mutex<BTreeMap<String, Data>>>,
+    ) {
+        let (storer, entries) = memory_storer();
+        let document = json!({
+            "user": { "name": "alice", "email": "a@example.com", "address": { "city": "ghent" } },
+            "other": 1
+        });
+        for d in flatten(document, &DataPath::new(".")) {
+            storer.create(d).await.unwrap();
+        }
+        (storer, entries)
+    }
+
+    #[tokio::test]
+    async fn test_put_subtree_replace() {
+        let (storer, entries) = seeded().await;
+        let patch = storer
+            .put_subtree(".user.", json!({ "name": "bob" }), MergeStrategy::Replace)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json!({ "user": { "name": "bob" }, "other": 1 }),
+            stored(&entries)
+        );
+        assert_eq!(2, patch.removed().len());
+        assert_eq!(1, patch.changed().len());
+    }
+
+    #[tokio::test]
+    async fn test_put_subtree_deep_merge() {
+        let (storer, entries) = seeded().await;
+        storer
+            .put_subtree(
+                ".user.",
+                json!({ "email": null, "address": { "zip": "9000" }, "name": { "first": "bob" } }),
+                MergeStrategy::DeepMerge,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json!({
+                "user": { "name": { "first": "bob" }, "address": { "city": "ghent", "zip": "9000" } },
+                "other": 1
+            }),
+            stored(&entries)
+        );
+    }
+}
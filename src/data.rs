@@ -1,63 +1,654 @@
-use serde::{Deserialize, Serialize};
+use crate::builder::DataBuilder;
+use crate::storage::query::Cursor;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use smallvec::{smallvec, SmallVec};
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    str::FromStr,
     fmt::{self, Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
     vec::Vec,
 };
 
 /// `Data` stores a unit of data in the redact system. A chunk of
 /// data is a `DataValue` (contained within), which can be a `bool`,
-/// `u64`, `i64`, `f64`, or `string`. Each data is associated with a `DataPath`
+/// `u64`, `i64`, `f64`, `string`, or raw `bytes`. Each data is associated with a `DataPath`
 /// which is just a json-style path, and can optionally be encrypted
 /// by a variety of keys as specified by the key names in `encryptedby`.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+/// An optional `integrity` checksum over the path and values can be stored
+/// alongside the data to detect corruption or tampering in the storage layer,
+/// and an optional detached `signature` proves which producer wrote it.
+/// Storers keeping history record when the data was written in `written_at`.
+/// Producers may also attach `labels` and an expiry time, see `Data::builder`.
+/// Values envelope encrypted under a single data-encryption key carry that
+/// key wrapped by each recipient key allowed to decrypt them in `recipients`.
+/// Typed `links` to other paths record relationships such as lineage, see
+/// `DataStorer::get_linked`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Data {
     path: DataPath,
+    #[serde(alias = "values")]
     value: DataValueCollection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    integrity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<DataSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    written_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    encryptedby: Vec<KeyName>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        with = "binary::map"
+    )]
+    recipients: BTreeMap<KeyName, Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<DataLink>,
+}
+
+struct UnredactedData<'a>(&'a Data);
+
+impl Debug for UnredactedData<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let values: Vec<Box<dyn Debug + '_>> = self
+            .0
+            .value
+            .0
+            .iter()
+            .map(|value| -> Box<dyn Debug + '_> {
+                match value {
+                    DataValue::Unencrypted(value) => Box::new(UnredactedUnencrypted(value)),
+                    DataValue::Encrypted(value) => Box::new(value),
+                }
+            })
+            .collect();
+        f.debug_struct("Data")
+            .field("path", &self.0.path)
+            .field("value", &values)
+            .field("integrity", &self.0.integrity)
+            .field("signature", &self.0.signature)
+            .field("written_at", &self.0.written_at)
+            .field("encryptedby", &self.0.encryptedby)
+            .field("labels", &self.0.labels)
+            .field("expires_at", &self.0.expires_at)
+            .field("recipients", &self.0.recipients)
+            .field("links", &self.0.links)
+            .finish()
+    }
+}
+
+/// Shows an unencrypted value in full as `DataValue`'s `Debug` would
+struct UnredactedUnencrypted<'a>(&'a UnencryptedDataValue);
+
+impl Debug for UnredactedUnencrypted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Unencrypted")
+            .field(&self.0.debug_unredacted())
+            .finish()
+    }
+}
+
+/// A typed reference from one `Data` to the path of another, e.g. to its
+/// `owner` or to the record it was `derived_from`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DataLink {
+    pub link_type: String,
+    pub target: DataPath,
+}
+
+/// A detached signature over the canonical encoding of a `Data`, along with
+/// the algorithm used and the id of the key which produced it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DataSignature {
+    pub algorithm: String,
+    pub keyid: String,
+    #[serde(with = "binary")]
+    pub signature: Vec<u8>,
 }
 
 impl Data {
     /// Builds a new Data struct using the provided values
     pub fn new(path: &str, value: DataValue) -> Self {
+        Self::with_values(path, DataValueCollection(smallvec![value]))
+    }
+
+    /// Builds a new Data struct holding an entire collection of values
+    pub fn with_values(path: &str, value: DataValueCollection) -> Self {
         Data {
             path: DataPath::from(path),
-            value: DataValueCollection(vec![value]),
+            value,
+            ..Default::default()
+        }
+    }
+
+    /// Starts building data field by field, validating it on `build`
+    pub fn builder() -> DataBuilder {
+        DataBuilder::default()
+    }
+
+    /// Returns a deterministic encoding of the data's path and values, suitable
+    /// for hashing and signing. The encoding is compact JSON with a fixed field
+    /// order, e.g. `{"path":".a.","value":[{"Unencrypted":{"U64":1}}]}`, and is
+    /// written by hand so that it stays stable across serde changes and between
+    /// services. Metadata such as the integrity checksum and signature is excluded.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        out.push_str("{\"path\":");
+        push_json_string(&mut out, &self.path.to_string());
+        out.push_str(",\"value\":[");
+        for (i, value) in self.value.0.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            push_canonical_value(&mut out, value);
         }
+        out.push_str("]}");
+        out.into_bytes()
+    }
+
+    /// Computes the hex-encoded SHA-256 digest of the data's path and values
+    pub fn compute_integrity(&self) -> String {
+        Sha256::digest(self.canonical_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Stores a checksum of the data's current path and values
+    pub fn seal_integrity(&mut self) {
+        self.integrity = Some(self.compute_integrity());
+    }
+
+    /// Returns the stored integrity checksum, if any
+    pub fn integrity(&self) -> Option<&str> {
+        self.integrity.as_deref()
+    }
+
+    /// Replaces the stored integrity checksum, e.g. when decoding data from
+    /// another encoding
+    pub(crate) fn set_integrity(&mut self, integrity: Option<String>) {
+        self.integrity = integrity;
+    }
+
+    /// Returns the detached signature over the data, if any
+    pub fn signature(&self) -> Option<&DataSignature> {
+        self.signature.as_ref()
+    }
+
+    /// Moves the data to another path, e.g. when re-rooting it under a prefix.
+    /// Integrity checksums and signatures cover the path, so callers must only
+    /// move data between the path it was sealed at and its storage location.
+    pub(crate) fn set_path(&mut self, path: &str) {
+        self.path = DataPath::from(path);
+    }
+
+    /// Returns the data's values for changing them in place, e.g. when
+    /// migrating them to another type. Integrity checksums and signatures
+    /// cover the values, so callers must reseal data whose values changed.
+    pub(crate) fn values_mut(&mut self) -> &mut DataValueCollection {
+        &mut self.value
+    }
+
+    /// Attaches or removes the detached signature over the data
+    pub fn set_signature(&mut self, signature: Option<DataSignature>) {
+        self.signature = signature;
+    }
+
+    /// Returns when the data was written, in milliseconds since the unix epoch,
+    /// if the storer it was read from records it
+    pub fn written_at(&self) -> Option<u64> {
+        self.written_at
+    }
+
+    /// Records when the data was written, in milliseconds since the unix epoch
+    pub fn set_written_at(&mut self, written_at: Option<u64>) {
+        self.written_at = written_at;
+    }
+
+    /// Returns the labels attached to the data
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    /// Returns the value of a label attached to the data, if any
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels.get(key).map(String::as_str)
+    }
+
+    /// Attaches a label to the data, replacing any label with the same key
+    pub fn set_label(&mut self, key: &str, value: &str) {
+        self.labels.insert(key.to_owned(), value.to_owned());
+    }
+
+    /// Returns when the data expires, in milliseconds since the unix epoch, if
+    /// ever
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    /// Sets when the data expires, in milliseconds since the unix epoch
+    pub fn set_expires_at(&mut self, expires_at: Option<u64>) {
+        self.expires_at = expires_at;
+    }
+
+    /// Returns the names of the keys the data is to be encrypted by, as given
+    /// to `add_encrypted_by` or `DataBuilder::encrypted_by`
+    pub fn requested_keys(&self) -> &[KeyName] {
+        &self.encryptedby
+    }
+
+    /// Adds a key the data is to be encrypted by, returning whether it was not
+    /// already listed
+    pub fn add_encrypted_by(&mut self, keyname: KeyName) -> bool {
+        if self.encryptedby.contains(&keyname) {
+            return false;
+        }
+        self.encryptedby.push(keyname);
+        true
+    }
+
+    /// Removes a key the data is to be encrypted by, returning whether it was
+    /// listed. Values already encrypted by the key are left as they are.
+    pub fn remove_encrypted_by(&mut self, keyname: &KeyName) -> bool {
+        let listed = self.encryptedby.len();
+        self.encryptedby.retain(|k| k != keyname);
+        self.encryptedby.len() != listed
+    }
+
+    /// Returns the data-encryption key of envelope encrypted values, wrapped
+    /// by each of the recipient keys allowed to decrypt them
+    pub fn recipients(&self) -> &BTreeMap<KeyName, Vec<u8>> {
+        &self.recipients
+    }
+
+    /// Returns the data-encryption key as wrapped by a recipient key, if the
+    /// key is a recipient
+    pub fn wrapped_key(&self, keyname: &str) -> Option<&[u8]> {
+        self.recipients
+            .iter()
+            .find(|(k, _)| k.as_str() == keyname)
+            .map(|(_, wrapped)| wrapped.as_slice())
+    }
+
+    /// Adds a recipient key along with the data-encryption key wrapped by it,
+    /// replacing any wrapped key the recipient already had
+    pub fn add_recipient(&mut self, keyname: KeyName, wrapped_key: Vec<u8>) {
+        self.recipients.insert(keyname, wrapped_key);
+    }
+
+    /// Removes a recipient key, returning whether it was one. Its wrapped key
+    /// is discarded, so it can no longer decrypt the values.
+    pub fn remove_recipient(&mut self, keyname: &KeyName) -> bool {
+        self.recipients.remove(keyname).is_some()
+    }
+
+    /// Returns the links from the data to other paths, in the order they were
+    /// added
+    pub fn links(&self) -> &[DataLink] {
+        &self.links
+    }
+
+    /// Returns the paths the data links to with the given type of link
+    pub fn link_targets(&self, link_type: &str) -> Vec<&str> {
+        self.links
+            .iter()
+            .filter(|link| link.link_type == link_type)
+            .map(|link| link.target.as_str())
+            .collect()
+    }
+
+    /// Links the data to another path, which is normalized as `Data::new`
+    /// does, returning whether the link did not already exist
+    pub fn add_link(&mut self, link_type: &str, target: &str) -> bool {
+        let link = DataLink {
+            link_type: link_type.to_owned(),
+            target: DataPath::from(target),
+        };
+        if self.links.contains(&link) {
+            return false;
+        }
+        self.links.push(link);
+        true
+    }
+
+    /// Removes a link to another path, returning whether it existed
+    pub fn remove_link(&mut self, link_type: &str, target: &str) -> bool {
+        let target = DataPath::from(target);
+        let listed = self.links.len();
+        self.links
+            .retain(|link| link.link_type != link_type || link.target != target);
+        self.links.len() != listed
+    }
+
+    /// Returns whether the stored checksum matches the data's path and values,
+    /// or `None` if the data carries no checksum
+    pub fn verify_integrity(&self) -> Option<bool> {
+        self.integrity
+            .as_ref()
+            .map(|integrity| *integrity == self.compute_integrity())
     }
 
     /// Returns an owned string representing the data's jsonpath
     pub fn path(&self) -> String {
         self.path.to_string()
     }
+
+    /// Returns the data's path without copying it
+    pub fn data_path(&self) -> &DataPath {
+        &self.path
+    }
+
+    /// Returns a view of the data whose `Debug` output shows its values in
+    /// full, where the data's own `Debug` output masks plaintext values
+    pub fn debug_unredacted(&self) -> impl Debug + '_ {
+        UnredactedData(self)
+    }
+
+    /// Returns a reference to the collection of values held by the data
+    pub fn value(&self) -> &DataValueCollection {
+        &self.value
+    }
+
+    /// Returns the data's single value as a Rust type, e.g.
+    /// `data.value_as::<u64>()`, failing if the data holds several values, or
+    /// a value of another type or encrypted; see `ValueType` for the
+    /// conversions allowed between numbers
+    pub fn value_as<T: ValueType>(&self) -> Result<T, TypeMismatchError> {
+        match self.value.0.as_slice() {
+            [value] => T::try_from(value.clone()),
+            _ => Err(TypeMismatchError {
+                expected: T::DATATYPE,
+                found: None,
+                encrypted: false,
+            }),
+        }
+    }
+
+    /// Returns the distinct names of the keys the data is to be encrypted by,
+    /// followed by those of the keys encrypting any of its values, in the
+    /// order they first appear
+    pub fn encryptedby(&self) -> Vec<&str> {
+        let mut keynames: Vec<&str> = Vec::new();
+        let encrypting = self.value.0.iter().filter_map(|value| match *value {
+            DataValue::Encrypted(ref e) => Some(e.keyname()),
+            DataValue::Unencrypted(_) => None,
+        });
+        for keyname in self.encryptedby.iter().map(KeyName::as_str).chain(encrypting) {
+            if !keynames.contains(&keyname) {
+                keynames.push(keyname);
+            }
+        }
+        keynames
+    }
+}
+
+/// Appends a JSON-escaped string literal to the canonical encoding
+fn push_json_string(out: &mut String, s: &str) {
+    out.push_str(&Value::from(s).to_string());
+}
+
+/// Appends a JSON array of bytes to the canonical encoding
+fn push_json_bytes(out: &mut String, bytes: &[u8]) {
+    out.push('[');
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&byte.to_string());
+    }
+    out.push(']');
+}
+
+/// Appends a single value to the canonical encoding
+fn push_canonical_value(out: &mut String, value: &DataValue) {
+    match value {
+        DataValue::Encrypted(e) => {
+            out.push_str("{\"Encrypted\":{\"value\":");
+            push_json_bytes(out, &e.value);
+            out.push_str(",\"datatype\":");
+            push_json_string(
+                out,
+                match e.datatype {
+                    DataType::Bool => "Bool",
+                    DataType::U64 => "U64",
+                    DataType::I64 => "I64",
+                    DataType::F64 => "F64",
+                    DataType::String => "String",
+                    DataType::Bytes => "Bytes",
+                },
+            );
+            out.push_str(",\"keyname\":");
+            push_json_string(out, &e.keyname);
+            if let Some(ref nonce) = e.nonce {
+                out.push_str(",\"nonce\":");
+                push_json_bytes(out, nonce);
+            }
+            if let Some(ref aad) = e.aad {
+                out.push_str(",\"aad\":");
+                push_json_bytes(out, aad);
+            }
+            if let Some(ref algorithm) = e.algorithm {
+                out.push_str(",\"algorithm\":");
+                push_json_string(out, algorithm);
+            }
+            out.push_str("}}");
+        }
+        DataValue::Unencrypted(u) => {
+            out.push_str("{\"Unencrypted\":{");
+            match u {
+                UnencryptedDataValue::Bool(b) => out.push_str(&format!("\"Bool\":{}", b)),
+                UnencryptedDataValue::U64(n) => out.push_str(&format!("\"U64\":{}", n)),
+                UnencryptedDataValue::I64(n) => out.push_str(&format!("\"I64\":{}", n)),
+                UnencryptedDataValue::F64(n) => {
+                    out.push_str(&format!("\"F64\":{}", Value::from(*n)))
+                }
+                UnencryptedDataValue::String(s) => {
+                    out.push_str("\"String\":");
+                    push_json_string(out, s);
+                }
+                UnencryptedDataValue::Bytes(b) => {
+                    out.push_str("\"Bytes\":");
+                    push_json_bytes(out, b);
+                }
+            }
+            out.push_str("}}");
+        }
+    }
 }
 
 impl Display for Data {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.value.to_string())
+        write!(f, "{}", self.value)
     }
 }
 
 /// Wraps a vector of `DataValue` enums. In the future, this type will implement
-/// group `DataValue` operations.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
-pub struct DataValueCollection(pub Vec<DataValue>);
+/// group `DataValue` operations. Deserializing also accepts a single value,
+/// see the `compat` module.
+/// Almost all data holds a single value, which is stored inline rather than
+/// on the heap; the collection serializes as a sequence either way.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DataValueCollection(pub SmallVec<[DataValue; 1]>);
+
+impl From<Vec<DataValue>> for DataValueCollection {
+    fn from(values: Vec<DataValue>) -> Self {
+        DataValueCollection(SmallVec::from_vec(values))
+    }
+}
+
+impl std::iter::FromIterator<DataValue> for DataValueCollection {
+    fn from_iter<I: IntoIterator<Item = DataValue>>(values: I) -> Self {
+        DataValueCollection(values.into_iter().collect())
+    }
+}
 
 impl Display for DataValueCollection {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.0
             .iter()
-            .try_for_each(|dv| write!(f, "{}", dv.to_string()))
+            .try_for_each(|dv| write!(f, "{}", dv))
+    }
+}
+
+/// A page of `Data` entries retrieved from a storer, typically all of the
+/// entries found under a common path prefix. When fetched with cursor-based
+/// pagination, `next_cursor` marks where the following page begins and is
+/// `None` once the collection is exhausted.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DataCollection {
+    pub data: Vec<Data>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Cursor>,
+}
+
+/// How entries of a collection sharing a path are combined into one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathMergeStrategy {
+    /// Appends the values of later entries to those of the first
+    Concat,
+    /// Appends the values of later entries the first does not already hold
+    Union,
+    /// Keeps the first entry
+    KeepFirst,
+    /// Keeps the entry written last, as told by `written_at`, or the first
+    /// of those written at the same time
+    KeepLatest,
+}
+
+impl DataCollection {
+    /// Builds a collection holding the given entries and no cursor
+    pub fn new(data: Vec<Data>) -> Self {
+        DataCollection {
+            data,
+            next_cursor: None,
+        }
+    }
+
+    /// Combines the entries sharing a path into one, at the position of the
+    /// first of them. Entries whose values are appended keep the metadata of
+    /// the first, e.g. its labels and `written_at`.
+    pub fn dedup_by_path(&mut self, strategy: PathMergeStrategy) {
+        let mut positions = HashMap::<DataPath, usize>::new();
+        let mut deduped: Vec<Data> = Vec::with_capacity(self.data.len());
+        for data in self.data.drain(..) {
+            let kept = match positions.get(&data.path) {
+                Some(&position) => &mut deduped[position],
+                None => {
+                    positions.insert(data.path.clone(), deduped.len());
+                    deduped.push(data);
+                    continue;
+                }
+            };
+            match strategy {
+                PathMergeStrategy::Concat => kept.value.0.extend(data.value.0),
+                PathMergeStrategy::Union => {
+                    for value in data.value.0 {
+                        if !kept.value.0.contains(&value) {
+                            kept.value.0.push(value);
+                        }
+                    }
+                }
+                PathMergeStrategy::KeepFirst => (),
+                PathMergeStrategy::KeepLatest => {
+                    if data.written_at > kept.written_at {
+                        *kept = data;
+                    }
+                }
+            }
+        }
+        self.data = deduped;
+    }
+
+    /// Appends the entries of another collection, e.g. one fetched from
+    /// another shard or mirror, combining those sharing a path. The cursors
+    /// of either collection do not apply to the result and are dropped.
+    pub fn merge(&mut self, other: DataCollection, strategy: PathMergeStrategy) {
+        self.data.extend(other.data);
+        self.next_cursor = None;
+        self.dedup_by_path(strategy);
     }
 }
 
 /// `DataValue` contains the actual raw value of a piece of `Data`.
 /// A `DataValue` should always be a leaf value, not an array or object.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Deserializing also accepts the encodings of previous wire versions, see
+/// the `compat` module.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DataValue {
     Encrypted(EncryptedDataValue),
     Unencrypted(UnencryptedDataValue),
 }
 
+impl DataValue {
+    /// Returns the type of the value, or of the plaintext for encrypted values
+    pub fn datatype(&self) -> DataType {
+        match *self {
+            DataValue::Encrypted(ref e) => e.datatype.clone(),
+            DataValue::Unencrypted(ref u) => u.datatype(),
+        }
+    }
+
+    /// Returns the value as an `f64` if it is an unencrypted number,
+    /// or `None` for encrypted, boolean, and string values.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            DataValue::Unencrypted(UnencryptedDataValue::U64(n)) => Some(n as f64),
+            DataValue::Unencrypted(UnencryptedDataValue::I64(n)) => Some(n as f64),
+            DataValue::Unencrypted(UnencryptedDataValue::F64(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Infers the type of a value written as text, e.g. in a CSV cell or on
+    /// the command line: `true` and `false` are booleans, integers are `u64`
+    /// if non-negative and `i64` otherwise, finite decimals are `f64`, and
+    /// anything else is kept as a string. Integers with leading zeros, such as
+    /// postal codes, are kept as strings so that no digit is lost.
+    pub fn parse(s: &str) -> DataValue {
+        let digits = s.trim_start_matches(['-', '+']);
+        let leading_zero = digits.len() > 1
+            && digits.starts_with('0')
+            && digits.bytes().all(|b| b.is_ascii_digit());
+        let numeric = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.');
+        match s {
+            "true" => true.into(),
+            "false" => false.into(),
+            _ if leading_zero || !numeric => s.into(),
+            _ => {
+                if let Ok(n) = s.parse::<u64>() {
+                    n.into()
+                } else if let Ok(n) = s.parse::<i64>() {
+                    n.into()
+                } else {
+                    match s.parse::<f64>() {
+                        Ok(n) if n.is_finite() => n.into(),
+                        _ => s.into(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parses a value with `DataValue::parse`, which never fails
+impl FromStr for DataValue {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DataValue::parse(s))
+    }
+}
+
 // impl Default for DataValue {
 //     fn default() -> Self {
 //         Self::Unencrypted(UnencryptedDataValue::Bool(false))
@@ -67,19 +658,26 @@ pub enum DataValue {
 impl Display for DataValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
-            DataValue::Encrypted(ref e) => write!(f, "{}", e.to_string()),
-            DataValue::Unencrypted(ref u) => write!(f, "{}", u.to_string()),
+            DataValue::Encrypted(ref e) => write!(f, "{}", e),
+            DataValue::Unencrypted(ref u) => write!(f, "{}", u),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DataType {
+    #[serde(alias = "bool")]
     Bool,
+    #[serde(alias = "u64")]
     U64,
+    #[serde(alias = "i64")]
     I64,
+    #[serde(alias = "f64")]
     F64,
+    #[serde(alias = "string")]
     String,
+    #[serde(alias = "bytes")]
+    Bytes,
 }
 
 impl Display for DataType {
@@ -90,17 +688,242 @@ impl Display for DataType {
             Self::I64 => write!(f, "i64"),
             Self::F64 => write!(f, "f64"),
             Self::String => write!(f, "string"),
+            Self::Bytes => write!(f, "bytes"),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// A plaintext value. Its `Debug` output masks the value, so that data
+/// logged with `{:?}` cannot leak it; see `debug_unredacted` to show it.
+#[derive(Serialize, Deserialize, Clone)]
 pub enum UnencryptedDataValue {
     Bool(bool),
     U64(u64),
     I64(i64),
     F64(f64),
     String(String),
+    Bytes(#[serde(with = "binary")] Vec<u8>),
+}
+
+impl UnencryptedDataValue {
+    /// Returns the type of the value
+    pub fn datatype(&self) -> DataType {
+        match *self {
+            UnencryptedDataValue::Bool(_) => DataType::Bool,
+            UnencryptedDataValue::U64(_) => DataType::U64,
+            UnencryptedDataValue::I64(_) => DataType::I64,
+            UnencryptedDataValue::F64(_) => DataType::F64,
+            UnencryptedDataValue::String(_) => DataType::String,
+            UnencryptedDataValue::Bytes(_) => DataType::Bytes,
+        }
+    }
+
+    /// Returns a view of the value whose `Debug` output shows it in full,
+    /// for debugging where leaking it is acceptable
+    pub fn debug_unredacted(&self) -> impl Debug + '_ {
+        UnredactedValue(self)
+    }
+
+    fn variant_name(&self) -> &'static str {
+        match *self {
+            UnencryptedDataValue::Bool(_) => "Bool",
+            UnencryptedDataValue::U64(_) => "U64",
+            UnencryptedDataValue::I64(_) => "I64",
+            UnencryptedDataValue::F64(_) => "F64",
+            UnencryptedDataValue::String(_) => "String",
+            UnencryptedDataValue::Bytes(_) => "Bytes",
+        }
+    }
+}
+
+impl Debug for UnencryptedDataValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(self.variant_name())
+            .field(&format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+struct UnredactedValue<'a>(&'a UnencryptedDataValue);
+
+impl Debug for UnredactedValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut tuple = f.debug_tuple(self.0.variant_name());
+        match *self.0 {
+            UnencryptedDataValue::Bool(ref b) => tuple.field(b),
+            UnencryptedDataValue::U64(ref n) => tuple.field(n),
+            UnencryptedDataValue::I64(ref n) => tuple.field(n),
+            UnencryptedDataValue::F64(ref n) => tuple.field(n),
+            UnencryptedDataValue::String(ref s) => tuple.field(s),
+            UnencryptedDataValue::Bytes(ref b) => tuple.field(b),
+        };
+        tuple.finish()
+    }
+}
+
+/// Serializes byte fields as byte strings in binary formats such as CBOR and
+/// MessagePack, and as serde serializes `Vec<u8>`, an array of numbers, in
+/// human-readable ones, so that JSON and BSON documents keep their layout.
+/// Arrays of numbers written before still deserialize in every format.
+pub(crate) mod binary {
+    use serde::{
+        de::{SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+    use std::{collections::BTreeMap, fmt};
+
+    pub fn serialize<B, S>(bytes: &B, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        B: AsRef<[u8]> + ?Sized,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            bytes.as_ref().serialize(serializer)
+        } else {
+            serializer.serialize_bytes(bytes.as_ref())
+        }
+    }
+
+    /// Deserializes byte strings and arrays of numbers alike, without asking
+    /// the deserializer whether it is human-readable, which the buffers of
+    /// untagged enums answer wrongly
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("bytes")
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+
+    /// Bytes serialized as `serialize` does, for fields nesting them
+    struct Binary<'a>(&'a [u8]);
+
+    impl Serialize for Binary<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize(self.0, serializer)
+        }
+    }
+
+    /// Bytes deserialized as `deserialize` does, for fields nesting them
+    struct BinaryBuf(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for BinaryBuf {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserialize(deserializer).map(BinaryBuf)
+        }
+    }
+
+    /// Optional bytes, serialized as `binary` serializes bytes
+    pub mod option {
+        use super::{Binary, BinaryBuf};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            bytes: &Option<Vec<u8>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match *bytes {
+                Some(ref bytes) => serializer.serialize_some(&Binary(bytes)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec<u8>>, D::Error> {
+            Ok(Option::<BinaryBuf>::deserialize(deserializer)?.map(|bytes| bytes.0))
+        }
+    }
+
+    /// Maps to bytes, with each value serialized as `binary` serializes bytes
+    pub mod map {
+        use super::{Binary, BinaryBuf, BTreeMap};
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<K: Serialize, S: Serializer>(
+            map: &BTreeMap<K, Vec<u8>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(map.iter().map(|(key, bytes)| (key, Binary(bytes))))
+        }
+
+        pub fn deserialize<'de, K, D>(deserializer: D) -> Result<BTreeMap<K, Vec<u8>>, D::Error>
+        where
+            K: Deserialize<'de> + Ord,
+            D: Deserializer<'de>,
+        {
+            let map = BTreeMap::<K, BinaryBuf>::deserialize(deserializer)?;
+            Ok(map.into_iter().map(|(key, bytes)| (key, bytes.0)).collect())
+        }
+    }
+}
+
+/// Returns the bits a float is compared and hashed by: its own, except that
+/// 0.0 and -0.0 are alike, as are all `NaN`s, so that equality is total
+fn float_bits(n: f64) -> u64 {
+    if n == 0.0 {
+        0
+    } else if n.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        n.to_bits()
+    }
+}
+
+/// Floats compare by `float_bits` rather than by value, so a `NaN` equals
+/// itself and values holding one can be map keys and set members.
+impl PartialEq for UnencryptedDataValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (UnencryptedDataValue::Bool(a), UnencryptedDataValue::Bool(b)) => a == b,
+            (UnencryptedDataValue::U64(a), UnencryptedDataValue::U64(b)) => a == b,
+            (UnencryptedDataValue::I64(a), UnencryptedDataValue::I64(b)) => a == b,
+            (UnencryptedDataValue::F64(a), UnencryptedDataValue::F64(b)) => {
+                float_bits(*a) == float_bits(*b)
+            }
+            (UnencryptedDataValue::String(a), UnencryptedDataValue::String(b)) => a == b,
+            (UnencryptedDataValue::Bytes(a), UnencryptedDataValue::Bytes(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for UnencryptedDataValue {}
+
+impl Hash for UnencryptedDataValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            UnencryptedDataValue::Bool(b) => b.hash(state),
+            UnencryptedDataValue::U64(n) => n.hash(state),
+            UnencryptedDataValue::I64(n) => n.hash(state),
+            UnencryptedDataValue::F64(n) => float_bits(n).hash(state),
+            UnencryptedDataValue::String(ref s) => s.hash(state),
+            UnencryptedDataValue::Bytes(ref b) => b.hash(state),
+        }
+    }
 }
 
 impl Display for UnencryptedDataValue {
@@ -111,15 +934,97 @@ impl Display for UnencryptedDataValue {
             UnencryptedDataValue::I64(ref n) => write!(f, "{}", n),
             UnencryptedDataValue::F64(ref n) => write!(f, "{}", n),
             UnencryptedDataValue::String(ref s) => write!(f, "{}", s),
+            UnencryptedDataValue::Bytes(ref b) => {
+                b.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// A ciphertext along with what is needed to decrypt it. The AEAD parameters
+/// are optional so that values encrypted before they were recorded, and by
+/// schemes without them, still deserialize.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EncryptedDataValue {
+    #[serde(with = "binary")]
     value: Vec<u8>,
     datatype: DataType,
     keyname: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "binary::option"
+    )]
+    nonce: Option<Vec<u8>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "binary::option"
+    )]
+    aad: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    algorithm: Option<String>,
+}
+
+impl EncryptedDataValue {
+    /// Builds an encrypted value from its ciphertext, the type of the
+    /// plaintext, and the name of the key it was encrypted by
+    pub fn new(value: Vec<u8>, datatype: DataType, keyname: &str) -> Self {
+        EncryptedDataValue {
+            value,
+            datatype,
+            keyname: keyname.to_owned(),
+            nonce: None,
+            aad: None,
+            algorithm: None,
+        }
+    }
+
+    /// Sets the nonce, or IV, the value was encrypted with
+    pub fn with_nonce(mut self, nonce: Vec<u8>) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Sets the additional authenticated data the value was encrypted with
+    pub fn with_aad(mut self, aad: Vec<u8>) -> Self {
+        self.aad = Some(aad);
+        self
+    }
+
+    /// Sets the identifier of the algorithm the value was encrypted with,
+    /// e.g. `AES-256-GCM`
+    pub fn with_algorithm(mut self, algorithm: &str) -> Self {
+        self.algorithm = Some(algorithm.to_owned());
+        self
+    }
+
+    /// Returns the ciphertext
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// Returns the name of the key the value was encrypted by
+    pub fn keyname(&self) -> &str {
+        &self.keyname
+    }
+
+    /// Returns the nonce, or IV, the value was encrypted with, if recorded
+    pub fn nonce(&self) -> Option<&[u8]> {
+        self.nonce.as_deref()
+    }
+
+    /// Returns the additional authenticated data the value was encrypted
+    /// with, if recorded
+    pub fn aad(&self) -> Option<&[u8]> {
+        self.aad.as_deref()
+    }
+
+    /// Returns the identifier of the algorithm the value was encrypted with,
+    /// if recorded
+    pub fn algorithm(&self) -> Option<&str> {
+        self.algorithm.as_deref()
+    }
 }
 
 impl Display for EncryptedDataValue {
@@ -176,6 +1081,12 @@ impl From<&str> for DataValue {
     }
 }
 
+impl From<Vec<u8>> for DataValue {
+    fn from(b: Vec<u8>) -> Self {
+        DataValue::Unencrypted(UnencryptedDataValue::Bytes(b))
+    }
+}
+
 impl From<Value> for DataValue {
     fn from(v: Value) -> Self {
         match v {
@@ -202,90 +1113,449 @@ impl From<Value> for DataValue {
 /// The path should always be formatted as `.my.json.path.`; note the beginning and
 /// ending periods. `DataPath` will automatically handle path validation when
 /// created or deserialized, just provide any valid json-path on creation.
-#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+/// The path is reference-counted, so clones share it, and a `PathInterner`
+/// can make separately created paths share it too.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(into = "String", from = "String")]
 pub struct DataPath {
-    path: String,
+    path: Arc<str>,
+}
+
+impl Default for DataPath {
+    fn default() -> Self {
+        DataPath { path: Arc::from("") }
+    }
+}
+
+impl DataPath {
+    /// Validates a given string and returns a new DataPath
+    pub fn new(path: &str) -> Self {
+        let path = Self::validate_path(path).into();
+        Self { path }
+    }
+
+    /// Returns the path as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns whether the path lies within the subtree described by a pattern,
+    /// a path in which a `*` segment matches any single segment, e.g. `.users.*.email.`
+    pub fn matches(&self, pattern: &str) -> bool {
+        let mut segments = self.path.split('.').filter(|s| !s.is_empty());
+        pattern
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .all(|p| segments.next().is_some_and(|s| p == "*" || p == s))
+    }
+
+    /// Parses a path exactly as given, failing rather than adding missing
+    /// beginning or ending periods, so that only paths already in the
+    /// `.my.json.path.` form are accepted
+    pub fn parse_strict(path: &str) -> Result<Self, DataPathError> {
+        let dp = path.parse::<DataPath>()?;
+        match dp.as_str() == path {
+            true => Ok(dp),
+            false => Err(DataPathError::NotNormalized {
+                path: path.to_owned(),
+            }),
+        }
+    }
+
+    /// Deserializes a path with `parse_strict`, for use as
+    /// `#[serde(deserialize_with = "DataPath::deserialize_strict")]` where
+    /// malformed paths should be rejected instead of normalized
+    pub fn deserialize_strict<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let path = String::deserialize(deserializer)?;
+        Self::parse_strict(&path).map_err(serde::de::Error::custom)
+    }
+
+    // Ensures that a data entry path begins and ends with a period ('.')
+    // Empty strings will return as "."
+    // Strings of length 1 where the only char is a period will return as "."
+    // All other strings will have periods added to the beginning or end if needed.
+    // For now, string containing multiple periods in a row, or composed only of
+    // multiple periods, will be accepted and returned as given, with the same
+    // behavior as any other standard string of len > 1.
+    // This function is implemented as a boolean circuit to avoid iterating through
+    // the same string numerous times.
+    fn validate_path(path: &str) -> String {
+        // Short circuit if path is empty
+        if path.is_empty() {
+            return ".".to_owned();
+        }
+
+        // Collect the first and last characters of the path
+        let mut path_chars = path.chars();
+        let first_char = path_chars.next();
+        let last_char = path_chars.last();
+
+        // Match on the results of char extraction
+        match (first_char, last_char) {
+            // String length >= 2
+            (Some(fc), Some(lc)) => {
+                if fc != '.' && lc != '.' {
+                    format!(".{}.", path)
+                } else if fc == '.' && lc == '.' {
+                    path.to_owned()
+                } else if fc != '.' {
+                    format!(".{}", path)
+                } else {
+                    format!("{}.", path)
+                }
+            }
+            // String length == 1
+            (Some(fc), None) => {
+                if fc == '.' {
+                    path.to_owned()
+                } else {
+                    format!(".{}.", path)
+                }
+            }
+            // Impossible case: string length == 0, should never be here because
+            // of the short-circuit implemented at the beginning of the function
+            (None, None) => panic!(
+                "this is an impossible situation; if you have gotten here, \\
+	     a short-circuit earlier in the function has failed to function as \\
+	     intended"
+            ),
+            // Impossible case: if this happens we should panic because something is
+            // fundamentally wrong with the computing environment and someone should
+            // know about it.
+            // If the last char is != None, then it MUST BE that the
+            // first char is != None, as the last char is collected after the
+            // iterator has ticked over one spot to account for the first char,
+            // therefore if the iterator finds something in the last() call, then
+            // it must be after having collected something from the nth(0) call.
+            (None, Some(_)) => panic!(
+                "this is an impossible situation; if you have gotten here, \\
+	     something has happened that should never happen according to the \\
+	     laws of computing and/or the rust compiler. if you have gotten here, \\
+	     some major memory or computing trickery has occurred and you should \\
+	     be concerned for the integrity of your computing base"
+            ),
+        }
+    }
+}
+
+/// Returned when a value is not of the type it was expected to be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatchError {
+    pub expected: DataType,
+    /// The type of the value found, or `None` if the data did not hold a
+    /// single value
+    pub found: Option<DataType>,
+    pub encrypted: bool,
+}
+
+impl std::error::Error for TypeMismatchError {}
+
+impl Display for TypeMismatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.found {
+            None => write!(f, "Expected a single value of type {}", self.expected),
+            Some(ref found) if self.encrypted => write!(
+                f,
+                "Expected an unencrypted value of type {}, found an encrypted one of type {}",
+                self.expected, found
+            ),
+            Some(ref found) => write!(
+                f,
+                "Expected a value of type {}, found one of type {}",
+                self.expected, found
+            ),
+        }
+    }
+}
+
+/// The Rust types values can be extracted as with `Data::value_as`. Integers
+/// convert between `u64` and `i64` when they fit, and any number converts to
+/// `f64`, possibly losing precision.
+pub trait ValueType: TryFrom<DataValue, Error = TypeMismatchError> {
+    /// The type of value the type is extracted from
+    const DATATYPE: DataType;
+}
+
+fn mismatch(expected: DataType, value: &DataValue) -> TypeMismatchError {
+    TypeMismatchError {
+        expected,
+        found: Some(value.datatype()),
+        encrypted: matches!(*value, DataValue::Encrypted(_)),
+    }
+}
+
+impl TryFrom<DataValue> for bool {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Unencrypted(UnencryptedDataValue::Bool(b)) => Ok(b),
+            _ => Err(mismatch(DataType::Bool, &value)),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for u64 {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Unencrypted(UnencryptedDataValue::U64(n)) => Ok(n),
+            DataValue::Unencrypted(UnencryptedDataValue::I64(n)) if n >= 0 => Ok(n as u64),
+            _ => Err(mismatch(DataType::U64, &value)),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for i64 {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Unencrypted(UnencryptedDataValue::I64(n)) => Ok(n),
+            DataValue::Unencrypted(UnencryptedDataValue::U64(n)) if n <= i64::MAX as u64 => {
+                Ok(n as i64)
+            }
+            _ => Err(mismatch(DataType::I64, &value)),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for f64 {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or_else(|| mismatch(DataType::F64, &value))
+    }
+}
+
+impl TryFrom<DataValue> for String {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Unencrypted(UnencryptedDataValue::String(s)) => Ok(s),
+            _ => Err(mismatch(DataType::String, &value)),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for Vec<u8> {
+    type Error = TypeMismatchError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Unencrypted(UnencryptedDataValue::Bytes(b)) => Ok(b),
+            _ => Err(mismatch(DataType::Bytes, &value)),
+        }
+    }
+}
+
+impl ValueType for bool {
+    const DATATYPE: DataType = DataType::Bool;
+}
+
+impl ValueType for u64 {
+    const DATATYPE: DataType = DataType::U64;
+}
+
+impl ValueType for i64 {
+    const DATATYPE: DataType = DataType::I64;
+}
+
+impl ValueType for f64 {
+    const DATATYPE: DataType = DataType::F64;
+}
+
+impl ValueType for String {
+    const DATATYPE: DataType = DataType::String;
+}
+
+impl ValueType for Vec<u8> {
+    const DATATYPE: DataType = DataType::Bytes;
+}
+
+/// The name of an encryption key, which is never empty and holds no whitespace
+/// or control characters, so that a mangled name is rejected when the data
+/// naming it is built or decoded rather than once decryption fails.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyName(String);
+
+/// Returned when a string is not a valid `KeyName`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKeyName {
+    pub keyname: String,
+}
+
+impl std::error::Error for InvalidKeyName {}
+
+impl Display for InvalidKeyName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Key name {:?} is invalid", self.keyname)
+    }
+}
+
+impl KeyName {
+    /// Validates a key name
+    pub fn new(keyname: &str) -> Result<Self, InvalidKeyName> {
+        Self::try_from(keyname.to_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for KeyName {
+    type Error = InvalidKeyName;
+
+    fn try_from(keyname: String) -> Result<Self, Self::Error> {
+        let valid = !keyname.is_empty()
+            && !keyname.chars().any(|c| c.is_whitespace() || c.is_control());
+        match valid {
+            true => Ok(KeyName(keyname)),
+            false => Err(InvalidKeyName { keyname }),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for KeyName {
+    type Error = InvalidKeyName;
+
+    fn try_from(keyname: &'a str) -> Result<Self, Self::Error> {
+        Self::new(keyname)
+    }
+}
+
+impl FromStr for KeyName {
+    type Err = InvalidKeyName;
+
+    fn from_str(keyname: &str) -> Result<Self, Self::Err> {
+        Self::new(keyname)
+    }
+}
+
+impl AsRef<str> for KeyName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for KeyName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<KeyName> for String {
+    fn from(keyname: KeyName) -> Self {
+        keyname.0
+    }
+}
+
+/// Paths are ordered segment by segment, so that every path sorts directly
+/// before its descendants, and numeric segments such as array indices are
+/// ordered by value, e.g. `.a.` < `.a.2.` < `.a.2.x.` < `.a.10.` < `.a-b.`.
+impl Ord for DataPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.path
+            .split('.')
+            .zip(other.path.split('.'))
+            .map(|(a, b)| compare_segments(a, b))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| {
+                self.path
+                    .split('.')
+                    .count()
+                    .cmp(&other.path.split('.').count())
+            })
+    }
+}
+
+impl PartialOrd for DataPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares two path segments, numerically if both are made of digits
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if is_number(a) && is_number(b) {
+        let (ta, tb) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+        ta.len()
+            .cmp(&tb.len())
+            .then_with(|| ta.cmp(tb))
+            .then_with(|| a.cmp(b))
+    } else {
+        a.cmp(b)
+    }
 }
 
-impl DataPath {
-    /// Validates a given string and returns a new DataPath
-    pub fn new(path: &str) -> Self {
-        let path = Self::validate_path(path);
-        Self { path }
+impl Display for DataPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)
     }
+}
 
-    // Ensures that a data entry path begins and ends with a period ('.')
-    // Empty strings will return as "."
-    // Strings of length 1 where the only char is a period will return as "."
-    // All other strings will have periods added to the beginning or end if needed.
-    // For now, string containing multiple periods in a row, or composed only of
-    // multiple periods, will be accepted and returned as given, with the same
-    // behavior as any other standard string of len > 1.
-    // This function is implemented as a boolean circuit to avoid iterating through
-    // the same string numerous times.
-    fn validate_path(path: &str) -> String {
-        // Short circuit if path is empty
-        if path.is_empty() {
-            return ".".to_owned();
-        }
+/// Returned when a string is not a valid `DataPath`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataPathError {
+    /// The path was an empty string
+    Empty,
+    /// The path has an empty segment, e.g. `.a..b.`
+    EmptySegment { path: String },
+    /// The path holds control characters
+    InvalidCharacter { path: String },
+    /// The path is missing its beginning or ending period, only returned by
+    /// `DataPath::parse_strict`
+    NotNormalized { path: String },
+}
 
-        // Collect the first and last characters of the path
-        let mut path_chars = path.chars();
-        let first_char = path_chars.next();
-        let last_char = path_chars.last();
+impl std::error::Error for DataPathError {}
 
-        // Match on the results of char extraction
-        match (first_char, last_char) {
-            // String length >= 2
-            (Some(fc), Some(lc)) => {
-                if fc != '.' && lc != '.' {
-                    format!(".{}.", path)
-                } else if fc == '.' && lc == '.' {
-                    path.to_owned()
-                } else if fc != '.' {
-                    format!(".{}", path)
-                } else {
-                    format!("{}.", path)
-                }
+impl Display for DataPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            DataPathError::Empty => write!(f, "Data path is empty"),
+            DataPathError::EmptySegment { ref path } => {
+                write!(f, "Data path {:?} has an empty segment", path)
             }
-            // String length == 1
-            (Some(fc), None) => {
-                if fc == '.' {
-                    path.to_owned()
-                } else {
-                    format!(".{}.", path)
-                }
+            DataPathError::InvalidCharacter { ref path } => {
+                write!(f, "Data path {:?} holds control characters", path)
+            }
+            DataPathError::NotNormalized { ref path } => {
+                write!(f, "Data path {:?} must begin and end with a period", path)
             }
-            // Impossible case: string length == 0, should never be here because
-            // of the short-circuit implemented at the beginning of the function
-            (None, None) => panic!(
-                "this is an impossible situation; if you have gotten here, \\
-	     a short-circuit earlier in the function has failed to function as \\
-	     intended"
-            ),
-            // Impossible case: if this happens we should panic because something is
-            // fundamentally wrong with the computing environment and someone should
-            // know about it.
-            // If the last char is != None, then it MUST BE that the
-            // first char is != None, as the last char is collected after the
-            // iterator has ticked over one spot to account for the first char,
-            // therefore if the iterator finds something in the last() call, then
-            // it must be after having collected something from the nth(0) call.
-            (None, Some(_)) => panic!(
-                "this is an impossible situation; if you have gotten here, \\
-	     something has happened that should never happen according to the \\
-	     laws of computing and/or the rust compiler. if you have gotten here, \\
-	     some major memory or computing trickery has occurred and you should \\
-	     be concerned for the integrity of your computing base"
-            ),
         }
     }
 }
 
-impl Display for DataPath {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.path)
+/// Parses a path, adding missing beginning or ending periods as `new` does but
+/// rejecting paths `new` would accept as given, such as those with empty
+/// segments
+impl FromStr for DataPath {
+    type Err = DataPathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        if path.is_empty() {
+            return Err(DataPathError::Empty);
+        }
+        if path.chars().any(char::is_control) {
+            return Err(DataPathError::InvalidCharacter {
+                path: path.to_owned(),
+            });
+        }
+        if path.contains("..") {
+            return Err(DataPathError::EmptySegment {
+                path: path.to_owned(),
+            });
+        }
+        Ok(Self::new(path))
     }
 }
 
@@ -309,8 +1579,372 @@ impl From<DataPath> for String {
     }
 }
 
+/// Deduplicates paths, so that data at the same path shares a single copy of
+/// it even when created or deserialized separately, e.g. when holding many
+/// versions of the same entries in memory. Clones share the same paths.
+#[derive(Debug, Clone, Default)]
+pub struct PathInterner {
+    paths: Arc<Mutex<HashSet<Arc<str>>>>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the path, sharing the copy held by the interner if any
+    pub fn intern(&self, path: &DataPath) -> DataPath {
+        let mut paths = self.paths.lock().unwrap();
+        match paths.get(&path.path) {
+            Some(interned) => DataPath {
+                path: interned.clone(),
+            },
+            None => {
+                paths.insert(path.path.clone());
+                path.clone()
+            }
+        }
+    }
+
+    /// Replaces the path of the data with the interned copy
+    pub fn intern_data(&self, data: &mut Data) {
+        data.path = self.intern(&data.path);
+    }
+
+    /// Returns how many distinct paths are held
+    pub fn len(&self) -> usize {
+        self.paths.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    mod data {
+        use crate::data::{
+            DataType, EncryptedDataValue, InvalidKeyName, KeyName, TypeMismatchError,
+        };
+        use std::convert::TryFrom;
+        use crate::{Data, DataValue, DataValueCollection};
+
+        #[test]
+        fn test_single_value_is_stored_inline() {
+            let d = Data::new(".path.", DataValue::from(1u64));
+            assert!(!d.value().0.spilled());
+            let json = serde_json::to_string(&d).unwrap();
+            let decoded: Data = serde_json::from_str(&json).unwrap();
+            assert!(!decoded.value().0.spilled());
+            assert_eq!(d, decoded);
+            let many: DataValueCollection = vec![DataValue::from(1u64), DataValue::from(2u64)].into();
+            assert_eq!("[{\"Unencrypted\":{\"U64\":1}},{\"Unencrypted\":{\"U64\":2}}]", serde_json::to_string(&many).unwrap());
+        }
+
+        #[test]
+        fn test_new_has_no_integrity() {
+            let d = Data::new(".path.", DataValue::from(1u64));
+            assert_eq!(None, d.integrity());
+            assert_eq!(None, d.verify_integrity());
+        }
+
+        #[test]
+        fn test_encryptedby_lists_distinct_key_names() {
+            let d = Data::with_values(
+                ".path.",
+                DataValueCollection::from(vec![
+                    DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, "k2")),
+                    DataValue::from(1u64),
+                    DataValue::Encrypted(EncryptedDataValue::new(vec![2], DataType::U64, "k1")),
+                    DataValue::Encrypted(EncryptedDataValue::new(vec![3], DataType::U64, "k2")),
+                ]),
+            );
+            assert_eq!(vec!["k2", "k1"], d.encryptedby());
+        }
+
+        #[test]
+        fn test_value_as() {
+            assert_eq!(Ok(true), Data::new(".a.", DataValue::from(true)).value_as());
+            assert_eq!(Ok(3u64), Data::new(".a.", DataValue::from(3i64)).value_as());
+            assert_eq!(Ok(-3i64), Data::new(".a.", DataValue::from(-3i64)).value_as());
+            assert_eq!(Ok(3.0), Data::new(".a.", DataValue::from(3u64)).value_as::<f64>());
+            assert_eq!(
+                Ok("x".to_owned()),
+                Data::new(".a.", DataValue::from("x")).value_as::<String>()
+            );
+            assert_eq!(
+                Ok(vec![1u8]),
+                Data::new(".a.", DataValue::from(vec![1u8])).value_as::<Vec<u8>>()
+            );
+
+            let mismatch = Data::new(".a.", DataValue::from(-3i64)).value_as::<u64>().unwrap_err();
+            assert_eq!("Expected a value of type u64, found one of type i64", mismatch.to_string());
+            let encrypted = Data::new(
+                ".a.",
+                DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, "k")),
+            );
+            assert_eq!(
+                Err(TypeMismatchError {
+                    expected: DataType::U64,
+                    found: Some(DataType::U64),
+                    encrypted: true,
+                }),
+                encrypted.value_as::<u64>()
+            );
+            let several = Data::with_values(
+                ".a.",
+                DataValueCollection::from(vec![DataValue::from(1u64), DataValue::from(2u64)]),
+            );
+            assert_eq!(
+                "Expected a single value of type u64",
+                several.value_as::<u64>().unwrap_err().to_string()
+            );
+            assert_eq!(Ok(2), u64::try_from(DataValue::from(2u64)));
+        }
+
+        #[test]
+        fn test_encrypted_by_mutation() {
+            let mut d = Data::new(".path.", DataValue::from(1u64));
+            let k1 = KeyName::new("k1").unwrap();
+            assert!(d.add_encrypted_by(k1.clone()));
+            assert!(!d.add_encrypted_by(k1.clone()));
+            assert!(d.add_encrypted_by(KeyName::new("k2").unwrap()));
+            assert_eq!(vec!["k1", "k2"], d.encryptedby());
+            assert!(d.remove_encrypted_by(&k1));
+            assert!(!d.remove_encrypted_by(&k1));
+            assert_eq!(vec!["k2"], d.encryptedby());
+        }
+
+        #[test]
+        fn test_recipients() {
+            let mut d = Data::new(".path.", DataValue::from(1u64));
+            let k1 = KeyName::new("k1").unwrap();
+            d.add_recipient(k1.clone(), vec![1]);
+            d.add_recipient(KeyName::new("k2").unwrap(), vec![2]);
+            d.add_recipient(k1.clone(), vec![3]);
+            assert_eq!(Some(&[3][..]), d.wrapped_key("k1"));
+            assert_eq!(2, d.recipients().len());
+            let json = serde_json::to_string(&d).unwrap();
+            assert_eq!(
+                r#"{"path":".path.","value":[{"Unencrypted":{"U64":1}}],"recipients":{"k1":[3],"k2":[2]}}"#,
+                json
+            );
+            assert_eq!(d, serde_json::from_str::<Data>(&json).unwrap());
+            assert!(d.remove_recipient(&k1));
+            assert!(!d.remove_recipient(&k1));
+            assert_eq!(None, d.wrapped_key("k1"));
+        }
+
+        #[test]
+        fn test_links() {
+            let mut d = Data::new(".derived.", DataValue::from(1u64));
+            assert!(d.add_link("derived_from", "source.1"));
+            assert!(!d.add_link("derived_from", ".source.1."));
+            assert!(d.add_link("derived_from", ".source.2."));
+            assert!(d.add_link("owner", ".users.1."));
+            assert_eq!(vec![".source.1.", ".source.2."], d.link_targets("derived_from"));
+            let json = serde_json::to_string(&d).unwrap();
+            assert!(json.ends_with(
+                r#""links":[{"link_type":"derived_from","target":".source.1."},{"link_type":"derived_from","target":".source.2."},{"link_type":"owner","target":".users.1."}]}"#
+            ));
+            assert_eq!(d, serde_json::from_str::<Data>(&json).unwrap());
+            assert!(d.remove_link("derived_from", "source.1"));
+            assert!(!d.remove_link("owner", ".source.2."));
+            assert_eq!(vec![".source.2."], d.link_targets("derived_from"));
+        }
+
+        #[test]
+        fn test_key_names_are_validated() {
+            assert_eq!("k1", KeyName::new("k1").unwrap().as_str());
+            for invalid in ["", "k 1", "k1\n"] {
+                assert_eq!(
+                    Err(InvalidKeyName { keyname: invalid.to_owned() }),
+                    invalid.parse::<KeyName>()
+                );
+            }
+            assert!(serde_json::from_str::<Data>(
+                r#"{"path":".a.","value":[],"encryptedby":["k 1"]}"#
+            )
+            .is_err());
+            assert_eq!(
+                "Key name \"k 1\" is invalid",
+                InvalidKeyName { keyname: "k 1".to_owned() }.to_string()
+            );
+        }
+
+        #[test]
+        fn test_hash_set_dedup() {
+            let data: std::collections::HashSet<Data> = vec![
+                Data::new(".a.", DataValue::from(0.0)),
+                Data::new(".a.", DataValue::from(-0.0)),
+                Data::new(".a.", DataValue::from(1u64)),
+                Data::new(".b.", DataValue::from(1u64)),
+                Data::new(".b.", DataValue::from(1u64)),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(3, data.len());
+        }
+
+        #[test]
+        fn test_nan_equals_itself() {
+            let nan = Data::new(".a.", DataValue::from(f64::NAN));
+            assert_eq!(nan, nan.clone());
+            assert_eq!(nan, Data::new(".a.", DataValue::from(-f64::NAN)));
+            assert_ne!(nan, Data::new(".a.", DataValue::from(0.0)));
+            let data: std::collections::HashSet<Data> =
+                vec![nan.clone(), nan].into_iter().collect();
+            assert_eq!(1, data.len());
+        }
+
+        #[test]
+        fn test_canonical_bytes() {
+            let d = Data::new(".path.", DataValue::from(1u64));
+            assert_eq!(
+                "{\"path\":\".path.\",\"value\":[{\"Unencrypted\":{\"U64\":1}}]}".as_bytes(),
+                &d.canonical_bytes()[..]
+            );
+        }
+
+        #[test]
+        fn test_canonical_bytes_matches_compact_json_for_every_type() {
+            let d = Data::with_values(
+                "path",
+                DataValueCollection::from(vec![
+                    DataValue::from(true),
+                    DataValue::from(-4i64),
+                    DataValue::from(1.5f64),
+                    DataValue::from("a \"quoted\" ünicode string"),
+                    DataValue::from(vec![0u8, 255]),
+                    DataValue::Encrypted(EncryptedDataValue::new(
+                        vec![1, 2, 3],
+                        DataType::String,
+                        "somekey",
+                    )),
+                    DataValue::Encrypted(
+                        EncryptedDataValue::new(vec![4], DataType::U64, "somekey")
+                            .with_nonce(vec![9, 8])
+                            .with_aad(b"ctx".to_vec())
+                            .with_algorithm("AES-256-GCM"),
+                    ),
+                ]),
+            );
+            assert_eq!(serde_json::to_vec(&d).unwrap(), d.canonical_bytes());
+        }
+
+        #[test]
+        fn test_canonical_bytes_excludes_integrity() {
+            let mut d = Data::new(".path.", DataValue::from(1u64));
+            let unsealed = d.canonical_bytes();
+            d.seal_integrity();
+            assert_eq!(unsealed, d.canonical_bytes());
+        }
+
+        #[test]
+        fn test_canonical_bytes_whole_float() {
+            let d = Data::new(".path.", DataValue::from(2.0f64));
+            assert_eq!(
+                "{\"path\":\".path.\",\"value\":[{\"Unencrypted\":{\"F64\":2.0}}]}".as_bytes(),
+                &d.canonical_bytes()[..]
+            );
+        }
+
+        #[test]
+        fn test_sealed_integrity_verifies() {
+            let mut d = Data::new(".path.", DataValue::from(1u64));
+            d.seal_integrity();
+            assert_eq!(64, d.integrity().unwrap().len());
+            assert_eq!(Some(true), d.verify_integrity());
+        }
+
+        #[test]
+        fn test_tampered_value_fails_verification() {
+            let mut d = Data::new(".path.", DataValue::from(1u64));
+            d.seal_integrity();
+            let json = serde_json::to_string(&d).unwrap().replace("1}", "2}");
+            let tampered: Data = serde_json::from_str(&json).unwrap();
+            assert_eq!(Some(false), tampered.verify_integrity());
+        }
+
+        #[test]
+        fn test_integrity_is_omitted_when_absent() {
+            let d = Data::new(".path.", DataValue::from(1u64));
+            assert!(!serde_json::to_string(&d).unwrap().contains("integrity"));
+        }
+    }
+    mod datacollection {
+        use crate::data::{Data, DataCollection, DataValue, PathMergeStrategy};
+        use crate::Cursor;
+
+        fn values(dc: &DataCollection) -> Vec<String> {
+            dc.data
+                .iter()
+                .map(|d| {
+                    let values: Vec<String> = d.value().0.iter().map(|v| v.to_string()).collect();
+                    format!("{}={}", d.path(), values.join(","))
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_dedup_by_path() {
+            let mut later = Data::new(".a.", DataValue::from(2u64));
+            later.set_written_at(Some(10));
+            let dc = DataCollection::new(vec![
+                Data::new(".a.", DataValue::from(1u64)),
+                Data::new(".b.", DataValue::from(1u64)),
+                later,
+                Data::new(".a.", DataValue::from(1u64)),
+            ]);
+            let deduped = |strategy| {
+                let mut dc = dc.clone();
+                dc.dedup_by_path(strategy);
+                values(&dc)
+            };
+            assert_eq!(vec![".a.=1,2,1", ".b.=1"], deduped(PathMergeStrategy::Concat));
+            assert_eq!(vec![".a.=1,2", ".b.=1"], deduped(PathMergeStrategy::Union));
+            assert_eq!(vec![".a.=1", ".b.=1"], deduped(PathMergeStrategy::KeepFirst));
+            assert_eq!(vec![".a.=2", ".b.=1"], deduped(PathMergeStrategy::KeepLatest));
+        }
+
+        #[test]
+        fn test_merge() {
+            let mut dc = DataCollection::new(vec![Data::new(".a.", DataValue::from(1u64))]);
+            dc.next_cursor = Some(Cursor::new("1"));
+            dc.merge(
+                DataCollection::new(vec![
+                    Data::new(".c.", DataValue::from(3u64)),
+                    Data::new(".a.", DataValue::from(2u64)),
+                ]),
+                PathMergeStrategy::Union,
+            );
+            assert_eq!(vec![".a.=1,2", ".c.=3"], values(&dc));
+            assert_eq!(None, dc.next_cursor);
+        }
+
+        #[test]
+        fn test_deserialize_without_cursor() {
+            let dc: DataCollection = serde_json::from_str("{\"data\":[]}").unwrap();
+            assert_eq!(DataCollection::new(vec![]), dc);
+        }
+
+        #[test]
+        fn test_serialize_omits_missing_cursor() {
+            let dc = DataCollection::new(vec![]);
+            assert_eq!("{\"data\":[]}", serde_json::to_string(&dc).unwrap());
+        }
+
+        #[test]
+        fn test_serialize_with_cursor() {
+            let mut dc = DataCollection::new(vec![]);
+            dc.next_cursor = Some(Cursor::new("10"));
+            assert_eq!(
+                "{\"data\":[],\"next_cursor\":\"10\"}",
+                serde_json::to_string(&dc).unwrap()
+            );
+        }
+    }
     mod datavaluecollection {
         use crate::data::DataValueCollection;
 
@@ -332,11 +1966,11 @@ mod tests {
 
         #[test]
         fn test_to_string_encrypted() {
-            let dv = DataValue::Encrypted(EncryptedDataValue {
-                value: "hello".into(),
-                datatype: DataType::String,
-                keyname: "somekey".to_owned(),
-            });
+            let dv = DataValue::Encrypted(EncryptedDataValue::new(
+                "hello".into(),
+                DataType::String,
+                "somekey",
+            ));
 
             assert_eq!(
                 "encrypted(key: \"somekey\", type: \"string\", value: \"hello\")",
@@ -350,6 +1984,48 @@ mod tests {
 
             assert_eq!("true", dv.to_string())
         }
+
+        #[test]
+        fn test_as_f64_numbers() {
+            assert_eq!(Some(3.0), DataValue::from(3u64).as_f64());
+            assert_eq!(Some(-3.0), DataValue::from(-3i64).as_f64());
+            assert_eq!(Some(1.5), DataValue::from(1.5f64).as_f64());
+        }
+
+        #[test]
+        fn test_parse_infers_types() {
+            assert_eq!(DataValue::from(true), DataValue::parse("true"));
+            assert_eq!(DataValue::from(false), "false".parse().unwrap());
+            assert_eq!(DataValue::from(42u64), DataValue::parse("42"));
+            assert_eq!(DataValue::from(0u64), DataValue::parse("0"));
+            assert_eq!(DataValue::from(-42i64), DataValue::parse("-42"));
+            assert_eq!(DataValue::from(1.5f64), DataValue::parse("1.5"));
+            assert_eq!(DataValue::from(-0.5f64), DataValue::parse("-.5"));
+            assert_eq!(DataValue::from(1e3f64), DataValue::parse("1e3"));
+            assert_eq!(DataValue::from(u64::MAX as f64), DataValue::parse("18446744073709551616"));
+        }
+
+        #[test]
+        fn test_parse_falls_back_to_string() {
+            for s in ["", "True", "abc", "1.2.3", "007", "-01", "inf", "NaN", "1e999", " 1"] {
+                assert_eq!(DataValue::from(s), DataValue::parse(s));
+            }
+        }
+
+        #[test]
+        fn test_as_f64_non_numbers() {
+            assert_eq!(None, DataValue::from(true).as_f64());
+            assert_eq!(None, DataValue::from("3").as_f64());
+            assert_eq!(
+                None,
+                DataValue::Encrypted(EncryptedDataValue::new(
+                    "3".into(),
+                    DataType::U64,
+                    "somekey",
+                ))
+                .as_f64()
+            );
+        }
     }
     mod datatype {
         use crate::data::DataType;
@@ -388,9 +2064,41 @@ mod tests {
 
             assert_eq!("string", dt.to_string())
         }
+
+        #[test]
+        fn test_to_string_bytes() {
+            let dt = DataType::Bytes;
+
+            assert_eq!("bytes", dt.to_string())
+        }
     }
     mod unencrypteddatavalue {
         use crate::data::{DataValue, UnencryptedDataValue};
+        use crate::Data;
+
+        #[test]
+        fn test_debug_redacts_values() {
+            let d = Data::new(".user.email.", DataValue::from("ada@example.com"));
+            let debug = format!("{:?}", d);
+            assert!(!debug.contains("ada@example.com"));
+            assert!(debug.contains(".user.email."));
+            assert!(debug.contains("Unencrypted(String(<redacted>))"));
+            assert_eq!(
+                "U64(<redacted>)",
+                format!("{:?}", UnencryptedDataValue::U64(42))
+            );
+        }
+
+        #[test]
+        fn test_debug_unredacted_shows_values() {
+            let d = Data::new(".user.email.", DataValue::from("ada@example.com"));
+            assert!(format!("{:?}", d.debug_unredacted())
+                .contains("Unencrypted(String(\"ada@example.com\"))"));
+            assert_eq!(
+                "U64(42)",
+                format!("{:?}", UnencryptedDataValue::U64(42).debug_unredacted())
+            );
+        }
 
         #[test]
         fn test_to_string_unencrypted_bool_true() {
@@ -433,23 +2141,59 @@ mod tests {
 
             assert_eq!("hello", dv.to_string())
         }
+
+        #[test]
+        fn test_to_string_unencrypted_bytes_is_hex() {
+            let dv = DataValue::from(vec![0x00, 0xab, 0x10]);
+
+            assert_eq!("00ab10", dv.to_string())
+        }
     }
     mod encrypteddatavalue {
         use crate::data::{DataType, DataValue, EncryptedDataValue};
 
         #[test]
         fn test_to_string_encrypted() {
-            let dv = DataValue::Encrypted(EncryptedDataValue {
-                value: "hello".into(),
-                datatype: DataType::String,
-                keyname: "somekey".to_owned(),
-            });
+            let dv = DataValue::Encrypted(EncryptedDataValue::new(
+                "hello".into(),
+                DataType::String,
+                "somekey",
+            ));
 
             assert_eq!(
                 "encrypted(key: \"somekey\", type: \"string\", value: \"hello\")",
                 dv.to_string()
             )
         }
+
+        #[test]
+        fn test_deserializes_without_aead_parameters() {
+            let dv: EncryptedDataValue =
+                serde_json::from_str(r#"{"value":[1],"datatype":"U64","keyname":"k"}"#).unwrap();
+            assert_eq!(EncryptedDataValue::new(vec![1], DataType::U64, "k"), dv);
+            assert_eq!(None, dv.nonce());
+            assert_eq!(
+                r#"{"value":[1],"datatype":"U64","keyname":"k"}"#,
+                serde_json::to_string(&dv).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_round_trips_aead_parameters() {
+            let dv = EncryptedDataValue::new(vec![1], DataType::U64, "k")
+                .with_nonce(vec![2, 3])
+                .with_aad(vec![4])
+                .with_algorithm("AES-256-GCM");
+            let json = serde_json::to_string(&dv).unwrap();
+            assert_eq!(
+                r#"{"value":[1],"datatype":"U64","keyname":"k","nonce":[2,3],"aad":[4],"algorithm":"AES-256-GCM"}"#,
+                json
+            );
+            let dv: EncryptedDataValue = serde_json::from_str(&json).unwrap();
+            assert_eq!(Some(&[2, 3][..]), dv.nonce());
+            assert_eq!(Some(&[4][..]), dv.aad());
+            assert_eq!(Some("AES-256-GCM"), dv.algorithm());
+        }
     }
 
     // #[test]
@@ -699,7 +2443,8 @@ mod tests {
     // }
 
     mod datapath {
-        use crate::data::DataPath;
+        use crate::data::{DataPath, DataPathError, PathInterner};
+        use crate::{Data, DataValue};
         use std::convert::From;
 
         #[test]
@@ -708,6 +2453,33 @@ mod tests {
             assert_eq!(dp.to_string(), ".my.path.");
         }
 
+        #[test]
+        fn test_ordering() {
+            let mut paths: Vec<DataPath> = vec![".a-b.", ".a.10.", ".", ".a.2.x.", ".a.", ".a.2.", ".a.02."]
+                .into_iter()
+                .map(DataPath::new)
+                .collect();
+            paths.sort();
+            let sorted: Vec<String> = paths.iter().map(DataPath::to_string).collect();
+            assert_eq!(vec![".", ".a.", ".a.02.", ".a.2.", ".a.2.x.", ".a.10.", ".a-b."], sorted);
+        }
+
+        #[test]
+        fn test_hash_set_dedup() {
+            let paths: std::collections::HashSet<DataPath> =
+                vec![".a.", "a", ".b."].into_iter().map(DataPath::new).collect();
+            assert_eq!(2, paths.len());
+        }
+
+        #[test]
+        fn test_matches_pattern() {
+            let dp = DataPath::new(".users.abc.email.");
+            assert!(dp.matches(".users.*.email."));
+            assert!(dp.matches(".users."));
+            assert!(!dp.matches(".users.*.phone."));
+            assert!(!dp.matches(".users.abc.email.primary."));
+        }
+
         #[test]
         fn test_new_with_path_missing_first_period() {
             let dp = DataPath::new("my.path.");
@@ -762,6 +2534,85 @@ mod tests {
             assert_eq!(dp.to_string(), ".my.path.");
         }
 
+        #[test]
+        fn test_from_str_normalizes_valid_paths() {
+            assert_eq!(Ok(DataPath::new(".my.path.")), "my.path".parse::<DataPath>());
+            assert_eq!(Ok(DataPath::new(".")), ".".parse::<DataPath>());
+        }
+
+        #[test]
+        fn test_from_str_rejects_malformed_paths() {
+            assert_eq!(Err(DataPathError::Empty), "".parse::<DataPath>());
+            for path in &["..", ".a..b.", "a.."] {
+                assert_eq!(
+                    Err(DataPathError::EmptySegment { path: path.to_string() }),
+                    path.parse::<DataPath>()
+                );
+            }
+            assert_eq!(
+                Err(DataPathError::InvalidCharacter { path: ".a\n.".to_owned() }),
+                ".a\n.".parse::<DataPath>()
+            );
+        }
+
+        #[test]
+        fn test_parse_strict() {
+            assert_eq!(Ok(DataPath::new(".my.path.")), DataPath::parse_strict(".my.path."));
+            assert_eq!(
+                Err(DataPathError::NotNormalized { path: "my.path".to_owned() }),
+                DataPath::parse_strict("my.path")
+            );
+            assert_eq!(Err(DataPathError::Empty), DataPath::parse_strict(""));
+        }
+
+        #[test]
+        fn test_deserialize_strict() {
+            #[derive(serde::Deserialize, Debug)]
+            struct Entry {
+                #[serde(deserialize_with = "DataPath::deserialize_strict")]
+                path: DataPath,
+            }
+
+            let entry: Entry = serde_json::from_str(r#"{"path":".a.b."}"#).unwrap();
+            assert_eq!(DataPath::new(".a.b."), entry.path);
+            for path in &["a.b", ".a..b.", ""] {
+                let json = format!(r#"{{"path":"{}"}}"#, path);
+                assert!(serde_json::from_str::<Entry>(&json).is_err());
+            }
+            let lenient: DataPath = serde_json::from_str(r#""a..b""#).unwrap();
+            assert_eq!(".a..b.", lenient.to_string());
+        }
+
+        #[test]
+        fn test_to_string_data_path_error() {
+            assert_eq!(
+                "Data path \"a.b\" must begin and end with a period",
+                DataPathError::NotNormalized { path: "a.b".to_owned() }.to_string()
+            );
+        }
+
+        #[test]
+        fn test_clones_share_path() {
+            let dp = DataPath::new(".a.b.");
+            assert_eq!(dp.as_str().as_ptr(), dp.clone().as_str().as_ptr());
+        }
+
+        #[test]
+        fn test_interner_shares_paths() {
+            let interner = PathInterner::new();
+            let mut first = Data::new(".a.b.", DataValue::from(1u64));
+            let mut second = Data::new("a.b", DataValue::from(2u64));
+            interner.intern_data(&mut first);
+            interner.intern_data(&mut second);
+            interner.intern_data(&mut Data::new(".c.", DataValue::from(3u64)));
+            assert_eq!(2, interner.len());
+            assert_eq!(
+                first.data_path().as_str().as_ptr(),
+                second.data_path().as_str().as_ptr()
+            );
+            assert_eq!(".a.b.", second.path());
+        }
+
         #[test]
         fn test_from_datapath_for_string() {
             let dp = DataPath::new(".my.path");
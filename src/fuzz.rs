@@ -0,0 +1,231 @@
+// Generation of arbitrary data for fuzzing and property tests. With the
+// `arbitrary` feature, `Data`, `DataPath`, and `DataValue` implement
+// `arbitrary::Arbitrary`; with the `proptest` feature, they implement
+// `proptest::arbitrary::Arbitrary` and this module exports the strategies
+// behind those implementations.
+//
+// Generated data is always valid: paths are in the `.my.json.path.` form
+// with non-empty segments, floats are finite, encrypted values carry
+// ciphertext and a valid key name, and data holds between one and three
+// values. It therefore round-trips through every encoding, including
+// `Strict` deserialization.
+
+use crate::{Data, DataPath, DataType, DataValue, DataValueCollection};
+
+/// The key names encrypted values are generated with
+const KEYNAMES: [&str; 3] = ["k1", "k2", "backup-key"];
+
+/// The types encrypted values are generated with
+const DATATYPES: [DataType; 6] = [
+    DataType::Bool,
+    DataType::U64,
+    DataType::I64,
+    DataType::F64,
+    DataType::String,
+    DataType::Bytes,
+];
+
+/// Builds a path from its segments, each non-empty and free of periods
+fn path_from_segments(segments: &[String]) -> DataPath {
+    match segments.is_empty() {
+        true => DataPath::new("."),
+        false => DataPath::new(&format!(".{}.", segments.join("."))),
+    }
+}
+
+fn data_from_parts(path: &DataPath, values: Vec<DataValue>, labels: Vec<(String, String)>) -> Data {
+    let mut data = Data::with_values(&path.to_string(), DataValueCollection::from(values));
+    for (key, value) in labels {
+        data.set_label(&key, &value);
+    }
+    data
+}
+
+#[cfg(feature = "arbitrary")]
+mod fuzzing {
+    use super::{data_from_parts, path_from_segments, DATATYPES, KEYNAMES};
+    use crate::{Data, DataPath, DataValue, EncryptedDataValue};
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    /// Returns a segment made of the arbitrary string's alphanumeric
+    /// characters, or `0` if it has none
+    fn segment(u: &mut Unstructured<'_>) -> Result<String> {
+        let segment: String = String::arbitrary(u)?
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        Ok(match segment.is_empty() {
+            true => "0".to_owned(),
+            false => segment,
+        })
+    }
+
+    impl<'a> Arbitrary<'a> for DataPath {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let len = u.int_in_range(0..=4)?;
+            let segments = (0..len).map(|_| segment(u)).collect::<Result<Vec<_>>>()?;
+            Ok(path_from_segments(&segments))
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for DataValue {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(match u.int_in_range(0..=6)? {
+                0 => DataValue::from(bool::arbitrary(u)?),
+                1 => DataValue::from(u64::arbitrary(u)?),
+                2 => DataValue::from(i64::arbitrary(u)?),
+                3 => {
+                    let n = f64::arbitrary(u)?;
+                    DataValue::from(if n.is_finite() { n } else { 0.0 })
+                }
+                4 => DataValue::from(String::arbitrary(u)?),
+                5 => DataValue::from(Vec::<u8>::arbitrary(u)?),
+                _ => {
+                    let mut ciphertext = Vec::<u8>::arbitrary(u)?;
+                    if ciphertext.is_empty() {
+                        ciphertext.push(0);
+                    }
+                    DataValue::Encrypted(EncryptedDataValue::new(
+                        ciphertext,
+                        u.choose(&DATATYPES)?.clone(),
+                        u.choose(&KEYNAMES)?,
+                    ))
+                }
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for Data {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let path = DataPath::arbitrary(u)?;
+            let len = u.int_in_range(1..=3)?;
+            let values = (0..len)
+                .map(|_| DataValue::arbitrary(u))
+                .collect::<Result<Vec<_>>>()?;
+            let labels = match bool::arbitrary(u)? {
+                true => vec![(segment(u)?, String::arbitrary(u)?)],
+                false => Vec::new(),
+            };
+            Ok(data_from_parts(&path, values, labels))
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+pub use strategies::{data, data_path, data_value};
+
+#[cfg(feature = "proptest")]
+mod strategies {
+    use super::{data_from_parts, path_from_segments, DATATYPES, KEYNAMES};
+    use crate::{Data, DataPath, DataValue, EncryptedDataValue};
+    use proptest::{
+        arbitrary::{any, Arbitrary},
+        collection::{btree_map, vec},
+        prop_oneof,
+        sample::select,
+        strategy::{BoxedStrategy, Strategy},
+    };
+
+    /// Generates paths of up to four segments of alphanumeric characters,
+    /// dashes, and underscores
+    pub fn data_path() -> impl Strategy<Value = DataPath> {
+        vec("[a-zA-Z0-9_-]{1,8}", 0..=4).prop_map(|segments| path_from_segments(&segments))
+    }
+
+    /// Generates unencrypted values of every type and encrypted values
+    pub fn data_value() -> impl Strategy<Value = DataValue> {
+        let encrypted = (
+            vec(any::<u8>(), 1..32),
+            select(DATATYPES.to_vec()),
+            select(KEYNAMES.to_vec()),
+        )
+            .prop_map(|(ciphertext, datatype, keyname)| {
+                DataValue::Encrypted(EncryptedDataValue::new(ciphertext, datatype, keyname))
+            });
+        prop_oneof![
+            any::<bool>().prop_map(DataValue::from),
+            any::<u64>().prop_map(DataValue::from),
+            any::<i64>().prop_map(DataValue::from),
+            (proptest::num::f64::NORMAL | proptest::num::f64::ZERO).prop_map(DataValue::from),
+            any::<String>().prop_map(DataValue::from),
+            vec(any::<u8>(), 0..32).prop_map(DataValue::from),
+            encrypted,
+        ]
+    }
+
+    /// Generates data holding one to three values and up to two labels
+    pub fn data() -> impl Strategy<Value = Data> {
+        (
+            data_path(),
+            vec(data_value(), 1..=3),
+            btree_map("[a-z]{1,8}", any::<String>(), 0..=2),
+        )
+            .prop_map(|(path, values, labels)| {
+                data_from_parts(&path, values, labels.into_iter().collect())
+            })
+    }
+
+    impl Arbitrary for DataPath {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+            data_path().boxed()
+        }
+    }
+
+    impl Arbitrary for DataValue {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+            data_value().boxed()
+        }
+    }
+
+    impl Arbitrary for Data {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+            data().boxed()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "proptest"))]
+mod tests {
+    use crate::{Data, DataPath, Strict};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_data_round_trips_through_json(data in any::<Data>()) {
+            let json = serde_json::to_string(&data).unwrap();
+            prop_assert_eq!(&data, &serde_json::from_str::<Data>(&json).unwrap());
+            prop_assert_eq!(data, serde_json::from_str::<Strict<Data>>(&json).unwrap().into_inner());
+        }
+
+        #[test]
+        fn test_paths_parse_strictly(path in any::<DataPath>()) {
+            prop_assert_eq!(Ok(path.clone()), DataPath::parse_strict(&path.to_string()));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "arbitrary"))]
+mod fuzzing_tests {
+    use crate::{Data, DataPath};
+    use arbitrary::{Arbitrary, Unstructured};
+
+    #[test]
+    fn test_arbitrary_data_is_valid() {
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        while !u.is_empty() {
+            let data = Data::arbitrary(&mut u).unwrap();
+            assert!(DataPath::parse_strict(&data.path()).is_ok());
+            assert!(crate::strict::validate(&data).is_ok());
+        }
+    }
+}
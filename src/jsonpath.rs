@@ -0,0 +1,241 @@
+use crate::DataPath;
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Returned when a JSONPath expression is malformed or uses a feature, such
+/// as wildcards or filters, which cannot be expressed as a single `DataPath`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPathError {
+    pub expression: String,
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl Error for JsonPathError {}
+
+impl Display for JsonPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid JSONPath {:?} at position {}: {}",
+            self.expression, self.position, self.reason
+        )
+    }
+}
+
+/// Returns whether a segment can be written in JSONPath dot notation
+fn is_identifier(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_index(segment: &str) -> bool {
+    !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl DataPath {
+    /// Returns the non-empty segments of the path, from the root down
+    fn segments(&self) -> Vec<String> {
+        self.to_string()
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Converts the path to a normalized JSONPath expression, e.g. `.users.0.name.`
+    /// becomes `$.users[0].name`. Numeric segments are written as array indices,
+    /// and segments which are not plain identifiers use bracket notation.
+    pub fn to_jsonpath(&self) -> String {
+        self.segments()
+            .iter()
+            .fold("$".to_owned(), |mut expression, segment| {
+                if is_index(segment) {
+                    expression.push_str(&format!("[{}]", segment));
+                } else if is_identifier(segment) {
+                    expression.push('.');
+                    expression.push_str(segment);
+                } else {
+                    let escaped = segment.replace('\\', "\\\\").replace('\'', "\\'");
+                    expression.push_str(&format!("['{}']", escaped));
+                }
+                expression
+            })
+    }
+
+    /// Parses a JSONPath expression made only of member names and array
+    /// indices, such as `$.users[0]['first name']`, into a `DataPath`
+    pub fn from_jsonpath(expression: &str) -> Result<DataPath, JsonPathError> {
+        let error = |position: usize, reason: &'static str| JsonPathError {
+            expression: expression.to_owned(),
+            position,
+            reason,
+        };
+        let chars: Vec<char> = expression.chars().collect();
+        if chars.first() != Some(&'$') {
+            return Err(error(0, "expression must start at the root `$`"));
+        }
+
+        let mut segments = Vec::new();
+        let mut i = 1;
+        while i < chars.len() {
+            let start = i;
+            let segment = match chars[i] {
+                '.' => {
+                    i += 1;
+                    if chars.get(i) == Some(&'.') {
+                        return Err(error(start, "recursive descent is not supported"));
+                    }
+                    let name: String = chars[i..]
+                        .iter()
+                        .take_while(|c| **c != '.' && **c != '[')
+                        .collect();
+                    i += name.chars().count();
+                    if name == "*" {
+                        return Err(error(start, "wildcards are not supported"));
+                    }
+                    name
+                }
+                '[' => {
+                    i += 1;
+                    let segment = match chars.get(i) {
+                        Some(&quote) if quote == '\'' || quote == '"' => {
+                            i += 1;
+                            let mut name = String::new();
+                            loop {
+                                match chars.get(i) {
+                                    Some('\\') => {
+                                        name.extend(chars.get(i + 1));
+                                        i += 2;
+                                    }
+                                    Some(&c) if c == quote => break,
+                                    Some(&c) => {
+                                        name.push(c);
+                                        i += 1;
+                                    }
+                                    None => return Err(error(start, "unterminated string")),
+                                }
+                            }
+                            i += 1;
+                            name
+                        }
+                        _ => {
+                            let index: String =
+                                chars[i..].iter().take_while(|c| **c != ']').collect();
+                            i += index.chars().count();
+                            if !is_index(&index) {
+                                return Err(error(
+                                    start,
+                                    "only member names and array indices are supported",
+                                ));
+                            }
+                            index
+                        }
+                    };
+                    if chars.get(i) != Some(&']') {
+                        return Err(error(i, "expected `]`"));
+                    }
+                    i += 1;
+                    segment
+                }
+                _ => return Err(error(start, "expected `.` or `[`")),
+            };
+            if segment.is_empty() {
+                return Err(error(start, "empty member name"));
+            }
+            if segment.contains('.') {
+                return Err(error(
+                    start,
+                    "member names containing `.` cannot be represented in a data path",
+                ));
+            }
+            segments.push(segment);
+        }
+        Ok(DataPath::new(&segments.join(".")))
+    }
+
+    /// Returns the value the path refers to within a JSON document, where each
+    /// segment names an object member or, for arrays, an index
+    pub fn select<'a>(&self, document: &'a Value) -> Option<&'a Value> {
+        self.segments()
+            .iter()
+            .try_fold(document, |value, segment| match value {
+                Value::Object(members) => members.get(segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DataPath, JsonPathError};
+    use serde_json::json;
+
+    #[test]
+    fn test_to_jsonpath() {
+        assert_eq!("$", DataPath::new(".").to_jsonpath());
+        assert_eq!(
+            "$.users[0].name['first name']['it\\'s']",
+            DataPath::new(".users.0.name.first name.it's.").to_jsonpath()
+        );
+    }
+
+    #[test]
+    fn test_from_jsonpath() {
+        assert_eq!(
+            DataPath::new(".users.0.first name.it's."),
+            DataPath::from_jsonpath("$.users[0]['first name'][\"it's\"]").unwrap()
+        );
+        assert_eq!(DataPath::new("."), DataPath::from_jsonpath("$").unwrap());
+    }
+
+    #[test]
+    fn test_jsonpath_round_trip() {
+        let dp = DataPath::new(".a.1.b c.d_e.");
+        assert_eq!(dp, DataPath::from_jsonpath(&dp.to_jsonpath()).unwrap());
+    }
+
+    #[test]
+    fn test_from_jsonpath_rejects_unsupported_expressions() {
+        for (expression, position) in &[
+            ("users", 0),
+            ("$..name", 1),
+            ("$.users.*", 7),
+            ("$.users[*]", 7),
+            ("$.users[?(@.age)]", 7),
+            ("$['a.b']", 1),
+            ("$['a'", 5),
+        ] {
+            assert!(matches!(
+                DataPath::from_jsonpath(expression),
+                Err(JsonPathError { position: p, .. }) if p == *position
+            ));
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let document = json!({
+            "users": [{ "name": "alice", "tags": ["a", "b"] }],
+            "count": 1
+        });
+        assert_eq!(
+            Some(&json!("alice")),
+            DataPath::new(".users.0.name.").select(&document)
+        );
+        assert_eq!(
+            Some(&json!("b")),
+            DataPath::new(".users.0.tags.1.").select(&document)
+        );
+        assert_eq!(Some(&document), DataPath::new(".").select(&document));
+        assert_eq!(None, DataPath::new(".users.1.").select(&document));
+        assert_eq!(None, DataPath::new(".count.value.").select(&document));
+    }
+}
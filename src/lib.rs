@@ -6,22 +6,188 @@
 //! retrieving redact data with a variety of sources.
 //!
 //! File directory:
+//! - avro.rs: Avro schemas and binary encoding of data, behind the `avro` feature
+//! - borrowed.rs: data borrowing from the buffer it was deserialized from
+//! - builder.rs: fluent construction and validation of data
+//! - codec.rs: encodings data can be cached and transported with
+//! - compat.rs: wire versions of the data schema and tolerant decoding of older ones
+//! - config.rs: storer and cacher configuration loaded from files or the environment
+//! - config/error.rs: error types for loading configuration
+//! - config/factory.rs: storers selected by the scheme of a URI
+//! - config/registry.rs: registry of storer and cacher factories provided by other crates
+//! - convert.rs: flattening JSON documents into data and back, and writing them as subtrees
 //! - data.rs: data definitions and conversions
+//! - fuzz.rs: arbitrary data for fuzzing and property tests, behind the `arbitrary` and
+//!   `proptest` features
+//! - jsonpath.rs: conversions between data paths and JSONPath expressions
+//! - lock.rs: distributed locks on paths with fencing tokens
+//! - lock/error.rs: error types for path locks
+//! - lock/lease.rs: leases on paths with background renewal
+//! - lock/redis.rs: redis implementation of path locks
+//! - merkle.rs: merkle trees and inclusion proofs over sets of data
+//! - mocks.rs: mockall mocks of the storer, cacher, and locker traits, behind the `mocks`
+//!   feature
+//! - patch.rs: diffs between data sets as patches, and applying patches to storers
+//! - proto.rs: protobuf messages of data and conversions to them, behind the `proto` feature
 //! - storage.rs: trait for a data type that stores Data
+//! - storage/aggregation.rs: numeric aggregations over stored values
+//! - storage/any.rs: enum of the built-in storers dispatching statically
+//! - storage/atlas.rs: storage implementation for the MongoDB Atlas Data API over HTTPS
+//! - storage/atomic.rs: client-side fallbacks for atomic read-modify-write operations
+//! - storage/audit.rs: hash-chained audit log of storer operations, and its replay
+//! - storage/authorization.rs: path-level access control and an authorizing storer wrapper
+//! - storage/boxed.rs: dynamically dispatched storer for runtime backend selection
+//! - storage/buffered.rs: writer buffering writes and flushing them in batches
+//! - storage/chunking.rs: storer wrapper limiting or chunking oversized values
+//! - storage/conformance.rs: behavioral test suite for storer implementations
+//! - storage/conflict.rs: strategies resolving writes colliding with newer stored versions
+//! - storage/context.rs: deadline, trace id, and principal of an operation
+//! - storage/encrypted_file.rs: filesystem storer encrypting every record at rest
+//! - storage/encrypted_only.rs: storer rejecting plaintext values which should be encrypted
+//! - storage/erasure.rs: reports of data erased under a prefix
 //! - storage/error.rs: error types for the storage abstractions
+//! - storage/event_sourced.rs: storer wrapper keeping every write as an append-only event
+//! - storage/fetch.rs: concurrent gets of many paths with bounded concurrency
+//! - storage/file.rs: storage implementation keeping one file per record
+//! - storage/file/wal.rs: write-ahead log and fsync policies for the file storer
+//! - storage/hooks.rs: callbacks run around every operation of a storer
+//! - storage/integrity.rs: storer wrapper sealing and verifying integrity checksums
+//! - storage/keyring.rs: named encryption keys and a storer rejecting unknown key names
+//! - storage/links.rs: fetching and traversing the data linked to from other data
+//! - storage/memory.rs: in-memory storage implementation with optional snapshots to disk
+//! - storage/metrics.rs: operation counts and latencies recorded by storers, and a metering
+//!   storer wrapper
 //! - storage/mongodb.rs: storage implentation for mongodb
+//! - storage/nonexistence.rs: storer wrapper remembering paths found to hold no data
+//! - storage/offline.rs: storer wrapper queueing writes while offline and replaying them
+//! - storage/query.rs: options for querying collections of data
 //! - storage/redact.rs: storage implementation for a redact-store server
+//! - storage/redact/api.rs: typed endpoints, requests, and responses of the redact-store API
+//! - storage/redact/etag.rs: entries held with their ETags for conditional fetches
+//! - storage/redact/ndjson.rs: incremental decoding of collections streamed as newline-delimited
+//!   JSON
+//! - storage/retention.rs: retention policies and a job enforcing them
+//! - storage/scoped.rs: storer view with all paths relative to a prefix
+//! - storage/signing.rs: data signing traits and a signing storer wrapper
+//! - storage/slow_log.rs: storer wrapper logging operations slower than a threshold
+//! - storage/stats.rs: usage statistics of the data under a prefix
+//! - storage/sync.rs: resumable two-way sync of the data under a prefix of two storers
+//! - storage/tenant.rs: storer wrapper scoping all operations to a single tenant
+//! - storage/type_migration.rs: storer wrapper converting values stored with the wrong type
+//! - storage/versioning.rs: storer wrapper keeping the history of every entry, and its
+//!   compaction
+//! - strict.rs: strict deserialization of data from untrusted sources
+//! - test_util.rs: generated fixtures of data for tests, behind the `test_util` feature
+//! - transform.rs: anonymizing value transforms and a transforming storer view
 
+#[cfg(feature = "avro")]
+pub mod avro;
+pub mod borrowed;
+pub mod builder;
+pub mod codec;
+pub mod config;
+pub mod compat;
+pub mod convert;
 mod data;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod fuzz;
+pub mod jsonpath;
+pub mod lock;
+pub mod merkle;
+#[cfg(any(test, feature = "mocks"))]
+pub mod mocks;
+pub mod patch;
+#[cfg(feature = "proto")]
+pub mod proto;
 pub mod storage;
 pub mod cache;
+pub mod strict;
+#[cfg(feature = "test_util")]
+pub mod test_util;
+pub mod transform;
 
+pub use borrowed::{DataRef, DataValueRef, EncryptedDataValueRef, UnencryptedDataValueRef};
+pub use builder::{DataBuilder, DataBuilderError};
+pub use codec::{CodecError, DataCodec};
+pub use compat::{to_wire, WireVersion};
+pub use config::{
+    build_cacher, build_storer,
+    error::ConfigError,
+    factory::{DataStorerFactory, StorerOptions},
+    registry::{BackendRegistry, CacherFactoryFn, StorerFactoryFn},
+    CacheConfig, ConfiguredDataCacher, RedactConfig, StorageConfig,
+};
+pub use convert::{flatten, unflatten, MergeStrategy};
 pub use data::{
-    Data, DataPath, DataType, DataValue, DataValueCollection, EncryptedDataValue,
-    UnencryptedDataValue,
+    Data, DataCollection, DataLink, DataPath, DataPathError, DataSignature, DataType, DataValue, DataValueCollection,
+    EncryptedDataValue, InvalidKeyName, KeyName, PathInterner, PathMergeStrategy, TypeMismatchError, UnencryptedDataValue, ValueType,
+};
+pub use jsonpath::JsonPathError;
+pub use lock::{
+    error::LockError, lease::{Lease, LeaseHandle}, lock, redis::RedisDataLocker, with_lock, DataLocker,
+    PathLock,
 };
+pub use merkle::{MerkleHash, MerkleProof, MerkleProofStep, MerkleSide, MerkleTree};
+pub use patch::{diff, DataPatch, PatchOperation};
 pub use storage::{
-    error::StorageError, mongodb::MongoDataStorer, redact::RedactDataStorer, DataStorer, CachedDataStorer, error::DataStorerError
-};pub use cache::{
-    error::CacheError, DataCacher, tests::MockDataCacher
+    aggregation::{Aggregation, Aggregator},
+    any::AnyDataStorer,
+    atlas::AtlasDataApiStorer,
+    audit::{
+        replay_audit, AuditOutcome, AuditRecord, AuditSink, AuditSource, AuditedDataStorer,
+        FileAuditSink, ReplayReport, StorerAuditSink,
+    },
+    authorization::{AuthorizedDataStorer, Authorizer, Decision, Operation, PrefixAuthorizer},
+    boxed::BoxedDataStorer,
+    buffered::{BufferConfig, BufferedDataWriter},
+    chunking::{ChunkingDataStorer, ValueSizePolicy},
+    conformance::{ConformanceFailure, ConformanceReport},
+    conflict::{
+        Conflict, ConflictResolver, LastWriteWins, MergeResolver, PreferLocal, PreferRemote,
+        Resolution,
+    },
+    context::OpContext,
+    encrypted_file::{EncryptedFileDataStorer, FileEncryptionKey},
+    encrypted_only::EncryptedOnlyDataStorer,
+    erasure::EraseReport,
+    event_sourced::EventSourcedDataStorer,
+    error::{DataStorerError, SigningError, StorageError},
+    fetch::fetch_all,
+    file::{wal::FsyncPolicy, FileDataStorer},
+    hooks::{HookedDataStorer, StorerHooks},
+    integrity::{IntegrityDataStorer, IntegrityPolicy},
+    keyring::{validate_encryptedby, Keyring, KeyringDataStorer, StaticKeyring},
+    links::LinkDirection,
+    memory::MemoryDataStorer,
+    metrics::{MeteredDataStorer, Metrics, MetricsSnapshot, OperationMetrics},
+    mongodb::{
+        MongoDataStorer, MongoHealth, MongoHealthConfig, MongoIndex, MongoOperationOptions,
+    },
+    nonexistence::NonexistenceFilterDataStorer,
+    offline::{OfflineDataStorer, QueuedWrite},
+    query::{
+        Cursor, DataFilter, EncryptionFilter, PageSizeLimits, PageSizePolicy, Sort, SortBy,
+        SortOrder,
+    },
+    redact::{RedactClientConfig, RedactDataStorer},
+    retention::{
+        run_retention, run_retention_with_archive, RetentionMode, RetentionPolicies,
+        RetentionPolicy, RetentionReport, RetentionRule,
+    },
+    scoped::ScopedDataStorer,
+    signing::{DataSigner, DataVerifier, HmacDataSigner, SigningDataStorer},
+    slow_log::SlowLogDataStorer,
+    stats::DataStats,
+    sync::{run_sync, SyncReport, SyncState},
+    tenant::{TenantPartitioned, TenantScopedDataStorer, TenantStrategy},
+    type_migration::TypeMigratingDataStorer,
+    versioning::{CompactionReport, CompactionRule, VersionedDataStorer},
+    CacheMode, CachedDataStorer, DataStorer,
+};
+pub use strict::{Strict, StrictDataError};
+pub use transform::{
+    Bucketing, Mask, SaltedHash, TransformingDataStorer, Transforms, ValueTransform,
+};
+pub use cache::{
+    bloom::{BloomDataCacher, BloomFilter}, boxed::BoxedDataCacher, collection::{CollectionCacher, MemoryCollectionCacher, PageKey}, encryption::{AesGcmCacheEncryptor, CacheEncryptor}, error::CacheError, hashed::HashedKeyDataCacher, memory::{MemoryCacheConfig, MemoryDataCacher}, slow_log::SlowLogDataCacher, DataCacher,
 };
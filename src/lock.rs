@@ -0,0 +1,177 @@
+pub mod error;
+pub mod lease;
+pub mod redis;
+
+use crate::lock::error::LockError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Longest pause between attempts to acquire a held lock
+const MAX_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A lock held on a path. The fencing token grows with every acquisition of
+/// the path, so storage receiving it alongside writes can reject a holder
+/// whose lock has expired and been taken over.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PathLock {
+    pub path: String,
+    pub owner: String,
+    pub fencing_token: u64,
+}
+
+/// The operations a distributed lock on paths must be able to fulfill. Locks
+/// are exclusive per path; to serialize writes to a subtree, every writer
+/// locks the root of the subtree.
+#[async_trait]
+pub trait DataLocker: Clone + Send + Sync {
+    /// Attempts once to lock the path for `ttl`, returning `None` if another
+    /// owner holds it
+    async fn try_lock(&self, path: &str, ttl: Duration) -> Result<Option<PathLock>, LockError>;
+
+    /// Extends the lock to expire `ttl` from now, returning whether it was
+    /// still held by its owner
+    async fn renew(&self, lock: &PathLock, ttl: Duration) -> Result<bool, LockError>;
+
+    /// Releases the lock, returning whether it was still held by its owner
+    async fn unlock(&self, lock: &PathLock) -> Result<bool, LockError>;
+}
+
+/// Allows an `Arc<DataLocker>` to act exactly like a `DataLocker`
+#[async_trait]
+impl<U> DataLocker for Arc<U>
+where
+    U: DataLocker,
+{
+    async fn try_lock(&self, path: &str, ttl: Duration) -> Result<Option<PathLock>, LockError> {
+        self.deref().try_lock(path, ttl).await
+    }
+
+    async fn renew(&self, lock: &PathLock, ttl: Duration) -> Result<bool, LockError> {
+        self.deref().renew(lock, ttl).await
+    }
+
+    async fn unlock(&self, lock: &PathLock) -> Result<bool, LockError> {
+        self.deref().unlock(lock).await
+    }
+}
+
+/// Locks the path for `ttl`, waiting at most `wait` for another owner to
+/// release it
+pub async fn lock<L: DataLocker>(
+    locker: &L,
+    path: &str,
+    ttl: Duration,
+    wait: Duration,
+) -> Result<PathLock, LockError> {
+    let started = Instant::now();
+    let interval = (ttl / 10).clamp(Duration::from_millis(1), MAX_RETRY_INTERVAL);
+    loop {
+        if let Some(lock) = locker.try_lock(path, ttl).await? {
+            return Ok(lock);
+        }
+        if started.elapsed() >= wait {
+            return Err(LockError::Timeout {
+                path: path.to_owned(),
+            });
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Runs `f` while holding a lock on the path, waiting up to `ttl` for the lock
+/// and releasing it once `f` completes. If the lock expired while `f` ran, its
+/// exclusivity was not guaranteed and `LockError::Expired` is returned in
+/// place of the result.
+pub async fn with_lock<L, F, Fut, T>(
+    locker: &L,
+    path: &str,
+    ttl: Duration,
+    f: F,
+) -> Result<T, LockError>
+where
+    L: DataLocker,
+    F: FnOnce(PathLock) -> Fut,
+    Fut: Future<Output = T>,
+{
+    let lock = lock(locker, path, ttl, ttl).await?;
+    let result = f(lock.clone()).await;
+    if locker.unlock(&lock).await? {
+        Ok(result)
+    } else {
+        Err(LockError::Expired { path: lock.path })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::mocks::MockDataLocker;
+    use crate::{LockError, PathLock};
+    use std::time::Duration;
+
+    pub(crate) fn path_lock(fencing_token: u64) -> PathLock {
+        PathLock {
+            path: ".jobs.".to_owned(),
+            owner: "owner".to_owned(),
+            fencing_token,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_lock_retries_until_acquired() {
+        let mut locker = MockDataLocker::new();
+        let mut attempts = 0;
+        locker.expect_try_lock().times(3).returning(move |_, _| {
+            attempts += 1;
+            Ok(if attempts < 3 {
+                None
+            } else {
+                Some(path_lock(7))
+            })
+        });
+        locker
+            .expect_unlock()
+            .withf(|lock| lock.fencing_token == 7)
+            .times(1)
+            .returning(|_| Ok(true));
+
+        let token = crate::with_lock(
+            &locker,
+            ".jobs.",
+            Duration::from_secs(1),
+            |lock| async move { lock.fencing_token },
+        )
+        .await
+        .unwrap();
+        assert_eq!(7, token);
+    }
+
+    #[tokio::test]
+    async fn test_with_lock_times_out() {
+        let mut locker = MockDataLocker::new();
+        locker.expect_try_lock().returning(|_, _| Ok(None));
+        locker.expect_unlock().times(0);
+
+        let result =
+            crate::with_lock(&locker, ".jobs.", Duration::from_millis(20), |_| async {}).await;
+        assert!(matches!(result, Err(LockError::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_with_lock_reports_expiry() {
+        let mut locker = MockDataLocker::new();
+        locker
+            .expect_try_lock()
+            .returning(|_, _| Ok(Some(path_lock(1))));
+        locker.expect_unlock().returning(|_| Ok(false));
+
+        let result =
+            crate::with_lock(&locker, ".jobs.", Duration::from_secs(1), |_| async {}).await;
+        assert!(matches!(result, Err(LockError::Expired { .. })));
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Errors occurring while acquiring or releasing path locks
+#[derive(Debug)]
+pub enum LockError {
+    /// Represents an error which occurred while talking to the lock backend
+    InternalError {
+        source: Box<dyn Error + Send + Sync>,
+    },
+
+    /// Indicates the lock could not be acquired before giving up
+    Timeout { path: String },
+
+    /// Indicates the lock expired, and may have been taken by another owner,
+    /// before it was released
+    Expired { path: String },
+}
+
+impl Error for LockError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LockError::InternalError { ref source } => Some(source.as_ref()),
+            LockError::Timeout { .. } => None,
+            LockError::Expired { .. } => None,
+        }
+    }
+}
+
+impl Display for LockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            LockError::InternalError { .. } => write!(f, "Internal error occurred"),
+            LockError::Timeout { ref path } => write!(f, "Timed out locking {}", path),
+            LockError::Expired { ref path } => {
+                write!(f, "Lock on {} expired before it was released", path)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LockError;
+
+    #[test]
+    fn test_to_string_timeout() {
+        let s = LockError::Timeout {
+            path: ".a.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Timed out locking .a.");
+    }
+
+    #[test]
+    fn test_to_string_expired() {
+        let s = LockError::Expired {
+            path: ".a.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Lock on .a. expired before it was released");
+    }
+}
@@ -0,0 +1,219 @@
+use crate::lock::{error::LockError, lock, DataLocker, PathLock};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+/// Exclusive ownership of a path for a limited time, which the holder keeps
+/// by renewing it before the time runs out
+pub struct Lease<L: DataLocker> {
+    locker: L,
+    lock: PathLock,
+    ttl: Duration,
+}
+
+impl<L: DataLocker> Lease<L> {
+    /// Acquires a lease on the path lasting `ttl`, waiting at most `wait` for
+    /// the current holder to release it
+    pub async fn acquire(
+        locker: L,
+        path: &str,
+        ttl: Duration,
+        wait: Duration,
+    ) -> Result<Self, LockError> {
+        let lock = lock(&locker, path, ttl, wait).await?;
+        Ok(Lease { locker, lock, ttl })
+    }
+
+    /// Returns the lock backing the lease, including its fencing token
+    pub fn lock(&self) -> &PathLock {
+        &self.lock
+    }
+
+    /// Extends the lease by its ttl, failing with `LockError::Expired` if it
+    /// was already lost
+    pub async fn renew(&self) -> Result<(), LockError> {
+        renew(&self.locker, &self.lock, self.ttl).await
+    }
+
+    /// Gives up the lease, failing with `LockError::Expired` if it was
+    /// already lost
+    pub async fn release(self) -> Result<(), LockError> {
+        if self.locker.unlock(&self.lock).await? {
+            Ok(())
+        } else {
+            Err(LockError::Expired {
+                path: self.lock.path,
+            })
+        }
+    }
+}
+
+impl<L: DataLocker + 'static> Lease<L> {
+    /// Renews the lease in the background every third of its ttl until the
+    /// returned handle is released or dropped
+    pub fn auto_renew(self) -> LeaseHandle<L> {
+        let lost = Arc::new(AtomicBool::new(false));
+        let task = {
+            let (locker, lock, ttl, lost) = (
+                self.locker.clone(),
+                self.lock.clone(),
+                self.ttl,
+                lost.clone(),
+            );
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(ttl / 3).await;
+                    if renew(&locker, &lock, ttl).await.is_err() {
+                        lost.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                }
+            })
+        };
+        LeaseHandle {
+            lease: self,
+            lost,
+            task,
+        }
+    }
+}
+
+async fn renew<L: DataLocker>(locker: &L, lock: &PathLock, ttl: Duration) -> Result<(), LockError> {
+    if locker.renew(lock, ttl).await? {
+        Ok(())
+    } else {
+        Err(LockError::Expired {
+            path: lock.path.clone(),
+        })
+    }
+}
+
+/// A lease renewed by a background task. Dropping the handle stops renewing,
+/// leaving the lease to expire; `release` gives it up immediately.
+pub struct LeaseHandle<L: DataLocker> {
+    lease: Lease<L>,
+    lost: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl<L: DataLocker> LeaseHandle<L> {
+    /// Returns the lock backing the lease, including its fencing token
+    pub fn lock(&self) -> &PathLock {
+        self.lease.lock()
+    }
+
+    /// Returns whether a renewal failed, in which case the lease may have
+    /// expired and been acquired by someone else
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::SeqCst)
+    }
+
+    /// Stops renewing and gives up the lease
+    pub async fn release(self) -> Result<(), LockError> {
+        self.task.abort();
+        let lease = Lease {
+            locker: self.lease.locker.clone(),
+            lock: self.lease.lock.clone(),
+            ttl: self.lease.ttl,
+        };
+        lease.release().await
+    }
+}
+
+impl<L: DataLocker> Drop for LeaseHandle<L> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock::tests::path_lock;
+    use crate::mocks::MockDataLocker;
+    use crate::{Lease, LockError};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    fn locker(renewals: Arc<AtomicUsize>, renew_limit: usize) -> MockDataLocker {
+        let mut locker = MockDataLocker::new();
+        locker
+            .expect_try_lock()
+            .returning(|_, _| Ok(Some(path_lock(1))));
+        locker
+            .expect_renew()
+            .returning(move |_, _| Ok(renewals.fetch_add(1, Ordering::SeqCst) < renew_limit));
+        locker.expect_unlock().returning(|_| Ok(true));
+        locker
+    }
+
+    #[tokio::test]
+    async fn test_acquire_renew_release() {
+        let renewals = Arc::new(AtomicUsize::new(0));
+        let lease = Lease::acquire(
+            locker(renewals.clone(), 1),
+            ".jobs.",
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, lease.lock().fencing_token);
+
+        lease.renew().await.unwrap();
+        assert!(matches!(
+            lease.renew().await,
+            Err(LockError::Expired { .. })
+        ));
+        lease.release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auto_renew() {
+        let renewals = Arc::new(AtomicUsize::new(0));
+        let lease = Lease::acquire(
+            Arc::new(locker(renewals.clone(), usize::MAX)),
+            ".jobs.",
+            Duration::from_millis(30),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+
+        let handle = lease.auto_renew();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(renewals.load(Ordering::SeqCst) >= 2);
+        assert!(!handle.is_lost());
+        handle.release().await.unwrap();
+
+        let released = renewals.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(released, renewals.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_auto_renew_detects_loss() {
+        let renewals = Arc::new(AtomicUsize::new(0));
+        let lease = Lease::acquire(
+            Arc::new(locker(renewals, 1)),
+            ".jobs.",
+            Duration::from_millis(30),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+
+        let handle = lease.auto_renew();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(handle.is_lost());
+    }
+}
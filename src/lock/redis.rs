@@ -0,0 +1,146 @@
+use crate::lock::{error::LockError, DataLocker, PathLock};
+use crate::DataPath;
+use async_trait::async_trait;
+use mobc::{Connection, Pool};
+use mobc_redis::{redis, RedisConnectionManager};
+use std::time::Duration;
+
+/// Sets the lock key if it is free and, only then, bumps the path's fencing
+/// counter, so that every successful acquisition gets a larger token
+const ACQUIRE_SCRIPT: &str = r"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return redis.call('INCR', KEYS[2])
+end
+return 0
+";
+
+/// Deletes the lock key only if it still holds the releasing owner's token
+const RELEASE_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// Extends the lock key's expiry only if it still holds the renewing owner's token
+const RENEW_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+";
+
+/// Locks paths in redis with `SET NX PX`. The lock key holds a random token
+/// identifying its owner, and a per-path counter incremented on every
+/// acquisition provides the fencing token.
+#[derive(Clone)]
+pub struct RedisDataLocker {
+    pool: Pool<RedisConnectionManager>,
+}
+
+impl RedisDataLocker {
+    /// Instantiates a locker connecting to redis with a default connection pool
+    pub fn new(connection_string: &str) -> Result<RedisDataLocker, LockError> {
+        let client = redis::Client::open(connection_string).map_err(internal_error)?;
+        Ok(RedisDataLocker::with_pool(
+            Pool::builder().build(RedisConnectionManager::new(client)),
+        ))
+    }
+
+    /// Instantiates a locker using an existing connection pool
+    pub fn with_pool(pool: Pool<RedisConnectionManager>) -> RedisDataLocker {
+        RedisDataLocker { pool }
+    }
+
+    async fn get_con(&self) -> Result<Connection<RedisConnectionManager>, LockError> {
+        self.pool.get().await.map_err(internal_error)
+    }
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> LockError {
+    LockError::InternalError {
+        source: Box::new(e),
+    }
+}
+
+/// Returns the redis key holding the lock on a path
+fn lock_key(path: &str) -> String {
+    format!("lock:{}", DataPath::from(path))
+}
+
+/// Returns the redis key holding the fencing counter of a path
+fn fence_key(path: &str) -> String {
+    format!("{}:fence", lock_key(path))
+}
+
+/// Generates a random token identifying the owner of a lock
+fn owner_token() -> Result<String, LockError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(internal_error)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[async_trait]
+impl DataLocker for RedisDataLocker {
+    async fn try_lock(&self, path: &str, ttl: Duration) -> Result<Option<PathLock>, LockError> {
+        let owner = owner_token()?;
+        let mut con = self.get_con().await?;
+        let fencing_token: u64 = redis::Script::new(ACQUIRE_SCRIPT)
+            .key(lock_key(path))
+            .key(fence_key(path))
+            .arg(&owner)
+            .arg(ttl.as_millis().max(1) as u64)
+            .invoke_async(&mut *con)
+            .await
+            .map_err(internal_error)?;
+        Ok(match fencing_token {
+            0 => None,
+            fencing_token => Some(PathLock {
+                path: DataPath::from(path).to_string(),
+                owner,
+                fencing_token,
+            }),
+        })
+    }
+
+    async fn renew(&self, lock: &PathLock, ttl: Duration) -> Result<bool, LockError> {
+        let mut con = self.get_con().await?;
+        let renewed: u64 = redis::Script::new(RENEW_SCRIPT)
+            .key(lock_key(&lock.path))
+            .arg(&lock.owner)
+            .arg(ttl.as_millis().max(1) as u64)
+            .invoke_async(&mut *con)
+            .await
+            .map_err(internal_error)?;
+        Ok(renewed == 1)
+    }
+
+    async fn unlock(&self, lock: &PathLock) -> Result<bool, LockError> {
+        let mut con = self.get_con().await?;
+        let deleted: u64 = redis::Script::new(RELEASE_SCRIPT)
+            .key(lock_key(&lock.path))
+            .arg(&lock.owner)
+            .invoke_async(&mut *con)
+            .await
+            .map_err(internal_error)?;
+        Ok(deleted == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fence_key, lock_key, owner_token};
+
+    #[test]
+    fn test_keys() {
+        assert_eq!("lock:.users.", lock_key("users"));
+        assert_eq!("lock:.users.:fence", fence_key(".users."));
+    }
+
+    #[test]
+    fn test_owner_tokens_are_unique() {
+        let token = owner_token().unwrap();
+        assert_eq!(32, token.len());
+        assert_ne!(token, owner_token().unwrap());
+    }
+}
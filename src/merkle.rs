@@ -0,0 +1,216 @@
+use crate::{Data, DataStorer, DataStorerError};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A SHA-256 digest identifying a node of a `MerkleTree`
+pub type MerkleHash = [u8; 32];
+
+/// Hashes a leaf; leaves and interior nodes use distinct prefixes so that an
+/// interior node can never be passed off as a leaf.
+fn hash_leaf(data: &Data) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(data.canonical_bytes());
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Which side of the running hash a sibling sits on in a `MerkleProof`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleSide {
+    Left,
+    Right,
+}
+
+/// A sibling hash to combine with the running hash while verifying a proof
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProofStep {
+    pub side: MerkleSide,
+    pub hash: MerkleHash,
+}
+
+/// Proves that a single `Data` entry is included in a tree with a given root,
+/// without revealing any of the other entries.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub steps: Vec<MerkleProofStep>,
+}
+
+impl MerkleProof {
+    /// Returns whether the data is included in the tree with the given root
+    pub fn verify(&self, data: &Data, root: &MerkleHash) -> bool {
+        let computed = self
+            .steps
+            .iter()
+            .fold(hash_leaf(data), |hash, step| match step.side {
+                MerkleSide::Left => hash_node(&step.hash, &hash),
+                MerkleSide::Right => hash_node(&hash, &step.hash),
+            });
+        computed == *root
+    }
+}
+
+/// A Merkle tree over a set of `Data` entries, ordered by path. Each level is
+/// built by hashing adjacent pairs of nodes; a node left without a partner is
+/// promoted to the next level unchanged.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    paths: Vec<String>,
+    levels: Vec<Vec<MerkleHash>>,
+}
+
+impl MerkleTree {
+    /// Builds a tree over the given entries
+    pub fn new(data: &[Data]) -> Self {
+        let mut leaves: Vec<(String, MerkleHash)> =
+            data.iter().map(|d| (d.path(), hash_leaf(d))).collect();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        let (paths, hashes): (Vec<_>, Vec<_>) = leaves.into_iter().unzip();
+
+        let mut levels = vec![hashes];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        MerkleTree { paths, levels }
+    }
+
+    /// Builds a tree over every entry stored under the given path prefix
+    pub async fn from_storer<S: DataStorer>(
+        storer: &S,
+        path: &str,
+    ) -> Result<Self, DataStorerError> {
+        let mut data = Vec::new();
+        let mut entries = storer.iter_collection(path);
+        while let Some(entry) = entries.next().await {
+            data.push(entry?);
+        }
+        Ok(MerkleTree::new(&data))
+    }
+
+    /// Returns the root hash of the tree, or `None` if the tree is empty
+    pub fn root(&self) -> Option<MerkleHash> {
+        self.levels.last().and_then(|level| level.first()).copied()
+    }
+
+    /// Returns the number of entries in the tree
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns whether the tree holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Produces an inclusion proof for the entry at the given path, or `None`
+    /// if no entry with that path is in the tree
+    pub fn proof(&self, path: &str) -> Option<MerkleProof> {
+        let path = crate::DataPath::from(path).to_string();
+        let mut index = self.paths.binary_search(&path).ok()?;
+        let mut steps = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                steps.push(MerkleProofStep {
+                    side: if sibling < index {
+                        MerkleSide::Left
+                    } else {
+                        MerkleSide::Right
+                    },
+                    hash: level[sibling],
+                });
+            }
+            index /= 2;
+        }
+        Some(MerkleProof { steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataValue, MerkleTree};
+
+    fn entries(n: u64) -> Vec<Data> {
+        (0..n)
+            .map(|i| Data::new(&format!(".users.{}.", i), DataValue::from(i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_tree_has_no_root() {
+        let tree = MerkleTree::new(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(None, tree.root());
+        assert_eq!(None, tree.proof(".users.0."));
+    }
+
+    #[test]
+    fn test_every_entry_proves_inclusion() {
+        for n in 1..=9 {
+            let data = entries(n);
+            let tree = MerkleTree::new(&data);
+            let root = tree.root().unwrap();
+            for d in &data {
+                let proof = tree.proof(&d.path()).unwrap();
+                assert!(proof.verify(d, &root), "n = {}, path = {}", n, d.path());
+            }
+        }
+    }
+
+    #[test]
+    fn test_root_is_independent_of_input_order() {
+        let mut data = entries(5);
+        let root = MerkleTree::new(&data).root();
+        data.reverse();
+        assert_eq!(root, MerkleTree::new(&data).root());
+    }
+
+    #[test]
+    fn test_modified_entry_fails_verification() {
+        let data = entries(4);
+        let tree = MerkleTree::new(&data);
+        let proof = tree.proof(".users.2.").unwrap();
+        let forged = Data::new(".users.2.", DataValue::from(100u64));
+        assert!(!proof.verify(&forged, &tree.root().unwrap()));
+    }
+
+    #[test]
+    fn test_proof_for_missing_path() {
+        let tree = MerkleTree::new(&entries(3));
+        assert_eq!(None, tree.proof(".users.7."));
+    }
+
+    #[tokio::test]
+    async fn test_from_storer() {
+        let (storer, _) = memory_storer();
+        for d in entries(3) {
+            storer.create(d).await.unwrap();
+        }
+        storer
+            .create(Data::new(".other.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let tree = MerkleTree::from_storer(&storer, ".users.").await.unwrap();
+        assert_eq!(3, tree.len());
+        assert_eq!(MerkleTree::new(&entries(3)).root(), tree.root());
+    }
+}
@@ -0,0 +1,57 @@
+//! Mocks of the `DataStorer`, `DataCacher`, and `DataLocker` traits, built
+//! with mockall, for testing code written against them. Only compiled with
+//! the `mocks` feature, which is best enabled from dev-dependencies:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! redact-data = { version = "0.1", features = ["mocks"] }
+//! ```
+
+use crate::{CacheError, Data, DataCacher, DataCollection, DataLocker, DataStorer, DataStorerError, LockError, PathLock, Sort};
+use async_trait::async_trait;
+use mockall::mock;
+use std::time::Duration;
+
+mock! {
+pub DataStorer {}
+#[async_trait]
+impl DataStorer for DataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError>;
+    async fn get_collection(&self, path: &str, skip: i64, page_size: i64, sort: Option<Sort>) -> Result<DataCollection, DataStorerError>;
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError>;
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError>;
+}
+impl Clone for DataStorer {
+    fn clone(&self) -> Self;
+}
+}
+
+mock! {
+pub DataCacher {}
+#[async_trait]
+impl DataCacher for DataCacher {
+    async fn set(&self, key: &str, value: Data) -> Result<(), CacheError>;
+    async fn get(&self, key: &str) -> Result<Data, CacheError>;
+    async fn exists(&self, key: &str) -> Result<bool, CacheError>;
+    async fn expire(&self, key: &str, seconds: usize) -> Result<bool, CacheError>;
+    async fn delete(&self, key: &str) -> Result<bool, CacheError>;
+    async fn delete_prefix(&self, prefix: &str) -> Result<u64, CacheError>;
+    fn get_default_key_expiration_seconds(&self) -> usize;
+}
+impl Clone for DataCacher {
+    fn clone(&self) -> Self;
+}
+}
+
+mock! {
+pub DataLocker {}
+#[async_trait]
+impl DataLocker for DataLocker {
+    async fn try_lock(&self, path: &str, ttl: Duration) -> Result<Option<PathLock>, LockError>;
+    async fn renew(&self, lock: &PathLock, ttl: Duration) -> Result<bool, LockError>;
+    async fn unlock(&self, lock: &PathLock) -> Result<bool, LockError>;
+}
+impl Clone for DataLocker {
+    fn clone(&self) -> Self;
+}
+}
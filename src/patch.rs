@@ -0,0 +1,465 @@
+use crate::{Data, DataPath, DataStorer, DataStorerError, DataValueCollection, StorageError};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// A single change to the data at a path, modelled on JSON Patch operations
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add {
+        path: DataPath,
+        value: DataValueCollection,
+    },
+    Remove {
+        path: DataPath,
+    },
+    Replace {
+        path: DataPath,
+        value: DataValueCollection,
+    },
+    Test {
+        path: DataPath,
+        value: DataValueCollection,
+    },
+}
+
+impl PatchOperation {
+    /// Returns the path the operation applies to
+    pub fn path(&self) -> &DataPath {
+        match *self {
+            PatchOperation::Add { ref path, .. }
+            | PatchOperation::Remove { ref path }
+            | PatchOperation::Replace { ref path, .. }
+            | PatchOperation::Test { ref path, .. } => path,
+        }
+    }
+}
+
+impl Display for PatchOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            PatchOperation::Add {
+                ref path,
+                ref value,
+            } => write!(f, "+ {} = {}", path, value),
+            PatchOperation::Remove { ref path } => write!(f, "- {}", path),
+            PatchOperation::Replace {
+                ref path,
+                ref value,
+            } => write!(f, "~ {} = {}", path, value),
+            PatchOperation::Test {
+                ref path,
+                ref value,
+            } => write!(f, "? {} == {}", path, value),
+        }
+    }
+}
+
+/// An ordered list of operations turning one set of data into another. Its
+/// `Display` output is one line per operation, e.g. `+ .a. = 1`, for humans
+/// reviewing a change; its serde form is a JSON Patch style array of objects
+/// tagged by `op`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct DataPatch {
+    pub operations: Vec<PatchOperation>,
+}
+
+impl DataPatch {
+    /// Builds a patch from a list of operations
+    pub fn new(operations: Vec<PatchOperation>) -> Self {
+        DataPatch { operations }
+    }
+
+    /// Returns whether the patch changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns the paths the patch adds
+    pub fn added(&self) -> Vec<&DataPath> {
+        self.paths(|op| matches!(op, PatchOperation::Add { .. }))
+    }
+
+    /// Returns the paths the patch removes
+    pub fn removed(&self) -> Vec<&DataPath> {
+        self.paths(|op| matches!(op, PatchOperation::Remove { .. }))
+    }
+
+    /// Returns the paths whose values the patch replaces
+    pub fn changed(&self) -> Vec<&DataPath> {
+        self.paths(|op| matches!(op, PatchOperation::Replace { .. }))
+    }
+
+    fn paths<F: Fn(&PatchOperation) -> bool>(&self, filter: F) -> Vec<&DataPath> {
+        self.operations
+            .iter()
+            .filter(|op| filter(op))
+            .map(PatchOperation::path)
+            .collect()
+    }
+}
+
+impl Display for DataPatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.operations
+            .iter()
+            .try_for_each(|op| writeln!(f, "{}", op))
+    }
+}
+
+/// Computes the patch turning the `old` data set into the `new` one, with one
+/// operation per added, removed, or changed path, ordered by path. Only paths
+/// and values are compared; metadata such as signatures is ignored.
+pub fn diff(old: &[Data], new: &[Data]) -> DataPatch {
+    let old: BTreeMap<String, &DataValueCollection> =
+        old.iter().map(|d| (d.path(), d.value())).collect();
+    let new: BTreeMap<String, &DataValueCollection> =
+        new.iter().map(|d| (d.path(), d.value())).collect();
+
+    let mut operations: Vec<(String, PatchOperation)> = Vec::new();
+    for (path, value) in new.iter() {
+        match old.get(path) {
+            None => operations.push((
+                path.clone(),
+                PatchOperation::Add {
+                    path: DataPath::from(path.as_str()),
+                    value: (*value).clone(),
+                },
+            )),
+            Some(previous) if previous != value => operations.push((
+                path.clone(),
+                PatchOperation::Replace {
+                    path: DataPath::from(path.as_str()),
+                    value: (*value).clone(),
+                },
+            )),
+            Some(_) => (),
+        }
+    }
+    for path in old.keys().filter(|path| !new.contains_key(*path)) {
+        operations.push((
+            path.clone(),
+            PatchOperation::Remove {
+                path: DataPath::from(path.as_str()),
+            },
+        ));
+    }
+    operations.sort_by(|a, b| a.0.cmp(&b.0));
+    DataPatch::new(operations.into_iter().map(|(_, op)| op).collect())
+}
+
+/// Resolves a path of a patch relative to the prefix it is applied under
+fn resolve(prefix: &DataPath, path: &DataPath) -> String {
+    let prefix = prefix.to_string();
+    format!("{}{}", prefix.trim_end_matches('.'), path)
+}
+
+fn rejected(path: &str, reason: &str) -> DataStorerError {
+    DataStorerError::PatchRejected {
+        path: path.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+/// Applies a patch whose paths are relative to `prefix`. Every operation is
+/// checked against the current data, and the effect of the operations before
+/// it, before anything is written, so a failing `test` or a `replace` or
+/// `remove` of a missing path leaves the storer untouched. Should a write then
+/// fail, the paths already written are restored to their previous data on a
+/// best-effort basis before the error is returned.
+pub(crate) async fn apply_patch<S: DataStorer>(
+    storer: &S,
+    prefix: &str,
+    patch: &DataPatch,
+) -> Result<(), DataStorerError> {
+    let prefix = DataPath::from(prefix);
+    let mut original: BTreeMap<String, Option<Data>> = BTreeMap::new();
+    let mut current: BTreeMap<String, Option<DataValueCollection>> = BTreeMap::new();
+
+    for op in patch.operations.iter() {
+        let path = resolve(&prefix, op.path());
+        if !current.contains_key(&path) {
+            let data = match storer.get(&path).await {
+                Ok(data) => Some(data),
+                Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                }) => None,
+                Err(e) => return Err(e),
+            };
+            current.insert(path.clone(), data.as_ref().map(|d| d.value().clone()));
+            original.insert(path.clone(), data);
+        }
+        let value = current.get_mut(&path).expect("read above");
+        match *op {
+            PatchOperation::Add { value: ref new, .. } => *value = Some(new.clone()),
+            PatchOperation::Replace { value: ref new, .. } => match *value {
+                Some(_) => *value = Some(new.clone()),
+                None => return Err(rejected(&path, "cannot replace a missing value")),
+            },
+            PatchOperation::Remove { .. } => match *value {
+                Some(_) => *value = None,
+                None => return Err(rejected(&path, "cannot remove a missing value")),
+            },
+            PatchOperation::Test {
+                value: ref expected,
+                ..
+            } => {
+                if value.as_ref() != Some(expected) {
+                    return Err(rejected(&path, "test failed"));
+                }
+            }
+        }
+    }
+
+    let mut written: Vec<&str> = Vec::new();
+    for (path, value) in current.iter() {
+        let previous = original[path].as_ref().map(Data::value);
+        if previous == value.as_ref() {
+            continue;
+        }
+        let result = match *value {
+            Some(ref value) => storer
+                .create(Data::with_values(path, value.clone()))
+                .await
+                .map(|_| ()),
+            None => storer.delete(path).await.map(|_| ()),
+        };
+        if let Err(e) = result {
+            for path in written {
+                let _ = match original[path] {
+                    Some(ref data) => storer.create(data.clone()).await,
+                    None => storer.delete(path).await,
+                };
+            }
+            return Err(e);
+        }
+        written.push(path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, DataPatch, PatchOperation};
+    use crate::storage::tests::{memory_storer, MockDataStorer};
+    use crate::{
+        Data, DataPath, DataStorer, DataStorerError, DataValue, DataValueCollection, StorageError,
+    };
+    use serde_json::json;
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    fn data_sets() -> (Vec<Data>, Vec<Data>) {
+        let old = vec![
+            Data::new(".a.", DataValue::from(1u64)),
+            Data::new(".b.", DataValue::from("same")),
+            Data::new(".c.", DataValue::from(true)),
+        ];
+        let new = vec![
+            Data::new(".d.", DataValue::from(4u64)),
+            Data::new(".b.", DataValue::from("same")),
+            Data::new(".a.", DataValue::from(2u64)),
+        ];
+        (old, new)
+    }
+
+    #[test]
+    fn test_diff() {
+        let (old, new) = data_sets();
+        let patch = diff(&old, &new);
+        assert_eq!(
+            DataPatch::new(vec![
+                PatchOperation::Replace {
+                    path: DataPath::new(".a."),
+                    value: DataValueCollection::from(vec![DataValue::from(2u64)]),
+                },
+                PatchOperation::Remove {
+                    path: DataPath::new(".c."),
+                },
+                PatchOperation::Add {
+                    path: DataPath::new(".d."),
+                    value: DataValueCollection::from(vec![DataValue::from(4u64)]),
+                },
+            ]),
+            patch
+        );
+        assert_eq!(vec![&DataPath::new(".d.")], patch.added());
+        assert_eq!(vec![&DataPath::new(".c.")], patch.removed());
+        assert_eq!(vec![&DataPath::new(".a.")], patch.changed());
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        let (old, new) = data_sets();
+        assert_eq!(
+            "~ .a. = 2\n- .c.\n+ .d. = 4\n",
+            diff(&old, &new).to_string()
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let (old, new) = data_sets();
+        let patch = diff(&old, &new);
+        let value = serde_json::to_value(&patch).unwrap();
+        assert_eq!(
+            json!([
+                { "op": "replace", "path": ".a.", "value": [{ "Unencrypted": { "U64": 2 } }] },
+                { "op": "remove", "path": ".c." },
+                { "op": "add", "path": ".d.", "value": [{ "Unencrypted": { "U64": 4 } }] }
+            ]),
+            value
+        );
+        assert_eq!(patch, serde_json::from_value(value).unwrap());
+    }
+
+    fn values(value: DataValue) -> DataValueCollection {
+        DataValueCollection::from(vec![value])
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch() {
+        let (storer, entries) = memory_storer();
+        storer
+            .create(Data::new(".users.a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".users.b.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+
+        let patch = DataPatch::new(vec![
+            PatchOperation::Test {
+                path: DataPath::new(".a."),
+                value: values(DataValue::from(1u64)),
+            },
+            PatchOperation::Replace {
+                path: DataPath::new(".a."),
+                value: values(DataValue::from(10u64)),
+            },
+            PatchOperation::Remove {
+                path: DataPath::new(".b."),
+            },
+            PatchOperation::Add {
+                path: DataPath::new(".c."),
+                value: values(DataValue::from(3u64)),
+            },
+        ]);
+        storer.apply_patch(".users.", &patch).await.unwrap();
+
+        let entries = entries.lock().unwrap();
+        let paths: Vec<_> = entries.keys().cloned().collect();
+        assert_eq!(vec![".users.a.".to_owned(), ".users.c.".to_owned()], paths);
+        assert_eq!(
+            &values(DataValue::from(10u64)),
+            entries[".users.a."].value()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_diff_reproduces_new_set() {
+        let (old, new) = data_sets();
+        let (storer, entries) = memory_storer();
+        for d in old.iter() {
+            storer.create(d.clone()).await.unwrap();
+        }
+        storer.apply_patch(".", &diff(&old, &new)).await.unwrap();
+
+        let mut expected = new;
+        expected.sort_by_key(Data::path);
+        let stored: Vec<Data> = entries.lock().unwrap().values().cloned().collect();
+        assert_eq!(expected, stored);
+    }
+
+    #[tokio::test]
+    async fn test_failed_checks_write_nothing() {
+        let (storer, entries) = memory_storer();
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        for op in [
+            PatchOperation::Test {
+                path: DataPath::new(".a."),
+                value: values(DataValue::from(2u64)),
+            },
+            PatchOperation::Replace {
+                path: DataPath::new(".missing."),
+                value: values(DataValue::from(2u64)),
+            },
+            PatchOperation::Remove {
+                path: DataPath::new(".missing."),
+            },
+        ] {
+            let patch = DataPatch::new(vec![
+                PatchOperation::Remove {
+                    path: DataPath::new(".a."),
+                },
+                PatchOperation::Add {
+                    path: DataPath::new(".a."),
+                    value: values(DataValue::from(1u64)),
+                },
+                op,
+            ]);
+            assert!(matches!(
+                storer.apply_patch(".", &patch).await,
+                Err(DataStorerError::PatchRejected { .. })
+            ));
+        }
+        assert_eq!(1, entries.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_restores_written_paths() {
+        let entries = Arc::new(Mutex::new(BTreeMap::new()));
+        entries
+            .lock()
+            .unwrap()
+            .insert(".a.".to_owned(), Data::new(".a.", DataValue::from(1u64)));
+        let mut storer = MockDataStorer::new();
+        let e = entries.clone();
+        storer.expect_get().returning(move |path| {
+            e.lock()
+                .unwrap()
+                .get(path)
+                .cloned()
+                .ok_or(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                })
+        });
+        let e = entries.clone();
+        storer
+            .expect_create()
+            .returning(move |data| match data.path().as_str() {
+                ".b." => Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                }),
+                path => Ok(e.lock().unwrap().insert(path.to_owned(), data).is_none()),
+            });
+
+        let patch = DataPatch::new(vec![
+            PatchOperation::Replace {
+                path: DataPath::new(".a."),
+                value: values(DataValue::from(2u64)),
+            },
+            PatchOperation::Add {
+                path: DataPath::new(".b."),
+                value: values(DataValue::from(3u64)),
+            },
+        ]);
+        assert!(storer.apply_patch(".", &patch).await.is_err());
+
+        let entries = entries.lock().unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(&values(DataValue::from(1u64)), entries[".a."].value());
+    }
+}
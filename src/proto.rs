@@ -0,0 +1,439 @@
+use crate::{Cursor, KeyName};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Returned when a protobuf message does not hold valid data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtoError {
+    pub reason: String,
+}
+
+impl Error for ProtoError {}
+
+impl Display for ProtoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid protobuf data: {}", self.reason)
+    }
+}
+
+fn error(reason: &str) -> ProtoError {
+    ProtoError {
+        reason: reason.to_owned(),
+    }
+}
+
+/// The `redact.data.v1.DataType` enum of `proto/redact/data/v1/data.proto`,
+/// whose messages this module mirrors, for exchanging data over gRPC
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum DataType {
+    Unspecified = 0,
+    Bool = 1,
+    U64 = 2,
+    I64 = 3,
+    F64 = 4,
+    String = 5,
+    Bytes = 6,
+}
+
+/// The message of an `UnencryptedDataValue`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UnencryptedDataValue {
+    #[prost(oneof = "unencrypted_data_value::Value", tags = "1, 2, 3, 4, 5, 6")]
+    pub value: Option<unencrypted_data_value::Value>,
+}
+
+pub mod unencrypted_data_value {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Value {
+        #[prost(bool, tag = "1")]
+        Bool(bool),
+        #[prost(uint64, tag = "2")]
+        U64(u64),
+        #[prost(sint64, tag = "3")]
+        I64(i64),
+        #[prost(double, tag = "4")]
+        F64(f64),
+        #[prost(string, tag = "5")]
+        String(String),
+        #[prost(bytes = "vec", tag = "6")]
+        Bytes(Vec<u8>),
+    }
+}
+
+/// The message of an `EncryptedDataValue`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EncryptedDataValue {
+    #[prost(bytes = "vec", tag = "1")]
+    pub value: Vec<u8>,
+    /// The type of the plaintext
+    #[prost(enumeration = "DataType", tag = "2")]
+    pub datatype: i32,
+    /// The name of the key the value was encrypted by
+    #[prost(string, tag = "3")]
+    pub keyname: String,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub nonce: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub aad: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "6")]
+    pub algorithm: Option<String>,
+}
+
+/// The message of a `DataValue`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataValue {
+    #[prost(oneof = "data_value::Value", tags = "1, 2")]
+    pub value: Option<data_value::Value>,
+}
+
+pub mod data_value {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "1")]
+        Encrypted(super::EncryptedDataValue),
+        #[prost(message, tag = "2")]
+        Unencrypted(super::UnencryptedDataValue),
+    }
+}
+
+/// The message of a `DataSignature`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataSignature {
+    #[prost(string, tag = "1")]
+    pub algorithm: String,
+    #[prost(string, tag = "2")]
+    pub keyid: String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+/// The message of a `DataLink`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataLink {
+    #[prost(string, tag = "1")]
+    pub link_type: String,
+    #[prost(string, tag = "2")]
+    pub target: String,
+}
+
+/// The message of a `Data`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Data {
+    /// A json-style path such as `.my.json.path.`
+    #[prost(string, tag = "1")]
+    pub path: String,
+    #[prost(message, repeated, tag = "2")]
+    pub value: Vec<DataValue>,
+    #[prost(string, optional, tag = "3")]
+    pub integrity: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    pub signature: Option<DataSignature>,
+    /// Milliseconds since the unix epoch
+    #[prost(uint64, optional, tag = "5")]
+    pub written_at: Option<u64>,
+    /// The names of the keys the values are to be encrypted by
+    #[prost(string, repeated, tag = "6")]
+    pub encryptedby: Vec<String>,
+    #[prost(btree_map = "string, string", tag = "7")]
+    pub labels: BTreeMap<String, String>,
+    /// Milliseconds since the unix epoch
+    #[prost(uint64, optional, tag = "8")]
+    pub expires_at: Option<u64>,
+    /// The data-encryption key wrapped by each recipient key, by key name
+    #[prost(btree_map = "string, bytes", tag = "9")]
+    pub recipients: BTreeMap<String, Vec<u8>>,
+    #[prost(message, repeated, tag = "10")]
+    pub links: Vec<DataLink>,
+}
+
+/// The message of a `DataCollection`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataCollection {
+    #[prost(message, repeated, tag = "1")]
+    pub data: Vec<Data>,
+    #[prost(string, optional, tag = "2")]
+    pub next_cursor: Option<String>,
+}
+
+impl From<crate::DataType> for DataType {
+    fn from(datatype: crate::DataType) -> Self {
+        match datatype {
+            crate::DataType::Bool => DataType::Bool,
+            crate::DataType::U64 => DataType::U64,
+            crate::DataType::I64 => DataType::I64,
+            crate::DataType::F64 => DataType::F64,
+            crate::DataType::String => DataType::String,
+            crate::DataType::Bytes => DataType::Bytes,
+        }
+    }
+}
+
+impl TryFrom<DataType> for crate::DataType {
+    type Error = ProtoError;
+
+    fn try_from(datatype: DataType) -> Result<Self, Self::Error> {
+        match datatype {
+            DataType::Unspecified => Err(error("data type is unspecified")),
+            DataType::Bool => Ok(crate::DataType::Bool),
+            DataType::U64 => Ok(crate::DataType::U64),
+            DataType::I64 => Ok(crate::DataType::I64),
+            DataType::F64 => Ok(crate::DataType::F64),
+            DataType::String => Ok(crate::DataType::String),
+            DataType::Bytes => Ok(crate::DataType::Bytes),
+        }
+    }
+}
+
+impl From<crate::DataValue> for DataValue {
+    fn from(value: crate::DataValue) -> Self {
+        let datatype = value.datatype();
+        let value = match value {
+            crate::DataValue::Encrypted(e) => data_value::Value::Encrypted(EncryptedDataValue {
+                value: e.value().to_vec(),
+                datatype: DataType::from(datatype) as i32,
+                keyname: e.keyname().to_owned(),
+                nonce: e.nonce().map(<[u8]>::to_vec),
+                aad: e.aad().map(<[u8]>::to_vec),
+                algorithm: e.algorithm().map(str::to_owned),
+            }),
+            crate::DataValue::Unencrypted(u) => {
+                use unencrypted_data_value::Value;
+                data_value::Value::Unencrypted(UnencryptedDataValue {
+                    value: Some(match u {
+                        crate::UnencryptedDataValue::Bool(b) => Value::Bool(b),
+                        crate::UnencryptedDataValue::U64(n) => Value::U64(n),
+                        crate::UnencryptedDataValue::I64(n) => Value::I64(n),
+                        crate::UnencryptedDataValue::F64(n) => Value::F64(n),
+                        crate::UnencryptedDataValue::String(s) => Value::String(s),
+                        crate::UnencryptedDataValue::Bytes(b) => Value::Bytes(b),
+                    }),
+                })
+            }
+        };
+        DataValue { value: Some(value) }
+    }
+}
+
+impl TryFrom<DataValue> for crate::DataValue {
+    type Error = ProtoError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value.value.ok_or_else(|| error("data value is empty"))? {
+            data_value::Value::Encrypted(e) => {
+                let datatype = DataType::from_i32(e.datatype)
+                    .ok_or_else(|| error("data type is unknown"))
+                    .and_then(crate::DataType::try_from)?;
+                let mut encrypted = crate::EncryptedDataValue::new(e.value, datatype, &e.keyname);
+                if let Some(nonce) = e.nonce {
+                    encrypted = encrypted.with_nonce(nonce);
+                }
+                if let Some(aad) = e.aad {
+                    encrypted = encrypted.with_aad(aad);
+                }
+                if let Some(ref algorithm) = e.algorithm {
+                    encrypted = encrypted.with_algorithm(algorithm);
+                }
+                Ok(crate::DataValue::Encrypted(encrypted))
+            }
+            data_value::Value::Unencrypted(u) => {
+                use unencrypted_data_value::Value;
+                Ok(crate::DataValue::Unencrypted(
+                    match u.value.ok_or_else(|| error("unencrypted value is empty"))? {
+                        Value::Bool(b) => crate::UnencryptedDataValue::Bool(b),
+                        Value::U64(n) => crate::UnencryptedDataValue::U64(n),
+                        Value::I64(n) => crate::UnencryptedDataValue::I64(n),
+                        Value::F64(n) => crate::UnencryptedDataValue::F64(n),
+                        Value::String(s) => crate::UnencryptedDataValue::String(s),
+                        Value::Bytes(b) => crate::UnencryptedDataValue::Bytes(b),
+                    },
+                ))
+            }
+        }
+    }
+}
+
+impl From<crate::Data> for Data {
+    fn from(data: crate::Data) -> Self {
+        Data {
+            path: data.path(),
+            value: data
+                .value()
+                .0
+                .iter()
+                .cloned()
+                .map(DataValue::from)
+                .collect(),
+            integrity: data.integrity().map(str::to_owned),
+            signature: data.signature().map(|signature| DataSignature {
+                algorithm: signature.algorithm.clone(),
+                keyid: signature.keyid.clone(),
+                signature: signature.signature.clone(),
+            }),
+            written_at: data.written_at(),
+            encryptedby: data
+                .requested_keys()
+                .iter()
+                .map(|keyname| keyname.as_str().to_owned())
+                .collect(),
+            labels: data.labels().clone(),
+            expires_at: data.expires_at(),
+            recipients: data
+                .recipients()
+                .iter()
+                .map(|(keyname, wrapped_key)| (keyname.as_str().to_owned(), wrapped_key.clone()))
+                .collect(),
+            links: data
+                .links()
+                .iter()
+                .map(|link| DataLink {
+                    link_type: link.link_type.clone(),
+                    target: link.target.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<Data> for crate::Data {
+    type Error = ProtoError;
+
+    fn try_from(message: Data) -> Result<Self, Self::Error> {
+        let keyname = |name: &str| KeyName::new(name).map_err(|e| error(&e.to_string()));
+        let values = message
+            .value
+            .into_iter()
+            .map(crate::DataValue::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut data = crate::Data::with_values(&message.path, values.into());
+        data.set_integrity(message.integrity);
+        data.set_signature(message.signature.map(|signature| crate::DataSignature {
+            algorithm: signature.algorithm,
+            keyid: signature.keyid,
+            signature: signature.signature,
+        }));
+        data.set_written_at(message.written_at);
+        for name in &message.encryptedby {
+            data.add_encrypted_by(keyname(name)?);
+        }
+        for (key, value) in &message.labels {
+            data.set_label(key, value);
+        }
+        data.set_expires_at(message.expires_at);
+        for (name, wrapped_key) in message.recipients {
+            data.add_recipient(keyname(&name)?, wrapped_key);
+        }
+        for link in &message.links {
+            data.add_link(&link.link_type, &link.target);
+        }
+        Ok(data)
+    }
+}
+
+impl From<crate::DataCollection> for DataCollection {
+    fn from(collection: crate::DataCollection) -> Self {
+        DataCollection {
+            data: collection.data.into_iter().map(Data::from).collect(),
+            next_cursor: collection
+                .next_cursor
+                .map(|cursor| cursor.as_str().to_owned()),
+        }
+    }
+}
+
+impl TryFrom<DataCollection> for crate::DataCollection {
+    type Error = ProtoError;
+
+    fn try_from(message: DataCollection) -> Result<Self, Self::Error> {
+        Ok(crate::DataCollection {
+            data: message
+                .data
+                .into_iter()
+                .map(crate::Data::try_from)
+                .collect::<Result<_, _>>()?,
+            next_cursor: message.next_cursor.map(|cursor| Cursor::new(&cursor)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{data_value, DataType, DataValue, EncryptedDataValue, ProtoError};
+    use crate::{Cursor, Data, DataCollection, DataSignature, DataValueCollection, KeyName};
+    use prost::Message;
+    use std::convert::TryFrom;
+
+    fn data() -> Data {
+        let mut data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                crate::DataValue::from(true),
+                crate::DataValue::from(u64::MAX),
+                crate::DataValue::from(i64::MIN),
+                crate::DataValue::from(1.5f64),
+                crate::DataValue::from("x"),
+                crate::DataValue::from(vec![1u8, 2]),
+                crate::DataValue::Encrypted(
+                    crate::EncryptedDataValue::new(vec![4], crate::DataType::String, "k")
+                        .with_nonce(vec![5; 12])
+                        .with_aad(b"ctx".to_vec())
+                        .with_algorithm("AES-256-GCM"),
+                ),
+            ]),
+        );
+        data.seal_integrity();
+        data.set_written_at(Some(42));
+        data.add_encrypted_by(KeyName::new("k1").unwrap());
+        data.set_label("team", "billing");
+        data.set_expires_at(Some(43));
+        data.add_recipient(KeyName::new("k3").unwrap(), vec![8; 40]);
+        data.add_link("derived_from", ".b.");
+        data.set_signature(Some(DataSignature {
+            algorithm: "hmac-sha256".to_owned(),
+            keyid: "k1".to_owned(),
+            signature: vec![9; 32],
+        }));
+        data
+    }
+
+    #[test]
+    fn test_round_trips_data_through_protobuf() {
+        let bytes = super::Data::from(data()).encode_to_vec();
+        let message = super::Data::decode(&bytes[..]).unwrap();
+        assert_eq!(data(), Data::try_from(message).unwrap());
+
+        let collection = DataCollection {
+            data: vec![data(), Data::new(".b.", crate::DataValue::from(1u64))],
+            next_cursor: Some(Cursor::new("2")),
+        };
+        let bytes = super::DataCollection::from(collection.clone()).encode_to_vec();
+        let message = super::DataCollection::decode(&bytes[..]).unwrap();
+        assert_eq!(collection, DataCollection::try_from(message).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_invalid_messages() {
+        assert_eq!(
+            Err(ProtoError {
+                reason: "data value is empty".to_owned()
+            }),
+            crate::DataValue::try_from(DataValue::default())
+        );
+        let unspecified = DataValue {
+            value: Some(data_value::Value::Encrypted(EncryptedDataValue {
+                datatype: DataType::Unspecified as i32,
+                ..EncryptedDataValue::default()
+            })),
+        };
+        assert!(crate::DataValue::try_from(unspecified).is_err());
+        let message = super::Data {
+            encryptedby: vec!["k 1".to_owned()],
+            ..super::Data::from(Data::new(".a.", crate::DataValue::from(1u64)))
+        };
+        assert!(Data::try_from(message).is_err());
+    }
+}
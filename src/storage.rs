@@ -1,13 +1,78 @@
+pub mod aggregation;
+pub mod any;
+pub mod atlas;
+pub mod atomic;
+pub mod audit;
+pub mod authorization;
+pub mod boxed;
+pub mod buffered;
+pub mod chunking;
+pub mod conformance;
+pub mod conflict;
+pub mod context;
+pub mod encrypted_file;
+pub mod encrypted_only;
+pub mod erasure;
 pub mod error;
+pub mod event_sourced;
+pub mod fetch;
+pub mod file;
+pub mod hooks;
+pub mod integrity;
+pub mod keyring;
+pub mod links;
+pub mod memory;
+pub mod metrics;
 pub mod mongodb;
+pub mod nonexistence;
+pub mod offline;
+pub mod query;
 pub mod redact;
+pub mod retention;
+pub mod scoped;
+pub mod signing;
+pub mod slow_log;
+pub mod stats;
+pub mod sync;
+pub mod tenant;
+pub mod type_migration;
+pub mod versioning;
 
-use crate::data::Data;
+use crate::data::{Data, DataCollection, DataValue};
 use async_trait::async_trait;
-use std::{ops::Deref, sync::Arc};
+use futures::{
+    channel::oneshot,
+    future::{self, FutureExt, Shared},
+    stream::{self, BoxStream, StreamExt},
+};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
 use crate::{DataCacher};
-use crate::storage::error::DataStorerError;
+use crate::storage::aggregation::{Aggregation, Aggregator};
+use crate::storage::erasure::EraseReport;
+use crate::storage::metrics::{Metrics, MetricsSnapshot};
+use crate::storage::links::LinkDirection;
+use crate::storage::error::{DataStorerError, StorageError};
+use crate::storage::context::OpContext;
+use crate::storage::query::{Cursor, DataFilter, EncryptionFilter, Sort};
+use crate::storage::hooks::{HookedDataStorer, StorerHooks};
+use crate::storage::scoped::ScopedDataStorer;
+use crate::storage::stats::DataStats;
+use crate::patch::{self, DataPatch};
+use crate::convert::{self, MergeStrategy};
+use serde_json::Value;
 
+/// Number of entries fetched per page when a storer has to walk a collection
+/// client-side, e.g. to compute an aggregation.
+const CLIENT_SIDE_PAGE_SIZE: i64 = 100;
 
 /// The operations a storer of `Data` structs must be able to fulfill.
 #[async_trait]
@@ -15,8 +80,312 @@ pub trait DataStorer: Clone + Send + Sync {
     /// Fetches one instance of a `Data` stored at that path.
     /// If the `Data` is an array, the first retrieved element is returned.
     async fn get(&self, path: &str) -> Result<Data, DataStorerError>;
+    /// Fetches a page of the `Data` stored under the given path prefix,
+    /// ordered as specified by `sort`, or in an unspecified order if `None`.
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError>;
+    /// Fetches a page of the `Data` stored under the given path prefix which
+    /// match the filter, skipping the first `skip` matches.
+    /// The default implementation pages through `get_collection` and filters
+    /// client-side; storers that can filter server-side should override it.
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let (mut skip, mut data, mut offset) = (skip.max(0), Vec::new(), 0);
+        while (data.len() as i64) < page_size {
+            let page = self.get_collection(path, offset, CLIENT_SIDE_PAGE_SIZE, sort).await?;
+            let fetched = page.data.len() as i64;
+            for d in page.data.into_iter().filter(|d| filter.matches(d)) {
+                if skip > 0 {
+                    skip -= 1;
+                } else if (data.len() as i64) < page_size {
+                    data.push(d);
+                }
+            }
+            if fetched < CLIENT_SIDE_PAGE_SIZE {
+                break;
+            }
+            offset += CLIENT_SIDE_PAGE_SIZE;
+        }
+        Ok(DataCollection::new(data))
+    }
+    /// Fetches a page of the `Data` stored under the given path prefix, starting
+    /// at `cursor` or at the beginning of the collection if `None`. Unlike
+    /// skip-based pagination, a cursor remains stable when entries are inserted
+    /// while iterating.
+    /// The default implementation encodes a skip offset in the cursor; storers
+    /// with a natural ordering key should override it.
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let skip = match cursor {
+            Some(cursor) => cursor
+                .as_str()
+                .parse::<i64>()
+                .map_err(|_| DataStorerError::InvalidCursor)?,
+            None => 0,
+        };
+        let mut page = self.get_collection(path, skip, page_size, None).await?;
+        if page.data.len() as i64 >= page_size {
+            page.next_cursor = Some(Cursor::new(&(skip + page_size).to_string()));
+        }
+        Ok(page)
+    }
+    /// Streams every `Data` stored under the given path prefix, transparently
+    /// fetching successive pages with `get_collection_page` as the stream is
+    /// consumed. The stream ends after yielding the first error encountered.
+    fn iter_collection<'a>(&'a self, path: &'a str) -> BoxStream<'a, Result<Data, DataStorerError>> {
+        stream::unfold(Some(None), move |next: Option<Option<Cursor>>| async move {
+            let cursor = next?;
+            match self.get_collection_page(path, cursor, CLIENT_SIDE_PAGE_SIZE).await {
+                Ok(page) => {
+                    let following = page.next_cursor.map(Some);
+                    let items: Vec<_> = page.data.into_iter().map(Ok).collect();
+                    Some((stream::iter(items), following))
+                }
+                Err(e) => Some((stream::iter(vec![Err(e)]), None)),
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+    /// Streams every `Data` with a value encrypted by the named key, e.g. to
+    /// find what must be re-encrypted when rotating or revoking the key.
+    /// Pages are fetched with `get_filtered_collection`, so storers filtering
+    /// server-side only return matching entries. Collect the results before
+    /// re-encrypting them, as changing entries while streaming shifts the pages.
+    fn find_by_key<'a>(&'a self, keyname: &'a str) -> BoxStream<'a, Result<Data, DataStorerError>> {
+        let filter = DataFilter::new().with_encryption(EncryptionFilter::EncryptedBy(keyname.to_owned()));
+        stream::unfold((Some(0), filter), move |(skip, filter)| async move {
+            let skip = skip?;
+            match self.get_filtered_collection(".", &filter, skip, CLIENT_SIDE_PAGE_SIZE, None).await {
+                Ok(page) => {
+                    let next = match page.data.len() as i64 {
+                        n if n < CLIENT_SIDE_PAGE_SIZE => None,
+                        n => Some(skip + n),
+                    };
+                    let items: Vec<_> = page.data.into_iter().map(Ok).collect();
+                    Some((stream::iter(items), (next, filter)))
+                }
+                Err(e) => Some((stream::iter(vec![Err(e)]), (None, filter))),
+            }
+        })
+        .flatten()
+        .boxed()
+    }
     /// Serializes a piece of `Data` to the the database.
     async fn create(&self, data: Data) -> Result<bool, DataStorerError>;
+    /// Serializes many pieces of `Data` to the database, returning how many
+    /// were written.
+    /// The default implementation creates them one by one, stopping at the
+    /// first failure; storers that can write in bulk should override it.
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        let mut created = 0;
+        for data in data {
+            if self.create(data).await? {
+                created += 1;
+            }
+        }
+        Ok(created)
+    }
+    /// Returns the `Data` stored at that path, or writes `default` there and
+    /// returns it if none exists.
+    /// The default implementation reads the path and writes the default if it
+    /// was not found, so concurrent callers may each write their own default;
+    /// storers with a conditional insert should override it.
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        atomic::get_or_create(self, path, default).await
+    }
+    /// Adds `delta` to the integer counter stored at that path, starting from
+    /// zero if none exists, and returns the new value.
+    /// The default implementation reads the counter and writes it back, so
+    /// concurrent increments may be lost; storers with an atomic increment
+    /// should override it.
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        atomic::increment(self, path, delta).await
+    }
+    /// Replaces the value stored at that path with `new` only if it currently
+    /// holds exactly the single value `expected`, returning whether it did.
+    /// The default implementation reads the value and writes it back, so a
+    /// concurrent write in between may be overwritten; storers with a
+    /// conditional write should override it.
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        atomic::compare_and_swap(self, path, expected, new).await
+    }
+    /// Deletes the `Data` stored at that path, returning whether any existed.
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError>;
+    /// Deletes every `Data` stored under the given path prefix, reporting the
+    /// paths deleted.
+    /// The default implementation collects the paths with `iter_collection`
+    /// and deletes them one by one; storers that can delete in bulk should
+    /// override it.
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let mut paths = Vec::new();
+        let mut entries = self.iter_collection(prefix);
+        while let Some(entry) = entries.next().await {
+            paths.push(entry?.path());
+        }
+        drop(entries);
+
+        let mut erased = Vec::with_capacity(paths.len());
+        for path in paths {
+            if self.delete(&path).await? {
+                erased.push(path);
+            }
+        }
+        Ok(EraseReport::new(erased))
+    }
+    /// Deletes every `Data` stored under the given path which matches the
+    /// filter, or all of it if there is none, returning how many were deleted.
+    /// The default implementation collects the matching paths with
+    /// `iter_collection` and deletes them one by one; storers that can delete
+    /// in bulk server-side should override it.
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        let mut paths = Vec::new();
+        let mut entries = self.iter_collection(path);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if filter.is_none_or(|filter| filter.matches(&entry)) {
+                paths.push(entry.path());
+            }
+        }
+        drop(entries);
+
+        let mut deleted = 0;
+        for path in paths {
+            if self.delete(&path).await? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+    /// Applies a patch whose paths are relative to the given prefix, either
+    /// completely or, if any operation cannot be applied, not at all.
+    /// The default implementation checks every operation before writing and
+    /// restores already-written paths should a write fail; storers with
+    /// transactions should override it.
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        patch::apply_patch(self, prefix, patch).await
+    }
+    /// Writes a JSON document under the given prefix, one `Data` per leaf as
+    /// laid out by `convert::flatten`, either replacing everything stored under
+    /// the prefix or merging into it. Returns the patch that was applied.
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        convert::put_subtree(self, prefix, value, strategy).await
+    }
+    /// Computes an aggregation over all unencrypted numeric values stored under
+    /// the given path prefix; see `Aggregator::finish` for empty results.
+    /// The default implementation pages through `get_collection` and aggregates
+    /// client-side; storers that can aggregate server-side should override it.
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        let mut aggregator = Aggregator::new(aggregation);
+        let mut skip = 0;
+        loop {
+            let page = self.get_collection(path, skip, CLIENT_SIDE_PAGE_SIZE, None).await?;
+            page.data.iter().for_each(|d| aggregator.add_data(d));
+            if (page.data.len() as i64) < CLIENT_SIDE_PAGE_SIZE {
+                return Ok(aggregator.finish());
+            }
+            skip += CLIENT_SIDE_PAGE_SIZE;
+        }
+    }
+    /// Computes usage statistics over the data stored under the given prefix.
+    /// The default implementation walks the collection client-side; storers
+    /// that can compute them server-side should override it.
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        let mut stats = DataStats::new();
+        let mut entries = self.iter_collection(prefix);
+        while let Some(entry) = entries.next().await {
+            stats.add_data(&entry?);
+        }
+        Ok(stats)
+    }
+    /// Returns the `Data` at each path the data at that path links to with the
+    /// given type of link, e.g. the records it was `derived_from`, skipping
+    /// links to paths holding no data.
+    /// The default implementation fetches the linked paths one by one; storers
+    /// able to fetch many paths at once should override it.
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError> {
+        links::get_linked(self, path, link_type).await
+    }
+    /// Returns the `Data` reachable from the data at `start` through links of
+    /// the given type followed in the given direction, up to `depth` links
+    /// away, e.g. every record derived from it. Entries are ordered
+    /// breadth-first starting with `start`, and the edges between them are the
+    /// links they hold.
+    /// The default implementation fetches one level at a time, scanning every
+    /// entry per level to follow incoming links; storers able to walk a graph
+    /// server-side should override it.
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        links::traverse(self, start, link_type, direction, depth).await
+    }
+    /// Returns a view of this storer in which all paths are relative to the
+    /// given prefix, and nothing outside of the prefix can be reached.
+    fn scoped(&self, prefix: &str) -> ScopedDataStorer<Self>
+    where
+        Self: Sized,
+    {
+        ScopedDataStorer::new(self.clone(), prefix)
+    }
+    /// Returns this storer with hooks run around each of its operations, for
+    /// attaching custom telemetry.
+    fn with_hooks<H: StorerHooks>(&self, hooks: H) -> HookedDataStorer<Self, H>
+    where
+        Self: Sized,
+    {
+        HookedDataStorer::new(self.clone(), hooks)
+    }
+    /// Returns a storer performing its operations under the given context.
+    /// The default implementation ignores the context; backends which can
+    /// propagate it, and wrappers around other storers, should override it.
+    fn with_context(&self, context: OpContext) -> Self
+    where
+        Self: Sized,
+    {
+        let _ = context;
+        self.clone()
+    }
 }
 
 /// Allows an `Arc<DataStorer>` to act exactly like a `DataStorer`, dereferencing
@@ -30,16 +399,256 @@ where
         self.deref().get(path).await
     }
 
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.deref().get_collection(path, skip, page_size, sort).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.deref().get_collection_page(path, cursor, page_size).await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.deref()
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await
+    }
+
     async fn create(&self, value: Data) -> Result<bool, DataStorerError> {
         self.deref().create(value).await
     }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        self.deref().create_many(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.deref().get_or_create(path, default).await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.deref().increment(path, delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.deref().compare_and_swap(path, expected, new).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.deref().delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.deref().erase(prefix).await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        self.deref().delete_collection(path, filter).await
+    }
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        self.deref().apply_patch(prefix, patch).await
+    }
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        self.deref().put_subtree(prefix, value, strategy).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.deref().aggregate(path, aggregation).await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.deref().stats(prefix).await
+    }
+
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.deref().get_linked(path, link_type).await
+    }
+
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.deref()
+            .traverse(start, link_type, direction, depth)
+            .await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        Arc::new(self.deref().with_context(context))
+    }
+}
+
+/// How a `CachedDataStorer` populates its cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Entries are cached when read or written.
+    #[default]
+    ReadThrough,
+    /// Entries are only cached when the caller asks for it with `populate`;
+    /// reads and writes never cache anything, and writes invalidate the
+    /// cached entry instead.
+    CacheAside,
 }
 
 /// Stores an instance of a redact-backed data storer, including a cache.
 #[derive(Clone)]
 pub struct CachedDataStorer<T: DataStorer, V: DataCacher> {
     storer: T,
-    cacher: V
+    cacher: V,
+    mode: CacheMode,
+    coalescer: Option<Arc<WriteCoalescer>>,
+    metrics: Arc<Metrics>,
+}
+
+/// The outcome of writing a batch of coalesced writes, shared with every
+/// writer in the batch: the error message of the write if it failed.
+type FlushOutcome = Result<(), String>;
+
+/// The writes to a path a `CachedDataStorer` coalesced into one, along with
+/// when they are due and how many of their writers are still waiting.
+struct PendingWrite {
+    data: Data,
+    batch: u64,
+    flush_at: Instant,
+    writers: usize,
+    flushed: oneshot::Sender<FlushOutcome>,
+    outcome: Shared<oneshot::Receiver<FlushOutcome>>,
+}
+
+/// The writes a `CachedDataStorer` is holding back, keyed by path.
+struct WriteCoalescer {
+    window: Duration,
+    next_batch: AtomicU64,
+    pending: Mutex<HashMap<String, PendingWrite>>,
+}
+
+impl WriteCoalescer {
+    /// Stages a write, replacing the data of any pending one for the path and
+    /// joining its batch, and returns the batch, when it is due, and its
+    /// outcome. A batch is due one window after its first write was staged.
+    fn stage(&self, path: &str, data: Data) -> (u64, Instant, Shared<oneshot::Receiver<FlushOutcome>>) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(write) = pending.get_mut(path) {
+            write.data = data;
+            write.writers += 1;
+            return (write.batch, write.flush_at, write.outcome.clone());
+        }
+        let batch = self.next_batch.fetch_add(1, Ordering::SeqCst);
+        let flush_at = Instant::now() + self.window;
+        let (flushed, outcome) = oneshot::channel();
+        let outcome = outcome.shared();
+        pending.insert(path.to_owned(), PendingWrite {
+            data,
+            batch,
+            flush_at,
+            writers: 1,
+            flushed,
+            outcome: outcome.clone(),
+        });
+        (batch, flush_at, outcome)
+    }
+
+    /// Removes the pending write for a path, if it belongs to the given batch
+    /// or to any if there is none, returning its data and where to report the
+    /// outcome of writing it.
+    fn take(&self, path: &str, batch: Option<u64>) -> Option<(Data, oneshot::Sender<FlushOutcome>)> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(path) {
+            Some(write) if batch.unwrap_or(write.batch) == write.batch => {
+                pending.remove(path).map(|write| (write.data, write.flushed))
+            }
+            _ => None,
+        }
+    }
+
+    fn get(&self, path: &str) -> Option<Data> {
+        self.pending.lock().unwrap().get(path).map(|write| write.data.clone())
+    }
+
+    /// Stops a writer waiting on a batch, discarding the batch unwritten if it
+    /// was the last one waiting.
+    fn leave(&self, path: &str, batch: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(write) = pending.get_mut(path) {
+            if write.batch == batch {
+                write.writers -= 1;
+                if write.writers == 0 {
+                    pending.remove(path);
+                }
+            }
+        }
+    }
+
+    /// Discards the pending writes under a prefix, reporting them written to
+    /// their writers as they were superseded by the delete doing so.
+    fn discard_prefix(&self, prefix: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let paths: Vec<String> = pending.keys().filter(|path| path.starts_with(prefix)).cloned().collect();
+        for path in paths {
+            if let Some(write) = pending.remove(&path) {
+                let _ = write.flushed.send(Ok(()));
+            }
+        }
+    }
+}
+
+/// Stops waiting on a staged write when its writer stops waiting for it, so
+/// that it is not served to readers without ever being written once none of
+/// its writers wait for it.
+struct StagedWrite<'a> {
+    coalescer: &'a WriteCoalescer,
+    path: &'a str,
+    batch: u64,
+}
+
+impl<'a> Drop for StagedWrite<'a> {
+    fn drop(&mut self) {
+        self.coalescer.leave(self.path, self.batch);
+    }
 }
 
 impl<T: DataStorer, V: DataCacher> CachedDataStorer<T, V> {
@@ -52,54 +661,354 @@ impl<T: DataStorer, V: DataCacher> CachedDataStorer<T, V> {
         CachedDataStorer {
             storer,
             cacher,
+            mode: CacheMode::default(),
+            coalescer: None,
+            metrics: Arc::new(Metrics::new()),
         }
     }
+
+    /// Selects how the cache is populated, read-through by default.
+    pub fn with_cache_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Reads the entry at a path from the storer and caches it, whatever the
+    /// cache mode.
+    pub async fn populate(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(path).await?;
+        self.cacher.set(path, data.clone()).await?;
+        Ok(data)
+    }
+
+    /// Coalesces successive writes to the same path within `window` of the
+    /// first of them, so that the storer and cache only see the last one.
+    /// Writes are held back for up to the window, during which reads of the
+    /// path return the pending value. Every coalesced write returns the
+    /// outcome of writing the last of them, or succeeds if a delete discarded
+    /// them first; should all of their writers be dropped before the window
+    /// ends, none of them are written. Counters and compare-and-swap write
+    /// the pending value of their path before updating it.
+    pub fn with_write_coalescing(mut self, window: Duration) -> Self {
+        self.coalescer = Some(Arc::new(WriteCoalescer {
+            window,
+            next_batch: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+        }));
+        self
+    }
+
+    /// Returns a snapshot of how the cache served reads: reads answered from
+    /// the cache or a pending write are counted as `cache_hits`, the rest as
+    /// `cache_misses`, and writes superseded within the coalescing window as
+    /// `writes_coalesced`.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    async fn write(&self, value: Data) -> Result<bool, DataStorerError> {
+        self.storer.create(value.clone()).await?;
+        self.cache_written(value).await?;
+        Ok(true)
+    }
+
+    /// Updates the cached entry of data just written, as the cache mode says
+    async fn cache_written(&self, value: Data) -> Result<(), DataStorerError> {
+        match self.mode {
+            CacheMode::ReadThrough => self.cacher.set(&value.path(), value).await?,
+            CacheMode::CacheAside => {
+                self.cacher.delete(&value.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the data of a batch of coalesced writes, reporting the outcome
+    /// to the writers waiting on it
+    async fn flush(&self, value: Data, flushed: oneshot::Sender<FlushOutcome>) -> Result<bool, DataStorerError> {
+        let result = self.write(value).await;
+        let _ = flushed.send(result.as_ref().map(|_| ()).map_err(|e| e.to_string()));
+        result
+    }
+
+    /// Writes the pending value of a path now, if there is one, so that an
+    /// update of the path applies on top of it
+    async fn flush_path(&self, path: &str) -> Result<(), DataStorerError> {
+        if let Some((value, flushed)) = self.coalescer.as_ref().and_then(|c| c.take(path, None)) {
+            self.flush(value, flushed).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl<T: DataStorer, V: DataCacher> DataStorer for CachedDataStorer<T, V> {
     async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
-        let cache_hit = self.cacher.exists(path).await?;
-        if cache_hit {
-            self.cacher.expire(
-                path,
-                self.cacher.get_default_key_expiration_seconds())
-                .await?;
-            self.cacher.get(path).await.map_err(|source| {
-                DataStorerError::CacheError {
-                    source: source
-                }
-            })
-        } else {
-            let res = self.storer.get(path).await?;
+        if let Some(pending) = self.coalescer.as_ref().and_then(|c| c.get(path)) {
+            self.metrics.increment("cache_hits");
+            return Ok(pending);
+        }
+        let seconds = self.cacher.get_default_key_expiration_seconds();
+        if let Some(cached) = self.cacher.get_and_expire(path, seconds).await? {
+            self.metrics.increment("cache_hits");
+            return Ok(cached);
+        }
+        self.metrics.increment("cache_misses");
+        let res = self.storer.get(path).await?;
+        if self.mode == CacheMode::ReadThrough {
             self.cacher.set(path, res.clone()).await?;
-            Ok(res)
         }
+        Ok(res)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer.get_collection(path, skip, page_size, sort).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer.get_collection_page(path, cursor, page_size).await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await
     }
 
     async fn create(&self, value: Data) -> Result<bool, DataStorerError> {
-        self.storer.create(value.clone()).await?;
-        self.cacher.set(&value.path(), value.clone()).await?;
-        Ok(true)
+        let coalescer = match self.coalescer {
+            Some(ref coalescer) => coalescer,
+            None => return self.write(value).await,
+        };
+        let path = value.path();
+        let (batch, flush_at, outcome) = coalescer.stage(&path, value);
+        let _staged = StagedWrite { coalescer, path: &path, batch };
+        tokio::time::sleep_until(flush_at).await;
+        if let Some((value, flushed)) = coalescer.take(&path, Some(batch)) {
+            return self.flush(value, flushed).await;
+        }
+        self.metrics.increment("writes_coalesced");
+        let failure = match outcome.await {
+            Ok(Ok(())) => return Ok(true),
+            Ok(Err(message)) => message,
+            Err(oneshot::Canceled) => "the writer flushing it stopped waiting".to_owned(),
+        };
+        Err(DataStorerError::StorageError {
+            source: StorageError::InternalError {
+                source: format!("coalesced write failed: {}", failure).into(),
+            },
+        })
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        if self.coalescer.is_none() {
+            let created = self.storer.create_many(data.clone()).await?;
+            for value in data {
+                self.cache_written(value).await?;
+            }
+            return Ok(created);
+        }
+        // Staging every entry before waiting on any of them coalesces the
+        // whole batch within a single window
+        let mut created = 0;
+        for result in future::join_all(data.into_iter().map(|data| self.create(data))).await {
+            if result? {
+                created += 1;
+            }
+        }
+        Ok(created)
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        if let Some(pending) = self.coalescer.as_ref().and_then(|c| c.get(path)) {
+            return Ok(pending);
+        }
+        let data = self.storer.get_or_create(path, default).await?;
+        if self.mode == CacheMode::ReadThrough {
+            self.cacher.set(path, data.clone()).await?;
+        }
+        Ok(data)
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.flush_path(path).await?;
+        let updated = self.storer.increment(path, delta).await?;
+        self.cacher.delete(path).await?;
+        Ok(updated)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.flush_path(path).await?;
+        let swapped = self.storer.compare_and_swap(path, expected, new).await?;
+        if swapped {
+            self.cacher.delete(path).await?;
+        }
+        Ok(swapped)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        if let Some((_, flushed)) = self.coalescer.as_ref().and_then(|c| c.take(path, None)) {
+            let _ = flushed.send(Ok(()));
+        }
+        let deleted = self.storer.delete(path).await?;
+        self.cacher.delete(path).await?;
+        Ok(deleted)
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        if let Some(ref coalescer) = self.coalescer {
+            coalescer.discard_prefix(prefix);
+        }
+        let mut report = self.storer.erase(prefix).await?;
+        report.cache_entries_purged = self.cacher.delete_prefix(prefix).await?;
+        Ok(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.storer.stats(prefix).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
     }
 }
 
+#[cfg(test)]
 pub mod tests {
-    use crate::{Data, DataStorer, DataStorerError, MockDataCacher, CachedDataStorer, DataValue, UnencryptedDataValue};
-    use async_trait::async_trait;
-    use mockall::predicate::*;
-    use mockall::*;
+    pub use crate::mocks::MockDataStorer;
+    use crate::mocks::MockDataCacher;
+    use crate::{Data, DataCollection, DataStorer, DataStorerError, Sort};
+    use crate::{Aggregation, CacheMode, Cursor, CachedDataStorer, DataFilter, DataType, DataValue, EncryptedDataValue, UnencryptedDataValue};
+    use crate::StorageError;
+    use futures::StreamExt;
+    use std::{collections::BTreeMap, sync::{Arc, Mutex}};
 
-    mock! {
-    pub DataStorer {}
-    #[async_trait]
-    impl DataStorer for DataStorer {
-        async fn get(&self, path: &str) -> Result<Data, DataStorerError>;
-        async fn create(&self, data: Data) -> Result<bool, DataStorerError>;
+    /// Builds a `MockDataStorer` backed by a shared in-memory map, for testing
+    /// storer wrappers which issue many calls against the storer they wrap.
+    pub(crate) fn memory_storer() -> (MockDataStorer, Arc<Mutex<BTreeMap<String, Data>>>) {
+        let entries = Arc::new(Mutex::new(BTreeMap::<String, Data>::new()));
+        let mut storer = MockDataStorer::new();
+
+        let e = entries.clone();
+        storer.expect_get()
+            .returning(move |path| {
+                e.lock().unwrap().get(path).cloned().ok_or(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                })
+            });
+        let e = entries.clone();
+        storer.expect_get_collection()
+            .returning(move |path, skip, page_size, sort| {
+                let mut data: Vec<Data> = e.lock()
+                    .unwrap()
+                    .values()
+                    .filter(|d| d.path().starts_with(path))
+                    .cloned()
+                    .collect();
+                if let Some(Sort { order: crate::SortOrder::Descending, .. }) = sort {
+                    data.reverse();
+                }
+                Ok(DataCollection::new(
+                    data.into_iter()
+                        .skip(skip as usize)
+                        .take(page_size as usize)
+                        .collect(),
+                ))
+            });
+        let e = entries.clone();
+        storer.expect_create()
+            .returning(move |data| {
+                e.lock().unwrap().insert(data.path(), data);
+                Ok(true)
+            });
+        let e = entries.clone();
+        storer.expect_delete()
+            .returning(move |path| Ok(e.lock().unwrap().remove(path).is_some()));
+
+        (storer, entries)
     }
-    impl Clone for DataStorer {
-        fn clone(&self) -> Self;
+
+    #[tokio::test]
+    async fn test_default_get_filtered_collection() {
+        let (storer, _) = memory_storer();
+        for i in 0..250u64 {
+            let value = if i % 2 == 0 { DataValue::from(i) } else { DataValue::from(i.to_string()) };
+            storer.create(Data::new(&format!(".n.{:03}.", i), value)).await.unwrap();
+        }
+
+        let filter = DataFilter::new().with_type(DataType::String);
+        let page = storer.get_filtered_collection(".n.", &filter, 60, 100, None).await.unwrap();
+        assert_eq!(65, page.data.len());
+        assert_eq!(".n.121.", page.data[0].path());
+        assert!(page.data.iter().all(|d| filter.matches(d)));
     }
+
+    #[tokio::test]
+    async fn test_default_delete_collection() {
+        let (storer, entries) = memory_storer();
+        for i in 0..10u64 {
+            let value = if i % 2 == 0 { DataValue::from(i) } else { DataValue::from(i.to_string()) };
+            storer.create(Data::new(&format!(".n.{}.", i), value)).await.unwrap();
+        }
+        storer.create(Data::new(".m.", DataValue::from("kept"))).await.unwrap();
+
+        let filter = DataFilter::new().with_type(DataType::String);
+        assert_eq!(5, storer.delete_collection(".n.", Some(&filter)).await.unwrap());
+        assert_eq!(6, entries.lock().unwrap().len());
+        assert_eq!(5, storer.delete_collection(".n.", None).await.unwrap());
+        assert_eq!(vec![".m."], entries.lock().unwrap().keys().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_find_by_key() {
+        let (storer, _) = memory_storer();
+        for i in 0..150u64 {
+            let keyname = if i % 3 == 0 { "k1" } else { "k2" };
+            let value = DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, keyname));
+            storer.create(Data::new(&format!(".n.{:03}.", i), value)).await.unwrap();
+        }
+        storer.create(Data::new(".plain.", DataValue::from(1u64))).await.unwrap();
+
+        let found: Vec<Data> = storer
+            .find_by_key("k1")
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(50, found.len());
+        assert!(found.iter().all(|d| d.encryptedby() == vec!["k1"]));
     }
 
     #[tokio::test]
@@ -129,6 +1038,8 @@ pub mod tests {
         let cached_storer = CachedDataStorer::new(storer, cacher);
         let result = cached_storer.get("abc").await.unwrap();
         assert_eq!(".path.", result.path());
+        assert_eq!(1, cached_storer.metrics().counter("cache_hits"));
+        assert_eq!(0, cached_storer.metrics().counter("cache_misses"));
     }
 
     #[tokio::test]
@@ -136,6 +1047,8 @@ pub mod tests {
         let mut storer = MockDataStorer::new();
         let mut cacher = MockDataCacher::new();
 
+        cacher.expect_get_default_key_expiration_seconds()
+            .returning(|| 60);
         cacher.expect_exists()
             .times(1)
             .returning(|_| { Ok(false) });
@@ -158,5 +1071,259 @@ pub mod tests {
         let cached_storer = CachedDataStorer::new(storer, cacher);
         let result = cached_storer.get(".path.").await.unwrap();
         assert_eq!(".path.", result.path());
+        assert_eq!(1, cached_storer.metrics().counter("cache_misses"));
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_default_pages_through_collection() {
+        let mut storer = MockDataStorer::new();
+
+        storer.expect_get_collection()
+            .times(2)
+            .returning(|_, skip, page_size, _| {
+                let count = if skip == 0 { page_size } else { 1 };
+                Ok(DataCollection::new(
+                    (0..count)
+                        .map(|_| Data::new(".path.", DataValue::from(2u64)))
+                        .collect(),
+                ))
+            });
+
+        let result = storer.aggregate(".path.", Aggregation::Sum).await.unwrap();
+        assert_eq!(Some(202.0), result);
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_page_default_offset_cursor() {
+        let mut storer = MockDataStorer::new();
+
+        storer.expect_get_collection()
+            .times(1)
+            .withf(|path, skip, page_size, sort| {
+                path == ".path." && *skip == 20 && *page_size == 10 && sort.is_none()
+            })
+            .returning(|_, _, page_size, _| {
+                Ok(DataCollection::new(
+                    (0..page_size)
+                        .map(|_| Data::new(".path.", DataValue::from(2u64)))
+                        .collect(),
+                ))
+            });
+
+        let page = storer
+            .get_collection_page(".path.", Some(Cursor::new("20")), 10)
+            .await
+            .unwrap();
+        assert_eq!(Some(Cursor::new("30")), page.next_cursor);
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_page_default_last_page_has_no_cursor() {
+        let mut storer = MockDataStorer::new();
+
+        storer.expect_get_collection()
+            .times(1)
+            .returning(|_, _, _, _| Ok(DataCollection::new(vec![])));
+
+        let page = storer.get_collection_page(".path.", None, 10).await.unwrap();
+        assert_eq!(None, page.next_cursor);
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_page_default_invalid_cursor() {
+        let storer = MockDataStorer::new();
+
+        let result = storer
+            .get_collection_page(".path.", Some(Cursor::new("abc")), 10)
+            .await;
+        assert!(matches!(result, Err(DataStorerError::InvalidCursor)));
+    }
+
+    #[tokio::test]
+    async fn test_iter_collection_fetches_all_pages() {
+        let mut storer = MockDataStorer::new();
+
+        storer.expect_get_collection()
+            .times(3)
+            .returning(|_, skip, page_size, _| {
+                let count = if skip < 2 * page_size { page_size } else { 5 };
+                Ok(DataCollection::new(
+                    (0..count)
+                        .map(|i| Data::new(&format!(".path.{}.", skip + i), DataValue::from(1u64)))
+                        .collect(),
+                ))
+            });
+
+        let paths: Vec<String> = storer
+            .iter_collection(".path.")
+            .map(|d| d.unwrap().path())
+            .collect()
+            .await;
+        assert_eq!(205, paths.len());
+        assert_eq!(".path.204.", paths[204]);
+    }
+
+    #[tokio::test]
+    async fn test_iter_collection_stops_after_error() {
+        let mut storer = MockDataStorer::new();
+
+        storer.expect_get_collection()
+            .times(1)
+            .returning(|_, _, _, _| Err(DataStorerError::InvalidCursor));
+
+        let results: Vec<_> = storer.iter_collection(".path.").collect().await;
+        assert_eq!(1, results.len());
+        assert!(results[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_erase_default_deletes_every_entry_under_prefix() {
+        let (storer, entries) = memory_storer();
+        for path in &[".users.1.", ".users.2.", ".other."] {
+            storer.create(Data::new(path, DataValue::from(1u64))).await.unwrap();
+        }
+
+        let report = storer.erase(".users.").await.unwrap();
+        assert_eq!(vec![".users.1.", ".users.2."], report.erased);
+        assert_eq!(vec![".other."], entries.lock().unwrap().keys().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_cache_aside() {
+        let (storer, _) = memory_storer();
+        let mut cacher = MockDataCacher::new();
+
+        cacher.expect_get_default_key_expiration_seconds()
+            .returning(|| 60);
+        cacher.expect_exists()
+            .returning(|_| Ok(false));
+        cacher.expect_delete()
+            .times(1)
+            .withf(|path: &str| path == ".path.")
+            .returning(|_| Ok(true));
+        cacher.expect_set()
+            .times(1)
+            .withf(|path: &str, _: &Data| path == ".path.")
+            .returning(|_, _| Ok(()));
+
+        let cached_storer = CachedDataStorer::new(storer, cacher)
+            .with_cache_mode(CacheMode::CacheAside);
+        let data = Data::new(".path.", DataValue::from(1u64));
+        assert!(cached_storer.create(data.clone()).await.unwrap());
+        assert_eq!(data, cached_storer.get(".path.").await.unwrap());
+        assert_eq!(data, cached_storer.populate(".path.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_coalesces_writes() {
+        let mut storer = MockDataStorer::new();
+        let mut cacher = MockDataCacher::new();
+
+        storer.expect_create()
+            .times(1)
+            .withf(|d: &Data| d == &Data::new(".path.", DataValue::from(3u64)))
+            .returning(|_| Ok(true));
+        cacher.expect_set()
+            .times(1)
+            .withf(|path: &str, d: &Data| path == ".path." && d == &Data::new(".path.", DataValue::from(3u64)))
+            .returning(|_, _| Ok(()));
+
+        let cached_storer = CachedDataStorer::new(storer, cacher)
+            .with_write_coalescing(std::time::Duration::from_millis(20));
+        let write = |n: u64| cached_storer.create(Data::new(".path.", DataValue::from(n)));
+        let (first, second, third, read) = tokio::join!(write(1), write(2), write(3), async {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            cached_storer.get(".path.").await
+        });
+        assert!(first.unwrap() && second.unwrap() && third.unwrap());
+        assert_eq!(Data::new(".path.", DataValue::from(3u64)), read.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_coalesced_writes_share_the_outcome() {
+        let mut storer = MockDataStorer::new();
+        storer.expect_create()
+            .times(1)
+            .returning(|_| Err(DataStorerError::StorageError { source: StorageError::NotFound }));
+
+        let cached_storer = CachedDataStorer::new(storer, MockDataCacher::new())
+            .with_write_coalescing(std::time::Duration::from_millis(20));
+        let write = |n: u64| cached_storer.create(Data::new(".path.", DataValue::from(n)));
+        let (first, second, third) = tokio::join!(write(1), write(2), write(3));
+        assert!(first.is_err() && second.is_err() && third.is_err());
+        assert_eq!(2, cached_storer.metrics().counter("writes_coalesced"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_flushes_pending_writes_before_counters() {
+        let storer = crate::MemoryDataStorer::new();
+        let mut cacher = MockDataCacher::new();
+        cacher.expect_set().returning(|_, _| Ok(()));
+        cacher.expect_delete().returning(|_| Ok(true));
+
+        let cached_storer = CachedDataStorer::new(storer.clone(), cacher)
+            .with_write_coalescing(std::time::Duration::from_millis(20));
+        let (created, incremented) = tokio::join!(
+            cached_storer.create(Data::new(".n.", DataValue::from(5i64))),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                cached_storer.increment(".n.", 1).await
+            }
+        );
+        assert!(created.unwrap());
+        assert_eq!(6, incremented.unwrap());
+        assert_eq!(Ok(6i64), storer.get(".n.").await.unwrap().value_as::<i64>());
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_coalesces_create_many_at_once() {
+        let (storer, entries) = memory_storer();
+        let mut cacher = MockDataCacher::new();
+        cacher.expect_set().times(2).returning(|_, _| Ok(()));
+
+        let cached_storer = CachedDataStorer::new(storer, cacher)
+            .with_write_coalescing(std::time::Duration::from_millis(20));
+        let created = cached_storer.create_many(vec![
+            Data::new(".a.", DataValue::from(1u64)),
+            Data::new(".a.", DataValue::from(2u64)),
+            Data::new(".b.", DataValue::from(1u64)),
+        ]).await.unwrap();
+        assert_eq!(3, created);
+        assert_eq!(1, cached_storer.metrics().counter("writes_coalesced"));
+        assert_eq!(Some(&Data::new(".a.", DataValue::from(2u64))), entries.lock().unwrap().get(".a."));
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_create_many_without_coalescing() {
+        let (storer, entries) = memory_storer();
+        let mut cacher = MockDataCacher::new();
+        cacher.expect_delete().times(2).returning(|_| Ok(true));
+
+        let cached_storer = CachedDataStorer::new(storer, cacher)
+            .with_cache_mode(CacheMode::CacheAside);
+        let created = cached_storer.create_many(vec![
+            Data::new(".a.", DataValue::from(1u64)),
+            Data::new(".b.", DataValue::from(1u64)),
+        ]).await.unwrap();
+        assert_eq!(2, created);
+        assert_eq!(0, cached_storer.metrics().counter("writes_coalesced"));
+        assert_eq!(2, entries.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_cached_data_storer_erase_purges_cache() {
+        let (storer, _) = memory_storer();
+        let mut cacher = MockDataCacher::new();
+
+        storer.create(Data::new(".users.1.", DataValue::from(1u64))).await.unwrap();
+        cacher.expect_delete_prefix()
+            .times(1)
+            .withf(|prefix: &str| prefix == ".users.")
+            .returning(|_| Ok(3));
+
+        let cached_storer = CachedDataStorer::new(storer, cacher);
+        let report = cached_storer.erase(".users.").await.unwrap();
+        assert_eq!(1, report.erased_count());
+        assert_eq!(3, report.cache_entries_purged);
     }
 }
@@ -0,0 +1,119 @@
+use crate::{Data, DataValue};
+use serde::{Deserialize, Serialize};
+
+/// The numeric aggregations that can be computed over the unencrypted numeric
+/// values stored under a path.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+/// Folds numeric values into the result of an `Aggregation`. Used by storers
+/// which cannot compute aggregations server-side.
+#[derive(Debug, Clone)]
+pub struct Aggregator {
+    aggregation: Aggregation,
+    count: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Aggregator {
+    /// Creates an empty aggregator for the given aggregation
+    pub fn new(aggregation: Aggregation) -> Self {
+        Aggregator {
+            aggregation,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Adds a single number to the aggregation
+    pub fn add(&mut self, n: f64) {
+        self.count += 1;
+        self.sum += n;
+        self.min = Some(self.min.map_or(n, |min| min.min(n)));
+        self.max = Some(self.max.map_or(n, |max| max.max(n)));
+    }
+
+    /// Adds every unencrypted numeric value held by the data to the aggregation;
+    /// all other values are ignored.
+    pub fn add_data(&mut self, data: &Data) {
+        data.value()
+            .0
+            .iter()
+            .filter_map(DataValue::as_f64)
+            .for_each(|n| self.add(n));
+    }
+
+    /// Returns the result of the aggregation. Sums and counts over no values
+    /// are zero, while averages, minimums, and maximums over no values are `None`.
+    pub fn finish(&self) -> Option<f64> {
+        match self.aggregation {
+            Aggregation::Sum => Some(self.sum),
+            Aggregation::Count => Some(self.count as f64),
+            Aggregation::Avg if self.count == 0 => None,
+            Aggregation::Avg => Some(self.sum / self.count as f64),
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Aggregation, Aggregator, Data, DataValue};
+
+    fn aggregate(aggregation: Aggregation, data: &[Data]) -> Option<f64> {
+        let mut aggregator = Aggregator::new(aggregation);
+        data.iter().for_each(|d| aggregator.add_data(d));
+        aggregator.finish()
+    }
+
+    fn sample() -> Vec<Data> {
+        vec![
+            Data::new(".a.", DataValue::from(2u64)),
+            Data::new(".b.", DataValue::from(-4i64)),
+            Data::new(".c.", DataValue::from(8.5f64)),
+            Data::new(".d.", DataValue::from("ignored")),
+            Data::new(".e.", DataValue::from(true)),
+        ]
+    }
+
+    #[test]
+    fn test_sum() {
+        assert_eq!(Some(6.5), aggregate(Aggregation::Sum, &sample()));
+    }
+
+    #[test]
+    fn test_avg() {
+        assert_eq!(Some(6.5 / 3.0), aggregate(Aggregation::Avg, &sample()));
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(Some(-4.0), aggregate(Aggregation::Min, &sample()));
+        assert_eq!(Some(8.5), aggregate(Aggregation::Max, &sample()));
+    }
+
+    #[test]
+    fn test_count_ignores_non_numeric_values() {
+        assert_eq!(Some(3.0), aggregate(Aggregation::Count, &sample()));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(Some(0.0), aggregate(Aggregation::Sum, &[]));
+        assert_eq!(Some(0.0), aggregate(Aggregation::Count, &[]));
+        assert_eq!(None, aggregate(Aggregation::Avg, &[]));
+        assert_eq!(None, aggregate(Aggregation::Min, &[]));
+        assert_eq!(None, aggregate(Aggregation::Max, &[]));
+    }
+}
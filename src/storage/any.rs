@@ -0,0 +1,244 @@
+use crate::convert::MergeStrategy;
+use crate::patch::DataPatch;
+use crate::storage::aggregation::Aggregation;
+use crate::storage::erasure::EraseReport;
+use crate::storage::links::LinkDirection;
+use crate::storage::stats::DataStats;
+use crate::{
+    AtlasDataApiStorer, BoxedDataStorer, Cursor, Data, DataCollection, DataFilter, DataStorer,
+    DataStorerError, DataValue, EncryptedFileDataStorer, FileDataStorer, MemoryDataStorer,
+    MongoDataStorer, OpContext, RedactDataStorer, Sort,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+
+/// Any of the storers of this crate, for selecting the backend at runtime
+/// while keeping calls to the built-in backends statically dispatched. Other
+/// storers can be held as a `BoxedDataStorer`, at the cost of a dynamic call.
+#[derive(Clone)]
+pub enum AnyDataStorer {
+    Memory(MemoryDataStorer),
+    File(Box<FileDataStorer>),
+    EncryptedFile(Box<EncryptedFileDataStorer>),
+    Mongodb(MongoDataStorer),
+    Redact(RedactDataStorer),
+    Atlas(AtlasDataApiStorer),
+    Boxed(BoxedDataStorer),
+}
+
+impl From<MemoryDataStorer> for AnyDataStorer {
+    fn from(storer: MemoryDataStorer) -> Self {
+        AnyDataStorer::Memory(storer)
+    }
+}
+
+impl From<FileDataStorer> for AnyDataStorer {
+    fn from(storer: FileDataStorer) -> Self {
+        AnyDataStorer::File(Box::new(storer))
+    }
+}
+
+impl From<EncryptedFileDataStorer> for AnyDataStorer {
+    fn from(storer: EncryptedFileDataStorer) -> Self {
+        AnyDataStorer::EncryptedFile(Box::new(storer))
+    }
+}
+
+impl From<MongoDataStorer> for AnyDataStorer {
+    fn from(storer: MongoDataStorer) -> Self {
+        AnyDataStorer::Mongodb(storer)
+    }
+}
+
+impl From<RedactDataStorer> for AnyDataStorer {
+    fn from(storer: RedactDataStorer) -> Self {
+        AnyDataStorer::Redact(storer)
+    }
+}
+
+impl From<AtlasDataApiStorer> for AnyDataStorer {
+    fn from(storer: AtlasDataApiStorer) -> Self {
+        AnyDataStorer::Atlas(storer)
+    }
+}
+
+impl From<BoxedDataStorer> for AnyDataStorer {
+    fn from(storer: BoxedDataStorer) -> Self {
+        AnyDataStorer::Boxed(storer)
+    }
+}
+
+/// Calls the same method on whichever storer is held
+macro_rules! dispatch {
+    ($self:ident, $inner:ident => $call:expr) => {
+        match $self {
+            AnyDataStorer::Memory($inner) => $call,
+            AnyDataStorer::File($inner) => $call,
+            AnyDataStorer::EncryptedFile($inner) => $call,
+            AnyDataStorer::Mongodb($inner) => $call,
+            AnyDataStorer::Redact($inner) => $call,
+            AnyDataStorer::Atlas($inner) => $call,
+            AnyDataStorer::Boxed($inner) => $call,
+        }
+    };
+}
+
+#[async_trait]
+impl DataStorer for AnyDataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        dispatch!(self, s => s.get(path).await)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        dispatch!(self, s => s.get_collection(path, skip, page_size, sort).await)
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        dispatch!(self, s => {
+            s.get_filtered_collection(path, filter, skip, page_size, sort).await
+        })
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        dispatch!(self, s => s.get_collection_page(path, cursor, page_size).await)
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        dispatch!(self, s => s.create(data).await)
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        dispatch!(self, s => s.create_many(data).await)
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        dispatch!(self, s => s.get_or_create(path, default).await)
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        dispatch!(self, s => s.increment(path, delta).await)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        dispatch!(self, s => s.compare_and_swap(path, expected, new).await)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        dispatch!(self, s => s.delete(path).await)
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        dispatch!(self, s => s.erase(prefix).await)
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        dispatch!(self, s => s.delete_collection(path, filter).await)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        dispatch!(self, s => s.aggregate(path, aggregation).await)
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        dispatch!(self, s => s.stats(prefix).await)
+    }
+
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError> {
+        dispatch!(self, s => s.get_linked(path, link_type).await)
+    }
+
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        dispatch!(self, s => s.traverse(start, link_type, direction, depth).await)
+    }
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        dispatch!(self, s => s.apply_patch(prefix, patch).await)
+    }
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        dispatch!(self, s => s.put_subtree(prefix, value, strategy).await)
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        match self {
+            AnyDataStorer::Memory(s) => AnyDataStorer::Memory(s.with_context(context)),
+            AnyDataStorer::File(s) => AnyDataStorer::File(Box::new(s.with_context(context))),
+            AnyDataStorer::EncryptedFile(s) => {
+                AnyDataStorer::EncryptedFile(Box::new(s.with_context(context)))
+            }
+            AnyDataStorer::Mongodb(s) => AnyDataStorer::Mongodb(s.with_context(context)),
+            AnyDataStorer::Redact(s) => AnyDataStorer::Redact(s.with_context(context)),
+            AnyDataStorer::Atlas(s) => AnyDataStorer::Atlas(s.with_context(context)),
+            AnyDataStorer::Boxed(s) => AnyDataStorer::Boxed(s.with_context(context)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnyDataStorer;
+    use crate::storage::tests::memory_storer;
+    use crate::{BoxedDataStorer, Data, DataStorer, DataValue, MemoryDataStorer, OpContext};
+
+    #[tokio::test]
+    async fn test_dispatches_to_held_storer() {
+        let storer = AnyDataStorer::from(MemoryDataStorer::new());
+        let data = Data::new(".a.", DataValue::from(1u64));
+        assert!(storer.create(data.clone()).await.unwrap());
+        assert_eq!(data, storer.get(".a.").await.unwrap());
+        let storer = storer.with_context(OpContext::new());
+        assert!(matches!(storer, AnyDataStorer::Memory(_)));
+        assert_eq!(2, storer.increment(".n.", 2).await.unwrap());
+
+        let (mock, entries) = memory_storer();
+        let storer = AnyDataStorer::from(BoxedDataStorer::new(mock));
+        assert!(storer.create(data).await.unwrap());
+        assert_eq!(1, entries.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::storage::mongodb::escape_regex;
+use crate::storage::redact::retry_after;
+use crate::{
+    Data, DataCollection, DataFilter, DataStorer, DataStorerError, MongoDataStorer, OpContext,
+    PageSizeLimits, RedactClientConfig, Sort, SortBy, SortOrder, StorageError,
+};
+use async_trait::async_trait;
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::time::SystemTime;
+
+/// Stores data in a MongoDB Atlas collection through the Atlas Data API,
+/// which is called over HTTPS rather than the wire protocol, for runtimes
+/// such as serverless functions and edge workers which cannot open raw TCP
+/// connections. Documents are laid out as `MongoDataStorer` lays them out,
+/// without GridFS, so both can share a collection.
+#[derive(Clone)]
+pub struct AtlasDataApiStorer {
+    url: String,
+    api_key: String,
+    data_source: String,
+    database: String,
+    collection_name: String,
+    client: reqwest::Client,
+    page_size_limits: PageSizeLimits,
+    context: OpContext,
+}
+
+#[derive(Deserialize)]
+struct FindOneResponse {
+    document: Option<Data>,
+}
+
+#[derive(Deserialize)]
+struct FindResponse {
+    documents: Vec<Data>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteResponse {
+    deleted_count: u64,
+}
+
+impl AtlasDataApiStorer {
+    /// Instantiates a storer calling the Data API at the given base URL, e.g.
+    /// `https://data.mongodb-api.com/app/<app id>/endpoint/data/v1`, with an
+    /// API key, on a database of the named cluster
+    pub fn new(url: &str, api_key: &str, data_source: &str, database: &str) -> Self {
+        AtlasDataApiStorer {
+            url: url.trim_end_matches('/').to_owned(),
+            api_key: api_key.to_owned(),
+            data_source: data_source.to_owned(),
+            database: database.to_owned(),
+            collection_name: "data".to_owned(),
+            client: reqwest::Client::new(),
+            page_size_limits: PageSizeLimits::default(),
+            context: OpContext::default(),
+        }
+    }
+
+    /// Stores data in the named collection rather than the default `data` collection
+    pub fn with_collection_name(mut self, collection_name: &str) -> Self {
+        self.collection_name = collection_name.to_owned();
+        self
+    }
+
+    /// Replaces the default page size limits applied to collection queries
+    pub fn with_page_size_limits(mut self, page_size_limits: PageSizeLimits) -> Self {
+        self.page_size_limits = page_size_limits;
+        self
+    }
+
+    /// Replaces the HTTP client with one built with the given settings
+    pub fn with_client_config(
+        mut self,
+        config: &RedactClientConfig,
+    ) -> Result<Self, DataStorerError> {
+        self.client = config.build().map_err(internal_error)?;
+        Ok(self)
+    }
+
+    /// Builds the body of an action on the storer's collection
+    fn body(&self, mut fields: Value) -> Value {
+        let body = fields.as_object_mut().expect("action fields are an object");
+        body.insert("dataSource".to_owned(), json!(self.data_source));
+        body.insert("database".to_owned(), json!(self.database));
+        body.insert("collection".to_owned(), json!(self.collection_name));
+        fields
+    }
+
+    /// Calls an action of the Data API, timing out at the context's deadline,
+    /// and decodes its response
+    async fn call<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        fields: Value,
+    ) -> Result<T, DataStorerError> {
+        let mut request = self
+            .client
+            .post(format!("{}/action/{}", self.url, action))
+            .header("api-key", self.api_key.as_str())
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&self.body(fields)).map_err(internal_error)?);
+        if let Some(remaining) = self.context.remaining() {
+            if remaining.is_zero() {
+                return Err(DataStorerError::DeadlineExceeded);
+            }
+            request = request.timeout(remaining);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() && self.context.deadline().is_some() {
+                DataStorerError::DeadlineExceeded
+            } else if e.is_connect() {
+                DataStorerError::Unavailable {
+                    reason: e.to_string(),
+                }
+            } else {
+                internal_error(e)
+            }
+        })?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(DataStorerError::RateLimited {
+                retry_after: retry_after(response.headers(), SystemTime::now()),
+            });
+        }
+        let response = response.error_for_status().map_err(internal_error)?;
+        response.json::<T>().await.map_err(internal_error)
+    }
+}
+
+/// Builds a filter matching every document whose path begins with the given path
+fn prefix_filter(path: &str) -> Value {
+    json!({ "path": { "$regex": format!("^{}", escape_regex(path)) } })
+}
+
+/// Builds a filter matching every document under the given path holding a
+/// value which matches a filter, with the condition `MongoDataStorer` uses
+fn filtered(path: &str, filter: &DataFilter) -> Result<Value, DataStorerError> {
+    let mut query = prefix_filter(path);
+    if let Some(value_filter) = MongoDataStorer::value_filter(filter) {
+        query["value"] = json!({
+            "$elemMatch": serde_json::to_value(&value_filter).map_err(internal_error)?
+        });
+    }
+    Ok(query)
+}
+
+/// Converts a sort specification into a sort document, as `MongoDataStorer`
+/// does
+fn sort_document(sort: Sort) -> Value {
+    let field = match sort.by {
+        SortBy::Path => "path",
+        SortBy::CreatedAt => "_id",
+    };
+    let direction = match sort.order {
+        SortOrder::Ascending => 1,
+        SortOrder::Descending => -1,
+    };
+    json!({ field: direction })
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
+}
+
+#[async_trait]
+impl DataStorer for AtlasDataApiStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let response: FindOneResponse = self
+            .call(
+                "findOne",
+                json!({ "filter": { "path": path }, "projection": { "_id": 0 } }),
+            )
+            .await?;
+        response.document.ok_or(DataStorerError::StorageError {
+            source: StorageError::NotFound,
+        })
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut fields = json!({
+            "filter": prefix_filter(path),
+            "projection": { "_id": 0 },
+            "skip": skip,
+            "limit": self.page_size_limits.resolve(page_size)?,
+        });
+        if let Some(sort) = sort {
+            fields["sort"] = sort_document(sort);
+        }
+        let response: FindResponse = self.call("find", fields).await?;
+        Ok(DataCollection::new(response.documents))
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut fields = json!({
+            "filter": filtered(path, filter)?,
+            "projection": { "_id": 0 },
+            "skip": skip,
+            "limit": self.page_size_limits.resolve(page_size)?,
+        });
+        if let Some(sort) = sort {
+            fields["sort"] = sort_document(sort);
+        }
+        let response: FindResponse = self.call("find", fields).await?;
+        Ok(DataCollection::new(response.documents))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let replacement = serde_json::to_value(&data).map_err(internal_error)?;
+        self.call::<Value>(
+            "replaceOne",
+            json!({
+                "filter": { "path": data.path() },
+                "replacement": replacement,
+                "upsert": true,
+            }),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let response: DeleteResponse = self
+            .call("deleteOne", json!({ "filter": { "path": path } }))
+            .await?;
+        Ok(response.deleted_count > 0)
+    }
+
+    /// Deletes every matching document with a single `deleteMany`
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        let filter = match filter {
+            Some(filter) => filtered(path, filter)?,
+            None => prefix_filter(path),
+        };
+        let response: DeleteResponse = self.call("deleteMany", json!({ "filter": filter })).await?;
+        Ok(response.deleted_count)
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        AtlasDataApiStorer {
+            context,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::filtered;
+    use super::{prefix_filter, sort_document, AtlasDataApiStorer};
+    use crate::{
+        DataFilter, DataStorer, DataStorerError, EncryptionFilter, OpContext, Sort, SortBy,
+        SortOrder,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn storer() -> AtlasDataApiStorer {
+        AtlasDataApiStorer::new("http://localhost:1/", "key", "Cluster0", "redact")
+            .with_collection_name("records")
+    }
+
+    #[test]
+    fn test_body_names_the_collection() {
+        assert_eq!(
+            json!({
+                "filter": prefix_filter(".a.b."),
+                "dataSource": "Cluster0",
+                "database": "redact",
+                "collection": "records",
+            }),
+            storer().body(json!({ "filter": prefix_filter(".a.b.") }))
+        );
+        assert_eq!(
+            json!({ "path": { "$regex": "^\\.a\\.b\\." } }),
+            prefix_filter(".a.b.")
+        );
+        let filter =
+            DataFilter::new().with_encryption(EncryptionFilter::EncryptedBy("k1".to_owned()));
+        assert_eq!(
+            json!({
+                "path": { "$regex": "^\\.a\\." },
+                "value": { "$elemMatch": {
+                    "Encrypted": { "$exists": true },
+                    "Encrypted.keyname": "k1",
+                } },
+            }),
+            filtered(".a.", &filter).unwrap()
+        );
+        assert_eq!(
+            json!({ "_id": -1 }),
+            sort_document(Sort::new(SortBy::CreatedAt, SortOrder::Descending))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_deadline_fails_before_calling() {
+        let storer = storer().with_context(OpContext::new().with_timeout(Duration::ZERO));
+        assert!(matches!(
+            storer.get(".a.").await,
+            Err(DataStorerError::DeadlineExceeded)
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{Data, DataStorer, DataStorerError, DataValue, StorageError, UnencryptedDataValue};
+
+/// Returns the integer a counter holds, or `None` if the data is not a counter:
+/// a single unencrypted integer value which fits in an `i64`
+pub(crate) fn counter_value(data: &Data) -> Option<i64> {
+    match data.value().0.as_slice() {
+        [DataValue::Unencrypted(UnencryptedDataValue::I64(n))] => Some(*n),
+        [DataValue::Unencrypted(UnencryptedDataValue::U64(n))] if *n <= i64::MAX as u64 => {
+            Some(*n as i64)
+        }
+        _ => None,
+    }
+}
+
+/// Increments the counter at a path by reading it and writing the result back.
+/// A missing counter starts at zero. Concurrent increments may be lost.
+pub(crate) async fn increment<S: DataStorer>(
+    storer: &S,
+    path: &str,
+    delta: i64,
+) -> Result<i64, DataStorerError> {
+    let current = match storer.get(path).await {
+        Ok(data) => counter_value(&data).ok_or_else(|| DataStorerError::InvalidCounter {
+            path: path.to_owned(),
+        })?,
+        Err(DataStorerError::StorageError {
+            source: StorageError::NotFound,
+        }) => 0,
+        Err(e) => return Err(e),
+    };
+    let updated = current
+        .checked_add(delta)
+        .ok_or_else(|| DataStorerError::InvalidCounter {
+            path: path.to_owned(),
+        })?;
+    storer
+        .create(Data::new(path, DataValue::from(updated)))
+        .await?;
+    Ok(updated)
+}
+
+/// Returns the data at a path, or writes the default there if there is none,
+/// by reading it and writing the default back. A concurrent writer may write
+/// its own default in between, which is then overwritten.
+pub(crate) async fn get_or_create<S: DataStorer>(
+    storer: &S,
+    path: &str,
+    mut default: Data,
+) -> Result<Data, DataStorerError> {
+    match storer.get(path).await {
+        Err(DataStorerError::StorageError {
+            source: StorageError::NotFound,
+        }) => {
+            default.set_path(path);
+            storer.create(default.clone()).await?;
+            Ok(default)
+        }
+        found => found,
+    }
+}
+
+/// Replaces the value at a path if it currently holds exactly `expected`, by
+/// reading it and writing the new value back. A concurrent write between the
+/// two may be overwritten.
+pub(crate) async fn compare_and_swap<S: DataStorer>(
+    storer: &S,
+    path: &str,
+    expected: DataValue,
+    new: DataValue,
+) -> Result<bool, DataStorerError> {
+    let current = match storer.get(path).await {
+        Ok(data) => data,
+        Err(DataStorerError::StorageError {
+            source: StorageError::NotFound,
+        }) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if current.value().0.as_slice() != [expected] {
+        return Ok(false);
+    }
+    storer.create(Data::new(path, new)).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataStorerError, DataValue};
+
+    #[tokio::test]
+    async fn test_get_or_create() {
+        let (storer, entries) = memory_storer();
+        let first = Data::new(".a.", DataValue::from(1u64));
+        assert_eq!(
+            first,
+            storer.get_or_create(".a.", first.clone()).await.unwrap()
+        );
+        assert_eq!(
+            first,
+            storer
+                .get_or_create(".a.", Data::new(".a.", DataValue::from(2u64)))
+                .await
+                .unwrap()
+        );
+        assert_eq!(first, entries.lock().unwrap()[".a."]);
+    }
+
+    #[tokio::test]
+    async fn test_increment_starts_at_zero() {
+        let (storer, entries) = memory_storer();
+        assert_eq!(5, storer.increment(".hits.", 5).await.unwrap());
+        assert_eq!(3, storer.increment(".hits.", -2).await.unwrap());
+        assert_eq!(
+            Data::new(".hits.", DataValue::from(3i64)),
+            entries.lock().unwrap()[".hits."]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_increment_unsigned_counter() {
+        let (storer, _) = memory_storer();
+        storer
+            .create(Data::new(".hits.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert_eq!(2, storer.increment(".hits.", 1).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_increment_rejects_non_counters_and_overflow() {
+        let (storer, _) = memory_storer();
+        storer
+            .create(Data::new(".name.", DataValue::from("alice")))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".max.", DataValue::from(i64::MAX)))
+            .await
+            .unwrap();
+
+        for path in &[".name.", ".max."] {
+            assert!(matches!(
+                storer.increment(path, 1).await,
+                Err(DataStorerError::InvalidCounter { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_swap() {
+        let (storer, entries) = memory_storer();
+        storer
+            .create(Data::new(".state.", DataValue::from("pending")))
+            .await
+            .unwrap();
+
+        assert!(!storer
+            .compare_and_swap(
+                ".state.",
+                DataValue::from("done"),
+                DataValue::from("failed")
+            )
+            .await
+            .unwrap());
+        assert!(storer
+            .compare_and_swap(
+                ".state.",
+                DataValue::from("pending"),
+                DataValue::from("done")
+            )
+            .await
+            .unwrap());
+        assert!(!storer
+            .compare_and_swap(
+                ".missing.",
+                DataValue::from("pending"),
+                DataValue::from("done")
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            Data::new(".state.", DataValue::from("done")),
+            entries.lock().unwrap()[".state."]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataPath, DataStats, DataStorer,
+    DataStorerError, DataValue, EraseReport, OpContext, Sort,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// The kinds of operation a storer performs on a path
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Get,
+    GetCollection,
+    Create,
+    Aggregate,
+    Delete,
+    Erase,
+}
+
+impl Operation {
+    /// Returns whether the operation modifies stored data
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Operation::Create | Operation::Delete | Operation::Erase
+        )
+    }
+}
+
+/// The outcome of an authorization check
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny,
+}
+
+/// Decides whether a principal may perform an operation on a path. The
+/// principal is `None` when the operation's context does not name one.
+#[async_trait]
+pub trait Authorizer: Clone + Send + Sync {
+    async fn allow(&self, principal: Option<&str>, op: Operation, path: &str) -> Decision;
+}
+
+/// A rule granting a principal access to every path under a prefix
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct PrefixRule {
+    principal: Option<String>,
+    prefix: String,
+    write: bool,
+}
+
+/// Authorizes operations against an allow-list of path prefixes. Anything not
+/// explicitly allowed is denied; write access to a prefix implies read access.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct PrefixAuthorizer {
+    rules: Vec<PrefixRule>,
+}
+
+impl PrefixAuthorizer {
+    /// Builds an authorizer denying every operation
+    pub fn new() -> Self {
+        PrefixAuthorizer::default()
+    }
+
+    /// Allows the principal, or any principal if `None`, to read under the prefix
+    pub fn allow_read(self, principal: Option<&str>, prefix: &str) -> Self {
+        self.rule(principal, prefix, false)
+    }
+
+    /// Allows the principal, or any principal if `None`, to read and write
+    /// under the prefix
+    pub fn allow_write(self, principal: Option<&str>, prefix: &str) -> Self {
+        self.rule(principal, prefix, true)
+    }
+
+    fn rule(mut self, principal: Option<&str>, prefix: &str, write: bool) -> Self {
+        self.rules.push(PrefixRule {
+            principal: principal.map(str::to_owned),
+            prefix: DataPath::from(prefix).to_string(),
+            write,
+        });
+        self
+    }
+}
+
+#[async_trait]
+impl Authorizer for PrefixAuthorizer {
+    async fn allow(&self, principal: Option<&str>, op: Operation, path: &str) -> Decision {
+        let path = DataPath::from(path).to_string();
+        let allowed = self.rules.iter().any(|rule| {
+            (rule.principal.is_none() || rule.principal.as_deref() == principal)
+                && (rule.write || !op.is_write())
+                && path.starts_with(&rule.prefix)
+        });
+        if allowed {
+            Decision::Allow
+        } else {
+            Decision::Deny
+        }
+    }
+}
+
+/// Wraps a storer to check every operation with an `Authorizer`, using the
+/// principal of the storer's `OpContext`, and fail denied operations with
+/// `DataStorerError::Forbidden` before they reach the wrapped storer.
+#[derive(Clone)]
+pub struct AuthorizedDataStorer<S: DataStorer, A: Authorizer> {
+    storer: S,
+    authorizer: A,
+    context: OpContext,
+}
+
+impl<S: DataStorer, A: Authorizer> AuthorizedDataStorer<S, A> {
+    /// Instantiates an authorizing storer around an existing storer
+    pub fn new(storer: S, authorizer: A) -> Self {
+        AuthorizedDataStorer {
+            storer,
+            authorizer,
+            context: OpContext::default(),
+        }
+    }
+
+    async fn authorize(&self, op: Operation, path: &str) -> Result<(), DataStorerError> {
+        let principal = self.context.principal();
+        match self.authorizer.allow(principal, op, path).await {
+            Decision::Allow => Ok(()),
+            Decision::Deny => Err(DataStorerError::Forbidden {
+                principal: principal.map(str::to_owned),
+                path: path.to_owned(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer, A: Authorizer> DataStorer for AuthorizedDataStorer<S, A> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.authorize(Operation::Get, path).await?;
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.authorize(Operation::GetCollection, path).await?;
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.authorize(Operation::GetCollection, path).await?;
+        self.storer
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.authorize(Operation::GetCollection, path).await?;
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.authorize(Operation::Create, &data.path()).await?;
+        self.storer.create(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.authorize(Operation::Get, path).await?;
+        self.authorize(Operation::Create, path).await?;
+        self.storer.get_or_create(path, default).await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.authorize(Operation::Create, path).await?;
+        self.storer.increment(path, delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.authorize(Operation::Create, path).await?;
+        self.storer.compare_and_swap(path, expected, new).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.authorize(Operation::Delete, path).await?;
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.authorize(Operation::Erase, prefix).await?;
+        self.storer.erase(prefix).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.authorize(Operation::Aggregate, path).await?;
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.authorize(Operation::GetCollection, prefix).await?;
+        self.storer.stats(prefix).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        AuthorizedDataStorer {
+            storer: self.storer.with_context(context.clone()),
+            authorizer: self.authorizer.clone(),
+            context,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        AuthorizedDataStorer, Authorizer, Data, DataStorer, DataStorerError, DataValue, Decision,
+        OpContext, Operation, PrefixAuthorizer,
+    };
+
+    #[tokio::test]
+    async fn test_prefix_authorizer() {
+        let authorizer = PrefixAuthorizer::new()
+            .allow_read(None, ".public.")
+            .allow_write(Some("alice"), ".users.alice.");
+
+        let cases = vec![
+            (None, Operation::Get, ".public.a.", Decision::Allow),
+            (None, Operation::Create, ".public.a.", Decision::Deny),
+            (
+                Some("alice"),
+                Operation::Create,
+                ".users.alice.a.",
+                Decision::Allow,
+            ),
+            (
+                Some("alice"),
+                Operation::Get,
+                ".users.alice.a.",
+                Decision::Allow,
+            ),
+            (
+                Some("bob"),
+                Operation::Get,
+                ".users.alice.a.",
+                Decision::Deny,
+            ),
+            (
+                Some("alice"),
+                Operation::GetCollection,
+                ".users.",
+                Decision::Deny,
+            ),
+        ];
+        for (principal, op, path, expected) in cases {
+            assert_eq!(
+                expected,
+                authorizer.allow(principal, op, path).await,
+                "{:?} {:?} {}",
+                principal,
+                op,
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_denied_operations_are_forbidden() {
+        let (mut inner, _) = memory_storer();
+        inner.checkpoint();
+        inner.expect_create().times(0);
+        let storer = AuthorizedDataStorer::new(
+            inner,
+            PrefixAuthorizer::new().allow_write(Some("alice"), ".users.alice."),
+        );
+
+        let result = storer
+            .create(Data::new(".users.bob.", DataValue::from(1u64)))
+            .await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::Forbidden { principal: None, ref path }) if path == ".users.bob."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_operations_use_context_principal() {
+        let (inner, entries) = memory_storer();
+        let storer = AuthorizedDataStorer {
+            storer: inner,
+            authorizer: PrefixAuthorizer::new().allow_write(Some("alice"), ".users.alice."),
+            context: OpContext::new().with_principal("alice"),
+        };
+
+        storer
+            .create(Data::new(".users.alice.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert!(entries.lock().unwrap().contains_key(".users.alice."));
+    }
+}
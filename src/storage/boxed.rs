@@ -0,0 +1,399 @@
+use crate::convert::MergeStrategy;
+use crate::patch::DataPatch;
+use crate::storage::aggregation::Aggregation;
+use crate::storage::erasure::EraseReport;
+use crate::storage::links::LinkDirection;
+use crate::storage::stats::DataStats;
+use crate::{
+    Cursor, Data, DataCollection, DataFilter, DataStorer, DataStorerError, DataValue, OpContext,
+    Sort,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// The object-safe subset of `DataStorer` which `BoxedDataStorer` dispatches
+/// through, implemented for every storer. Kept private so that its methods
+/// never clash with those of `DataStorer` in callers' scope.
+#[async_trait]
+trait DynDataStorer: Send + Sync {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError>;
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError>;
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError>;
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError>;
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError>;
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError>;
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError>;
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError>;
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError>;
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError>;
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError>;
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError>;
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError>;
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError>;
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError>;
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError>;
+
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError>;
+
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError>;
+
+    fn with_context(&self, context: OpContext) -> BoxedDataStorer;
+}
+
+#[async_trait]
+impl<S: DataStorer + 'static> DynDataStorer for S {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        DataStorer::get(self, path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        DataStorer::get_collection(self, path, skip, page_size, sort).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        DataStorer::get_collection_page(self, path, cursor, page_size).await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        DataStorer::get_filtered_collection(self, path, filter, skip, page_size, sort).await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        DataStorer::create(self, data).await
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        DataStorer::create_many(self, data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        DataStorer::get_or_create(self, path, default).await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        DataStorer::increment(self, path, delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        DataStorer::compare_and_swap(self, path, expected, new).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        DataStorer::delete(self, path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        DataStorer::erase(self, prefix).await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        DataStorer::delete_collection(self, path, filter).await
+    }
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        DataStorer::apply_patch(self, prefix, patch).await
+    }
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        DataStorer::put_subtree(self, prefix, value, strategy).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        DataStorer::aggregate(self, path, aggregation).await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        DataStorer::stats(self, prefix).await
+    }
+
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError> {
+        DataStorer::get_linked(self, path, link_type).await
+    }
+
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        DataStorer::traverse(self, start, link_type, direction, depth).await
+    }
+
+    fn with_context(&self, context: OpContext) -> BoxedDataStorer {
+        BoxedDataStorer::new(DataStorer::with_context(self, context))
+    }
+}
+
+/// A storer of any type behind a pointer, for selecting the backend at
+/// runtime where generics are impractical. Every call is dynamically
+/// dispatched to the wrapped storer, including the ones it overrides.
+#[derive(Clone)]
+pub struct BoxedDataStorer {
+    storer: Arc<dyn DynDataStorer>,
+}
+
+impl BoxedDataStorer {
+    pub fn new<S: DataStorer + 'static>(storer: S) -> Self {
+        BoxedDataStorer {
+            storer: Arc::new(storer),
+        }
+    }
+}
+
+#[async_trait]
+impl DataStorer for BoxedDataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.storer.create(data).await
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        self.storer.create_many(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.storer.get_or_create(path, default).await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.storer.increment(path, delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.storer.compare_and_swap(path, expected, new).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(prefix).await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        self.storer.delete_collection(path, filter).await
+    }
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        self.storer.apply_patch(prefix, patch).await
+    }
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        self.storer.put_subtree(prefix, value, strategy).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.storer.stats(prefix).await
+    }
+
+    async fn get_linked(
+        &self,
+        path: &str,
+        link_type: &str,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer.get_linked(path, link_type).await
+    }
+
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .traverse(start, link_type, direction, depth)
+            .await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        self.storer.with_context(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoxedDataStorer;
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataValue, MemoryDataStorer, OpContext};
+
+    #[tokio::test]
+    async fn test_dispatches_to_wrapped_storer() {
+        let (storer, entries) = memory_storer();
+        let boxed = BoxedDataStorer::new(storer);
+        let data = Data::new(".a.", DataValue::from(1u64));
+        assert!(boxed.create(data.clone()).await.unwrap());
+        assert_eq!(1, entries.lock().unwrap().len());
+        assert_eq!(data, boxed.get(".a.").await.unwrap());
+
+        let boxed = BoxedDataStorer::new(MemoryDataStorer::new());
+        assert_eq!(3, boxed.increment(".n.", 3).await.unwrap());
+        let contextual = boxed.with_context(OpContext::new());
+        assert_eq!(5, contextual.increment(".n.", 2).await.unwrap());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{Data, DataStorer, DataStorerError};
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+/// When a `BufferedDataWriter` flushes the writes it has buffered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferConfig {
+    /// Flushes as soon as this many writes are buffered
+    pub max_batch_size: usize,
+    /// Flushes whatever is buffered at this interval
+    pub flush_interval: Duration,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        BufferConfig {
+            max_batch_size: 500,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+struct BufferState<S: DataStorer> {
+    storer: S,
+    pending: Mutex<Vec<Data>>,
+    closed: AtomicBool,
+    max_batch_size: usize,
+}
+
+impl<S: DataStorer> BufferState<S> {
+    /// Writes everything buffered with a single `create_many`, putting the
+    /// batch back in front of newer writes should it fail
+    async fn flush(&self) -> Result<u64, DataStorerError> {
+        let batch = mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return Ok(0);
+        }
+        match self.storer.create_many(batch.clone()).await {
+            Ok(created) => Ok(created),
+            Err(e) => {
+                let mut pending = self.pending.lock().unwrap();
+                let newer = mem::replace(&mut *pending, batch);
+                pending.extend(newer);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Buffers writes to a storer and writes them in batches with `create_many`,
+/// once enough are buffered or at a fixed interval, turning many small writes
+/// into few large ones. Buffered writes are not visible to readers of the
+/// storer until flushed, and are lost if the process exits before then, so
+/// call `shutdown` before exiting.
+///
+/// Clones share the same buffer. Failed background flushes are logged and
+/// retried with the next flush.
+#[derive(Clone)]
+pub struct BufferedDataWriter<S: DataStorer> {
+    state: Arc<BufferState<S>>,
+}
+
+impl<S: DataStorer + 'static> BufferedDataWriter<S> {
+    /// Instantiates a writer buffering writes to an existing storer, flushing
+    /// them in the background at the configured interval until every clone of
+    /// the writer has been dropped
+    pub fn new(storer: S, config: BufferConfig) -> Self {
+        let state = Arc::new(BufferState {
+            storer,
+            pending: Mutex::new(Vec::new()),
+            closed: AtomicBool::new(false),
+            max_batch_size: config.max_batch_size.max(1),
+        });
+        tokio::spawn(flush_periodically(
+            Arc::downgrade(&state),
+            config.flush_interval,
+        ));
+        BufferedDataWriter { state }
+    }
+}
+
+impl<S: DataStorer> BufferedDataWriter<S> {
+    /// Buffers a write, flushing the buffer if it is full. Only fails if the
+    /// writer has been shut down or the flush failed, in which case the write
+    /// stays buffered.
+    pub async fn create(&self, data: Data) -> Result<(), DataStorerError> {
+        if self.state.closed.load(Ordering::SeqCst) {
+            return Err(DataStorerError::Unavailable {
+                reason: "the buffered writer has been shut down".to_owned(),
+            });
+        }
+        let full = {
+            let mut pending = self.state.pending.lock().unwrap();
+            pending.push(data);
+            pending.len() >= self.state.max_batch_size
+        };
+        if full {
+            self.state.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Returns how many writes are buffered
+    pub fn pending(&self) -> usize {
+        self.state.pending.lock().unwrap().len()
+    }
+
+    /// Writes everything buffered now, returning how many entries were written
+    pub async fn flush(&self) -> Result<u64, DataStorerError> {
+        self.state.flush().await
+    }
+
+    /// Stops accepting writes and flushes those already buffered
+    pub async fn shutdown(&self) -> Result<u64, DataStorerError> {
+        self.state.closed.store(true, Ordering::SeqCst);
+        self.state.flush().await
+    }
+}
+
+async fn flush_periodically<S: DataStorer>(state: Weak<BufferState<S>>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if state.closed.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = state.flush().await {
+            tracing::warn!(error = %e, "failed to flush buffered writes");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferConfig, BufferedDataWriter};
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorerError, DataValue};
+    use std::time::Duration;
+
+    fn data(path: &str) -> Data {
+        Data::new(path, DataValue::from(1u64))
+    }
+
+    #[tokio::test]
+    async fn test_flushes_full_batches() {
+        let (storer, entries) = memory_storer();
+        let writer = BufferedDataWriter::new(
+            storer,
+            BufferConfig {
+                max_batch_size: 2,
+                flush_interval: Duration::from_secs(60),
+            },
+        );
+        writer.create(data(".a.")).await.unwrap();
+        assert_eq!(1, writer.pending());
+        assert!(entries.lock().unwrap().is_empty());
+        writer.create(data(".b.")).await.unwrap();
+        assert_eq!(0, writer.pending());
+        assert_eq!(2, entries.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_flushes_periodically() {
+        let (storer, entries) = memory_storer();
+        let writer = BufferedDataWriter::new(
+            storer,
+            BufferConfig {
+                max_batch_size: 100,
+                flush_interval: Duration::from_millis(10),
+            },
+        );
+        writer.create(data(".a.")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(0, writer.pending());
+        assert!(entries.lock().unwrap().contains_key(".a."));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_and_rejects_writes() {
+        let (storer, entries) = memory_storer();
+        let writer = BufferedDataWriter::new(storer, BufferConfig::default());
+        writer.create(data(".a.")).await.unwrap();
+        writer.create(data(".b.")).await.unwrap();
+        assert_eq!(2, writer.shutdown().await.unwrap());
+        assert_eq!(2, entries.lock().unwrap().len());
+        assert!(matches!(
+            writer.create(data(".c.")).await,
+            Err(DataStorerError::Unavailable { .. })
+        ));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataStorer, DataStorerError, DataValue,
+    DataValueCollection, EraseReport, OpContext, Sort, StorageError, UnencryptedDataValue,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// The path segment under which the chunks of an oversized entry are stored
+const CHUNK_SEGMENT: &str = "_chunk";
+
+/// Determines what a `ChunkingDataStorer` does with values whose serialized
+/// size exceeds its maximum.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSizePolicy {
+    /// Fails the write with `DataStorerError::ValueTooLarge`
+    Reject,
+    /// Splits the value across `.path._chunk.N.` entries reassembled on read
+    Chunk,
+}
+
+/// Wraps a storer to bound the serialized size of the values written through it.
+///
+/// When chunking, the serialized values of an oversized entry are split into
+/// strings of at most `max_value_size` bytes stored at `.path._chunk.0.`,
+/// `.path._chunk.1.`, and so on, followed by a terminating chunk holding no
+/// values. The entry at `.path.` itself is then written with no values to mark
+/// it as chunked. Reads stop at the terminator, so leftover chunks from an
+/// earlier, larger write are ignored.
+#[derive(Clone)]
+pub struct ChunkingDataStorer<S: DataStorer> {
+    storer: S,
+    max_value_size: usize,
+    policy: ValueSizePolicy,
+}
+
+impl<S: DataStorer> ChunkingDataStorer<S> {
+    /// Instantiates a size-limited storer around an existing storer
+    pub fn new(storer: S, max_value_size: usize, policy: ValueSizePolicy) -> Self {
+        ChunkingDataStorer {
+            storer,
+            max_value_size,
+            policy,
+        }
+    }
+
+    fn chunk_path(path: &str, index: usize) -> String {
+        format!("{}{}.{}.", path, CHUNK_SEGMENT, index)
+    }
+
+    fn is_chunk_path(path: &str) -> bool {
+        path.contains(&format!(".{}.", CHUNK_SEGMENT))
+    }
+
+    /// Reassembles the values of a chunked entry, returning any other entry as-is
+    async fn reassemble(&self, data: Data) -> Result<Data, DataStorerError> {
+        if !data.value().0.is_empty() {
+            return Ok(data);
+        }
+
+        let path = data.path();
+        let mut serialized = String::new();
+        let mut index = 0;
+        loop {
+            let chunk = match self.storer.get(&Self::chunk_path(&path, index)).await {
+                Ok(chunk) => chunk,
+                // The entry legitimately holds no values and was never chunked
+                Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                }) if index == 0 => return Ok(data),
+                Err(e) => return Err(e),
+            };
+            match chunk.value().0.first() {
+                Some(DataValue::Unencrypted(UnencryptedDataValue::String(piece))) => {
+                    serialized.push_str(piece)
+                }
+                None => break,
+                Some(_) => {
+                    return Err(DataStorerError::StorageError {
+                        source: StorageError::InternalError {
+                            source: format!("chunk {} of {} is malformed", index, path).into(),
+                        },
+                    })
+                }
+            }
+            index += 1;
+        }
+
+        let values = serde_json::from_str::<DataValueCollection>(&serialized).map_err(|e| {
+            DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e),
+                },
+            }
+        })?;
+        Ok(Data::with_values(&path, values))
+    }
+
+    /// Hides chunk entries from a page and reassembles the chunked entries in it
+    async fn reassemble_page(&self, page: DataCollection) -> Result<DataCollection, DataStorerError> {
+        let mut data = Vec::with_capacity(page.data.len());
+        for d in page.data {
+            if !Self::is_chunk_path(&d.path()) {
+                data.push(self.reassemble(d).await?);
+            }
+        }
+        Ok(DataCollection {
+            data,
+            next_cursor: page.next_cursor,
+        })
+    }
+}
+
+/// Splits a string into pieces of at most `max` bytes without splitting any
+/// character; a character wider than `max` gets a piece of its own.
+fn split_at_char_boundaries(s: &str, max: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = max.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (piece, remainder) = rest.split_at(end);
+        pieces.push(piece);
+        rest = remainder;
+    }
+    pieces
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for ChunkingDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(path).await?;
+        self.reassemble(data).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self.storer.get_collection(path, skip, page_size, sort).await?;
+        self.reassemble_page(page).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self.storer.get_collection_page(path, cursor, page_size).await?;
+        self.reassemble_page(page).await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let serialized = serde_json::to_string(data.value()).map_err(|e| {
+            DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e),
+                },
+            }
+        })?;
+        if serialized.len() <= self.max_value_size {
+            return self.storer.create(data).await;
+        }
+
+        match self.policy {
+            ValueSizePolicy::Reject => Err(DataStorerError::ValueTooLarge {
+                size: serialized.len(),
+                max: self.max_value_size,
+            }),
+            ValueSizePolicy::Chunk => {
+                let path = data.path();
+                let pieces = split_at_char_boundaries(&serialized, self.max_value_size);
+                for (index, piece) in pieces.iter().enumerate() {
+                    self.storer
+                        .create(Data::new(&Self::chunk_path(&path, index), DataValue::from(*piece)))
+                        .await?;
+                }
+                self.storer
+                    .create(Data::with_values(
+                        &Self::chunk_path(&path, pieces.len()),
+                        DataValueCollection::default(),
+                    ))
+                    .await?;
+                self.storer
+                    .create(Data::with_values(&path, DataValueCollection::default()))
+                    .await
+            }
+        }
+    }
+
+    /// Deletes the entry along with all of its chunks, including stale ones
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let path = crate::DataPath::from(path).to_string();
+        let mut index = 0;
+        while self.storer.delete(&Self::chunk_path(&path, index)).await? {
+            index += 1;
+        }
+        self.storer.delete(&path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let mut report = self.storer.erase(prefix).await?;
+        report.erased.retain(|path| !Self::is_chunk_path(path));
+        Ok(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_at_char_boundaries;
+    use crate::storage::tests::memory_storer;
+    use crate::{ChunkingDataStorer, Data, DataStorer, DataStorerError, DataValue, ValueSizePolicy};
+
+    #[test]
+    fn test_split_at_char_boundaries() {
+        assert_eq!(vec!["abc", "def", "g"], split_at_char_boundaries("abcdefg", 3));
+    }
+
+    #[test]
+    fn test_split_at_char_boundaries_multibyte() {
+        assert_eq!(vec!["a", "é", "b"], split_at_char_boundaries("aéb", 1));
+    }
+
+    #[tokio::test]
+    async fn test_small_value_is_stored_as_is() {
+        let (inner, entries) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 64, ValueSizePolicy::Chunk);
+
+        storer.create(Data::new(".small.", DataValue::from("hi"))).await.unwrap();
+
+        assert_eq!(1, entries.lock().unwrap().len());
+        assert_eq!("hi", storer.get(".small.").await.unwrap().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_value_is_rejected() {
+        let (inner, _) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 16, ValueSizePolicy::Reject);
+
+        let result = storer
+            .create(Data::new(".big.", DataValue::from("x".repeat(100))))
+            .await;
+        assert!(matches!(result, Err(DataStorerError::ValueTooLarge { max: 16, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_value_is_chunked_and_reassembled() {
+        let (inner, entries) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 16, ValueSizePolicy::Chunk);
+        let data = Data::new(".big.", DataValue::from("x".repeat(100)));
+
+        storer.create(data.clone()).await.unwrap();
+
+        assert!(entries.lock().unwrap().contains_key(".big._chunk.0."));
+        assert_eq!(data, storer.get(".big.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stale_chunks_are_ignored() {
+        let (inner, _) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 16, ValueSizePolicy::Chunk);
+        let data = Data::new(".big.", DataValue::from("y".repeat(40)));
+
+        storer
+            .create(Data::new(".big.", DataValue::from("x".repeat(100))))
+            .await
+            .unwrap();
+        storer.create(data.clone()).await.unwrap();
+
+        assert_eq!(data, storer.get(".big.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_chunks() {
+        let (inner, entries) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 16, ValueSizePolicy::Chunk);
+
+        storer.create(Data::new(".big.", DataValue::from("x".repeat(100)))).await.unwrap();
+
+        assert!(storer.delete(".big.").await.unwrap());
+        assert!(entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_collection_hides_chunks() {
+        let (inner, _) = memory_storer();
+        let storer = ChunkingDataStorer::new(inner, 16, ValueSizePolicy::Chunk);
+        let data = Data::new(".big.", DataValue::from("x".repeat(100)));
+
+        storer.create(data.clone()).await.unwrap();
+
+        let page = storer.get_collection(".big.", 0, 100, None).await.unwrap();
+        assert_eq!(vec![data], page.data);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::Data;
+use std::fmt::{self, Debug, Formatter};
+
+/// A local write colliding with a version of its entry stored elsewhere,
+/// e.g. a write queued while offline being replayed onto an entry written
+/// since, or an entry changed on both sides of a sync
+#[derive(Debug, Clone, Copy)]
+pub struct Conflict<'a> {
+    /// The path of the entry written on both sides
+    pub path: &'a str,
+    /// The local version of the entry, or `None` if it was deleted locally
+    pub local: Option<&'a Data>,
+    /// When the local version was written, in milliseconds since the unix
+    /// epoch, if known
+    pub local_written_at: Option<u64>,
+    /// The version of the entry stored remotely
+    pub remote: &'a Data,
+}
+
+/// Which version of a conflicting entry is kept
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Keeps the local version, overwriting or deleting the remote version
+    Local,
+    /// Keeps the remote version, dropping the local version
+    Remote,
+    /// Replaces both versions with new data merged from them
+    Merged(Box<Data>),
+}
+
+/// Decides which version of an entry is kept when a replayed or replicated
+/// write collides with a newer stored version
+pub trait ConflictResolver: Send + Sync {
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+/// Keeps whichever version was written last, as told by their `written_at`.
+/// A version without a timestamp loses to one with a timestamp, and ties are
+/// won by the local version.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LastWriteWins;
+
+impl ConflictResolver for LastWriteWins {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        if conflict.remote.written_at() > conflict.local_written_at {
+            Resolution::Remote
+        } else {
+            Resolution::Local
+        }
+    }
+}
+
+/// Always keeps the local version
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PreferLocal;
+
+impl ConflictResolver for PreferLocal {
+    fn resolve(&self, _: &Conflict) -> Resolution {
+        Resolution::Local
+    }
+}
+
+/// Always keeps the remote version
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PreferRemote;
+
+impl ConflictResolver for PreferRemote {
+    fn resolve(&self, _: &Conflict) -> Resolution {
+        Resolution::Remote
+    }
+}
+
+/// Resolves conflicts with a callback, e.g. to merge the fields of both
+/// versions of a document
+#[derive(Clone)]
+pub struct MergeResolver<F> {
+    merge: F,
+}
+
+impl<F> MergeResolver<F>
+where
+    F: Fn(&Conflict) -> Resolution + Send + Sync,
+{
+    pub fn new(merge: F) -> Self {
+        MergeResolver { merge }
+    }
+}
+
+impl<F> Debug for MergeResolver<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergeResolver").finish_non_exhaustive()
+    }
+}
+
+impl<F> ConflictResolver for MergeResolver<F>
+where
+    F: Fn(&Conflict) -> Resolution + Send + Sync,
+{
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        (self.merge)(conflict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Conflict, ConflictResolver, LastWriteWins, MergeResolver, PreferLocal, PreferRemote,
+        Resolution,
+    };
+    use crate::{Data, DataValue};
+
+    #[test]
+    fn test_strategies() {
+        let local = Data::new(".a.", DataValue::from(1u64));
+        let mut remote = Data::new(".a.", DataValue::from(2u64));
+        remote.set_written_at(Some(20));
+        let conflict = |local_written_at| Conflict {
+            path: ".a.",
+            local: Some(&local),
+            local_written_at,
+            remote: &remote,
+        };
+
+        assert_eq!(Resolution::Remote, LastWriteWins.resolve(&conflict(None)));
+        assert_eq!(
+            Resolution::Remote,
+            LastWriteWins.resolve(&conflict(Some(10)))
+        );
+        assert_eq!(
+            Resolution::Local,
+            LastWriteWins.resolve(&conflict(Some(20)))
+        );
+        assert_eq!(Resolution::Local, PreferLocal.resolve(&conflict(None)));
+        assert_eq!(
+            Resolution::Remote,
+            PreferRemote.resolve(&conflict(Some(30)))
+        );
+
+        let sum = MergeResolver::new(|conflict: &Conflict| {
+            let value = |data: &Data| data.value().0[0].to_string().parse::<u64>().unwrap();
+            let local = conflict.local.map(value).unwrap_or(0);
+            Resolution::Merged(Box::new(Data::new(
+                conflict.path,
+                DataValue::from(local + value(conflict.remote)),
+            )))
+        });
+        assert_eq!(
+            Resolution::Merged(Box::new(Data::new(".a.", DataValue::from(3u64)))),
+            sum.resolve(&conflict(None))
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    Data, DataPath, DataStorer, DataStorerError, DataType, DataValue, DataValueCollection,
+    EncryptedDataValue, Sort, SortBy, SortOrder, StorageError,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+};
+
+/// A check of the conformance suite which a storer failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceFailure {
+    pub check: &'static str,
+    pub reason: String,
+}
+
+impl Display for ConformanceFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.check, self.reason)
+    }
+}
+
+/// The outcome of running the conformance suite against a storer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub passed: Vec<&'static str>,
+    pub failures: Vec<ConformanceFailure>,
+}
+
+impl ConformanceReport {
+    /// Returns whether every check passed
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Panics listing every failed check, if any, for use in tests
+    pub fn assert_success(&self) {
+        if !self.is_success() {
+            let failures: Vec<String> = self.failures.iter().map(ToString::to_string).collect();
+            panic!(
+                "{} of {} storer conformance checks failed:\n{}",
+                self.failures.len(),
+                self.failures.len() + self.passed.len(),
+                failures.join("\n")
+            );
+        }
+    }
+
+    fn record(&mut self, check: &'static str, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.passed.push(check),
+            Err(reason) => self.failures.push(ConformanceFailure { check, reason }),
+        }
+    }
+}
+
+/// Runs a battery of behavioral checks against a storer, verifying that it
+/// honors the same semantics as the storers of this crate: retrieving what
+/// was created, overwriting on create, reporting missing entries as
+/// `StorageError::NotFound`, deleting, paginating sorted collections, keeping
+/// collections to their prefix, and storing unicode paths, every value type,
+/// and large values.
+///
+/// Every check writes beneath its own path under `prefix`, which should be
+/// empty when the suite starts, so that a single storer instance can be used.
+/// The suite does not clean up after itself.
+///
+/// ```no_run
+/// # async fn test() {
+/// # let storer = redact_data::MemoryDataStorer::new();
+/// redact_data::storage::conformance::run(&storer, ".conformance.")
+///     .await
+///     .assert_success();
+/// # }
+/// ```
+pub async fn run<S: DataStorer>(storer: &S, prefix: &str) -> ConformanceReport {
+    let prefix = DataPath::new(prefix).to_string();
+    let root = |check: &str| format!("{}{}.", prefix, check);
+    let mut report = ConformanceReport::default();
+    report.record(
+        "get_missing_is_not_found",
+        get_missing_is_not_found(storer, &root("get_missing_is_not_found")).await,
+    );
+    report.record(
+        "create_then_get",
+        create_then_get(storer, &root("create_then_get")).await,
+    );
+    report.record(
+        "create_overwrites",
+        create_overwrites(storer, &root("create_overwrites")).await,
+    );
+    report.record("delete", delete(storer, &root("delete")).await);
+    report.record(
+        "value_types",
+        value_types(storer, &root("value_types")).await,
+    );
+    report.record("pagination", pagination(storer, &root("pagination")).await);
+    report.record(
+        "collection_prefix_isolation",
+        collection_prefix_isolation(storer, &root("collection_prefix_isolation")).await,
+    );
+    report.record(
+        "unicode_paths",
+        unicode_paths(storer, &root("unicode_paths")).await,
+    );
+    report.record(
+        "large_values",
+        large_values(storer, &root("large_values")).await,
+    );
+    report
+}
+
+fn ensure(condition: bool, reason: impl FnOnce() -> String) -> Result<(), String> {
+    match condition {
+        true => Ok(()),
+        false => Err(reason()),
+    }
+}
+
+fn is_not_found(result: &Result<Data, DataStorerError>) -> bool {
+    matches!(
+        result,
+        Err(DataStorerError::StorageError {
+            source: StorageError::NotFound
+        })
+    )
+}
+
+async fn create<S: DataStorer>(storer: &S, data: Data) -> Result<(), String> {
+    let path = data.path();
+    storer
+        .create(data)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("create of {} failed: {}", path, e))
+}
+
+/// Retrieves the entry at the path and checks it holds the expected values
+async fn get_values<S: DataStorer>(
+    storer: &S,
+    path: &str,
+    expected: &DataValueCollection,
+) -> Result<(), String> {
+    let data = storer
+        .get(path)
+        .await
+        .map_err(|e| format!("get of {} failed: {}", path, e))?;
+    ensure(data.path() == path, || {
+        format!("get of {} returned the entry at {}", path, data.path())
+    })?;
+    ensure(data.value() == expected, || {
+        format!(
+            "get of {} returned values {:?} instead of {:?}",
+            path,
+            data.value().0,
+            expected.0
+        )
+    })
+}
+
+async fn get_missing_is_not_found<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let result = storer.get(&format!("{}missing.", root)).await;
+    ensure(is_not_found(&result), || {
+        format!(
+            "get of a missing path returned {:?} instead of NotFound",
+            result
+        )
+    })
+}
+
+async fn create_then_get<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let data = Data::new(&format!("{}entry.", root), DataValue::from(1u64));
+    create(storer, data.clone()).await?;
+    get_values(storer, &data.path(), data.value()).await
+}
+
+async fn create_overwrites<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let path = format!("{}entry.", root);
+    create(storer, Data::new(&path, DataValue::from("first"))).await?;
+    let second = Data::new(&path, DataValue::from("second"));
+    create(storer, second.clone()).await?;
+    get_values(storer, &path, second.value()).await
+}
+
+async fn delete<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let path = format!("{}entry.", root);
+    create(storer, Data::new(&path, DataValue::from(true))).await?;
+    let deleted = storer.delete(&path).await;
+    ensure(matches!(deleted, Ok(true)), || {
+        format!(
+            "delete of an existing entry returned {:?} instead of true",
+            deleted
+        )
+    })?;
+    let result = storer.get(&path).await;
+    ensure(is_not_found(&result), || {
+        format!(
+            "get of a deleted entry returned {:?} instead of NotFound",
+            result
+        )
+    })?;
+    let deleted = storer.delete(&path).await;
+    ensure(matches!(deleted, Ok(false)), || {
+        format!(
+            "delete of a missing entry returned {:?} instead of false",
+            deleted
+        )
+    })
+}
+
+async fn value_types<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let values = vec![
+        DataValue::from(true),
+        DataValue::from(u64::MAX),
+        DataValue::from(i64::MIN),
+        DataValue::from(-1.5f64),
+        DataValue::from("a string"),
+        DataValue::from(vec![0u8, 1, 254, 255]),
+        DataValue::Encrypted(EncryptedDataValue::new(vec![7; 16], DataType::String, "k1")),
+    ];
+    for (i, value) in values.iter().enumerate() {
+        let data = Data::new(&format!("{}{}.", root, i), value.clone());
+        create(storer, data.clone()).await?;
+        get_values(storer, &data.path(), data.value()).await?;
+    }
+    let many = Data::with_values(&format!("{}many.", root), DataValueCollection::from(values));
+    create(storer, many.clone()).await?;
+    get_values(storer, &many.path(), many.value()).await
+}
+
+async fn pagination<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let entries = 25u64;
+    for i in 0..entries {
+        create(
+            storer,
+            Data::new(&format!("{}{:02}.", root, i), DataValue::from(i)),
+        )
+        .await?;
+    }
+    let sort = Some(Sort {
+        by: SortBy::Path,
+        order: SortOrder::Ascending,
+    });
+    let mut paths = Vec::new();
+    for (skip, expected) in [(0, 10), (10, 10), (20, 5), (30, 0)] {
+        let page = storer
+            .get_collection(root, skip, 10, sort)
+            .await
+            .map_err(|e| format!("get_collection skipping {} failed: {}", skip, e))?;
+        ensure(page.data.len() == expected, || {
+            format!(
+                "get_collection skipping {} of {} entries returned {} entries instead of {}",
+                skip,
+                entries,
+                page.data.len(),
+                expected
+            )
+        })?;
+        paths.extend(page.data.iter().map(|d| DataPath::new(&d.path())));
+    }
+    ensure(paths.windows(2).all(|w| w[0] < w[1]), || {
+        format!("pages were not sorted by path: {:?}", paths)
+    })?;
+    let distinct: BTreeSet<_> = paths.iter().collect();
+    ensure(distinct.len() == entries as usize, || {
+        format!(
+            "pages held {} distinct entries instead of {}",
+            distinct.len(),
+            entries
+        )
+    })
+}
+
+async fn collection_prefix_isolation<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let inside = format!("{}a.x.", root);
+    let sibling = format!("{}ab.", root);
+    create(storer, Data::new(&inside, DataValue::from(1u64))).await?;
+    create(storer, Data::new(&sibling, DataValue::from(2u64))).await?;
+    let prefix = format!("{}a.", root);
+    let page = storer
+        .get_collection(&prefix, 0, 10, None)
+        .await
+        .map_err(|e| format!("get_collection of {} failed: {}", prefix, e))?;
+    let paths: Vec<String> = page.data.iter().map(Data::path).collect();
+    ensure(paths == vec![inside], || {
+        format!("get_collection of {} returned {:?}", prefix, paths)
+    })
+}
+
+async fn unicode_paths<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let segments = ["ünïcødé", "日本語", "emoji-🦀", "mixed_Ωμέγα-1"];
+    for (i, segment) in segments.iter().enumerate() {
+        let data = Data::new(&format!("{}{}.", root, segment), DataValue::from(i as u64));
+        create(storer, data.clone()).await?;
+        get_values(storer, &data.path(), data.value()).await?;
+    }
+    let page = storer
+        .get_collection(root, 0, 10, None)
+        .await
+        .map_err(|e| format!("get_collection of {} failed: {}", root, e))?;
+    ensure(page.data.len() == segments.len(), || {
+        format!(
+            "get_collection of unicode paths returned {} entries instead of {}",
+            page.data.len(),
+            segments.len()
+        )
+    })
+}
+
+async fn large_values<S: DataStorer>(storer: &S, root: &str) -> Result<(), String> {
+    let size = 1024 * 1024;
+    let string: String = "redact".chars().cycle().take(size).collect();
+    let bytes: Vec<u8> = (0..size).map(|i| i as u8).collect();
+    for (name, value) in [
+        ("string", DataValue::from(string)),
+        ("bytes", DataValue::from(bytes)),
+    ] {
+        let data = Data::new(&format!("{}{}.", root, name), value);
+        create(storer, data.clone()).await?;
+        get_values(storer, &data.path(), data.value()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::mocks::MockDataStorer;
+    use crate::storage::tests::memory_storer;
+    use crate::{DataStorerError, MemoryDataStorer, StorageError};
+
+    #[tokio::test]
+    async fn test_memory_storer_conforms() {
+        run(&MemoryDataStorer::new(), ".conformance.")
+            .await
+            .assert_success();
+        let (storer, _) = memory_storer();
+        run(&storer, ".conformance.").await.assert_success();
+    }
+
+    #[tokio::test]
+    async fn test_reports_failed_checks() {
+        let mut storer = MockDataStorer::new();
+        storer.expect_get().returning(|_| {
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            })
+        });
+        storer
+            .expect_get_collection()
+            .returning(|_, _, _, _| Ok(Default::default()));
+        storer.expect_create().returning(|_| Ok(true));
+        storer.expect_delete().returning(|_| Ok(true));
+
+        let report = run(&storer, ".conformance.").await;
+        assert!(!report.is_success());
+        assert_eq!(vec!["get_missing_is_not_found"], report.passed);
+        assert_eq!("create_then_get", report.failures[0].check);
+    }
+}
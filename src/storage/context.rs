@@ -0,0 +1,126 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header carrying the deadline as milliseconds since the unix epoch
+pub const DEADLINE_HEADER: &str = "x-redact-deadline";
+/// Header carrying the trace or correlation id
+pub const TRACE_ID_HEADER: &str = "x-redact-trace-id";
+/// Header carrying the acting principal
+pub const PRINCIPAL_HEADER: &str = "x-redact-principal";
+
+/// Describes the circumstances an operation is performed under: when the
+/// caller gives up on it, which trace it belongs to, and who it is performed
+/// on behalf of. Attach it to a storer with `DataStorer::with_context`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OpContext {
+    deadline: Option<SystemTime>,
+    trace_id: Option<String>,
+    principal: Option<String>,
+}
+
+impl OpContext {
+    /// Builds an empty context
+    pub fn new() -> Self {
+        OpContext::default()
+    }
+
+    /// Sets the time after which the caller no longer wants the result
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to the given duration from now
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(SystemTime::now() + timeout)
+    }
+
+    /// Sets the trace or correlation id the operation belongs to
+    pub fn with_trace_id(mut self, trace_id: &str) -> Self {
+        self.trace_id = Some(trace_id.to_owned());
+        self
+    }
+
+    /// Sets the principal the operation is performed on behalf of
+    pub fn with_principal(mut self, principal: &str) -> Self {
+        self.principal = Some(principal.to_owned());
+        self
+    }
+
+    /// Returns the time after which the caller no longer wants the result
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline
+    }
+
+    /// Returns the time left until the deadline, which is zero once it has
+    /// passed, or `None` if there is no deadline
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+
+    /// Returns the trace or correlation id the operation belongs to
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_id.as_deref()
+    }
+
+    /// Returns the principal the operation is performed on behalf of
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// Returns the context as HTTP headers, omitting any fields that are unset
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(deadline) = self.deadline {
+            let millis = deadline
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            headers.push((DEADLINE_HEADER, millis.to_string()));
+        }
+        if let Some(ref trace_id) = self.trace_id {
+            headers.push((TRACE_ID_HEADER, trace_id.clone()));
+        }
+        if let Some(ref principal) = self.principal {
+            headers.push((PRINCIPAL_HEADER, principal.clone()));
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEADLINE_HEADER, PRINCIPAL_HEADER, TRACE_ID_HEADER};
+    use crate::OpContext;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_empty_context_has_no_headers() {
+        assert!(OpContext::new().headers().is_empty());
+    }
+
+    #[test]
+    fn test_headers() {
+        let context = OpContext::new()
+            .with_deadline(UNIX_EPOCH + Duration::from_millis(1500))
+            .with_trace_id("abc")
+            .with_principal("alice");
+        assert_eq!(
+            vec![
+                (DEADLINE_HEADER, "1500".to_owned()),
+                (TRACE_ID_HEADER, "abc".to_owned()),
+                (PRINCIPAL_HEADER, "alice".to_owned()),
+            ],
+            context.headers()
+        );
+    }
+
+    #[test]
+    fn test_remaining() {
+        assert_eq!(None, OpContext::new().remaining());
+        let passed = OpContext::new().with_deadline(UNIX_EPOCH);
+        assert_eq!(Some(Duration::from_secs(0)), passed.remaining());
+        let future = OpContext::new().with_deadline(SystemTime::now() + Duration::from_secs(60));
+        assert!(future.remaining().unwrap() > Duration::from_secs(50));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    storage::{context::OpContext, file::FileDataStorer},
+    Data, DataCollection, DataStorer, DataStorerError, EraseReport, Sort, StorageError,
+};
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use sha2::Sha256;
+use std::{io::ErrorKind, path::PathBuf};
+use tokio::fs;
+
+/// Length in bytes of the nonce prefixed to every encrypted record
+const NONCE_LEN: usize = 12;
+/// Length in bytes of the salt used when deriving a key from a passphrase
+const SALT_LEN: usize = 16;
+/// Name of the file, within the storer's directory, holding the KDF salt
+const SALT_FILE_NAME: &str = "kdf.salt";
+/// PBKDF2 iterations used by `EncryptedFileDataStorer::with_passphrase`
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+
+/// A 256-bit AES key protecting the records of an `EncryptedFileDataStorer`
+#[derive(Clone)]
+pub struct FileEncryptionKey([u8; 32]);
+
+impl FileEncryptionKey {
+    /// Wraps an existing 256-bit key
+    pub fn new(key: [u8; 32]) -> Self {
+        FileEncryptionKey(key)
+    }
+
+    /// Derives a key from a passphrase using PBKDF2-HMAC-SHA256
+    pub fn derive(passphrase: &str, salt: &[u8], iterations: u32) -> Self {
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+        FileEncryptionKey(key)
+    }
+}
+
+/// Seals records with AES-256-GCM as `nonce || ciphertext`, binding each
+/// record to its path as associated data so that encrypted files cannot be
+/// swapped between paths without detection
+#[derive(Clone)]
+pub(crate) struct RecordCipher {
+    cipher: Aes256Gcm,
+}
+
+impl RecordCipher {
+    fn new(key: &FileEncryptionKey) -> Self {
+        RecordCipher {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0)),
+        }
+    }
+
+    pub(crate) fn encrypt(&self, path: &str, plaintext: &[u8]) -> Result<Vec<u8>, DataStorerError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map_err(|_| cipher_error("Could not encrypt the record"))?;
+        let mut record = nonce.to_vec();
+        record.extend_from_slice(&ciphertext);
+        Ok(record)
+    }
+
+    pub(crate) fn decrypt(&self, path: &str, record: &[u8]) -> Result<Vec<u8>, DataStorerError> {
+        if record.len() < NONCE_LEN {
+            return Err(cipher_error("Encrypted record is truncated"));
+        }
+        let (nonce, ciphertext) = record.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                cipher_error(
+                    "Could not decrypt the record; the key is wrong or it was tampered with",
+                )
+            })
+    }
+}
+
+fn cipher_error(message: &str) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: message.into(),
+        },
+    }
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
+}
+
+/// Stores data on the local filesystem like `FileDataStorer`, but encrypts
+/// every record at rest with AES-256-GCM. Meant for machines whose disks are
+/// not otherwise encrypted; paths remain visible as file names.
+#[derive(Clone)]
+pub struct EncryptedFileDataStorer {
+    storer: FileDataStorer,
+}
+
+impl EncryptedFileDataStorer {
+    /// Instantiates an encrypted file storer in the given directory using a
+    /// supplied key
+    pub async fn new<P: Into<PathBuf>>(
+        root: P,
+        key: FileEncryptionKey,
+    ) -> Result<Self, DataStorerError> {
+        let storer = FileDataStorer::new(root)
+            .await?
+            .with_cipher(RecordCipher::new(&key));
+        Ok(EncryptedFileDataStorer { storer })
+    }
+
+    /// Instantiates an encrypted file storer whose key is derived from a
+    /// passphrase with `DEFAULT_KDF_ITERATIONS` rounds of PBKDF2
+    pub async fn with_passphrase<P: Into<PathBuf>>(
+        root: P,
+        passphrase: &str,
+    ) -> Result<Self, DataStorerError> {
+        Self::with_passphrase_iterations(root, passphrase, DEFAULT_KDF_ITERATIONS).await
+    }
+
+    /// Instantiates an encrypted file storer whose key is derived from a
+    /// passphrase with the given number of PBKDF2 rounds. The salt is
+    /// generated on first use and kept alongside the records, so the same
+    /// passphrase and iterations always reopen the same directory.
+    pub async fn with_passphrase_iterations<P: Into<PathBuf>>(
+        root: P,
+        passphrase: &str,
+        iterations: u32,
+    ) -> Result<Self, DataStorerError> {
+        let root = root.into();
+        fs::create_dir_all(&root).await.map_err(internal_error)?;
+        let salt_path = root.join(SALT_FILE_NAME);
+        let salt = match fs::read(&salt_path).await {
+            Ok(salt) => salt,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut salt = vec![0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                fs::write(&salt_path, &salt).await.map_err(internal_error)?;
+                salt
+            }
+            Err(e) => return Err(internal_error(e)),
+        };
+        // Deriving the key takes long enough to stall the executor
+        let passphrase = passphrase.to_owned();
+        let key = tokio::task::spawn_blocking(move || {
+            FileEncryptionKey::derive(&passphrase, &salt, iterations)
+        })
+        .await
+        .map_err(internal_error)?;
+        Self::new(root, key).await
+    }
+}
+
+#[async_trait]
+impl DataStorer for EncryptedFileDataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, path: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(path).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SALT_FILE_NAME;
+    use crate::storage::file::tests::test_dir;
+    use crate::{
+        Data, DataStorer, DataStorerError, DataValue, EncryptedFileDataStorer, FileEncryptionKey,
+        StorageError,
+    };
+
+    #[tokio::test]
+    async fn test_records_are_encrypted_at_rest() {
+        let dir = test_dir("encrypted-file-at-rest");
+        let storer = EncryptedFileDataStorer::new(&dir, FileEncryptionKey::new([7; 32]))
+            .await
+            .unwrap();
+        let data = Data::new(".secrets.1.", DataValue::from("hunter2"));
+
+        storer.create(data.clone()).await.unwrap();
+        assert_eq!(data, storer.get(".secrets.1.").await.unwrap());
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let contents = std::fs::read(entry.unwrap().path()).unwrap();
+            assert!(!String::from_utf8_lossy(&contents).contains("hunter2"));
+        }
+        assert_eq!(
+            1,
+            storer
+                .get_collection(".secrets.", 0, 10, None)
+                .await
+                .unwrap()
+                .data
+                .len()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wrong_key_fails_to_decrypt() {
+        let dir = test_dir("encrypted-file-wrong-key");
+        let storer = EncryptedFileDataStorer::new(&dir, FileEncryptionKey::new([1; 32]))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".secrets.1.", DataValue::from("hunter2")))
+            .await
+            .unwrap();
+
+        let other = EncryptedFileDataStorer::new(&dir, FileEncryptionKey::new([2; 32]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            other.get(".secrets.1.").await,
+            Err(DataStorerError::StorageError {
+                source: StorageError::InternalError { .. }
+            })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_reopens_with_persisted_salt() {
+        let dir = test_dir("encrypted-file-passphrase");
+        let storer = EncryptedFileDataStorer::with_passphrase_iterations(&dir, "correct horse", 10)
+            .await
+            .unwrap();
+        let data = Data::new(".secrets.1.", DataValue::from("hunter2"));
+        storer.create(data.clone()).await.unwrap();
+        assert!(dir.join(SALT_FILE_NAME).exists());
+
+        let reopened =
+            EncryptedFileDataStorer::with_passphrase_iterations(&dir, "correct horse", 10)
+                .await
+                .unwrap();
+        assert_eq!(data, reopened.get(".secrets.1.").await.unwrap());
+        let wrong = EncryptedFileDataStorer::with_passphrase_iterations(&dir, "battery", 10)
+            .await
+            .unwrap();
+        assert!(wrong.get(".secrets.1.").await.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataPath, DataStorer, DataStorerError, DataValue,
+    EraseReport, OpContext, Sort,
+};
+use async_trait::async_trait;
+
+/// Wraps a storer to reject writes of plaintext values which should have been
+/// encrypted: values of data whose `encryptedby` names a key, and values
+/// under any prefix marked sensitive. Such mistakes would otherwise be
+/// stored silently.
+#[derive(Clone)]
+pub struct EncryptedOnlyDataStorer<S: DataStorer> {
+    storer: S,
+    sensitive_prefixes: Vec<String>,
+}
+
+impl<S: DataStorer> EncryptedOnlyDataStorer<S> {
+    /// Instantiates an enforcing storer around an existing storer
+    pub fn new(storer: S) -> Self {
+        EncryptedOnlyDataStorer {
+            storer,
+            sensitive_prefixes: Vec::new(),
+        }
+    }
+
+    /// Marks every path under the prefix as sensitive, so that only
+    /// encrypted values may be written beneath it
+    pub fn with_sensitive_prefix(mut self, prefix: &str) -> Self {
+        self.sensitive_prefixes
+            .push(DataPath::new(prefix).to_string());
+        self
+    }
+
+    /// Returns whether the path is under a prefix marked sensitive
+    pub fn is_sensitive(&self, path: &str) -> bool {
+        let path = DataPath::new(path);
+        self.sensitive_prefixes
+            .iter()
+            .any(|prefix| path.as_str().starts_with(prefix.as_str()))
+    }
+
+    fn validate(&self, data: &Data) -> Result<(), DataStorerError> {
+        let has_plaintext = data
+            .value()
+            .0
+            .iter()
+            .any(|value| matches!(value, DataValue::Unencrypted(_)));
+        if has_plaintext && (!data.encryptedby().is_empty() || self.is_sensitive(&data.path())) {
+            return Err(DataStorerError::UnencryptedSensitiveValue { path: data.path() });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for EncryptedOnlyDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.validate(&data)?;
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, path: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(path).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EncryptedOnlyDataStorer;
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        Data, DataStorer, DataStorerError, DataType, DataValue, EncryptedDataValue, KeyName,
+    };
+
+    fn encrypted() -> DataValue {
+        DataValue::Encrypted(EncryptedDataValue::new(
+            b"ciphertext".to_vec(),
+            DataType::String,
+            "k1",
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_rejects_plaintext_claiming_encryption() {
+        let (inner, entries) = memory_storer();
+        let storer = EncryptedOnlyDataStorer::new(inner);
+        let mut data = Data::new(".email.", DataValue::from("ada@example.com"));
+        data.add_encrypted_by(KeyName::new("k1").unwrap());
+
+        assert!(matches!(
+            storer.create(data).await,
+            Err(DataStorerError::UnencryptedSensitiveValue { ref path }) if path == ".email."
+        ));
+        assert!(storer
+            .create(Data::new(".plain.", DataValue::from(1u64)))
+            .await
+            .unwrap());
+        assert_eq!(1, entries.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_plaintext_under_sensitive_prefixes() {
+        let (inner, entries) = memory_storer();
+        let storer = EncryptedOnlyDataStorer::new(inner).with_sensitive_prefix("users.ssn");
+
+        assert!(storer.is_sensitive(".users.ssn.1."));
+        assert!(!storer.is_sensitive(".users.ssnx."));
+        assert!(matches!(
+            storer
+                .create(Data::new(".users.ssn.1.", DataValue::from("078-05-1120")))
+                .await,
+            Err(DataStorerError::UnencryptedSensitiveValue { .. })
+        ));
+        assert!(matches!(
+            storer.increment(".users.ssn.2.", 1).await,
+            Err(DataStorerError::UnencryptedSensitiveValue { .. })
+        ));
+        assert!(storer
+            .create(Data::new(".users.ssn.1.", encrypted()))
+            .await
+            .unwrap());
+        assert_eq!(1, entries.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// Describes what an erasure removed, for evidencing right-to-erasure requests
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct EraseReport {
+    /// The paths of the data deleted from the backend
+    pub erased: Vec<String>,
+    /// The number of cache entries purged alongside the data
+    pub cache_entries_purged: u64,
+}
+
+impl EraseReport {
+    /// Builds a report of data erased from the backend
+    pub fn new(erased: Vec<String>) -> Self {
+        EraseReport {
+            erased,
+            cache_entries_purged: 0,
+        }
+    }
+
+    /// Returns the number of data deleted from the backend
+    pub fn erased_count(&self) -> usize {
+        self.erased.len()
+    }
+}
@@ -1,6 +1,7 @@
 use std::error::Error;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::time::Duration;
 use crate::CacheError;
 
 /// Error type that converts to a warp::Rejection
@@ -15,13 +16,128 @@ pub enum DataStorerError {
     StorageError {
         source: StorageError
     },
+
+    /// Indicates a pagination cursor was malformed or not produced by this storer
+    InvalidCursor,
+
+    /// Indicates the requested page size was negative or exceeded the storer's maximum
+    InvalidPageSize {
+        page_size: i64,
+        max: i64,
+    },
+
+    /// Indicates a value's serialized size exceeded the storer's maximum
+    ValueTooLarge {
+        size: usize,
+        max: usize,
+    },
+
+    /// Indicates stored data did not match its integrity checksum
+    IntegrityMismatch {
+        path: String,
+    },
+
+    /// Represents an error which occurred while signing or verifying data
+    SigningError {
+        source: SigningError,
+    },
+
+    /// Indicates data was unsigned or its signature did not verify
+    InvalidSignature {
+        path: String,
+    },
+
+    /// Indicates the principal, if any, was not authorized to access the path
+    Forbidden {
+        principal: Option<String>,
+        path: String,
+    },
+
+    /// Indicates a storer view scoped to a prefix touched data outside of it
+    ScopeViolation {
+        prefix: String,
+        path: String,
+    },
+
+    /// Indicates a tenant id was empty or contained a path separator
+    InvalidTenant {
+        tenant: String,
+    },
+
+    /// Indicates an operation scoped to one tenant touched another tenant's data
+    TenantViolation {
+        tenant: String,
+        path: String,
+    },
+
+    /// Indicates data was encrypted by a key name the keyring does not know
+    UnknownEncryptionKey {
+        keyname: String,
+        path: String,
+    },
+
+    /// Indicates a patch could not be applied because of the data at a path
+    PatchRejected {
+        path: String,
+        reason: String,
+    },
+
+    /// Indicates a counter operation targeted data which is not a single
+    /// integer, or would overflow it
+    InvalidCounter {
+        path: String,
+    },
+
+    /// Indicates data was to be written with unencrypted values although it
+    /// names keys to encrypt it or its path is marked sensitive
+    UnencryptedSensitiveValue {
+        path: String,
+    },
+
+    /// Indicates data cannot be encoded for the wire version a server speaks
+    WireIncompatible {
+        path: String,
+        reason: String,
+    },
+
+    /// Indicates the backing store is known to be unreachable
+    Unavailable {
+        reason: String,
+    },
+
+    /// Indicates the deadline of the operation's context passed before it completed
+    DeadlineExceeded,
+
+    /// Indicates the backing store is shedding load and asked for requests to
+    /// be retried no sooner than `retry_after`, if it said when
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
 }
 
 impl Error for DataStorerError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match *self {
             DataStorerError::CacheError { ref source } => Some(source),
-            DataStorerError::StorageError { ref source } => Some(source)
+            DataStorerError::StorageError { ref source } => Some(source),
+            DataStorerError::InvalidCursor => None,
+            DataStorerError::InvalidPageSize { .. } => None,
+            DataStorerError::ValueTooLarge { .. } => None,
+            DataStorerError::IntegrityMismatch { .. } => None,
+            DataStorerError::SigningError { ref source } => Some(source),
+            DataStorerError::InvalidSignature { .. } => None,
+            DataStorerError::Forbidden { .. } => None,
+            DataStorerError::ScopeViolation { .. } => None,
+            DataStorerError::InvalidTenant { .. } => None,
+            DataStorerError::TenantViolation { .. } => None,
+            DataStorerError::UnknownEncryptionKey { .. } => None,
+            DataStorerError::PatchRejected { .. } => None,
+            DataStorerError::InvalidCounter { .. } => None,
+            DataStorerError::UnencryptedSensitiveValue { .. } => None,
+            DataStorerError::WireIncompatible { .. } => None,
+            DataStorerError::Unavailable { .. } => None,
+            DataStorerError::DeadlineExceeded => None,
+            DataStorerError::RateLimited { .. } => None,
         }
     }
 }
@@ -37,6 +153,66 @@ impl Display for DataStorerError {
                 // TODO: display source error
                 write!(f, "Storage error")
             }
+            DataStorerError::InvalidCursor => {
+                write!(f, "Invalid pagination cursor")
+            }
+            DataStorerError::InvalidPageSize { page_size, max } => {
+                write!(f, "Invalid page size {}, must be between 0 and {}", page_size, max)
+            }
+            DataStorerError::ValueTooLarge { size, max } => {
+                write!(f, "Value of {} bytes exceeds the maximum of {} bytes", size, max)
+            }
+            DataStorerError::IntegrityMismatch { ref path } => {
+                write!(f, "Data at {} failed integrity verification", path)
+            }
+            DataStorerError::SigningError { .. } => {
+                write!(f, "Signing error")
+            }
+            DataStorerError::InvalidSignature { ref path } => {
+                write!(f, "Data at {} is not validly signed", path)
+            }
+            DataStorerError::Forbidden { ref principal, ref path } => match principal {
+                Some(principal) => write!(f, "{} is not allowed to access {}", principal, path),
+                None => write!(f, "Anonymous access to {} is not allowed", path),
+            },
+            DataStorerError::ScopeViolation { ref prefix, ref path } => {
+                write!(f, "Data at {} is outside of scope {}", path, prefix)
+            }
+            DataStorerError::InvalidTenant { ref tenant } => {
+                write!(f, "Invalid tenant id {:?}", tenant)
+            }
+            DataStorerError::TenantViolation { ref tenant, ref path } => {
+                write!(f, "Data at {} is outside of tenant {}", path, tenant)
+            }
+            DataStorerError::UnknownEncryptionKey { ref keyname, ref path } => {
+                write!(f, "Data at {} is encrypted by unknown key {}", path, keyname)
+            }
+            DataStorerError::PatchRejected { ref path, ref reason } => {
+                write!(f, "Patch rejected at {}: {}", path, reason)
+            }
+            DataStorerError::InvalidCounter { ref path } => {
+                write!(f, "Data at {} is not an integer counter or would overflow", path)
+            }
+            DataStorerError::UnencryptedSensitiveValue { ref path } => {
+                write!(f, "Data at {} must be encrypted but holds unencrypted values", path)
+            }
+            DataStorerError::WireIncompatible { ref path, ref reason } => {
+                write!(f, "Data at {} cannot be sent with the server's wire version: {}", path, reason)
+            }
+            DataStorerError::Unavailable { ref reason } => {
+                write!(f, "Storage is unavailable: {}", reason)
+            }
+            DataStorerError::DeadlineExceeded => {
+                write!(f, "Operation deadline exceeded")
+            }
+            DataStorerError::RateLimited {
+                retry_after: Some(retry_after),
+            } => {
+                write!(f, "Storage is rate limiting requests; retry after {:?}", retry_after)
+            }
+            DataStorerError::RateLimited { retry_after: None } => {
+                write!(f, "Storage is rate limiting requests")
+            }
         }
     }
 }
@@ -49,6 +225,14 @@ impl From<CacheError> for DataStorerError {
     }
 }
 
+impl From<SigningError> for DataStorerError {
+    fn from(e: SigningError) -> DataStorerError {
+        DataStorerError::SigningError {
+            source: e
+        }
+    }
+}
+
 /// Error type that converts to a warp::Rejection
 #[derive(Debug)]
 pub enum StorageError {
@@ -77,16 +261,218 @@ impl Display for StorageError {
             StorageError::InternalError { .. } => {
                 write!(f, "Internal error occurred")
             }
-            StorageError::NotFound { .. } => {
+            StorageError::NotFound => {
                 write!(f, "Data not found")
             }
         }
     }
 }
 
+/// Error type for signing and verifying data
+#[derive(Debug)]
+pub enum SigningError {
+    /// Represents an error which occurred within the signing implementation
+    InternalError {
+        source: Box<dyn Error + Send + Sync>,
+    },
+
+    /// Indicates the signature names a key the verifier does not know
+    UnknownKey {
+        keyid: String,
+    },
+}
+
+impl Error for SigningError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SigningError::InternalError { ref source } => Some(source.as_ref()),
+            SigningError::UnknownKey { .. } => None,
+        }
+    }
+}
+
+impl Display for SigningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            SigningError::InternalError { .. } => {
+                write!(f, "Internal error occurred")
+            }
+            SigningError::UnknownKey { ref keyid } => {
+                write!(f, "Unknown signing key {}", keyid)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::StorageError;
+    use crate::{DataStorerError, SigningError, StorageError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_to_string_invalid_signature() {
+        let s = DataStorerError::InvalidSignature {
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .path. is not validly signed");
+    }
+
+    #[test]
+    fn test_to_string_forbidden() {
+        let s = DataStorerError::Forbidden {
+            principal: Some("alice".to_owned()),
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "alice is not allowed to access .path.");
+    }
+
+    #[test]
+    fn test_to_string_scope_violation() {
+        let s = DataStorerError::ScopeViolation {
+            prefix: ".a.".to_owned(),
+            path: ".b.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .b. is outside of scope .a.");
+    }
+
+    #[test]
+    fn test_to_string_invalid_tenant() {
+        let s = DataStorerError::InvalidTenant {
+            tenant: "a.b".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Invalid tenant id \"a.b\"");
+    }
+
+    #[test]
+    fn test_to_string_tenant_violation() {
+        let s = DataStorerError::TenantViolation {
+            tenant: "t1".to_owned(),
+            path: ".t2.path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .t2.path. is outside of tenant t1");
+    }
+
+    #[test]
+    fn test_to_string_unknown_encryption_key() {
+        let s = DataStorerError::UnknownEncryptionKey {
+            keyname: "k1".to_owned(),
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .path. is encrypted by unknown key k1");
+    }
+
+    #[test]
+    fn test_to_string_patch_rejected() {
+        let s = DataStorerError::PatchRejected {
+            path: ".path.".to_owned(),
+            reason: "test failed".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Patch rejected at .path.: test failed");
+    }
+
+    #[test]
+    fn test_to_string_invalid_counter() {
+        let s = DataStorerError::InvalidCounter {
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .path. is not an integer counter or would overflow");
+    }
+
+    #[test]
+    fn test_to_string_unencrypted_sensitive_value() {
+        let s = DataStorerError::UnencryptedSensitiveValue {
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .path. must be encrypted but holds unencrypted values");
+    }
+
+    #[test]
+    fn test_to_string_wire_incompatible() {
+        let s = DataStorerError::WireIncompatible {
+            path: ".path.".to_owned(),
+            reason: "bytes values require wire version 2".to_owned(),
+        }
+        .to_string();
+        assert_eq!(
+            s,
+            "Data at .path. cannot be sent with the server's wire version: bytes values require wire version 2"
+        );
+    }
+
+    #[test]
+    fn test_to_string_unavailable() {
+        let s = DataStorerError::Unavailable {
+            reason: "ping timed out".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Storage is unavailable: ping timed out");
+    }
+
+    #[test]
+    fn test_to_string_deadline_exceeded() {
+        let s = DataStorerError::DeadlineExceeded.to_string();
+        assert_eq!(s, "Operation deadline exceeded");
+    }
+
+    #[test]
+    fn test_to_string_rate_limited() {
+        let s = DataStorerError::RateLimited {
+            retry_after: Some(Duration::from_secs(2)),
+        }
+        .to_string();
+        assert_eq!(s, "Storage is rate limiting requests; retry after 2s");
+        let s = DataStorerError::RateLimited { retry_after: None }.to_string();
+        assert_eq!(s, "Storage is rate limiting requests");
+    }
+
+    #[test]
+    fn test_to_string_unknown_key() {
+        let s = SigningError::UnknownKey {
+            keyid: "k1".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Unknown signing key k1");
+    }
+
+    #[test]
+    fn test_to_string_invalid_cursor() {
+        let s = DataStorerError::InvalidCursor.to_string();
+        assert_eq!(s, "Invalid pagination cursor");
+    }
+
+    #[test]
+    fn test_to_string_invalid_page_size() {
+        let s = DataStorerError::InvalidPageSize {
+            page_size: 5000,
+            max: 1000,
+        }
+        .to_string();
+        assert_eq!(s, "Invalid page size 5000, must be between 0 and 1000");
+    }
+
+    #[test]
+    fn test_to_string_value_too_large() {
+        let s = DataStorerError::ValueTooLarge { size: 20, max: 10 }.to_string();
+        assert_eq!(s, "Value of 20 bytes exceeds the maximum of 10 bytes");
+    }
+
+    #[test]
+    fn test_to_string_integrity_mismatch() {
+        let s = DataStorerError::IntegrityMismatch {
+            path: ".path.".to_owned(),
+        }
+        .to_string();
+        assert_eq!(s, "Data at .path. failed integrity verification");
+    }
 
     #[test]
     fn test_to_string_internal_error() {
@@ -0,0 +1,341 @@
+use crate::storage::versioning::now_millis;
+use crate::{
+    Data, DataCollection, DataPath, DataStorer, DataStorerError, DataValueCollection, EraseReport,
+    OpContext, Sort, SortBy, SortOrder, StorageError,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The path segment under which the events of an entry are stored
+const EVENT_SEGMENT: &str = "_event";
+
+/// The path segment under which the snapshots of an entry are stored
+const SNAPSHOT_SEGMENT: &str = "_snapshot";
+
+/// The number of events after which an entry is snapshotted by default
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+/// Returns the path of the event or snapshot with the given sequence number
+/// of an entry; sequence numbers are zero-padded so records sort by path.
+fn record_path(path: &str, segment: &str, sequence: u64) -> String {
+    format!("{}{}.{:020}.", DataPath::from(path), segment, sequence)
+}
+
+/// Returns the path under which every record of an entry with the given
+/// segment is stored
+fn records_prefix(path: &str, segment: &str) -> String {
+    format!("{}{}.", DataPath::from(path), segment)
+}
+
+/// Returns the path of the entry an event or snapshot belongs to and its
+/// sequence number, or `None` if the path is not that of a record
+fn parse_record(path: &str) -> Option<(&str, u64)> {
+    [EVENT_SEGMENT, SNAPSHOT_SEGMENT]
+        .iter()
+        .find_map(|segment| {
+            let marker = format!(".{}.", segment);
+            let index = path.rfind(&marker)?;
+            let sequence = path[index + marker.len()..].strip_suffix('.')?;
+            Some((&path[..index + 1], sequence.parse().ok()?))
+        })
+}
+
+/// Returns whether an event records the deletion of its entry rather than a
+/// write
+fn is_deletion(record: &Data) -> bool {
+    record.value().0.is_empty()
+}
+
+/// Wraps a storer to keep every write as an immutable, append-only event.
+///
+/// Writing an entry appends an event at `.path._event.SEQ.` holding the
+/// written values, and deleting it appends an event holding none; the inner
+/// storer is only ever asked to create records at new paths. Reads
+/// materialize the state of an entry from its latest event. Every
+/// `snapshot_interval` events, the state is also written as a snapshot at
+/// `.path._snapshot.SEQ.`, so that the events preceding a snapshot can be
+/// archived without losing the state they materialize to.
+///
+/// Sequence numbers are assigned by reading the latest event first, so
+/// concurrent writes to the same entry should be serialized, e.g. with
+/// `with_lock`.
+#[derive(Clone)]
+pub struct EventSourcedDataStorer<S: DataStorer> {
+    storer: S,
+    snapshot_interval: u64,
+}
+
+impl<S: DataStorer> EventSourcedDataStorer<S> {
+    /// Instantiates an event-sourced storer around an existing storer
+    pub fn new(storer: S) -> Self {
+        EventSourcedDataStorer {
+            storer,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+
+    /// Sets the number of events after which an entry is snapshotted
+    pub fn with_snapshot_interval(mut self, snapshot_interval: u64) -> Self {
+        self.snapshot_interval = snapshot_interval.max(1);
+        self
+    }
+
+    /// Returns every event of the entry at the path, oldest first
+    pub async fn events(&self, path: &str) -> Result<Vec<Data>, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        let prefix = records_prefix(&path, EVENT_SEGMENT);
+        let mut events = Vec::new();
+        let mut records = self.storer.iter_collection(&prefix);
+        while let Some(record) = records.next().await {
+            let record = record?;
+            if parse_record(&record.path()).map(|(owner, _)| owner) == Some(path.as_str()) {
+                events.push(record);
+            }
+        }
+        events.sort_by_key(|e| e.path());
+        Ok(events)
+    }
+
+    /// Returns the sequence number and record of the latest event or snapshot
+    /// of the entry at the path, if it has any
+    async fn latest(&self, path: &str) -> Result<Option<(u64, Data)>, DataStorerError> {
+        let sort = Some(Sort::new(SortBy::Path, SortOrder::Descending));
+        let mut latest: Option<(u64, Data)> = None;
+        for segment in [SNAPSHOT_SEGMENT, EVENT_SEGMENT] {
+            let prefix = records_prefix(path, segment);
+            let page = self.storer.get_collection(&prefix, 0, 1, sort).await?;
+            for record in page.data {
+                if let Some((owner, sequence)) = parse_record(&record.path()) {
+                    if owner == path && latest.as_ref().is_none_or(|(s, _)| sequence > *s) {
+                        latest = Some((sequence, record));
+                    }
+                }
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Appends an event to the entry at the data's path, snapshotting it if
+    /// the event completes a snapshot interval
+    async fn append(&self, mut data: Data, sequence: u64) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        data.set_written_at(Some(now_millis()));
+        let mut event = data.clone();
+        event.set_path(&record_path(&path, EVENT_SEGMENT, sequence));
+        let appended = self.storer.create(event).await?;
+        if sequence.is_multiple_of(self.snapshot_interval) {
+            data.set_path(&record_path(&path, SNAPSHOT_SEGMENT, sequence));
+            self.storer.create(data).await?;
+        }
+        Ok(appended)
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for EventSourcedDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        match self.latest(&path).await? {
+            Some((_, mut data)) if !is_deletion(&data) => {
+                data.set_path(&path);
+                Ok(data)
+            }
+            _ => Err(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            }),
+        }
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut latest = BTreeMap::<String, (u64, Data)>::new();
+        let mut records = self.storer.iter_collection(path);
+        while let Some(record) = records.next().await {
+            let record = record?;
+            let record_path = record.path();
+            if let Some((owner, sequence)) = parse_record(&record_path) {
+                if latest.get(owner).is_none_or(|(s, _)| sequence > *s) {
+                    latest.insert(owner.to_owned(), (sequence, record));
+                }
+            }
+        }
+        drop(records);
+
+        let mut data: Vec<Data> = latest
+            .into_iter()
+            .filter(|(_, (_, record))| !is_deletion(record))
+            .map(|(owner, (_, mut record))| {
+                record.set_path(&owner);
+                record
+            })
+            .collect();
+        if let Some(Sort {
+            order: SortOrder::Descending,
+            ..
+        }) = sort
+        {
+            data.reverse();
+        }
+        Ok(DataCollection::new(
+            data.into_iter()
+                .skip(skip.max(0) as usize)
+                .take(page_size.max(0) as usize)
+                .collect(),
+        ))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        let sequence = self.latest(&path).await?.map_or(0, |(s, _)| s) + 1;
+        self.append(data, sequence).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        match self.latest(&path).await? {
+            Some((sequence, data)) if !is_deletion(&data) => {
+                let deletion = Data::with_values(&path, DataValueCollection::default());
+                self.append(deletion, sequence + 1).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let report = self.storer.erase(prefix).await?;
+        let erased: BTreeSet<_> = report
+            .erased
+            .iter()
+            .filter_map(|path| parse_record(path).map(|(owner, _)| owner.to_owned()))
+            .collect();
+        Ok(EraseReport {
+            erased: erased.into_iter().collect(),
+            ..report
+        })
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        EventSourcedDataStorer {
+            storer: self.storer.with_context(context),
+            snapshot_interval: self.snapshot_interval,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_record, record_path, EVENT_SEGMENT, SNAPSHOT_SEGMENT};
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataValue, EventSourcedDataStorer};
+
+    #[test]
+    fn test_parse_record() {
+        let path = record_path(".a.b.", EVENT_SEGMENT, 12);
+        assert_eq!(".a.b._event.00000000000000000012.", path);
+        assert_eq!(Some((".a.b.", 12)), parse_record(&path));
+        let path = record_path(".a.", SNAPSHOT_SEGMENT, 3);
+        assert_eq!(Some((".a.", 3)), parse_record(&path));
+        assert_eq!(None, parse_record(".a.b."));
+    }
+
+    #[tokio::test]
+    async fn test_writes_append_events() {
+        let (inner, entries) = memory_storer();
+        let storer = EventSourcedDataStorer::new(inner);
+
+        for i in 1..=3u64 {
+            storer
+                .create(Data::new(".a.", DataValue::from(i)))
+                .await
+                .unwrap();
+        }
+        assert!(storer.delete(".a.").await.unwrap());
+        assert!(!storer.delete(".a.").await.unwrap());
+        assert!(storer.get(".a.").await.is_err());
+        storer
+            .create(Data::new(".a.", DataValue::from(5u64)))
+            .await
+            .unwrap();
+
+        assert_eq!("5", storer.get("a").await.unwrap().to_string());
+        assert_eq!(5, storer.events(".a.").await.unwrap().len());
+        assert_eq!(5, entries.lock().unwrap().len());
+        assert!(!entries.lock().unwrap().contains_key(".a."));
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_bound_replay() {
+        let (inner, entries) = memory_storer();
+        let storer = EventSourcedDataStorer::new(inner).with_snapshot_interval(2);
+
+        for i in 1..=5u64 {
+            storer
+                .create(Data::new(".a.", DataValue::from(i)))
+                .await
+                .unwrap();
+        }
+        let snapshots = entries
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.contains(SNAPSHOT_SEGMENT))
+            .count();
+        assert_eq!(2, snapshots);
+        assert_eq!("5", storer.get(".a.").await.unwrap().to_string());
+
+        // Events covered by a snapshot can be archived
+        entries
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.contains(EVENT_SEGMENT));
+        assert_eq!("4", storer.get(".a.").await.unwrap().to_string());
+        storer
+            .create(Data::new(".a.", DataValue::from(6u64)))
+            .await
+            .unwrap();
+        let event = record_path(".a.", EVENT_SEGMENT, 5);
+        assert_eq!("6", entries.lock().unwrap()[&event].to_string());
+    }
+
+    #[tokio::test]
+    async fn test_collections_materialize_current_state() {
+        let (inner, _) = memory_storer();
+        let storer = EventSourcedDataStorer::new(inner);
+
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.b.", DataValue::from(3u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.c.", DataValue::from(4u64)))
+            .await
+            .unwrap();
+        storer.delete(".a.c.").await.unwrap();
+
+        let page = storer.get_collection(".a.", 0, 100, None).await.unwrap();
+        let data: Vec<_> = page
+            .data
+            .iter()
+            .map(|d| format!("{}={}", d.path(), d))
+            .collect();
+        assert_eq!(vec![".a.=2", ".a.b.=3"], data);
+
+        let report = storer.erase(".a.").await.unwrap();
+        assert_eq!(vec![".a.", ".a.b.", ".a.c."], report.erased);
+    }
+}
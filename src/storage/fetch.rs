@@ -0,0 +1,103 @@
+use crate::{Data, DataStorer, DataStorerError};
+use futures::stream::{self, StreamExt};
+
+/// Gets the entries at many paths concurrently, with at most `concurrency`
+/// gets in flight at once so as not to exhaust the storer's connection pool.
+/// Returns the result of each get in the order of the paths given, so paths
+/// holding no entry yield `StorageError::NotFound` in their position.
+pub async fn fetch_all<S: DataStorer>(
+    storer: &S,
+    paths: &[&str],
+    concurrency: usize,
+) -> Vec<Result<Data, DataStorerError>> {
+    stream::iter(paths.iter().map(|path| storer.get(path)))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fetch_all;
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataCollection, DataStorer, DataStorerError, DataValue, Sort, StorageError};
+    use async_trait::async_trait;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Answers gets after a delay shorter for later paths, recording the most
+    /// gets ever in flight
+    #[derive(Clone, Default)]
+    struct SlowStorer {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl DataStorer for SlowStorer {
+        async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            let n: u64 = path.trim_matches('.').parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(20 - n)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Data::new(path, DataValue::from(n)))
+        }
+
+        async fn get_collection(
+            &self,
+            _: &str,
+            _: i64,
+            _: i64,
+            _: Option<Sort>,
+        ) -> Result<DataCollection, DataStorerError> {
+            Ok(DataCollection::default())
+        }
+
+        async fn create(&self, _: Data) -> Result<bool, DataStorerError> {
+            Ok(true)
+        }
+
+        async fn delete(&self, _: &str) -> Result<bool, DataStorerError> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bounds_concurrency_and_preserves_order() {
+        let storer = SlowStorer::default();
+        let paths: Vec<String> = (0..12).map(|i| format!(".{}.", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+        let fetched: Vec<String> = fetch_all(&storer, &paths, 3)
+            .await
+            .into_iter()
+            .map(|data| data.unwrap().path())
+            .collect();
+        assert_eq!(paths, fetched);
+        assert_eq!(3, storer.max_in_flight.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_reports_missing_paths_in_place() {
+        let (storer, _) = memory_storer();
+        storer
+            .create(Data::new(".b.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let fetched = fetch_all(&storer, &[".a.", ".b."], 0).await;
+        assert!(matches!(
+            fetched[0],
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound
+            })
+        ));
+        assert_eq!(".b.", fetched[1].as_ref().unwrap().path());
+    }
+}
@@ -0,0 +1,380 @@
+pub mod wal;
+
+use crate::storage::encrypted_file::RecordCipher;
+use crate::{
+    Data, DataCollection, DataPath, DataStorer, DataStorerError, Sort, SortOrder, StorageError,
+};
+use async_trait::async_trait;
+use std::{
+    collections::BTreeSet,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::fs;
+use wal::{FsyncPolicy, WriteAheadLog};
+
+/// Extension of files holding plaintext JSON records
+const PLAIN_EXTENSION: &str = "json";
+/// Extension of files holding encrypted records
+const ENCRYPTED_EXTENSION: &str = "enc";
+
+/// Stores each `Data` as a file in a single directory, named after its path
+/// with any characters unsafe in file names percent-encoded. Writes go to a
+/// temporary file which is then renamed over the record, so a record is never
+/// observed half-written. Collections are always ordered by path.
+///
+/// Renaming alone does not survive power loss, which can persist the rename
+/// before the record's contents; storers opened with `with_wal` log every
+/// write ahead of applying it and flush to disk according to a
+/// `FsyncPolicy`, so that a torn record is rewritten when next opened.
+#[derive(Clone)]
+pub struct FileDataStorer {
+    root: PathBuf,
+    cipher: Option<RecordCipher>,
+    wal: Option<Arc<WriteAheadLog>>,
+}
+
+impl FileDataStorer {
+    /// Instantiates a file-backed storer keeping its records in the given
+    /// directory, which is created if it does not exist
+    pub async fn new<P: Into<PathBuf>>(root: P) -> Result<Self, DataStorerError> {
+        let root = root.into();
+        fs::create_dir_all(&root).await.map_err(internal_error)?;
+        Ok(FileDataStorer {
+            root,
+            cipher: None,
+            wal: None,
+        })
+    }
+
+    /// Instantiates a file-backed storer which logs writes ahead of applying
+    /// them and flushes them to disk according to the policy. Writes logged
+    /// before a crash are recovered into their records first.
+    pub async fn with_wal<P: Into<PathBuf>>(
+        root: P,
+        policy: FsyncPolicy,
+    ) -> Result<Self, DataStorerError> {
+        let mut storer = FileDataStorer::new(root).await?;
+        storer.wal = Some(Arc::new(WriteAheadLog::open(&storer.root, policy).await?));
+        Ok(storer)
+    }
+
+    /// Applies and flushes every write pending in the write-ahead log, if any
+    pub async fn sync(&self) -> Result<(), DataStorerError> {
+        match self.wal {
+            Some(ref wal) => wal.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the directory the records are kept in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Encrypts every record written from now on with the given cipher
+    pub(crate) fn with_cipher(mut self, cipher: RecordCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.cipher {
+            Some(_) => ENCRYPTED_EXTENSION,
+            None => PLAIN_EXTENSION,
+        }
+    }
+
+    fn record_file_name(&self, path: &str) -> String {
+        let path = DataPath::from(path).to_string();
+        format!("{}.{}", encode_file_name(&path), self.extension())
+    }
+
+    fn record_path(&self, path: &str) -> PathBuf {
+        self.root.join(self.record_file_name(path))
+    }
+
+    /// Reads the bytes of a record, or `None` if it does not exist, taking
+    /// writes pending in the write-ahead log into account
+    async fn read_record(&self, path: &str) -> Result<Option<Vec<u8>>, DataStorerError> {
+        if let Some(ref wal) = self.wal {
+            if let Some(pending) = wal.pending(&self.record_file_name(path)).await {
+                return Ok(pending);
+            }
+        }
+        match fs::read(self.record_path(path)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(internal_error(e)),
+        }
+    }
+
+    async fn read(&self, path: &str) -> Result<Data, DataStorerError> {
+        let bytes = self
+            .read_record(path)
+            .await?
+            .ok_or(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            })?;
+        let bytes = match self.cipher {
+            Some(ref cipher) => cipher.decrypt(path, &bytes)?,
+            None => bytes,
+        };
+        serde_json::from_slice(&bytes).map_err(internal_error)
+    }
+
+    /// Lists the paths of every record under the prefix, ordered by path
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, DataStorerError> {
+        let prefix = DataPath::from(prefix).to_string();
+        let suffix = format!(".{}", self.extension());
+        let mut entries = fs::read_dir(&self.root).await.map_err(internal_error)?;
+        let mut names = BTreeSet::new();
+        while let Some(entry) = entries.next_entry().await.map_err(internal_error)? {
+            if let Some(name) = entry.file_name().to_str() {
+                names.insert(name.to_owned());
+            }
+        }
+        if let Some(ref wal) = self.wal {
+            for (name, exists) in wal.pending_files().await {
+                if exists {
+                    names.insert(name);
+                } else {
+                    names.remove(&name);
+                }
+            }
+        }
+        let mut paths: Vec<_> = names
+            .iter()
+            .filter_map(|name| name.strip_suffix(&suffix))
+            .filter_map(decode_file_name)
+            .filter(|path| path.starts_with(&prefix))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Percent-encodes every character of a path which is not safe in file names
+fn encode_file_name(path: &str) -> String {
+    path.bytes()
+        .fold(String::with_capacity(path.len()), |mut name, b| {
+            if b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'_' {
+                name.push(b as char);
+            } else {
+                name.push_str(&format!("%{:02X}", b));
+            }
+            name
+        })
+}
+
+/// Reverses `encode_file_name`, returning `None` for names it cannot produce
+fn decode_file_name(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut chars = name.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Returns a path next to a record to write it through, unique to this write
+/// so that concurrent writes of one record, from this process or another,
+/// never share a temporary file
+fn temporary_path(record: &Path) -> PathBuf {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    record.with_extension(format!(
+        "tmp.{}.{}",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
+}
+
+#[async_trait]
+impl DataStorer for FileDataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.read(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut paths = self.list(path).await?;
+        if let Some(Sort {
+            order: SortOrder::Descending,
+            ..
+        }) = sort
+        {
+            paths.reverse();
+        }
+        let mut data = Vec::new();
+        for path in paths
+            .iter()
+            .skip(skip.max(0) as usize)
+            .take(page_size.max(0) as usize)
+        {
+            match self.read(path).await {
+                Ok(d) => data.push(d),
+                // Deleted since it was listed
+                Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                }) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(DataCollection::new(data))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        let bytes = serde_json::to_vec(&data).map_err(internal_error)?;
+        let bytes = match self.cipher {
+            Some(ref cipher) => cipher.encrypt(&path, &bytes)?,
+            None => bytes,
+        };
+        if let Some(ref wal) = self.wal {
+            wal.write(&self.record_file_name(&path), Some(bytes))
+                .await?;
+            return Ok(true);
+        }
+        let record = self.record_path(&path);
+        let temporary = temporary_path(&record);
+        fs::write(&temporary, &bytes)
+            .await
+            .map_err(internal_error)?;
+        fs::rename(&temporary, &record)
+            .await
+            .map_err(internal_error)?;
+        Ok(true)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        if let Some(ref wal) = self.wal {
+            let existed = self.read_record(path).await?.is_some();
+            wal.write(&self.record_file_name(path), None).await?;
+            return Ok(existed);
+        }
+        match fs::remove_file(self.record_path(path)).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(internal_error(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{decode_file_name, encode_file_name};
+    use crate::{
+        Aggregation, Data, DataStorer, DataStorerError, DataValue, FileDataStorer, Sort, SortBy,
+        SortOrder, StorageError,
+    };
+    use std::path::PathBuf;
+
+    /// Returns a fresh, empty directory for a test to keep records in
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("redact-data-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_file_names_round_trip() {
+        let path = ".users.a/b c.%.";
+        let name = encode_file_name(path);
+        assert_eq!(".users.a%2Fb%20c.%25.", name);
+        assert_eq!(Some(path.to_owned()), decode_file_name(&name));
+    }
+
+    #[tokio::test]
+    async fn test_create_get_delete() {
+        let dir = test_dir("file-crud");
+        let storer = FileDataStorer::new(&dir).await.unwrap();
+        let data = Data::new(".users.1.", DataValue::from("alice"));
+
+        storer.create(data.clone()).await.unwrap();
+        assert_eq!(data, storer.get(".users.1.").await.unwrap());
+        assert!(storer.delete(".users.1.").await.unwrap());
+        assert!(!storer.delete(".users.1.").await.unwrap());
+        assert!(matches!(
+            storer.get(".users.1.").await,
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound
+            })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_writes_of_a_record() {
+        let dir = test_dir("file-concurrent");
+        let storer = FileDataStorer::new(&dir).await.unwrap();
+        let writes = (0..16u64).map(|n| {
+            let storer = storer.clone();
+            tokio::spawn(async move {
+                storer
+                    .create(Data::new(".counter.", DataValue::from(n)))
+                    .await
+            })
+        });
+        for write in futures::future::join_all(writes).await {
+            assert!(write.unwrap().unwrap());
+        }
+        assert!(storer.get(".counter.").await.is_ok());
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_collections_are_ordered_by_path() {
+        let dir = test_dir("file-collection");
+        let storer = FileDataStorer::new(&dir).await.unwrap();
+        for i in &[3u64, 1, 2] {
+            storer
+                .create(Data::new(&format!(".n.{}.", i), DataValue::from(*i)))
+                .await
+                .unwrap();
+        }
+        storer
+            .create(Data::new(".other.", DataValue::from(9u64)))
+            .await
+            .unwrap();
+
+        let page = storer
+            .get_collection(
+                ".n.",
+                1,
+                10,
+                Some(Sort::new(SortBy::Path, SortOrder::Descending)),
+            )
+            .await
+            .unwrap();
+        let paths: Vec<_> = page.data.iter().map(Data::path).collect();
+        assert_eq!(vec![".n.2.".to_owned(), ".n.1.".to_owned()], paths);
+        assert_eq!(
+            Some(6.0),
+            storer.aggregate(".n.", Aggregation::Sum).await.unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use super::{internal_error, temporary_path};
+use crate::DataStorerError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+/// Name of the write-ahead log within the directory of the records
+pub const WAL_FILE_NAME: &str = "write-ahead.log";
+
+/// Number of pending writes after which a log which is never flushed is
+/// checkpointed anyway, to bound its size
+const UNFLUSHED_CHECKPOINT_WRITES: usize = 1024;
+
+/// When writes to a file storer with a write-ahead log are flushed to disk
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Flushes every write to disk before acknowledging it
+    Always,
+    /// Flushes pending writes on the first write once the interval has
+    /// elapsed since the last flush, or on `FileDataStorer::sync`. Writes
+    /// since the last flush can be lost to power loss, but never leave a
+    /// record half-written.
+    Interval(Duration),
+    /// Leaves flushing to the operating system, which survives the process
+    /// crashing but not power loss
+    Never,
+}
+
+/// A write to a record file, as logged
+#[derive(Serialize, Deserialize)]
+struct WalEntry {
+    file: String,
+    /// The bytes of the record, or `None` if it was deleted
+    record: Option<Vec<u8>>,
+}
+
+struct WalState {
+    log: File,
+    /// Writes logged since the last checkpoint, by record file name
+    pending: BTreeMap<String, Option<Vec<u8>>>,
+    last_flush: Instant,
+}
+
+/// Logs writes to record files before they are applied. Logged writes are
+/// kept in memory and applied to their records at a checkpoint, after the
+/// log has been flushed, so that every record being written is covered by a
+/// durable log entry and can be rewritten on recovery.
+pub(crate) struct WriteAheadLog {
+    root: PathBuf,
+    policy: FsyncPolicy,
+    state: Mutex<WalState>,
+}
+
+impl WriteAheadLog {
+    /// Opens the log kept in the directory, first replaying into their records
+    /// any writes logged before a crash
+    pub(crate) async fn open(root: &Path, policy: FsyncPolicy) -> Result<Self, DataStorerError> {
+        let path = root.join(WAL_FILE_NAME);
+        let logged = match fs::read(&path).await {
+            Ok(logged) => logged,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(internal_error(e)),
+        };
+        // A torn final entry was never acknowledged, so is dropped
+        let pending = logged
+            .split(|b| *b == b'\n')
+            .map_while(|line| serde_json::from_slice::<WalEntry>(line).ok())
+            .map(|entry| (entry.file, entry.record))
+            .collect();
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(internal_error)?;
+
+        let wal = WriteAheadLog {
+            root: root.to_owned(),
+            policy,
+            state: Mutex::new(WalState {
+                log,
+                pending,
+                last_flush: Instant::now(),
+            }),
+        };
+        wal.checkpoint(&mut *wal.state.lock().await, true).await?;
+        Ok(wal)
+    }
+
+    /// Logs a write of the record file, or its deletion if `record` is `None`,
+    /// checkpointing the log if the fsync policy calls for it
+    pub(crate) async fn write(
+        &self,
+        file: &str,
+        record: Option<Vec<u8>>,
+    ) -> Result<(), DataStorerError> {
+        let mut state = self.state.lock().await;
+        let entry = WalEntry {
+            file: file.to_owned(),
+            record,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(internal_error)?;
+        line.push(b'\n');
+        state.log.write_all(&line).await.map_err(internal_error)?;
+        state.log.flush().await.map_err(internal_error)?;
+        state.pending.insert(entry.file, entry.record);
+
+        let due = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Interval(interval) => state.last_flush.elapsed() >= interval,
+            FsyncPolicy::Never => state.pending.len() >= UNFLUSHED_CHECKPOINT_WRITES,
+        };
+        if due {
+            self.checkpoint(&mut state, self.policy != FsyncPolicy::Never)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the pending write of the record file: `Some(None)` if it was
+    /// deleted, or `None` if it has not been written since the last checkpoint
+    pub(crate) async fn pending(&self, file: &str) -> Option<Option<Vec<u8>>> {
+        self.state.lock().await.pending.get(file).cloned()
+    }
+
+    /// Returns the record files with pending writes, along with whether they
+    /// exist after those writes
+    pub(crate) async fn pending_files(&self) -> Vec<(String, bool)> {
+        let state = self.state.lock().await;
+        state
+            .pending
+            .iter()
+            .map(|(file, record)| (file.clone(), record.is_some()))
+            .collect()
+    }
+
+    /// Applies every pending write to its record, flushing to disk unless the
+    /// policy is to never flush
+    pub(crate) async fn flush(&self) -> Result<(), DataStorerError> {
+        let mut state = self.state.lock().await;
+        self.checkpoint(&mut state, self.policy != FsyncPolicy::Never)
+            .await
+    }
+
+    async fn checkpoint(&self, state: &mut WalState, sync: bool) -> Result<(), DataStorerError> {
+        if sync {
+            state.log.sync_data().await.map_err(internal_error)?;
+        }
+        for (file, record) in std::mem::take(&mut state.pending) {
+            let target = self.root.join(&file);
+            match record {
+                Some(bytes) => {
+                    let temporary = temporary_path(&target);
+                    let mut f = File::create(&temporary).await.map_err(internal_error)?;
+                    f.write_all(&bytes).await.map_err(internal_error)?;
+                    f.flush().await.map_err(internal_error)?;
+                    if sync {
+                        f.sync_data().await.map_err(internal_error)?;
+                    }
+                    fs::rename(&temporary, &target)
+                        .await
+                        .map_err(internal_error)?;
+                }
+                None => match fs::remove_file(&target).await {
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(internal_error(e)),
+                    _ => (),
+                },
+            }
+        }
+        // Directories cannot be opened as files on every platform, in which
+        // case renames are left for the filesystem to persist
+        if sync {
+            if let Ok(dir) = File::open(&self.root).await {
+                dir.sync_all().await.map_err(internal_error)?;
+            }
+        }
+        state.log.set_len(0).await.map_err(internal_error)?;
+        state.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WalEntry, WAL_FILE_NAME};
+    use crate::storage::file::tests::test_dir;
+    use crate::{Data, DataStorer, DataValue, FileDataStorer, FsyncPolicy};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_recovery_rewrites_torn_records() {
+        let dir = test_dir("wal-recovery");
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = Data::new(".a.", DataValue::from(1u64));
+        let mut log = Vec::new();
+        for entry in [
+            WalEntry {
+                file: ".a..json".to_owned(),
+                record: Some(serde_json::to_vec(&data).unwrap()),
+            },
+            WalEntry {
+                file: ".b..json".to_owned(),
+                record: None,
+            },
+        ] {
+            log.extend(serde_json::to_vec(&entry).unwrap());
+            log.push(b'\n');
+        }
+        log.extend(b"{\"file\":\".c..json\",\"rec");
+        std::fs::write(dir.join(WAL_FILE_NAME), log).unwrap();
+        std::fs::write(dir.join(".a..json"), b"{\"path\":").unwrap();
+        std::fs::write(dir.join(".b..json"), b"{}").unwrap();
+
+        let storer = FileDataStorer::with_wal(&dir, FsyncPolicy::Always)
+            .await
+            .unwrap();
+        assert_eq!(data, storer.get(".a.").await.unwrap());
+        assert!(storer.get(".b.").await.is_err());
+        assert!(storer.get(".c.").await.is_err());
+        assert!(std::fs::read(dir.join(WAL_FILE_NAME)).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_always_applies_every_write() {
+        let dir = test_dir("wal-always");
+        let storer = FileDataStorer::with_wal(&dir, FsyncPolicy::Always)
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(dir.join(".a..json").exists());
+        assert!(std::fs::read(dir.join(WAL_FILE_NAME)).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_interval_serves_pending_writes() {
+        let dir = test_dir("wal-interval");
+        let storer =
+            FileDataStorer::with_wal(&dir, FsyncPolicy::Interval(Duration::from_secs(3600)))
+                .await
+                .unwrap();
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".b.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+        assert!(storer.delete(".b.").await.unwrap());
+
+        assert!(!dir.join(".a..json").exists());
+        assert_eq!("1", storer.get(".a.").await.unwrap().to_string());
+        let page = storer.get_collection(".", 0, 10, None).await.unwrap();
+        assert_eq!(1, page.data.len());
+
+        storer.sync().await.unwrap();
+        assert!(dir.join(".a..json").exists());
+        assert!(!dir.join(".b..json").exists());
+        assert!(std::fs::read(dir.join(WAL_FILE_NAME)).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
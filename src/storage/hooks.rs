@@ -0,0 +1,305 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataStorer, DataStorerError, DataValue,
+    EraseReport, OpContext, Operation, Sort,
+};
+use async_trait::async_trait;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Callbacks run around every operation of a storer, for attaching custom
+/// telemetry without writing a storer wrapper. Both callbacks default to
+/// doing nothing, so implementors only write the ones they need. They run
+/// inline with the operation and should return quickly.
+pub trait StorerHooks: Send + Sync {
+    /// Called before an operation on a path begins
+    fn on_start(&self, _op: Operation, _path: &str) {}
+
+    /// Called once an operation on a path has completed, with its outcome and
+    /// how long it took
+    fn on_complete(
+        &self,
+        _op: Operation,
+        _path: &str,
+        _result: Result<(), &DataStorerError>,
+        _elapsed: Duration,
+    ) {
+    }
+}
+
+/// A storer running hooks around every operation of another storer, as
+/// returned by `DataStorer::with_hooks`. Read-modify-writes such as
+/// `increment` are reported as a `Create` of their path, bulk creates as a
+/// `Create` of each entry, and operations computed from others, such as
+/// `stats`, as the operations they are made of. Clones share the hooks.
+pub struct HookedDataStorer<S: DataStorer, H: StorerHooks> {
+    storer: S,
+    hooks: Arc<H>,
+}
+
+impl<S: DataStorer, H: StorerHooks> Clone for HookedDataStorer<S, H> {
+    fn clone(&self) -> Self {
+        HookedDataStorer {
+            storer: self.storer.clone(),
+            hooks: self.hooks.clone(),
+        }
+    }
+}
+
+impl<S: DataStorer, H: StorerHooks> HookedDataStorer<S, H> {
+    /// Instantiates a storer running the hooks around an existing storer
+    pub fn new(storer: S, hooks: H) -> Self {
+        HookedDataStorer {
+            storer,
+            hooks: Arc::new(hooks),
+        }
+    }
+
+    async fn hook<T, F>(&self, op: Operation, path: &str, fut: F) -> Result<T, DataStorerError>
+    where
+        F: Future<Output = Result<T, DataStorerError>>,
+    {
+        self.hooks.on_start(op, path);
+        let started = Instant::now();
+        let result = fut.await;
+        self.hooks
+            .on_complete(op, path, result.as_ref().map(|_| ()), started.elapsed());
+        result
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer, H: StorerHooks> DataStorer for HookedDataStorer<S, H> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.hook(Operation::Get, path, self.storer.get(path)).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.hook(
+            Operation::GetCollection,
+            path,
+            self.storer.get_collection(path, skip, page_size, sort),
+        )
+        .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.hook(
+            Operation::GetCollection,
+            path,
+            self.storer
+                .get_filtered_collection(path, filter, skip, page_size, sort),
+        )
+        .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.hook(
+            Operation::GetCollection,
+            path,
+            self.storer.get_collection_page(path, cursor, page_size),
+        )
+        .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        self.hook(Operation::Create, &path, self.storer.create(data))
+            .await
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        let paths: Vec<String> = data.iter().map(Data::path).collect();
+        for path in &paths {
+            self.hooks.on_start(Operation::Create, path);
+        }
+        let started = Instant::now();
+        let result = self.storer.create_many(data).await;
+        let elapsed = started.elapsed();
+        for path in &paths {
+            self.hooks.on_complete(
+                Operation::Create,
+                path,
+                result.as_ref().map(|_| ()),
+                elapsed,
+            );
+        }
+        result
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.hook(
+            Operation::Create,
+            path,
+            self.storer.get_or_create(path, default),
+        )
+        .await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.hook(Operation::Create, path, self.storer.increment(path, delta))
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.hook(
+            Operation::Create,
+            path,
+            self.storer.compare_and_swap(path, expected, new),
+        )
+        .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.hook(Operation::Delete, path, self.storer.delete(path))
+            .await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        self.hook(
+            Operation::Delete,
+            path,
+            self.storer.delete_collection(path, filter),
+        )
+        .await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.hook(Operation::Erase, prefix, self.storer.erase(prefix))
+            .await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.hook(
+            Operation::Aggregate,
+            path,
+            self.storer.aggregate(path, aggregation),
+        )
+        .await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorerHooks;
+    use crate::{Data, DataStorer, DataStorerError, DataValue, MemoryDataStorer, Operation};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StorerHooks for Recorder {
+        fn on_start(&self, op: Operation, path: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("start {:?} {}", op, path));
+        }
+
+        fn on_complete(
+            &self,
+            op: Operation,
+            path: &str,
+            result: Result<(), &DataStorerError>,
+            _elapsed: Duration,
+        ) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("end {:?} {} {}", op, path, result.is_ok()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_around_operations() {
+        let recorder = Recorder::default();
+        let events = recorder.events.clone();
+        let storer = MemoryDataStorer::new().with_hooks(recorder);
+
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert!(storer.get(".b.").await.is_err());
+        assert_eq!(
+            vec![
+                "start Create .a.",
+                "end Create .a. true",
+                "start Get .b.",
+                "end Get .b. false",
+            ],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forwards_read_modify_writes() {
+        let recorder = Recorder::default();
+        let events = recorder.events.clone();
+        let storer = MemoryDataStorer::new().with_hooks(recorder);
+
+        storer
+            .create_many(vec![
+                Data::new(".a.", DataValue::from(1i64)),
+                Data::new(".b.", DataValue::from(1i64)),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(2, storer.increment(".a.", 1).await.unwrap());
+        assert_eq!(
+            vec![
+                "start Create .a.",
+                "start Create .b.",
+                "end Create .a. true",
+                "end Create .b. true",
+                "start Create .a.",
+                "end Create .a. true",
+            ],
+            *events.lock().unwrap()
+        );
+    }
+}
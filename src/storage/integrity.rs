@@ -0,0 +1,192 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataStorer, DataStorerError, EraseReport, OpContext,
+    Sort,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Determines what an `IntegrityDataStorer` does when it reads data whose
+/// integrity checksum does not match its contents.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityPolicy {
+    /// Logs a warning and returns the data anyway
+    Warn,
+    /// Fails the read with `DataStorerError::IntegrityMismatch`
+    Error,
+}
+
+/// Wraps a storer to seal every written `Data` with an integrity checksum and
+/// verify that checksum whenever the data is read back. Data without a
+/// checksum, such as data written before the wrapper was introduced, is
+/// returned unverified.
+#[derive(Clone)]
+pub struct IntegrityDataStorer<S: DataStorer> {
+    storer: S,
+    policy: IntegrityPolicy,
+}
+
+impl<S: DataStorer> IntegrityDataStorer<S> {
+    /// Instantiates an integrity-checking storer around an existing storer
+    pub fn new(storer: S, policy: IntegrityPolicy) -> Self {
+        IntegrityDataStorer { storer, policy }
+    }
+
+    fn verify(&self, data: Data) -> Result<Data, DataStorerError> {
+        match data.verify_integrity() {
+            Some(false) => match self.policy {
+                IntegrityPolicy::Warn => {
+                    tracing::warn!(path = %data.path(), "data failed integrity verification");
+                    Ok(data)
+                }
+                IntegrityPolicy::Error => {
+                    Err(DataStorerError::IntegrityMismatch { path: data.path() })
+                }
+            },
+            _ => Ok(data),
+        }
+    }
+
+    fn verify_page(&self, page: DataCollection) -> Result<DataCollection, DataStorerError> {
+        Ok(DataCollection {
+            data: page
+                .data
+                .into_iter()
+                .map(|d| self.verify(d))
+                .collect::<Result<_, _>>()?,
+            next_cursor: page.next_cursor,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for IntegrityDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(path).await?;
+        self.verify(data)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(path, skip, page_size, sort)
+            .await?;
+        self.verify_page(page)
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(path, cursor, page_size)
+            .await?;
+        self.verify_page(page)
+    }
+
+    async fn create(&self, mut data: Data) -> Result<bool, DataStorerError> {
+        data.seal_integrity();
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(prefix).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        Data, DataStorer, DataStorerError, DataValue, IntegrityDataStorer, IntegrityPolicy,
+    };
+
+    #[tokio::test]
+    async fn test_create_seals_data() {
+        let (inner, entries) = memory_storer();
+        let storer = IntegrityDataStorer::new(inner, IntegrityPolicy::Error);
+
+        storer
+            .create(Data::new(".path.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let stored = entries.lock().unwrap().get(".path.").cloned().unwrap();
+        assert_eq!(Some(true), stored.verify_integrity());
+        assert_eq!(stored, storer.get(".path.").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unsealed_data_is_returned() {
+        let (inner, entries) = memory_storer();
+        let storer = IntegrityDataStorer::new(inner, IntegrityPolicy::Error);
+        let data = Data::new(".path.", DataValue::from(1u64));
+
+        entries.lock().unwrap().insert(data.path(), data.clone());
+
+        assert_eq!(data, storer.get(".path.").await.unwrap());
+    }
+
+    fn tampered() -> Data {
+        let mut data = Data::new(".path.", DataValue::from(1u64));
+        data.seal_integrity();
+        let json = serde_json::to_string(&data).unwrap().replace("1}", "2}");
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_errors() {
+        let (inner, entries) = memory_storer();
+        let storer = IntegrityDataStorer::new(inner, IntegrityPolicy::Error);
+
+        entries
+            .lock()
+            .unwrap()
+            .insert(".path.".to_owned(), tampered());
+
+        let result = storer.get(".path.").await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::IntegrityMismatch { ref path }) if path == ".path."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_warns() {
+        let (inner, entries) = memory_storer();
+        let storer = IntegrityDataStorer::new(inner, IntegrityPolicy::Warn);
+
+        entries
+            .lock()
+            .unwrap()
+            .insert(".path.".to_owned(), tampered());
+
+        assert_eq!(tampered(), storer.get(".path.").await.unwrap());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataStorer, DataStorerError, EraseReport, KeyName,
+    OpContext, Sort,
+};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+
+/// Lists and resolves the named keys data can be encrypted by. The key names
+/// are the ones referenced by `Data::encryptedby`.
+#[async_trait]
+pub trait Keyring: Clone + Send + Sync {
+    type Key: Clone + Send + Sync;
+
+    /// Returns the names of every key in the keyring
+    async fn list(&self) -> Vec<String>;
+
+    /// Returns the key with the given name, or `None` if there is no such key
+    async fn resolve(&self, keyname: &str) -> Option<Self::Key>;
+}
+
+/// A keyring holding a fixed set of keys in memory
+#[derive(Clone)]
+pub struct StaticKeyring<K> {
+    keys: BTreeMap<String, K>,
+}
+
+impl<K> StaticKeyring<K> {
+    /// Builds an empty keyring
+    pub fn new() -> Self {
+        StaticKeyring {
+            keys: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a key under the given name, replacing any key already named so
+    pub fn with_key(mut self, keyname: &str, key: K) -> Self {
+        self.keys.insert(keyname.to_owned(), key);
+        self
+    }
+}
+
+impl<K> Default for StaticKeyring<K> {
+    fn default() -> Self {
+        StaticKeyring::new()
+    }
+}
+
+#[async_trait]
+impl<K: Clone + Send + Sync> Keyring for StaticKeyring<K> {
+    type Key = K;
+
+    async fn list(&self) -> Vec<String> {
+        self.keys.keys().cloned().collect()
+    }
+
+    async fn resolve(&self, keyname: &str) -> Option<K> {
+        self.keys.get(keyname).cloned()
+    }
+}
+
+/// Checks that every key named in the data's `encryptedby` or among its
+/// recipients can be resolved by the keyring, failing with
+/// `DataStorerError::UnknownEncryptionKey` otherwise
+pub async fn validate_encryptedby<K: Keyring>(
+    keyring: &K,
+    data: &Data,
+) -> Result<(), DataStorerError> {
+    let recipients = data.recipients().keys().map(KeyName::as_str);
+    for keyname in data.encryptedby().into_iter().chain(recipients) {
+        if keyring.resolve(keyname).await.is_none() {
+            return Err(DataStorerError::UnknownEncryptionKey {
+                keyname: keyname.to_owned(),
+                path: data.path(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Wraps a storer to reject writes of data encrypted by keys the keyring does
+/// not know, which would otherwise be stored but never be decryptable
+#[derive(Clone)]
+pub struct KeyringDataStorer<S: DataStorer, K: Keyring> {
+    storer: S,
+    keyring: K,
+}
+
+impl<S: DataStorer, K: Keyring> KeyringDataStorer<S, K> {
+    /// Instantiates a key-validating storer around an existing storer
+    pub fn new(storer: S, keyring: K) -> Self {
+        KeyringDataStorer { storer, keyring }
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer, K: Keyring> DataStorer for KeyringDataStorer<S, K> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        validate_encryptedby(&self.keyring, &data).await?;
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, path: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(path).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        Data, DataStorer, DataStorerError, DataType, DataValue, DataValueCollection,
+        EncryptedDataValue, KeyName, Keyring, KeyringDataStorer, StaticKeyring,
+    };
+
+    fn encrypted(keyname: &str) -> DataValue {
+        DataValue::Encrypted(EncryptedDataValue::new(
+            b"ciphertext".to_vec(),
+            DataType::String,
+            keyname,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_static_keyring_lists_and_resolves() {
+        let keyring = StaticKeyring::new().with_key("b", 2).with_key("a", 1);
+        assert_eq!(vec!["a".to_owned(), "b".to_owned()], keyring.list().await);
+        assert_eq!(Some(2), keyring.resolve("b").await);
+        assert_eq!(None, keyring.resolve("c").await);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unknown_keys() {
+        let (inner, entries) = memory_storer();
+        let storer = KeyringDataStorer::new(inner, StaticKeyring::new().with_key("k1", ()));
+        let data = Data::with_values(
+            ".secret.",
+            DataValueCollection::from(vec![
+                encrypted("k1"),
+                DataValue::from(1u64),
+                encrypted("k2"),
+            ]),
+        );
+
+        let err = storer.create(data).await.unwrap_err();
+        assert!(matches!(
+            err,
+            DataStorerError::UnknownEncryptionKey { ref keyname, ref path }
+                if keyname == "k2" && path == ".secret."
+        ));
+        assert!(entries.lock().unwrap().is_empty());
+
+        let mut data = Data::new(".secret.", encrypted("k1"));
+        data.add_recipient(KeyName::new("k3").unwrap(), vec![1]);
+        assert!(matches!(
+            storer.create(data).await,
+            Err(DataStorerError::UnknownEncryptionKey { ref keyname, .. }) if keyname == "k3"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_accepts_known_keys() {
+        let (inner, entries) = memory_storer();
+        let storer = KeyringDataStorer::new(inner, StaticKeyring::new().with_key("k1", ()));
+
+        assert!(storer
+            .create(Data::new(".secret.", encrypted("k1")))
+            .await
+            .unwrap());
+        assert!(storer
+            .create(Data::new(".plain.", DataValue::from(1u64)))
+            .await
+            .unwrap());
+        assert_eq!(2, entries.lock().unwrap().len());
+    }
+}
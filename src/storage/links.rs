@@ -0,0 +1,270 @@
+use crate::{Data, DataCollection, DataStorer, DataStorerError, StorageError};
+use futures::StreamExt;
+use std::collections::HashSet;
+
+/// Which way a traversal follows links between data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkDirection {
+    /// From data to the paths it links to, e.g. to the records it was
+    /// derived from
+    Outgoing,
+    /// From data to the data linking to it, e.g. to the records derived
+    /// from it
+    Incoming,
+}
+
+/// Fetches the data at each path the data at a path links to with the given
+/// type of link, one by one. Links to paths holding no data are skipped.
+pub(crate) async fn get_linked<S: DataStorer>(
+    storer: &S,
+    path: &str,
+    link_type: &str,
+) -> Result<DataCollection, DataStorerError> {
+    let data = storer.get(path).await?;
+    let mut linked = Vec::new();
+    for target in data.link_targets(link_type) {
+        match storer.get(target).await {
+            Ok(data) => linked.push(data),
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(DataCollection::new(linked))
+}
+
+/// Walks the links of the given type breadth-first from the data at a path,
+/// up to `depth` links away, fetching each level one by one. Incoming links
+/// are found by scanning every entry once per level.
+pub(crate) async fn traverse<S: DataStorer>(
+    storer: &S,
+    start: &str,
+    link_type: &str,
+    direction: LinkDirection,
+    depth: usize,
+) -> Result<DataCollection, DataStorerError> {
+    let mut graph = vec![storer.get(start).await?];
+    let mut seen: HashSet<String> = graph.iter().map(Data::path).collect();
+    let mut frontier = 0;
+    for _ in 0..depth {
+        let mut level = Vec::new();
+        match direction {
+            LinkDirection::Outgoing => {
+                for data in &graph[frontier..] {
+                    for target in data.link_targets(link_type) {
+                        if !seen.insert(target.to_owned()) {
+                            continue;
+                        }
+                        match storer.get(target).await {
+                            Ok(data) => level.push(data),
+                            Err(DataStorerError::StorageError {
+                                source: StorageError::NotFound,
+                            }) => continue,
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+            }
+            LinkDirection::Incoming => {
+                let targets: HashSet<String> = graph[frontier..].iter().map(Data::path).collect();
+                let mut entries = storer.iter_collection(".");
+                while let Some(entry) = entries.next().await {
+                    let entry = entry?;
+                    if links_to(&entry, link_type, &targets) && seen.insert(entry.path()) {
+                        level.push(entry);
+                    }
+                }
+            }
+        }
+        if level.is_empty() {
+            break;
+        }
+        frontier = graph.len();
+        graph.extend(level);
+    }
+    Ok(DataCollection::new(graph))
+}
+
+/// Walks the links of the given type breadth-first from the start data, up
+/// to `depth` links away, through a set of candidate entries fetched at once,
+/// e.g. by following links of every type. Candidates which cannot be reached,
+/// or are the start data itself, are dropped.
+pub(crate) fn reachable(
+    start: Data,
+    mut candidates: Vec<Data>,
+    link_type: &str,
+    direction: LinkDirection,
+    depth: usize,
+) -> Vec<Data> {
+    candidates.retain(|candidate| candidate.path() != start.path());
+    let mut graph = vec![start];
+    let mut frontier = 0;
+    for _ in 0..depth {
+        let sources: HashSet<String> = graph[frontier..].iter().map(Data::path).collect();
+        let targets: HashSet<String> = match direction {
+            LinkDirection::Outgoing => graph[frontier..]
+                .iter()
+                .flat_map(|data| data.link_targets(link_type))
+                .map(str::to_owned)
+                .collect(),
+            LinkDirection::Incoming => HashSet::new(),
+        };
+        let (level, rest): (Vec<Data>, Vec<Data>) =
+            candidates
+                .into_iter()
+                .partition(|candidate| match direction {
+                    LinkDirection::Outgoing => targets.contains(&candidate.path()),
+                    LinkDirection::Incoming => links_to(candidate, link_type, &sources),
+                });
+        candidates = rest;
+        if level.is_empty() {
+            break;
+        }
+        frontier = graph.len();
+        graph.extend(level);
+    }
+    graph
+}
+
+/// Returns whether the data links to any of the paths with the given type of
+/// link
+fn links_to(data: &Data, link_type: &str, paths: &HashSet<String>) -> bool {
+    data.link_targets(link_type)
+        .iter()
+        .any(|target| paths.contains(*target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reachable, LinkDirection};
+    use crate::{Data, DataStorer, DataStorerError, DataValue, MemoryDataStorer, StorageError};
+
+    /// Builds `.a.` derived from `.b.` and `.c.`, `.b.` derived from `.d.`,
+    /// `.d.` derived from `.a.`, and `.e.` owned by `.a.`
+    fn lineage() -> Vec<Data> {
+        let link = |path: &str, links: &[(&str, &str)]| {
+            let mut data = Data::new(path, DataValue::from(1u64));
+            for (link_type, target) in links {
+                data.add_link(link_type, target);
+            }
+            data
+        };
+        vec![
+            link(".a.", &[("derived_from", ".b."), ("derived_from", ".c.")]),
+            link(".b.", &[("derived_from", ".d.")]),
+            link(".c.", &[]),
+            link(".d.", &[("derived_from", ".a.")]),
+            link(".e.", &[("owner", ".a.")]),
+        ]
+    }
+
+    fn paths(data: &[Data]) -> Vec<String> {
+        data.iter().map(Data::path).collect()
+    }
+
+    #[tokio::test]
+    async fn test_traverse() {
+        let storer = MemoryDataStorer::new();
+        for data in lineage() {
+            storer.create(data).await.unwrap();
+        }
+
+        let graph = |start, direction, depth| {
+            let storer = storer.clone();
+            async move {
+                paths(
+                    &storer
+                        .traverse(start, "derived_from", direction, depth)
+                        .await
+                        .unwrap()
+                        .data,
+                )
+            }
+        };
+        assert_eq!(vec![".a."], graph(".a.", LinkDirection::Outgoing, 0).await);
+        assert_eq!(
+            vec![".a.", ".b.", ".c."],
+            graph(".a.", LinkDirection::Outgoing, 1).await
+        );
+        assert_eq!(
+            vec![".a.", ".b.", ".c.", ".d."],
+            graph(".a.", LinkDirection::Outgoing, 5).await
+        );
+        assert_eq!(
+            vec![".d.", ".b.", ".a."],
+            graph(".d.", LinkDirection::Incoming, 2).await
+        );
+        assert!(storer
+            .traverse(".z.", "derived_from", LinkDirection::Incoming, 1)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_reachable_keeps_links_of_the_type() {
+        let mut data = lineage();
+        let start = data.remove(0);
+        assert_eq!(
+            vec![".a.", ".b.", ".c.", ".d."],
+            paths(&reachable(
+                start.clone(),
+                lineage(),
+                "derived_from",
+                LinkDirection::Outgoing,
+                3
+            ))
+        );
+        assert_eq!(
+            vec![".a.", ".d.", ".b."],
+            paths(&reachable(
+                start.clone(),
+                data.clone(),
+                "derived_from",
+                LinkDirection::Incoming,
+                3
+            ))
+        );
+        assert_eq!(
+            vec![".a.", ".e."],
+            paths(&reachable(start, data, "owner", LinkDirection::Incoming, 3))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_linked() {
+        let storer = MemoryDataStorer::new();
+        let mut derived = Data::new(".derived.", DataValue::from(3u64));
+        derived.add_link("derived_from", ".source.1.");
+        derived.add_link("derived_from", ".source.2.");
+        derived.add_link("derived_from", ".missing.");
+        derived.add_link("owner", ".users.1.");
+        for data in [
+            derived,
+            Data::new(".source.1.", DataValue::from(1u64)),
+            Data::new(".source.2.", DataValue::from(2u64)),
+            Data::new(".users.1.", DataValue::from("ada")),
+        ] {
+            storer.create(data).await.unwrap();
+        }
+
+        let sources = storer
+            .get_linked(".derived.", "derived_from")
+            .await
+            .unwrap();
+        let paths: Vec<_> = sources.data.iter().map(Data::path).collect();
+        assert_eq!(vec![".source.1.", ".source.2."], paths);
+        assert!(storer
+            .get_linked(".source.1.", "derived_from")
+            .await
+            .unwrap()
+            .data
+            .is_empty());
+        assert!(matches!(
+            storer.get_linked(".nothing.", "owner").await,
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound
+            })
+        ));
+    }
+}
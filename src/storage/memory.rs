@@ -0,0 +1,287 @@
+use crate::{
+    Data, DataCollection, DataPath, DataStorer, DataStorerError, Sort, SortOrder, StorageError,
+};
+use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, Weak,
+    },
+    time::Duration,
+};
+use tokio::fs;
+
+#[derive(Default)]
+struct MemoryState {
+    entries: RwLock<BTreeMap<String, Data>>,
+    /// Whether the entries changed since the last snapshot
+    dirty: AtomicBool,
+    /// Held while a snapshot is written, so that saves never share the
+    /// temporary file and each one writes entries at least as recent as the
+    /// last
+    saving: tokio::sync::Mutex<()>,
+}
+
+/// Stores `Data` in memory, for development environments, small tools, and
+/// tests. Clones share the same entries.
+///
+/// Storers opened with `with_snapshots` load their entries from a snapshot
+/// file on start and periodically write a new snapshot while anything changed,
+/// so data survives restarts; writes made since the last snapshot are lost
+/// unless `save_snapshot` is called before shutting down.
+#[derive(Clone, Default)]
+pub struct MemoryDataStorer {
+    state: Arc<MemoryState>,
+    snapshot_path: Option<PathBuf>,
+}
+
+impl MemoryDataStorer {
+    /// Instantiates an empty in-memory storer
+    pub fn new() -> Self {
+        MemoryDataStorer::default()
+    }
+
+    /// Instantiates an in-memory storer holding the entries of the snapshot
+    /// file, if it exists, and writing a snapshot to it at every interval
+    /// in which the entries changed. Snapshotting stops once every clone of
+    /// the storer has been dropped.
+    pub async fn with_snapshots<P: Into<PathBuf>>(
+        snapshot_path: P,
+        interval: Duration,
+    ) -> Result<Self, DataStorerError> {
+        let snapshot_path = snapshot_path.into();
+        let entries = match fs::read(&snapshot_path).await {
+            Ok(bytes) => serde_json::from_slice::<Vec<Data>>(&bytes)
+                .map_err(internal_error)?
+                .into_iter()
+                .map(|data| (data.path(), data))
+                .collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(internal_error(e)),
+        };
+        let storer = MemoryDataStorer {
+            state: Arc::new(MemoryState {
+                entries: RwLock::new(entries),
+                ..MemoryState::default()
+            }),
+            snapshot_path: Some(snapshot_path.clone()),
+        };
+
+        let state = Arc::downgrade(&storer.state);
+        tokio::spawn(snapshot_periodically(state, snapshot_path, interval));
+        Ok(storer)
+    }
+
+    /// Returns the file snapshots are written to, if any
+    pub fn snapshot_path(&self) -> Option<&Path> {
+        self.snapshot_path.as_deref()
+    }
+
+    /// Writes a snapshot of the entries to the snapshot file now; does
+    /// nothing for storers without one
+    pub async fn save_snapshot(&self) -> Result<(), DataStorerError> {
+        match self.snapshot_path {
+            Some(ref path) => save_snapshot(&self.state, path).await,
+            None => Ok(()),
+        }
+    }
+}
+
+async fn snapshot_periodically(state: Weak<MemoryState>, path: PathBuf, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if state.dirty.load(Ordering::SeqCst) {
+            if let Err(e) = save_snapshot(&state, &path).await {
+                tracing::warn!(path = %path.display(), error = %e, "failed to write snapshot");
+            }
+        }
+    }
+}
+
+/// Writes the entries to a temporary file which is then renamed over the
+/// snapshot, so a snapshot is never observed half-written. Saves run one at a
+/// time.
+async fn save_snapshot(state: &MemoryState, path: &Path) -> Result<(), DataStorerError> {
+    let _saving = state.saving.lock().await;
+    state.dirty.store(false, Ordering::SeqCst);
+    let bytes = {
+        let entries = state.entries.read().unwrap();
+        serde_json::to_vec(&entries.values().collect::<Vec<_>>()).map_err(internal_error)?
+    };
+    let temporary = path.with_extension("tmp");
+    let written = async {
+        fs::write(&temporary, &bytes).await?;
+        fs::rename(&temporary, path).await
+    };
+    if let Err(e) = written.await {
+        state.dirty.store(true, Ordering::SeqCst);
+        return Err(internal_error(e));
+    }
+    Ok(())
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
+}
+
+#[async_trait]
+impl DataStorer for MemoryDataStorer {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        let entries = self.state.entries.read().unwrap();
+        entries
+            .get(&path)
+            .cloned()
+            .ok_or(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            })
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let prefix = DataPath::from(path).to_string();
+        let entries = self.state.entries.read().unwrap();
+        let matching = entries.range(prefix.clone()..).map(|(_, data)| data);
+        let matching = matching.take_while(|data| data.path().starts_with(&prefix));
+        let mut data: Vec<Data> = matching.cloned().collect();
+        if let Some(Sort {
+            order: SortOrder::Descending,
+            ..
+        }) = sort
+        {
+            data.reverse();
+        }
+        Ok(DataCollection::new(
+            data.into_iter()
+                .skip(skip.max(0) as usize)
+                .take(page_size.max(0) as usize)
+                .collect(),
+        ))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let mut entries = self.state.entries.write().unwrap();
+        entries.insert(data.path(), data);
+        self.state.dirty.store(true, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        let mut entries = self.state.entries.write().unwrap();
+        let existed = entries.remove(&path).is_some();
+        if existed {
+            self.state.dirty.store(true, Ordering::SeqCst);
+        }
+        Ok(existed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::file::tests::test_dir;
+    use crate::{Data, DataStorer, DataValue, MemoryDataStorer, Sort, SortBy, SortOrder};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_create_get_delete_and_collections() {
+        let storer = MemoryDataStorer::new();
+        for path in [".a.2.", ".a.1.", ".ab.", ".b."] {
+            storer
+                .create(Data::new(path, DataValue::from(1u64)))
+                .await
+                .unwrap();
+        }
+
+        assert!(storer.get("a.1").await.is_ok());
+        let page = storer
+            .get_collection(
+                ".a.",
+                0,
+                10,
+                Some(Sort::new(SortBy::Path, SortOrder::Descending)),
+            )
+            .await
+            .unwrap();
+        let paths: Vec<_> = page.data.iter().map(Data::path).collect();
+        assert_eq!(vec![".a.2.", ".a.1."], paths);
+        assert!(storer.delete(".a.1.").await.unwrap());
+        assert!(!storer.delete(".a.1.").await.unwrap());
+        assert!(storer.get(".a.1.").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_survive_restarts() {
+        let dir = test_dir("memory-snapshots");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot.json");
+
+        let storer = MemoryDataStorer::with_snapshots(&path, Duration::from_millis(10))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(path.exists());
+        storer
+            .create(Data::new(".b.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+        storer.save_snapshot().await.unwrap();
+        drop(storer);
+
+        let restarted = MemoryDataStorer::with_snapshots(&path, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!("1", restarted.get(".a.").await.unwrap().to_string());
+        assert_eq!("2", restarted.get(".b.").await.unwrap().to_string());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_snapshots() {
+        let dir = test_dir("memory-concurrent-snapshots");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot.json");
+
+        let storer = MemoryDataStorer::with_snapshots(&path, Duration::from_millis(1))
+            .await
+            .unwrap();
+        let saves = (0..16u64).map(|i| {
+            let storer = storer.clone();
+            tokio::spawn(async move {
+                storer
+                    .create(Data::new(&format!(".n.{}.", i), DataValue::from(i)))
+                    .await
+                    .unwrap();
+                storer.save_snapshot().await
+            })
+        });
+        for save in futures::future::join_all(saves).await {
+            save.unwrap().unwrap();
+        }
+        let saved: Vec<Data> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(16, saved.len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataStats, DataStorer, DataStorerError,
+    DataValue, EraseReport, OpContext, Sort,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The number of times one kind of operation ran, how many of those failed,
+/// and how long they took
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct OperationMetrics {
+    /// How many times the operation ran, successfully or not
+    pub count: u64,
+    /// How many of those runs failed
+    pub errors: u64,
+    /// The time all runs took together
+    pub total_latency: Duration,
+    /// The time the slowest run took
+    pub max_latency: Duration,
+}
+
+impl OperationMetrics {
+    /// Returns the time a run took on average, if the operation ever ran
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total_latency.div_f64(self.count as f64))
+    }
+
+    fn merge(&mut self, other: &OperationMetrics) {
+        self.count += other.count;
+        self.errors += other.errors;
+        self.total_latency += other.total_latency;
+        self.max_latency = self.max_latency.max(other.max_latency);
+    }
+}
+
+/// A point-in-time copy of the metrics a storer recorded: the counts and
+/// latencies of its operations by name, e.g. `get`, and counters of events
+/// such as `cache_hits` or `rate_limited_retries`. Every figure is cumulative
+/// since the storer was built, so snapshots can be exported as they are, e.g.
+/// with `to_prometheus`, or serialized, without tying the crate to a metrics
+/// library.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The metrics of each operation, by name
+    pub operations: BTreeMap<String, OperationMetrics>,
+    /// The number of times each event occurred, by name
+    pub counters: BTreeMap<String, u64>,
+}
+
+impl MetricsSnapshot {
+    /// Returns the metrics of the named operation, if it ever ran
+    pub fn operation(&self, name: &str) -> Option<&OperationMetrics> {
+        self.operations.get(name)
+    }
+
+    /// Returns the number of times the named event occurred
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters.get(name).copied().unwrap_or(0)
+    }
+
+    /// Adds the figures of another snapshot to these, e.g. to report the
+    /// wrappers and backend of a stack of storers together
+    pub fn merge(&mut self, other: &MetricsSnapshot) {
+        for (name, metrics) in &other.operations {
+            self.operations
+                .entry(name.clone())
+                .or_default()
+                .merge(metrics);
+        }
+        for (name, count) in &other.counters {
+            *self.counters.entry(name.clone()).or_default() += count;
+        }
+    }
+
+    /// Renders the snapshot in the Prometheus text exposition format, with
+    /// every metric name prefixed by the given namespace, e.g. `redact_data`
+    pub fn to_prometheus(&self, namespace: &str) -> String {
+        let mut text = String::new();
+        let families: [MetricFamily; 4] = [
+            ("operations_total", "counter", |m| m.count.to_string()),
+            ("operation_errors_total", "counter", |m| {
+                m.errors.to_string()
+            }),
+            ("operation_latency_seconds_total", "counter", |m| {
+                m.total_latency.as_secs_f64().to_string()
+            }),
+            ("operation_latency_seconds_max", "gauge", |m| {
+                m.max_latency.as_secs_f64().to_string()
+            }),
+        ];
+        if !self.operations.is_empty() {
+            for (family, kind, value) in families.iter() {
+                let _ = writeln!(text, "# TYPE {}_{} {}", namespace, family, kind);
+                for (name, metrics) in &self.operations {
+                    let _ = writeln!(
+                        text,
+                        "{}_{}{{operation=\"{}\"}} {}",
+                        namespace,
+                        family,
+                        name,
+                        value(metrics)
+                    );
+                }
+            }
+        }
+        for (name, count) in &self.counters {
+            let _ = writeln!(text, "# TYPE {}_{}_total counter", namespace, name);
+            let _ = writeln!(text, "{}_{}_total {}", namespace, name, count);
+        }
+        text
+    }
+}
+
+/// The name, Prometheus type, and value of a metric reported per operation
+type MetricFamily = (&'static str, &'static str, fn(&OperationMetrics) -> String);
+
+/// Records the metrics of a storer as it runs, to be read as a
+/// `MetricsSnapshot`. Clones of a storer share its recorder.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    snapshot: Mutex<MetricsSnapshot>,
+}
+
+impl Metrics {
+    /// Creates a recorder with nothing recorded
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Records one run of the named operation
+    pub fn record(&self, operation: &str, elapsed: Duration, succeeded: bool) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        let metrics = snapshot.operations.entry(operation.to_owned()).or_default();
+        metrics.merge(&OperationMetrics {
+            count: 1,
+            errors: u64::from(!succeeded),
+            total_latency: elapsed,
+            max_latency: elapsed,
+        });
+    }
+
+    /// Records one occurrence of the named event
+    pub fn increment(&self, counter: &str) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        *snapshot.counters.entry(counter.to_owned()).or_default() += 1;
+    }
+
+    /// Runs an operation, recording how long it took and whether it failed
+    pub async fn measure<T, E, F>(&self, operation: &str, fut: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let started = Instant::now();
+        let result = fut.await;
+        self.record(operation, started.elapsed(), result.is_ok());
+        result
+    }
+
+    /// Returns a copy of everything recorded so far
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+}
+
+/// Wraps a storer, typically a backend, to record the count, failures and
+/// latency of each of its operations, read with `metrics`.
+#[derive(Clone)]
+pub struct MeteredDataStorer<S: DataStorer> {
+    storer: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S: DataStorer> MeteredDataStorer<S> {
+    /// Instantiates a storer recording the metrics of an existing storer
+    pub fn new(storer: S) -> Self {
+        MeteredDataStorer {
+            storer,
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    /// Returns a snapshot of the metrics recorded so far
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for MeteredDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.metrics.measure("get", self.storer.get(path)).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.metrics
+            .measure(
+                "get_collection",
+                self.storer.get_collection(path, skip, page_size, sort),
+            )
+            .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.metrics
+            .measure(
+                "get_filtered_collection",
+                self.storer
+                    .get_filtered_collection(path, filter, skip, page_size, sort),
+            )
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.metrics
+            .measure(
+                "get_collection_page",
+                self.storer.get_collection_page(path, cursor, page_size),
+            )
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.metrics
+            .measure("create", self.storer.create(data))
+            .await
+    }
+
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        self.metrics
+            .measure("create_many", self.storer.create_many(data))
+            .await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.metrics
+            .measure("get_or_create", self.storer.get_or_create(path, default))
+            .await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.metrics
+            .measure("increment", self.storer.increment(path, delta))
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.metrics
+            .measure(
+                "compare_and_swap",
+                self.storer.compare_and_swap(path, expected, new),
+            )
+            .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.metrics
+            .measure("delete", self.storer.delete(path))
+            .await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        self.metrics
+            .measure(
+                "delete_collection",
+                self.storer.delete_collection(path, filter),
+            )
+            .await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.metrics
+            .measure("erase", self.storer.erase(prefix))
+            .await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.metrics
+            .measure("aggregate", self.storer.aggregate(path, aggregation))
+            .await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.metrics
+            .measure("stats", self.storer.stats(prefix))
+            .await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeteredDataStorer, Metrics, MetricsSnapshot};
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataValue, MemoryDataStorer};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_records_operations() {
+        let (inner, _) = memory_storer();
+        let storer = MeteredDataStorer::new(inner);
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer.get(".a.").await.unwrap();
+        assert!(storer.get(".b.").await.is_err());
+
+        let metrics = storer.metrics();
+        assert_eq!(1, metrics.operation("create").unwrap().count);
+        let gets = metrics.operation("get").unwrap();
+        assert_eq!((2, 1), (gets.count, gets.errors));
+        assert!(gets.mean_latency().unwrap() <= gets.max_latency);
+        assert!(metrics.operation("delete").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_records_read_modify_writes_as_themselves() {
+        let storer = MeteredDataStorer::new(MemoryDataStorer::new());
+        storer
+            .create_many(vec![Data::new(".a.", DataValue::from(1i64))])
+            .await
+            .unwrap();
+        storer.increment(".a.", 1).await.unwrap();
+
+        let metrics = storer.metrics();
+        assert_eq!(1, metrics.operation("create_many").unwrap().count);
+        assert_eq!(1, metrics.operation("increment").unwrap().count);
+        assert!(metrics.operation("get").is_none());
+        assert!(metrics.operation("create").is_none());
+    }
+
+    #[test]
+    fn test_snapshots_merge_and_render() {
+        let cache = Metrics::new();
+        cache.increment("cache_hits");
+        cache.record("get", Duration::from_millis(500), true);
+        let backend = Metrics::new();
+        backend.record("get", Duration::from_secs(1), false);
+
+        let mut snapshot = MetricsSnapshot::default();
+        snapshot.merge(&cache.snapshot());
+        snapshot.merge(&backend.snapshot());
+        let gets = snapshot.operation("get").unwrap();
+        assert_eq!((2, 1), (gets.count, gets.errors));
+        assert_eq!(Duration::from_secs(1), gets.max_latency);
+        assert_eq!(1, snapshot.counter("cache_hits"));
+        assert_eq!(0, snapshot.counter("cache_misses"));
+
+        let text = snapshot.to_prometheus("redact");
+        assert!(text.contains("# TYPE redact_operations_total counter\n"));
+        assert!(text.contains("redact_operations_total{operation=\"get\"} 2\n"));
+        assert!(text.contains("redact_operation_latency_seconds_total{operation=\"get\"} 1.5\n"));
+        assert!(text.contains("redact_cache_hits_total 1\n"));
+    }
+}
@@ -1,52 +1,738 @@
-use crate::storage::{error::StorageError, Data, DataStorer};
+use crate::storage::{atomic, error::StorageError, links, Data, DataStorer};
 use async_trait::async_trait;
 use futures::StreamExt;
-use mongodb::{bson, options::ClientOptions, options::FindOneOptions, Client, Database};
-use crate::DataStorerError;
+use mongodb::{
+    bson::{self, oid::ObjectId, spec::BinarySubtype, Binary, Bson, Document},
+    options::ClientOptions,
+    options::CollectionOptions,
+    options::ReadPreference,
+    options::SelectionCriteria,
+    options::WriteConcern,
+    options::AggregateOptions,
+    options::FindOneAndDeleteOptions,
+    options::FindOneAndReplaceOptions,
+    options::FindOneAndUpdateOptions,
+    options::FindOneOptions,
+    options::FindOptions,
+    options::ReturnDocument,
+    options::UpdateOptions,
+    Client, Collection, Database,
+};
+use crate::{
+    Aggregation, Cursor, Operation, DataCollection, DataFilter, DataStats, DataType, EncryptionFilter, DataStorerError, DataValue, EraseReport, LinkDirection, PageSizeLimits,
+    Sort, SortBy, OpContext, SortOrder, TenantPartitioned, UnencryptedDataValue,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, RwLock, Weak},
+    time::Duration,
+};
+
+/// Size of the chunks GridFS files are split into, matching the GridFS default
+const GRIDFS_CHUNK_SIZE: usize = 255 * 1024;
+
+/// The most upserts `create_many` has in flight at once
+const CREATE_MANY_CONCURRENCY: usize = 16;
+
+/// The most paths `erase` deletes with one command
+const ERASE_BATCH_SIZE: usize = 1000;
+
+/// Connection state of a `MongoDataStorer`, as last observed by its health
+/// monitor. Storers without a monitor always report themselves healthy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MongoHealth {
+    Healthy,
+    /// The cluster could not be reached; operations fail immediately with
+    /// `DataStorerError::Unavailable` until it can be again
+    Degraded { reason: String },
+}
+
+/// Settings of the background task monitoring a `MongoDataStorer`'s connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MongoHealthConfig {
+    /// How often the cluster is pinged
+    pub interval: Duration,
+    /// How long a ping may take before the cluster is considered unreachable
+    pub ping_timeout: Duration,
+    /// After how many consecutive failed pings the client is rebuilt, which
+    /// resolves the DNS seeds of the connection string again
+    pub reconnect_after: u32,
+}
+
+impl Default for MongoHealthConfig {
+    fn default() -> Self {
+        MongoHealthConfig {
+            interval: Duration::from_secs(10),
+            ping_timeout: Duration::from_secs(5),
+            reconnect_after: 3,
+        }
+    }
+}
+
+/// An index to create on the data collection in addition to those the storer
+/// needs itself, declared with `MongoDataStorer::with_index`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MongoIndex {
+    name: String,
+    keys: Document,
+    unique: bool,
+    partial_filter: Option<Document>,
+}
+
+impl MongoIndex {
+    /// Starts declaring an index with the given name and no keys
+    pub fn new(name: &str) -> Self {
+        MongoIndex {
+            name: name.to_owned(),
+            keys: Document::new(),
+            unique: false,
+            partial_filter: None,
+        }
+    }
+
+    /// Declares a compound index on the path and the value of a label, e.g.
+    /// to look up the entries under a path carrying that label
+    pub fn path_and_label(label: &str) -> Self {
+        MongoIndex::new(&format!("path_label_{}", label))
+            .key("path", SortOrder::Ascending)
+            .key(&format!("labels.{}", label), SortOrder::Ascending)
+    }
+
+    /// Appends a field to the keys of the index
+    pub fn key(mut self, field: &str, order: SortOrder) -> Self {
+        let direction = match order {
+            SortOrder::Ascending => 1,
+            SortOrder::Descending => -1,
+        };
+        self.keys.insert(field, direction);
+        self
+    }
+
+    /// Rejects documents sharing the values of the index's keys
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Only indexes the documents matching a filter expression
+    pub fn partial(mut self, filter: Document) -> Self {
+        self.partial_filter = Some(filter);
+        self
+    }
+
+    /// Only indexes the documents holding at least one encrypted value
+    pub fn encrypted_only(self) -> Self {
+        self.partial(bson::doc! { "value.Encrypted": { "$exists": true } })
+    }
+
+    /// Returns the index specification `createIndexes` expects
+    fn to_document(&self) -> Document {
+        let mut index = bson::doc! { "key": self.keys.clone(), "name": &self.name };
+        if self.unique {
+            index.insert("unique", true);
+        }
+        if let Some(ref filter) = self.partial_filter {
+            index.insert("partialFilterExpression", filter.clone());
+        }
+        index
+    }
+}
+
+/// The read preference and write concern of one kind of operation, overriding
+/// those of the connection string, e.g. to let reads which tolerate staleness
+/// go to secondaries while writes wait for a majority
+#[derive(Debug, Clone, Default)]
+pub struct MongoOperationOptions {
+    pub read_preference: Option<ReadPreference>,
+    pub write_concern: Option<WriteConcern>,
+}
+
+impl MongoOperationOptions {
+    /// Builds options keeping the defaults of the connection string
+    pub fn new() -> Self {
+        MongoOperationOptions::default()
+    }
+
+    /// Selects the members of the replica set the operation reads from
+    pub fn with_read_preference(mut self, read_preference: ReadPreference) -> Self {
+        self.read_preference = Some(read_preference);
+        self
+    }
+
+    /// Sets the acknowledgement the operation's writes wait for
+    pub fn with_write_concern(mut self, write_concern: WriteConcern) -> Self {
+        self.write_concern = Some(write_concern);
+        self
+    }
+}
+
+/// The database handle shared by clones of a storer, replaced when
+/// reconnecting. The handle keeps its client, and so the connection pool, alive.
+struct MongoConnection {
+    db: Database,
+    health: MongoHealth,
+}
 
 /// Stores an instance of a mongodb-backed data storer
 #[derive(Clone)]
 pub struct MongoDataStorer {
     url: String,
     db_name: String,
-    client: Client,
-    db: Database,
+    connection: Arc<RwLock<MongoConnection>>,
+    collection_name: String,
+    page_size_limits: PageSizeLimits,
+    gridfs_threshold: Option<usize>,
+    indexes: Vec<MongoIndex>,
+    operation_options: HashMap<Operation, MongoOperationOptions>,
+    context: OpContext,
 }
 
 impl MongoDataStorer {
     /// Instantiates a mongo-backed data storer using a URL to the mongo cluster and the
     /// name of the DB to connect to.
     pub async fn new(url: &str, db_name: &str) -> Self {
-        let db_client_options = ClientOptions::parse_with_resolver_config(
-            url,
-            mongodb::options::ResolverConfig::cloudflare(),
-        )
-        .await
-        .unwrap();
-        let client = Client::with_options(db_client_options).unwrap();
-        let db = client.database(db_name);
+        let db = connect(url, db_name).await.unwrap();
         MongoDataStorer {
             url: url.to_owned(),
             db_name: db_name.to_owned(),
-            client,
-            db,
+            connection: Arc::new(RwLock::new(MongoConnection {
+                db,
+                health: MongoHealth::Healthy,
+            })),
+            collection_name: "data".to_owned(),
+            page_size_limits: PageSizeLimits::default(),
+            gridfs_threshold: None,
+            indexes: Vec::new(),
+            operation_options: HashMap::new(),
+            context: OpContext::default(),
+        }
+    }
+
+    /// Stores data in the named collection rather than the default `data` collection
+    pub fn with_collection_name(mut self, collection_name: &str) -> Self {
+        self.collection_name = collection_name.to_owned();
+        self
+    }
+
+    /// Replaces the default page size limits applied to collection queries
+    pub fn with_page_size_limits(mut self, page_size_limits: PageSizeLimits) -> Self {
+        self.page_size_limits = page_size_limits;
+        self
+    }
+
+    /// Stores unencrypted bytes values larger than `threshold` bytes in GridFS
+    /// rather than inline in the data document, which only keeps a reference to
+    /// the GridFS file. Files are removed when the referencing data is replaced.
+    pub fn with_gridfs_threshold(mut self, threshold: usize) -> Self {
+        self.gridfs_threshold = Some(threshold);
+        self
+    }
+
+    /// Declares an additional index on the data collection, created along
+    /// with the storer's own by `ensure_indexes`
+    pub fn with_index(mut self, index: MongoIndex) -> Self {
+        self.indexes.push(index);
+        self
+    }
+
+    /// Overrides the read preference and write concern of one kind of
+    /// operation. Operations which read and write, such as `increment`, are
+    /// `Operation::Create`, and `stats` is `Operation::Aggregate`.
+    pub fn with_operation_options(
+        mut self,
+        operation: Operation,
+        options: MongoOperationOptions,
+    ) -> Self {
+        self.operation_options.insert(operation, options);
+        self
+    }
+
+    /// Monitors the connection to the cluster in the background, degrading the
+    /// storer while the cluster cannot be reached so that operations fail fast
+    /// instead of each waiting out server selection, and rebuilding the client
+    /// after repeated failures. The monitor stops once every clone of the
+    /// storer has been dropped.
+    pub fn with_health_monitor(self, config: MongoHealthConfig) -> Self {
+        tokio::spawn(monitor_health(
+            Arc::downgrade(&self.connection),
+            self.url.clone(),
+            self.db_name.clone(),
+            config,
+        ));
+        self
+    }
+
+    /// Returns the connection state last observed by the health monitor
+    pub fn health(&self) -> MongoHealth {
+        self.connection.read().unwrap().health.clone()
+    }
+
+    /// Returns the database, or fails if the health monitor found the cluster
+    /// unreachable
+    fn db(&self) -> Result<Database, DataStorerError> {
+        let connection = self.connection.read().unwrap();
+        match connection.health {
+            MongoHealth::Healthy => Ok(connection.db.clone()),
+            MongoHealth::Degraded { ref reason } => Err(DataStorerError::Unavailable {
+                reason: reason.clone(),
+            }),
+        }
+    }
+
+    /// Returns the data collection with the options of the given kind of
+    /// operation, or fails if the health monitor found the cluster unreachable
+    fn data_collection(&self, operation: Operation) -> Result<Collection, DataStorerError> {
+        let db = self.db()?;
+        match self.operation_options.get(&operation) {
+            Some(options) => Ok(db.collection_with_options(
+                &self.collection_name,
+                CollectionOptions::builder()
+                    .selection_criteria(
+                        options
+                            .read_preference
+                            .clone()
+                            .map(SelectionCriteria::ReadPreference),
+                    )
+                    .write_concern(options.write_concern.clone())
+                    .build(),
+            )),
+            None => Ok(db.collection(&self.collection_name)),
+        }
+    }
+
+    /// Returns the data collection as `data_collection` does, typed to
+    /// (de)serialize `Data` directly
+    fn typed_data_collection(&self, operation: Operation) -> Result<Collection<Data>, DataStorerError> {
+        Ok(self.data_collection(operation)?.clone_with_type::<Data>())
+    }
+
+    /// Returns how long the server may work on an operation before the
+    /// deadline of the storer's context, failing if it has already passed
+    fn max_time(&self) -> Result<Option<Duration>, DataStorerError> {
+        match self.context.remaining() {
+            Some(remaining) if remaining.is_zero() => Err(DataStorerError::DeadlineExceeded),
+            remaining => Ok(remaining),
+        }
+    }
+
+    /// Creates the index on the names of the keys encrypting each value, which
+    /// `find_by_key` queries. Creating an index which exists is a no-op.
+    pub async fn create_key_index(&self) -> Result<(), DataStorerError> {
+        self.db()?
+            .run_command(
+                bson::doc! {
+                    "createIndexes": &self.collection_name,
+                    "indexes": [{
+                        "key": { "value.Encrypted.keyname": 1 },
+                        "name": "encryptedby",
+                        "sparse": true,
+                    }],
+                },
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    /// Creates the index `create_key_index` creates and every index declared
+    /// with `with_index`. Creating indexes which exist is a no-op, but an
+    /// index declared with the name of an existing index with other options
+    /// fails.
+    pub async fn ensure_indexes(&self) -> Result<(), DataStorerError> {
+        self.create_key_index().await?;
+        if self.indexes.is_empty() {
+            return Ok(());
+        }
+        let indexes: Vec<Document> = self.indexes.iter().map(MongoIndex::to_document).collect();
+        self.db()?
+            .run_command(
+                bson::doc! {
+                    "createIndexes": &self.collection_name,
+                    "indexes": indexes,
+                },
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    /// Serializes data into a document, moving any bytes values above the
+    /// GridFS threshold into GridFS files
+    async fn to_document(&self, data: &Data) -> Result<Document, DataStorerError> {
+        let mut doc = bson::to_document(data).map_err(internal_error)?;
+        let threshold = match self.gridfs_threshold {
+            Some(threshold) => threshold,
+            None => return Ok(doc),
+        };
+
+        let serialized_values = doc.get_array("value").map_err(internal_error)?;
+        let mut values = Vec::with_capacity(serialized_values.len());
+        for (value, serialized) in data.value().0.iter().zip(serialized_values) {
+            match value {
+                DataValue::Unencrypted(UnencryptedDataValue::Bytes(bytes))
+                    if bytes.len() > threshold =>
+                {
+                    let id = self.gridfs_upload(&data.path(), bytes).await?;
+                    values.push(Bson::Document(bson::doc! {
+                        "GridFs": { "file_id": id, "length": bytes.len() as i64 }
+                    }));
+                }
+                _ => values.push(serialized.clone()),
+            }
+        }
+        doc.insert("value", values);
+        Ok(doc)
+    }
+
+    /// Deserializes a data document, downloading any values stored in GridFS
+    async fn to_data(&self, mut doc: Document) -> Result<Data, DataStorerError> {
+        if let Ok(values) = doc.get_array_mut("value") {
+            for value in values.iter_mut() {
+                if let Some(id) = gridfs_file_id(value) {
+                    let bytes = self.gridfs_download(&id).await?;
+                    *value = bson::to_bson(&DataValue::from(bytes)).map_err(internal_error)?;
+                }
+            }
+        }
+        bson::from_document::<Data>(doc).map_err(internal_error)
+    }
+
+    async fn gridfs_upload(&self, filename: &str, bytes: &[u8]) -> Result<ObjectId, DataStorerError> {
+        let id = ObjectId::new();
+        let chunks = bytes
+            .chunks(GRIDFS_CHUNK_SIZE)
+            .enumerate()
+            .map(|(n, chunk)| {
+                bson::doc! {
+                    "files_id": id.clone(),
+                    "n": n as i32,
+                    "data": Binary { subtype: BinarySubtype::Generic, bytes: chunk.to_vec() },
+                }
+            });
+        self.db()?
+            .collection("fs.chunks")
+            .insert_many(chunks, None)
+            .await
+            .map_err(internal_error)?;
+        self.db()?
+            .collection("fs.files")
+            .insert_one(
+                bson::doc! {
+                    "_id": id.clone(),
+                    "length": bytes.len() as i64,
+                    "chunkSize": GRIDFS_CHUNK_SIZE as i32,
+                    "uploadDate": id.timestamp(),
+                    "filename": filename,
+                },
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(id)
+    }
+
+    async fn gridfs_download(&self, id: &ObjectId) -> Result<Vec<u8>, DataStorerError> {
+        let filter_options = FindOptions::builder().sort(bson::doc! { "n": 1 }).build();
+        let mut cursor = self
+            .db()?
+            .collection("fs.chunks")
+            .find(bson::doc! { "files_id": id.clone() }, filter_options)
+            .await
+            .map_err(internal_error)?;
+        let mut bytes = Vec::new();
+        while let Some(chunk) = cursor.next().await {
+            let chunk = chunk.map_err(internal_error)?;
+            bytes.extend_from_slice(chunk.get_binary_generic("data").map_err(internal_error)?);
+        }
+        Ok(bytes)
+    }
+
+    /// Removes every GridFS file referenced by a data document
+    async fn gridfs_delete_referenced(&self, doc: &Document) -> Result<(), DataStorerError> {
+        let ids = doc
+            .get_array("value")
+            .map(|values| values.iter().filter_map(gridfs_file_id).collect())
+            .unwrap_or_else(|_| Vec::new());
+        for id in ids {
+            self.db()?
+                .collection("fs.chunks")
+                .delete_many(bson::doc! { "files_id": id.clone() }, None)
+                .await
+                .map_err(internal_error)?;
+            self.db()?
+                .collection("fs.files")
+                .delete_one(bson::doc! { "_id": id }, None)
+                .await
+                .map_err(internal_error)?;
+        }
+        Ok(())
+    }
+
+    /// Converts a sort specification into a mongo sort document. Documents are
+    /// never re-inserted on update, so their `_id` reflects their creation time.
+    fn sort_document(sort: Sort) -> Document {
+        let field = match sort.by {
+            SortBy::Path => "path",
+            SortBy::CreatedAt => "_id",
+        };
+        let direction = match sort.order {
+            SortOrder::Ascending => 1,
+            SortOrder::Descending => -1,
+        };
+        bson::doc! { field: direction }
+    }
+
+    /// Builds the `$elemMatch` condition a value must satisfy to match a
+    /// filter. Unencrypted bytes moved to GridFS count as unencrypted bytes.
+    pub(crate) fn value_filter(filter: &DataFilter) -> Option<Document> {
+        let gridfs = bson::doc! { "GridFs": { "$exists": true } };
+        let unencrypted = |datatype: Option<&DataType>| match datatype {
+            Some(DataType::Bytes) => bson::doc! { "$or": [
+                { "Unencrypted.Bytes": { "$exists": true } },
+                gridfs.clone(),
+            ] },
+            Some(datatype) => bson::doc! { format!("Unencrypted.{:?}", datatype): { "$exists": true } },
+            None => bson::doc! { "$or": [
+                { "Unencrypted": { "$exists": true } },
+                gridfs.clone(),
+            ] },
+        };
+        let encrypted = |datatype: Option<&DataType>, keyname: Option<&str>| {
+            let mut condition = bson::doc! { "Encrypted": { "$exists": true } };
+            if let Some(datatype) = datatype {
+                condition.insert("Encrypted.datatype", format!("{:?}", datatype));
+            }
+            if let Some(keyname) = keyname {
+                condition.insert("Encrypted.keyname", keyname);
+            }
+            condition
+        };
+
+        let datatype = filter.datatype.as_ref();
+        match (filter.encryption.as_ref(), datatype) {
+            (None, None) => None,
+            (None, Some(_)) => Some(bson::doc! { "$or": [
+                unencrypted(datatype),
+                encrypted(datatype, None),
+            ] }),
+            (Some(EncryptionFilter::Unencrypted), _) => Some(unencrypted(datatype)),
+            (Some(EncryptionFilter::Encrypted), _) => Some(encrypted(datatype, None)),
+            (Some(EncryptionFilter::EncryptedBy(keyname)), _) => {
+                Some(encrypted(datatype, Some(keyname)))
+            }
         }
     }
+
+    /// Builds a filter matching every document whose path begins with the given path
+    fn prefix_filter(path: &str) -> Document {
+        bson::doc! { "path": { "$regex": format!("^{}", escape_regex(path)) } }
+    }
+}
+
+/// Returns the GridFS file id if the serialized value is a GridFS reference
+fn gridfs_file_id(value: &Bson) -> Option<ObjectId> {
+    match value {
+        Bson::Document(doc) => doc
+            .get_document("GridFs")
+            .and_then(|reference| reference.get_object_id("file_id"))
+            .ok()
+            .cloned(),
+        _ => None,
+    }
+}
+
+/// Returns the `$graphLookup` stage fetching the entries within `depth`
+/// links of any type of the matched entry, following links in the given
+/// direction
+fn graph_lookup(collection_name: &str, direction: LinkDirection, depth: usize) -> Document {
+    let (start_with, connect_from, connect_to) = match direction {
+        LinkDirection::Outgoing => ("$links.target", "links.target", "path"),
+        LinkDirection::Incoming => ("$path", "path", "links.target"),
+    };
+    bson::doc! {
+        "from": collection_name,
+        "startWith": start_with,
+        "connectFromField": connect_from,
+        "connectToField": connect_to,
+        "as": "reachable",
+        "maxDepth": depth as i64 - 1,
+    }
+}
+
+/// Reads a non-negative count produced by an aggregation, whatever its numeric type
+fn bson_count(value: Option<&Bson>) -> u64 {
+    match value {
+        Some(Bson::Int32(n)) => (*n).max(0) as u64,
+        Some(Bson::Int64(n)) => (*n).max(0) as u64,
+        Some(Bson::Double(n)) => n.max(0.0) as u64,
+        _ => 0,
+    }
+}
+
+/// Reads the integer held by a counter document, stored as a single `I64` value
+fn counter_of(doc: &Document) -> Option<i64> {
+    doc.get_array("value")
+        .ok()?
+        .first()?
+        .as_document()?
+        .get_document("Unencrypted")
+        .ok()?
+        .get_i64("I64")
+        .ok()
+}
+
+/// Builds the audit metadata recorded alongside written data from the
+/// principal and trace id of the context, or `None` if neither is set
+fn audit_document(context: &OpContext) -> Option<Document> {
+    if context.principal().is_none() && context.trace_id().is_none() {
+        return None;
+    }
+    let mut audit = Document::new();
+    if let Some(principal) = context.principal() {
+        audit.insert("principal", principal);
+    }
+    if let Some(trace_id) = context.trace_id() {
+        audit.insert("trace_id", trace_id);
+    }
+    Some(audit)
+}
+
+/// Extends an update of the values of a document with the changes replacing
+/// it through `create` makes to the rest of it: the audit metadata of the
+/// context is recorded, or dropped if it has none, and the integrity checksum,
+/// signature and write time of the old values are dropped
+fn with_metadata_update(context: &OpContext, mut update: Document) -> Document {
+    let mut unset = bson::doc! { "integrity": "", "signature": "", "written_at": "" };
+    match audit_document(context) {
+        Some(audit) => {
+            let mut set = update.get_document("$set").cloned().unwrap_or_default();
+            set.insert("audit", audit);
+            update.insert("$set", set);
+        }
+        None => {
+            unset.insert("audit", "");
+        }
+    }
+    update.insert("$unset", unset);
+    update
+}
+
+/// Builds a client for the cluster, resolving the DNS seeds of the URL, and
+/// returns the handle of the named database
+async fn connect(url: &str, db_name: &str) -> mongodb::error::Result<Database> {
+    let db_client_options = ClientOptions::parse_with_resolver_config(
+        url,
+        mongodb::options::ResolverConfig::cloudflare(),
+    )
+    .await?;
+    let client = Client::with_options(db_client_options)?;
+    Ok(client.database(db_name))
+}
+
+/// Pings the cluster until the connection is dropped, recording its health
+/// and reconnecting after `reconnect_after` consecutive failures
+async fn monitor_health(
+    connection: Weak<RwLock<MongoConnection>>,
+    url: String,
+    db_name: String,
+    config: MongoHealthConfig,
+) {
+    let mut failures = 0u32;
+    loop {
+        tokio::time::sleep(config.interval).await;
+        let db = match connection.upgrade() {
+            Some(connection) => connection.read().unwrap().db.clone(),
+            None => return,
+        };
+        let ping = db.run_command(bson::doc! { "ping": 1 }, None);
+        let health = match tokio::time::timeout(config.ping_timeout, ping).await {
+            Ok(Ok(_)) => MongoHealth::Healthy,
+            Ok(Err(e)) => MongoHealth::Degraded { reason: e.to_string() },
+            Err(_) => MongoHealth::Degraded {
+                reason: format!("ping timed out after {:?}", config.ping_timeout),
+            },
+        };
+        failures = match health {
+            MongoHealth::Healthy => 0,
+            MongoHealth::Degraded { .. } => failures + 1,
+        };
+        let reconnect = failures > 0 && failures.is_multiple_of(config.reconnect_after.max(1));
+        let replacement = if reconnect {
+            match connect(&url, &db_name).await {
+                Ok(replacement) => Some(replacement),
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to reconnect to mongodb");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let connection = match connection.upgrade() {
+            Some(connection) => connection,
+            None => return,
+        };
+        let mut connection = connection.write().unwrap();
+        if connection.health != health {
+            match health {
+                MongoHealth::Healthy => tracing::info!("mongodb connection recovered"),
+                MongoHealth::Degraded { ref reason } => {
+                    tracing::warn!(reason = %reason, "mongodb connection degraded")
+                }
+            }
+        }
+        connection.health = health;
+        if let Some(db) = replacement {
+            connection.db = db;
+        }
+    }
+}
+
+/// Wraps a driver or (de)serialization error as an internal storage error
+fn internal_error<E: Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e)
+        }
+    }
+}
+
+/// Escapes all regex metacharacters in the given string so it can be matched literally
+pub(crate) fn escape_regex(s: &str) -> String {
+    s.chars().fold(String::with_capacity(s.len()), |mut escaped, c| {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// Gives each tenant its own collection, named after the storer's collection
+/// and suffixed with the tenant id, e.g. `data_acme`
+impl TenantPartitioned for MongoDataStorer {
+    fn for_tenant(&self, tenant: &str) -> Self {
+        let collection_name = format!("{}_{}", self.collection_name, tenant);
+        self.clone().with_collection_name(&collection_name)
+    }
 }
 
 #[async_trait]
 impl DataStorer for MongoDataStorer {
     async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
-        let filter_options = FindOneOptions::builder().build();
+        let filter_options = FindOneOptions::builder().max_time(self.max_time()?).build();
         let filter = bson::doc! { "path": path };
 
         match self
-            .db
-            .collection_with_type::<Data>("data")
+            .data_collection(Operation::Get)?
             .find_one(filter, filter_options)
             .await
         {
-            Ok(Some(data)) => Ok(data),
+            Ok(Some(doc)) => self.to_data(doc).await,
             Ok(None) => Err(DataStorerError::StorageError {
                 source: StorageError::NotFound
             }),
@@ -58,15 +744,163 @@ impl DataStorer for MongoDataStorer {
         }
     }
 
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page_size = self.page_size_limits.resolve(page_size)?;
+        let filter_options = FindOptions::builder()
+            .skip(skip)
+            .limit(page_size)
+            .sort(sort.map(MongoDataStorer::sort_document))
+            .max_time(self.max_time()?)
+            .build();
+        let filter = MongoDataStorer::prefix_filter(path);
+
+        match self
+            .data_collection(Operation::GetCollection)?
+            .find(filter, filter_options)
+            .await
+        {
+            Ok(mut cursor) => {
+                let mut data = Vec::new();
+                while let Some(item) = cursor.next().await {
+                    let doc = item.map_err(internal_error)?;
+                    data.push(self.to_data(doc).await?);
+                }
+                Ok(DataCollection::new(data))
+            }
+            Err(e) => Err(DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e)
+                }
+            }),
+        }
+    }
+
+    /// Pushes the value filter down as a `$elemMatch` on `value`, paging with
+    /// `skip` and `limit`
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page_size = self.page_size_limits.resolve(page_size)?;
+        let find_options = FindOptions::builder()
+            .skip(skip)
+            .limit(page_size)
+            .sort(sort.map(MongoDataStorer::sort_document))
+            .max_time(self.max_time()?)
+            .build();
+        let mut query = MongoDataStorer::prefix_filter(path);
+        if let Some(value_filter) = MongoDataStorer::value_filter(filter) {
+            query.insert("value", bson::doc! { "$elemMatch": value_filter });
+        }
+
+        let mut cursor = self
+            .data_collection(Operation::GetCollection)?
+            .find(query, find_options)
+            .await
+            .map_err(internal_error)?;
+        let mut data = Vec::new();
+        while let Some(item) = cursor.next().await {
+            data.push(self.to_data(item.map_err(internal_error)?).await?);
+        }
+        Ok(DataCollection::new(data))
+    }
+
+    /// Pages through the collection in `_id` order, using the `_id` of the last
+    /// entry on a page as the cursor for the next.
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page_size = self.page_size_limits.resolve(page_size)?;
+        let filter = match cursor {
+            Some(cursor) => {
+                let id = ObjectId::with_string(cursor.as_str())
+                    .map_err(|_| DataStorerError::InvalidCursor)?;
+                bson::doc! { "$and": [
+                    MongoDataStorer::prefix_filter(path),
+                    { "_id": { "$gt": id } },
+                ] }
+            }
+            None => MongoDataStorer::prefix_filter(path),
+        };
+        let filter_options = FindOptions::builder()
+            .limit(page_size)
+            .sort(bson::doc! { "_id": 1 })
+            .max_time(self.max_time()?)
+            .build();
+
+        let mut cursor = self
+            .data_collection(Operation::GetCollection)?
+            .find(filter, filter_options)
+            .await
+            .map_err(|e| DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e)
+                }
+            })?;
+        let mut data = Vec::new();
+        let mut last_id = None;
+        while let Some(item) = cursor.next().await {
+            let doc = item.map_err(|e| DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e)
+                }
+            })?;
+            last_id = doc.get_object_id("_id").ok().map(ObjectId::to_hex);
+            data.push(self.to_data(doc).await?);
+        }
+
+        let mut page = DataCollection::new(data);
+        if page.data.len() as i64 >= page_size {
+            page.next_cursor = last_id.map(|id| Cursor::new(&id));
+        }
+        Ok(page)
+    }
+
     async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let max_time = self.max_time()?;
+        let filter = bson::doc! { "path": data.path() };
+
+        let audit = audit_document(&self.context);
+        if self.gridfs_threshold.is_some() || audit.is_some() {
+            let filter_options = FindOneAndReplaceOptions::builder()
+                .upsert(true)
+                .return_document(ReturnDocument::Before)
+                .max_time(max_time)
+                .build();
+            let mut doc = self.to_document(&data).await?;
+            if let Some(audit) = audit {
+                doc.insert("audit", audit);
+            }
+            let replaced = self
+                .data_collection(Operation::Create)?
+                .find_one_and_replace(filter, doc, filter_options)
+                .await
+                .map_err(internal_error)?;
+            if let (Some(replaced), Some(_)) = (replaced, self.gridfs_threshold) {
+                self.gridfs_delete_referenced(&replaced).await?;
+            }
+            return Ok(true);
+        }
+
         let filter_options = mongodb::options::ReplaceOptions::builder()
             .upsert(true)
             .build();
-        let filter = bson::doc! { "path": data.path() };
 
         match self
-            .db
-            .collection_with_type::<Data>("data")
+            .typed_data_collection(Operation::Create)?
             .replace_one(filter, data, filter_options)
             .await
         {
@@ -78,4 +912,606 @@ impl DataStorer for MongoDataStorer {
             }),
         }
     }
+
+    /// Upserts the entries with one `replace_one` each, up to
+    /// `CREATE_MANY_CONCURRENCY` at a time, so that replaced entries keep
+    /// their `_id`, and with it their place in `SortBy::CreatedAt` order and
+    /// cursors, and no entry is ever missing. Only the last of several entries
+    /// at the same path is written. Storers moving values to GridFS or
+    /// recording audit metadata create entries through `create`.
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        if self.gridfs_threshold.is_some() || audit_document(&self.context).is_some() {
+            let mut created = 0;
+            for data in data {
+                if self.create(data).await? {
+                    created += 1;
+                }
+            }
+            return Ok(created);
+        }
+        self.max_time()?;
+
+        let mut latest = std::collections::HashMap::new();
+        for (i, data) in data.iter().enumerate() {
+            latest.insert(data.path(), i);
+        }
+        let collection = self.typed_data_collection(Operation::Create)?;
+        let upserts = data
+            .into_iter()
+            .enumerate()
+            .filter(|(i, data)| latest.get(&data.path()) == Some(i))
+            .map(|(_, data)| {
+                let collection = collection.clone();
+                async move {
+                    let options = mongodb::options::ReplaceOptions::builder()
+                        .upsert(true)
+                        .build();
+                    collection
+                        .replace_one(bson::doc! { "path": data.path() }, data, options)
+                        .await
+                        .map_err(internal_error)
+                }
+            });
+        let results: Vec<_> = futures::stream::iter(upserts)
+            .buffer_unordered(CREATE_MANY_CONCURRENCY)
+            .collect()
+            .await;
+        let mut created = 0;
+        for result in results {
+            result?;
+            created += 1;
+        }
+        Ok(created)
+    }
+
+    /// Inserts the default with a single upserting `find_one_and_update` that
+    /// only sets fields on insert, so concurrent callers all get the same
+    /// entry. GridFS files uploaded for a default which was not inserted are
+    /// removed again.
+    async fn get_or_create(&self, path: &str, mut default: Data) -> Result<Data, DataStorerError> {
+        let max_time = self.max_time()?;
+        default.set_path(path);
+        let mut doc = self.to_document(&default).await?;
+        if let Some(audit) = audit_document(&self.context) {
+            doc.insert("audit", audit);
+        }
+        let update_options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .max_time(max_time)
+            .build();
+        let existing = self
+            .data_collection(Operation::Create)?
+            .find_one_and_update(
+                bson::doc! { "path": path },
+                bson::doc! { "$setOnInsert": doc.clone() },
+                update_options,
+            )
+            .await
+            .map_err(internal_error)?;
+        match existing {
+            Some(existing) => {
+                if self.gridfs_threshold.is_some() {
+                    self.gridfs_delete_referenced(&doc).await?;
+                }
+                self.to_data(existing).await
+            }
+            None => Ok(default),
+        }
+    }
+
+    /// Increments with `$inc`, so concurrent increments are never lost. A missing
+    /// counter is inserted with `$setOnInsert`; should another writer insert it
+    /// first, the increment is retried against their document. Counters are
+    /// stored as `I64` values, with the audit metadata of the context.
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        let collection = self.data_collection(Operation::Create)?;
+        let counter_field = "value.0.Unencrypted.I64";
+        let update_options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .max_time(self.max_time()?)
+            .build();
+        for _ in 0..2 {
+            let updated = collection
+                .find_one_and_update(
+                    bson::doc! { "path": path, "value": { "$size": 1 }, counter_field: { "$type": "long" } },
+                    with_metadata_update(
+                        &self.context,
+                        bson::doc! { "$inc": { counter_field: delta } },
+                    ),
+                    update_options.clone(),
+                )
+                .await
+                .map_err(internal_error)?;
+            if let Some(doc) = updated {
+                return counter_of(&doc).ok_or_else(|| DataStorerError::InvalidCounter {
+                    path: path.to_owned(),
+                });
+            }
+
+            let mut counter = bson::doc! {
+                "path": path,
+                "value": [{ "Unencrypted": { "I64": delta } }]
+            };
+            if let Some(audit) = audit_document(&self.context) {
+                counter.insert("audit", audit);
+            }
+            let inserted = collection
+                .update_one(
+                    bson::doc! { "path": path },
+                    bson::doc! { "$setOnInsert": counter },
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await
+                .map_err(internal_error)?;
+            if inserted.upserted_id.is_some() {
+                return Ok(delta);
+            }
+        }
+        Err(DataStorerError::InvalidCounter {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Swaps with a single `update_one` conditioned on the stored values, so
+    /// the comparison and the write are atomic, recording the audit metadata
+    /// of the context as `create` does. Storers moving values to GridFS, whose
+    /// stored values are references, compare and write through `get` and
+    /// `create` instead.
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        if self.gridfs_threshold.is_some() {
+            return atomic::compare_and_swap(self, path, expected, new).await;
+        }
+        self.max_time()?;
+        let expected = bson::to_bson(&expected).map_err(internal_error)?;
+        let new = bson::to_bson(&new).map_err(internal_error)?;
+        let updated = self
+            .data_collection(Operation::Create)?
+            .update_one(
+                bson::doc! { "path": path, "value": [expected] },
+                with_metadata_update(&self.context, bson::doc! { "$set": { "value": [new] } }),
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(updated.matched_count == 1)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let filter = bson::doc! { "path": path };
+        let deleted = self
+            .data_collection(Operation::Delete)?
+            .find_one_and_delete(filter, FindOneAndDeleteOptions::builder().max_time(self.max_time()?).build())
+            .await
+            .map_err(internal_error)?;
+        match deleted {
+            Some(doc) => {
+                if self.gridfs_threshold.is_some() {
+                    self.gridfs_delete_referenced(&doc).await?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Deletes the matching documents with one `delete_many` per
+    /// `ERASE_BATCH_SIZE` paths once their paths, and any GridFS files they
+    /// reference, have been collected. The driver's `delete_many` takes no
+    /// `max_time`, so each batch is bounded by the context's deadline here.
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let filter = MongoDataStorer::prefix_filter(prefix);
+        let collection = self.data_collection(Operation::Erase)?;
+        let mut cursor = collection
+            .find(filter.clone(), FindOptions::builder().max_time(self.max_time()?).build())
+            .await
+            .map_err(internal_error)?;
+        let mut erased = Vec::new();
+        let mut documents = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let doc = doc.map_err(internal_error)?;
+            erased.push(doc.get_str("path").map_err(internal_error)?.to_owned());
+            if self.gridfs_threshold.is_some() {
+                documents.push(doc);
+            }
+        }
+
+        // Only the paths found are deleted, so that the report lists every
+        // entry erased, in batches keeping each command under the BSON size limit
+        for paths in erased.chunks(ERASE_BATCH_SIZE) {
+            let delete = collection.delete_many(bson::doc! { "path": { "$in": paths } }, None);
+            match self.max_time()? {
+                Some(remaining) => tokio::time::timeout(remaining, delete)
+                    .await
+                    .map_err(|_| DataStorerError::DeadlineExceeded)?,
+                None => delete.await,
+            }
+            .map_err(internal_error)?;
+        }
+        for doc in &documents {
+            self.gridfs_delete_referenced(doc).await?;
+        }
+        Ok(EraseReport::new(erased))
+    }
+
+    /// Deletes every matching document with a single `delete_many`, first
+    /// collecting them only if they may reference GridFS files
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        let mut query = MongoDataStorer::prefix_filter(path);
+        if let Some(value_filter) = filter.and_then(MongoDataStorer::value_filter) {
+            query.insert("value", bson::doc! { "$elemMatch": value_filter });
+        }
+        let collection = self.data_collection(Operation::Delete)?;
+        let mut documents = Vec::new();
+        if self.gridfs_threshold.is_some() {
+            let mut cursor = collection
+                .find(query.clone(), FindOptions::builder().max_time(self.max_time()?).build())
+                .await
+                .map_err(internal_error)?;
+            while let Some(doc) = cursor.next().await {
+                documents.push(doc.map_err(internal_error)?);
+            }
+        } else {
+            self.max_time()?;
+        }
+
+        let deleted = collection
+            .delete_many(query, None)
+            .await
+            .map_err(internal_error)?;
+        for doc in &documents {
+            self.gridfs_delete_referenced(doc).await?;
+        }
+        Ok(deleted.deleted_count as u64)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        let accumulator = match aggregation {
+            Aggregation::Sum => bson::doc! { "$sum": "$n" },
+            Aggregation::Avg => bson::doc! { "$avg": "$n" },
+            Aggregation::Min => bson::doc! { "$min": "$n" },
+            Aggregation::Max => bson::doc! { "$max": "$n" },
+            Aggregation::Count => bson::doc! { "$sum": 1 },
+        };
+        let pipeline = vec![
+            bson::doc! { "$match": MongoDataStorer::prefix_filter(path) },
+            bson::doc! { "$unwind": "$value" },
+            bson::doc! { "$project": { "n": { "$ifNull": [
+                "$value.Unencrypted.U64",
+                { "$ifNull": ["$value.Unencrypted.I64", "$value.Unencrypted.F64"] }
+            ] } } },
+            bson::doc! { "$match": { "n": { "$type": "number" } } },
+            bson::doc! { "$group": { "_id": Bson::Null, "result": accumulator } },
+        ];
+
+        let mut cursor = self
+            .data_collection(Operation::Aggregate)?
+            .aggregate(pipeline, AggregateOptions::builder().max_time(self.max_time()?).build())
+            .await
+            .map_err(|e| DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e)
+                }
+            })?;
+        match cursor.next().await {
+            Some(Ok(doc)) => Ok(match doc.get("result") {
+                Some(Bson::Double(n)) => Some(*n),
+                Some(Bson::Int32(n)) => Some(*n as f64),
+                Some(Bson::Int64(n)) => Some(*n as f64),
+                _ => None,
+            }),
+            Some(Err(e)) => Err(DataStorerError::StorageError {
+                source: StorageError::InternalError {
+                    source: Box::new(e)
+                }
+            }),
+            // No numeric values matched, so there was nothing to group
+            None => Ok(match aggregation {
+                Aggregation::Sum | Aggregation::Count => Some(0.0),
+                _ => None,
+            }),
+        }
+    }
+
+    /// Computes the statistics with a single aggregation, measuring entries by
+    /// their BSON size, which requires MongoDB 4.4 or later
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        let value_type = bson::doc! { "$switch": {
+            "branches": [
+                {
+                    "case": { "$eq": [{ "$type": "$value.Encrypted" }, "object"] },
+                    "then": "$value.Encrypted.datatype",
+                },
+                {
+                    "case": { "$eq": [{ "$type": "$value.GridFs" }, "object"] },
+                    "then": "Bytes",
+                },
+            ],
+            "default": { "$arrayElemAt": [
+                { "$map": { "input": { "$objectToArray": "$value.Unencrypted" }, "in": "$$this.k" } },
+                0,
+            ] },
+        } };
+        let pipeline = vec![
+            bson::doc! { "$match": MongoDataStorer::prefix_filter(prefix) },
+            bson::doc! { "$facet": {
+                "entries": [
+                    { "$group": {
+                        "_id": Bson::Null,
+                        "count": { "$sum": 1 },
+                        "size": { "$sum": { "$bsonSize": "$$ROOT" } },
+                    } },
+                ],
+                "values": [
+                    { "$unwind": "$value" },
+                    { "$group": {
+                        "_id": {
+                            "type": value_type,
+                            "encrypted": { "$eq": [{ "$type": "$value.Encrypted" }, "object"] },
+                        },
+                        "count": { "$sum": 1 },
+                    } },
+                ],
+            } },
+        ];
+
+        let mut cursor = self
+            .data_collection(Operation::Aggregate)?
+            .aggregate(pipeline, AggregateOptions::builder().max_time(self.max_time()?).build())
+            .await
+            .map_err(internal_error)?;
+        let mut stats = DataStats::new();
+        let facets = match cursor.next().await {
+            Some(facets) => facets.map_err(internal_error)?,
+            None => return Ok(stats),
+        };
+        if let Some(Bson::Document(entries)) = facets
+            .get_array("entries")
+            .map_err(internal_error)?
+            .first()
+        {
+            stats.entries = bson_count(entries.get("count"));
+            stats.total_size_bytes = bson_count(entries.get("size"));
+        }
+        for group in facets.get_array("values").map_err(internal_error)? {
+            let group = match group {
+                Bson::Document(group) => group,
+                _ => continue,
+            };
+            let id = group.get_document("_id").map_err(internal_error)?;
+            let datatype: DataType =
+                bson::from_bson(id.get("type").cloned().unwrap_or(Bson::Null))
+                    .map_err(internal_error)?;
+            let encrypted = id.get_bool("encrypted").unwrap_or(false);
+            stats.add_value(&datatype, encrypted, bson_count(group.get("count")));
+        }
+        Ok(stats)
+    }
+
+    /// Fetches every entry within `depth` links of any type with a single
+    /// `$graphLookup`, since it cannot follow only links of one type, then
+    /// keeps those reachable through links of the given type
+    async fn traverse(
+        &self,
+        start: &str,
+        link_type: &str,
+        direction: LinkDirection,
+        depth: usize,
+    ) -> Result<DataCollection, DataStorerError> {
+        if depth == 0 {
+            return Ok(DataCollection::new(vec![self.get(start).await?]));
+        }
+        let pipeline = vec![
+            bson::doc! { "$match": { "path": start } },
+            bson::doc! { "$graphLookup": graph_lookup(&self.collection_name, direction, depth) },
+        ];
+
+        let mut cursor = self
+            .data_collection(Operation::GetCollection)?
+            .aggregate(pipeline, AggregateOptions::builder().max_time(self.max_time()?).build())
+            .await
+            .map_err(internal_error)?;
+        let mut doc = match cursor.next().await {
+            Some(doc) => doc.map_err(internal_error)?,
+            None => return Err(DataStorerError::StorageError {
+                source: StorageError::NotFound
+            }),
+        };
+        let candidates = match doc.remove("reachable") {
+            Some(Bson::Array(candidates)) => candidates,
+            _ => Vec::new(),
+        };
+        let mut data = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            if let Bson::Document(candidate) = candidate {
+                data.push(self.to_data(candidate).await?);
+            }
+        }
+        let start = self.to_data(doc).await?;
+        Ok(DataCollection::new(links::reachable(start, data, link_type, direction, depth)))
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        MongoDataStorer {
+            context,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audit_document, bson_count, counter_of, escape_regex, gridfs_file_id, graph_lookup, MongoDataStorer, MongoIndex, MongoOperationOptions, with_metadata_update};
+    use crate::{
+        Data, DataFilter, DataType, DataValue, EncryptionFilter, LinkDirection, OpContext, Operation, Sort,
+        SortBy, SortOrder,
+    };
+    use mongodb::bson::{self, oid::ObjectId, Bson};
+    use mongodb::options::{Acknowledgment, WriteConcern};
+
+    #[tokio::test]
+    async fn test_writes_use_operation_options() {
+        let majority = WriteConcern::builder().w(Acknowledgment::Majority).build();
+        let storer = MongoDataStorer::new("mongodb://localhost:1", "redact")
+            .await
+            .with_operation_options(
+                Operation::Create,
+                MongoOperationOptions::new().with_write_concern(majority.clone()),
+            );
+        let created = storer.typed_data_collection(Operation::Create).unwrap();
+        assert_eq!(Some(&majority), created.write_concern());
+        let read = storer.typed_data_collection(Operation::Get).unwrap();
+        assert_eq!(None, read.write_concern());
+    }
+
+    #[test]
+    fn test_audit_document() {
+        assert_eq!(None, audit_document(&OpContext::new()));
+        let context = OpContext::new().with_principal("alice").with_trace_id("abc");
+        assert_eq!(
+            Some(bson::doc! { "principal": "alice", "trace_id": "abc" }),
+            audit_document(&context)
+        );
+    }
+
+    #[test]
+    fn test_with_metadata_update() {
+        let update = bson::doc! { "$set": { "value": [] } };
+        assert_eq!(
+            bson::doc! {
+                "$set": { "value": [] },
+                "$unset": { "integrity": "", "signature": "", "written_at": "", "audit": "" }
+            },
+            with_metadata_update(&OpContext::new(), update.clone())
+        );
+        let context = OpContext::new().with_principal("alice");
+        assert_eq!(
+            bson::doc! {
+                "$set": { "value": [], "audit": { "principal": "alice" } },
+                "$unset": { "integrity": "", "signature": "", "written_at": "" }
+            },
+            with_metadata_update(&context, update)
+        );
+    }
+
+    #[test]
+    fn test_bson_count() {
+        assert_eq!(3, bson_count(Some(&Bson::Int32(3))));
+        assert_eq!(4, bson_count(Some(&Bson::Int64(4))));
+        assert_eq!(5, bson_count(Some(&Bson::Double(5.0))));
+        assert_eq!(0, bson_count(None));
+    }
+
+    #[test]
+    fn test_graph_lookup() {
+        let stage = graph_lookup("data", LinkDirection::Incoming, 3);
+        assert_eq!("$path", stage.get_str("startWith").unwrap());
+        assert_eq!("links.target", stage.get_str("connectToField").unwrap());
+        assert_eq!(2, stage.get_i64("maxDepth").unwrap());
+        let stage = graph_lookup("data", LinkDirection::Outgoing, 1);
+        assert_eq!("$links.target", stage.get_str("startWith").unwrap());
+        assert_eq!("path", stage.get_str("connectToField").unwrap());
+        assert_eq!(0, stage.get_i64("maxDepth").unwrap());
+    }
+
+    #[test]
+    fn test_index_document() {
+        let index = MongoIndex::path_and_label("tenant").encrypted_only();
+        assert_eq!(
+            bson::doc! {
+                "key": { "path": 1, "labels.tenant": 1 },
+                "name": "path_label_tenant",
+                "partialFilterExpression": { "value.Encrypted": { "$exists": true } },
+            },
+            index.to_document()
+        );
+        let index = MongoIndex::new("path_unique")
+            .key("path", SortOrder::Descending)
+            .unique();
+        assert_eq!(
+            bson::doc! { "key": { "path": -1 }, "name": "path_unique", "unique": true },
+            index.to_document()
+        );
+    }
+
+    #[test]
+    fn test_counter_of() {
+        let doc = bson::to_document(&Data::new(".hits.", DataValue::from(3i64))).unwrap();
+        assert_eq!(Some(3), counter_of(&doc));
+        let doc = bson::to_document(&Data::new(".hits.", DataValue::from("3"))).unwrap();
+        assert_eq!(None, counter_of(&doc));
+    }
+
+    #[test]
+    fn test_gridfs_file_id_of_reference() {
+        let id = ObjectId::new();
+        let value = Bson::Document(bson::doc! { "GridFs": { "file_id": id.clone(), "length": 10 } });
+        assert_eq!(Some(id), gridfs_file_id(&value));
+    }
+
+    #[test]
+    fn test_gridfs_file_id_of_inline_value() {
+        let value = Bson::Document(bson::doc! { "Unencrypted": { "String": "hello" } });
+        assert_eq!(None, gridfs_file_id(&value));
+    }
+
+    #[test]
+    fn test_sort_document_path_ascending() {
+        let sort = Sort::new(SortBy::Path, SortOrder::Ascending);
+        assert_eq!(bson::doc! { "path": 1 }, MongoDataStorer::sort_document(sort));
+    }
+
+    #[test]
+    fn test_sort_document_created_at_descending() {
+        let sort = Sort::new(SortBy::CreatedAt, SortOrder::Descending);
+        assert_eq!(bson::doc! { "_id": -1 }, MongoDataStorer::sort_document(sort));
+    }
+
+    #[test]
+    fn test_value_filter() {
+        assert_eq!(None, MongoDataStorer::value_filter(&DataFilter::new()));
+        assert_eq!(
+            Some(bson::doc! { "Unencrypted.String": { "$exists": true } }),
+            MongoDataStorer::value_filter(
+                &DataFilter::new()
+                    .with_type(DataType::String)
+                    .with_encryption(EncryptionFilter::Unencrypted)
+            )
+        );
+        assert_eq!(
+            Some(bson::doc! {
+                "Encrypted": { "$exists": true },
+                "Encrypted.datatype": "U64",
+                "Encrypted.keyname": "k1",
+            }),
+            MongoDataStorer::value_filter(
+                &DataFilter::new()
+                    .with_type(DataType::U64)
+                    .with_encryption(EncryptionFilter::EncryptedBy("k1".to_owned()))
+            )
+        );
+    }
+
+    #[test]
+    fn test_escape_regex_escapes_periods() {
+        assert_eq!("\\.my\\.path\\.", escape_regex(".my.path."));
+    }
+
+    #[test]
+    fn test_escape_regex_escapes_metacharacters() {
+        assert_eq!("\\(a\\|b\\)\\*", escape_regex("(a|b)*"));
+    }
 }
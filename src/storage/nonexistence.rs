@@ -0,0 +1,286 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataStorer, DataStorerError,
+    EraseReport, OpContext, Sort, StorageError,
+};
+use async_trait::async_trait;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The paths found to hold no data, by 64-bit fingerprint, along with when
+/// they were found so. Fingerprints are removed when data is created at
+/// their path, which a Bloom filter could not do, and are wide enough that
+/// colliding with a path holding data is practically impossible.
+#[derive(Debug)]
+struct AbsentPaths {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<AbsentPathsState>,
+}
+
+#[derive(Debug, Default)]
+struct AbsentPathsState {
+    found_at: HashMap<u64, Instant>,
+    order: VecDeque<u64>,
+}
+
+impl AbsentPaths {
+    fn fingerprint(path: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .found_at
+            .get(&AbsentPaths::fingerprint(path))
+            .is_some_and(|found_at| found_at.elapsed() < self.ttl)
+    }
+
+    fn insert(&self, path: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let fingerprint = AbsentPaths::fingerprint(path);
+        let mut state = self.state.lock().unwrap();
+        if state.found_at.insert(fingerprint, Instant::now()).is_none() {
+            state.order.push_back(fingerprint);
+        }
+        while state.found_at.len() > self.capacity {
+            match state.order.pop_front() {
+                Some(oldest) => {
+                    state.found_at.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&self, path: &str) {
+        let fingerprint = AbsentPaths::fingerprint(path);
+        let mut state = self.state.lock().unwrap();
+        if state.found_at.remove(&fingerprint).is_some() {
+            state.order.retain(|held| *held != fingerprint);
+        }
+    }
+}
+
+/// Wraps a storer to remember the paths found to hold no data, so that
+/// repeated gets of absent paths are answered with `NotFound` without
+/// querying the backend. Paths are remembered when a get finds nothing and
+/// when they are deleted, and forgotten when data is created at them through
+/// this storer or its clones.
+///
+/// Data created at a remembered path by another process is not seen until
+/// the path is forgotten, after the time-to-live of at most a minute by
+/// default, or once more recently absent paths push it out.
+#[derive(Clone)]
+pub struct NonexistenceFilterDataStorer<S: DataStorer> {
+    storer: S,
+    absent: Arc<AbsentPaths>,
+}
+
+impl<S: DataStorer> NonexistenceFilterDataStorer<S> {
+    /// Instantiates a storer remembering up to 100,000 absent paths for a
+    /// minute each in front of an existing storer
+    pub fn new(storer: S) -> Self {
+        NonexistenceFilterDataStorer {
+            storer,
+            absent: Arc::new(AbsentPaths {
+                capacity: 100_000,
+                ttl: Duration::from_secs(60),
+                state: Mutex::new(AbsentPathsState::default()),
+            }),
+        }
+    }
+
+    /// Sets how many absent paths are remembered, and for how long; paths
+    /// are answered from memory for no longer than the time-to-live
+    pub fn with_capacity(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.absent = Arc::new(AbsentPaths {
+            capacity,
+            ttl,
+            state: Mutex::new(AbsentPathsState::default()),
+        });
+        self
+    }
+
+    /// Returns whether the path is remembered as holding no data
+    pub fn is_known_absent(&self, path: &str) -> bool {
+        self.absent.contains(path)
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for NonexistenceFilterDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        if self.absent.contains(path) {
+            return Err(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            });
+        }
+        let result = self.storer.get(path).await;
+        if let Err(DataStorerError::StorageError {
+            source: StorageError::NotFound,
+        }) = result
+        {
+            self.absent.insert(path);
+        }
+        result
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await
+    }
+
+    /// Forgets the path both before and after writing, so that a get racing
+    /// the write rarely leaves it remembered; the time-to-live bounds any
+    /// that does
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        self.absent.remove(&path);
+        let created = self.storer.create(data).await;
+        self.absent.remove(&path);
+        created
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let deleted = self.storer.delete(path).await?;
+        self.absent.insert(path);
+        Ok(deleted)
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let report = self.storer.erase(prefix).await?;
+        for path in &report.erased {
+            self.absent.insert(path);
+        }
+        Ok(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NonexistenceFilterDataStorer;
+    use crate::mocks::MockDataStorer;
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataStorerError, DataValue, StorageError};
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_absent_paths_skip_the_backend_until_created() {
+        let entries = Arc::new(Mutex::new(BTreeMap::<String, Data>::new()));
+        let gets = Arc::new(AtomicUsize::new(0));
+        let mut inner = MockDataStorer::new();
+        let (e, g) = (entries.clone(), gets.clone());
+        inner.expect_get().returning(move |path| {
+            g.fetch_add(1, Ordering::SeqCst);
+            e.lock()
+                .unwrap()
+                .get(path)
+                .cloned()
+                .ok_or(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                })
+        });
+        let e = entries.clone();
+        inner.expect_create().returning(move |data| {
+            e.lock().unwrap().insert(data.path(), data);
+            Ok(true)
+        });
+        let storer = NonexistenceFilterDataStorer::new(inner);
+
+        for _ in 0..3 {
+            assert!(matches!(
+                storer.get(".missing.").await,
+                Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound
+                })
+            ));
+        }
+        assert_eq!(1, gets.load(Ordering::SeqCst));
+        assert!(storer.is_known_absent(".missing."));
+
+        storer
+            .create(Data::new(".missing.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert!(!storer.is_known_absent(".missing."));
+        assert!(storer.get(".missing.").await.is_ok());
+        assert_eq!(2, gets.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_absent_paths_expire() {
+        let (inner, _) = memory_storer();
+        let storer =
+            NonexistenceFilterDataStorer::new(inner).with_capacity(1, Duration::from_secs(60));
+        assert!(storer.get(".a.").await.is_err());
+        assert!(storer.get(".b.").await.is_err());
+        assert!(!storer.is_known_absent(".a."));
+        assert!(storer.is_known_absent(".b."));
+
+        let (inner, _) = memory_storer();
+        let storer = NonexistenceFilterDataStorer::new(inner).with_capacity(10, Duration::ZERO);
+        assert!(storer.get(".a.").await.is_err());
+        assert!(!storer.is_known_absent(".a."));
+    }
+}
@@ -0,0 +1,465 @@
+use crate::storage::{
+    conflict::{Conflict, ConflictResolver, Resolution},
+    versioning::now_millis,
+};
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataStorer, DataStorerError, EraseReport, OpContext,
+    Sort, StorageError,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, io::ErrorKind, path::PathBuf, sync::Arc};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+/// A write held back by an `OfflineDataStorer` while its backend was
+/// unreachable
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum QueuedWrite {
+    Create(Box<Data>),
+    Delete(String),
+}
+
+impl QueuedWrite {
+    /// Returns the path the write targets
+    pub fn path(&self) -> String {
+        match *self {
+            QueuedWrite::Create(ref data) => data.path(),
+            QueuedWrite::Delete(ref path) => path.clone(),
+        }
+    }
+}
+
+/// A queued write, as appended to the queue file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct QueueEntry {
+    /// When the write was queued, in milliseconds since the unix epoch
+    queued_at: u64,
+    write: QueuedWrite,
+}
+
+/// Decides whether an error means the backend is unreachable, so that the
+/// write which failed with it is queued
+pub type OfflineCheck = dyn Fn(&DataStorerError) -> bool + Send + Sync;
+
+struct QueueState {
+    file: File,
+    pending: VecDeque<QueueEntry>,
+}
+
+/// Queues writes to a file while a storer is unreachable and replays them in
+/// order once it can be reached again, so that writes made while disconnected
+/// are not lost. Writes are queued when they fail with an error the offline
+/// check accepts, by default `Unavailable` or `RateLimited`, and while any
+/// earlier write is still queued, so that writes to a path are replayed in the
+/// order they were made.
+///
+/// Queued writes are replayed before every write, or by calling `replay`.
+/// Reads are not served from the queue, so queued writes are not visible
+/// until replayed. A queued write which fails with any other error when
+/// replayed stays at the head of the queue and the error is returned.
+///
+/// Clones share the same queue, which must not be opened by another storer.
+#[derive(Clone)]
+pub struct OfflineDataStorer<S: DataStorer> {
+    storer: S,
+    path: PathBuf,
+    queue: Arc<Mutex<QueueState>>,
+    is_offline: Arc<OfflineCheck>,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+}
+
+impl<S: DataStorer> OfflineDataStorer<S> {
+    /// Instantiates a storer queueing writes to an existing storer in the
+    /// given file, resuming any writes left queued in it
+    pub async fn open<P: Into<PathBuf>>(storer: S, path: P) -> Result<Self, DataStorerError> {
+        let path = path.into();
+        let queued = match fs::read(&path).await {
+            Ok(queued) => queued,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(internal_error(e)),
+        };
+        // A torn final entry was never acknowledged, so is dropped
+        let pending = queued
+            .split(|b| *b == b'\n')
+            .map_while(|line| serde_json::from_slice::<QueueEntry>(line).ok())
+            .collect();
+        let file = open_append(&path).await?;
+        Ok(OfflineDataStorer {
+            storer,
+            path,
+            queue: Arc::new(Mutex::new(QueueState { file, pending })),
+            is_offline: Arc::new(|e| {
+                matches!(
+                    e,
+                    DataStorerError::Unavailable { .. } | DataStorerError::RateLimited { .. }
+                )
+            }),
+            resolver: None,
+        })
+    }
+
+    /// Replaces the check of which errors mean the backend is unreachable,
+    /// e.g. to also queue writes failing with connection errors
+    pub fn with_offline_check<F>(mut self, is_offline: F) -> Self
+    where
+        F: Fn(&DataStorerError) -> bool + Send + Sync + 'static,
+    {
+        self.is_offline = Arc::new(is_offline);
+        self
+    }
+
+    /// Resolves conflicts when a queued write is replayed onto an entry
+    /// written after it was queued, as told by the entry's `written_at`.
+    /// Without a resolver, queued writes are replayed as they are.
+    pub fn with_conflict_resolver<R: ConflictResolver + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Returns how many writes are queued
+    pub async fn queued(&self) -> usize {
+        self.queue.lock().await.pending.len()
+    }
+
+    /// Replays queued writes in order until the queue is empty or the backend
+    /// is found unreachable again, returning how many were replayed
+    pub async fn replay(&self) -> Result<u64, DataStorerError> {
+        let mut queue = self.queue.lock().await;
+        self.replay_queued(&mut queue).await
+    }
+
+    async fn replay_queued(&self, queue: &mut QueueState) -> Result<u64, DataStorerError> {
+        let mut replayed = 0;
+        let mut result = Ok(());
+        while let Some(entry) = queue.pending.front() {
+            match self.replay_entry(entry).await {
+                Ok(()) => {
+                    queue.pending.pop_front();
+                    replayed += 1;
+                }
+                Err(e) if (self.is_offline)(&e) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if replayed > 0 {
+            self.rewrite(queue).await?;
+        }
+        result.map(|_| replayed)
+    }
+
+    async fn replay_entry(&self, entry: &QueueEntry) -> Result<(), DataStorerError> {
+        let mut write = entry.write.clone();
+        if let Some(ref resolver) = self.resolver {
+            let path = write.path();
+            match self.storer.get(&path).await {
+                Ok(stored) if stored.written_at().is_some_and(|at| at > entry.queued_at) => {
+                    let local = match write {
+                        QueuedWrite::Create(ref data) => Some(data.as_ref()),
+                        QueuedWrite::Delete(_) => None,
+                    };
+                    let conflict = Conflict {
+                        path: &path,
+                        local,
+                        local_written_at: Some(entry.queued_at),
+                        remote: &stored,
+                    };
+                    match resolver.resolve(&conflict) {
+                        Resolution::Local => (),
+                        Resolution::Remote => return Ok(()),
+                        Resolution::Merged(data) => write = QueuedWrite::Create(data),
+                    }
+                }
+                Ok(_)
+                | Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                }) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        self.apply(write).await.map(|_| ())
+    }
+
+    async fn apply(&self, write: QueuedWrite) -> Result<bool, DataStorerError> {
+        match write {
+            QueuedWrite::Create(data) => self.storer.create(*data).await,
+            QueuedWrite::Delete(path) => self.storer.delete(&path).await,
+        }
+    }
+
+    /// Replaces the queue file with the writes still queued
+    async fn rewrite(&self, queue: &mut QueueState) -> Result<(), DataStorerError> {
+        if queue.pending.is_empty() {
+            return queue.file.set_len(0).await.map_err(internal_error);
+        }
+        let mut queued = Vec::new();
+        for entry in &queue.pending {
+            queued.extend(serde_json::to_vec(entry).map_err(internal_error)?);
+            queued.push(b'\n');
+        }
+        let temporary = self.path.with_extension("tmp");
+        let mut f = File::create(&temporary).await.map_err(internal_error)?;
+        f.write_all(&queued).await.map_err(internal_error)?;
+        f.sync_data().await.map_err(internal_error)?;
+        fs::rename(&temporary, &self.path)
+            .await
+            .map_err(internal_error)?;
+        queue.file = open_append(&self.path).await?;
+        Ok(())
+    }
+
+    /// Appends a write to the queue file, flushing it to disk before it is
+    /// acknowledged
+    async fn enqueue(
+        &self,
+        queue: &mut QueueState,
+        write: QueuedWrite,
+    ) -> Result<(), DataStorerError> {
+        let entry = QueueEntry {
+            queued_at: now_millis(),
+            write,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(internal_error)?;
+        line.push(b'\n');
+        queue.file.write_all(&line).await.map_err(internal_error)?;
+        queue.file.sync_data().await.map_err(internal_error)?;
+        queue.pending.push_back(entry);
+        Ok(())
+    }
+
+    /// Applies a write, or queues it behind earlier queued writes or if the
+    /// backend is unreachable. Queued writes report success.
+    async fn write(&self, write: QueuedWrite) -> Result<bool, DataStorerError> {
+        {
+            let mut queue = self.queue.lock().await;
+            if !queue.pending.is_empty() {
+                self.replay_queued(&mut queue).await?;
+                if !queue.pending.is_empty() {
+                    self.enqueue(&mut queue, write).await?;
+                    return Ok(true);
+                }
+            }
+        }
+        match self.apply(write.clone()).await {
+            Err(e) if (self.is_offline)(&e) => {
+                let mut queue = self.queue.lock().await;
+                self.enqueue(&mut queue, write).await?;
+                Ok(true)
+            }
+            result => result,
+        }
+    }
+}
+
+async fn open_append(path: &std::path::Path) -> Result<File, DataStorerError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(internal_error)
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for OfflineDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection(path, skip, page_size, sort)
+            .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.storer
+            .get_collection_page(path, cursor, page_size)
+            .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.write(QueuedWrite::Create(Box::new(data))).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.write(QueuedWrite::Delete(path.to_owned())).await
+    }
+
+    async fn erase(&self, path: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(path).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OfflineDataStorer;
+    use crate::mocks::MockDataStorer;
+    use crate::storage::file::tests::test_dir;
+    use crate::{Data, DataStorer, DataStorerError, DataValue, LastWriteWins, StorageError};
+    use std::{
+        collections::BTreeMap,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    type Entries = Arc<Mutex<BTreeMap<String, Data>>>;
+
+    /// Builds a storer backed by a shared in-memory map, failing writes with
+    /// `Unavailable` while the returned flag is set
+    fn flaky_storer() -> (MockDataStorer, Entries, Arc<AtomicBool>) {
+        let entries = Entries::default();
+        let offline = Arc::new(AtomicBool::new(true));
+        let unavailable = || DataStorerError::Unavailable {
+            reason: "connection refused".to_owned(),
+        };
+        let mut storer = MockDataStorer::new();
+
+        let e = entries.clone();
+        storer.expect_get().returning(move |path| {
+            e.lock()
+                .unwrap()
+                .get(path)
+                .cloned()
+                .ok_or(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                })
+        });
+        let (e, o) = (entries.clone(), offline.clone());
+        storer.expect_create().returning(move |data| {
+            if o.load(Ordering::SeqCst) {
+                return Err(unavailable());
+            }
+            e.lock().unwrap().insert(data.path(), data);
+            Ok(true)
+        });
+        let (e, o) = (entries.clone(), offline.clone());
+        storer.expect_delete().returning(move |path| {
+            if o.load(Ordering::SeqCst) {
+                return Err(unavailable());
+            }
+            Ok(e.lock().unwrap().remove(path).is_some())
+        });
+
+        (storer, entries, offline)
+    }
+
+    fn queue_path(name: &str) -> PathBuf {
+        let dir = test_dir(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("queue.ndjson")
+    }
+
+    #[tokio::test]
+    async fn test_queues_writes_while_offline_and_replays_them_in_order() {
+        let queue = queue_path("offline-replay");
+        let (inner, _, _) = flaky_storer();
+        let storer = OfflineDataStorer::open(inner, &queue).await.unwrap();
+        assert!(storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap());
+        storer
+            .create(Data::new(".b.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer.delete(".a.").await.unwrap();
+        storer
+            .create(Data::new(".a.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+        assert_eq!(4, storer.queued().await);
+        assert_eq!(0, storer.replay().await.unwrap());
+        drop(storer);
+
+        // The queue outlives the storer which filled it
+        let (inner, entries, offline) = flaky_storer();
+        let storer = OfflineDataStorer::open(inner, &queue).await.unwrap();
+        assert_eq!(4, storer.queued().await);
+        offline.store(false, Ordering::SeqCst);
+        assert_eq!(4, storer.replay().await.unwrap());
+        assert_eq!(0, storer.queued().await);
+        assert_eq!(0, std::fs::metadata(&queue).unwrap().len());
+
+        let entries = entries.lock().unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!(Data::new(".a.", DataValue::from(2u64)), entries[".a."]);
+    }
+
+    #[tokio::test]
+    async fn test_resolves_conflicts_with_newer_entries() {
+        let (inner, entries, offline) = flaky_storer();
+        let storer = OfflineDataStorer::open(inner, queue_path("offline-conflict"))
+            .await
+            .unwrap()
+            .with_conflict_resolver(LastWriteWins);
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".b.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let mut newer = Data::new(".a.", DataValue::from(9u64));
+        newer.set_written_at(Some(u64::MAX));
+        entries
+            .lock()
+            .unwrap()
+            .insert(".a.".to_owned(), newer.clone());
+        offline.store(false, Ordering::SeqCst);
+
+        // Writing once the backend is back replays the queue first
+        storer
+            .create(Data::new(".c.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        assert_eq!(0, storer.queued().await);
+        let entries = entries.lock().unwrap();
+        assert_eq!(newer, entries[".a."]);
+        assert_eq!(3, entries.len());
+    }
+}
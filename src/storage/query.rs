@@ -0,0 +1,297 @@
+use crate::{Data, DataStorerError, DataType, DataValue};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// The field a collection of `Data` can be ordered by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Orders entries lexicographically by their path
+    Path,
+    /// Orders entries by the time they were first stored
+    CreatedAt,
+}
+
+impl Display for SortBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Path => write!(f, "path"),
+            Self::CreatedAt => write!(f, "created_at"),
+        }
+    }
+}
+
+/// The direction in which a collection of `Data` is ordered.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Ascending => write!(f, "asc"),
+            Self::Descending => write!(f, "desc"),
+        }
+    }
+}
+
+/// Describes how the entries of a collection query should be ordered.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub by: SortBy,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    /// Builds a new sort specification
+    pub fn new(by: SortBy, order: SortOrder) -> Self {
+        Sort { by, order }
+    }
+
+    /// Returns the query string parameters representing this sort
+    pub fn to_query_params(&self) -> String {
+        format!("sort_by={}&sort_order={}", self.by, self.order)
+    }
+}
+
+/// Restricts values by whether, and by which key, they are encrypted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionFilter {
+    Encrypted,
+    Unencrypted,
+    EncryptedBy(String),
+}
+
+/// Restricts a collection query to entries holding at least one value of the
+/// given type and encryption state; unset criteria match any value. The type
+/// of an encrypted value is the type of its plaintext.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataFilter {
+    pub datatype: Option<DataType>,
+    pub encryption: Option<EncryptionFilter>,
+}
+
+impl DataFilter {
+    /// Builds a filter matching every entry
+    pub fn new() -> Self {
+        DataFilter::default()
+    }
+
+    /// Only matches values of the given type
+    pub fn with_type(mut self, datatype: DataType) -> Self {
+        self.datatype = Some(datatype);
+        self
+    }
+
+    /// Only matches values in the given encryption state
+    pub fn with_encryption(mut self, encryption: EncryptionFilter) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Returns whether the value satisfies every criterion of the filter
+    pub fn matches_value(&self, value: &DataValue) -> bool {
+        let encryption = match (&self.encryption, value) {
+            (None, _) => true,
+            (Some(EncryptionFilter::Encrypted), DataValue::Encrypted(_)) => true,
+            (Some(EncryptionFilter::Unencrypted), DataValue::Unencrypted(_)) => true,
+            (Some(EncryptionFilter::EncryptedBy(keyname)), DataValue::Encrypted(e)) => {
+                e.keyname() == keyname
+            }
+            _ => false,
+        };
+        encryption
+            && match self.datatype {
+                Some(ref datatype) => value.datatype() == *datatype,
+                None => true,
+            }
+    }
+
+    /// Returns whether any of the entry's values satisfies the filter
+    pub fn matches(&self, data: &Data) -> bool {
+        data.value().0.iter().any(|value| self.matches_value(value))
+    }
+}
+
+/// An opaque token marking the position in a collection at which the next
+/// page of entries begins. Cursors are produced by a storer in the
+/// `next_cursor` of a `DataCollection` and are only meaningful to the storer
+/// that produced them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl Cursor {
+    /// Wraps a storer-specific position token in a cursor
+    pub fn new(token: &str) -> Self {
+        Cursor(token.to_owned())
+    }
+
+    /// Returns the raw token backing the cursor
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Determines what a storer does when asked for a page larger than its maximum.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSizePolicy {
+    /// Fails the request with `DataStorerError::InvalidPageSize`
+    Reject,
+    /// Silently reduces the page size to the maximum
+    Clamp,
+}
+
+/// Bounds the page sizes a storer will accept for collection queries.
+/// A requested page size of zero is replaced with `default`, negative page sizes
+/// are always rejected, and page sizes above `max` are handled per `policy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageSizeLimits {
+    pub default: i64,
+    pub max: i64,
+    pub policy: PageSizePolicy,
+}
+
+impl PageSizeLimits {
+    /// Builds a new set of page size limits
+    pub fn new(default: i64, max: i64, policy: PageSizePolicy) -> Self {
+        PageSizeLimits {
+            default,
+            max,
+            policy,
+        }
+    }
+
+    /// Returns the page size a storer should use for the requested page size
+    pub fn resolve(&self, page_size: i64) -> Result<i64, DataStorerError> {
+        match page_size {
+            0 => Ok(self.default),
+            n if n < 0 => Err(DataStorerError::InvalidPageSize {
+                page_size: n,
+                max: self.max,
+            }),
+            n if n > self.max => match self.policy {
+                PageSizePolicy::Reject => Err(DataStorerError::InvalidPageSize {
+                    page_size: n,
+                    max: self.max,
+                }),
+                PageSizePolicy::Clamp => Ok(self.max),
+            },
+            n => Ok(n),
+        }
+    }
+}
+
+impl Default for PageSizeLimits {
+    fn default() -> Self {
+        PageSizeLimits::new(100, 1000, PageSizePolicy::Reject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Cursor, Data, DataFilter, DataStorerError, DataType, DataValue, DataValueCollection,
+        EncryptedDataValue, EncryptionFilter, PageSizeLimits, PageSizePolicy, Sort, SortBy,
+        SortOrder,
+    };
+
+    #[test]
+    fn test_resolve_zero_uses_default() {
+        let limits = PageSizeLimits::new(25, 50, PageSizePolicy::Reject);
+        assert_eq!(25, limits.resolve(0).unwrap());
+    }
+
+    #[test]
+    fn test_resolve_within_bounds() {
+        let limits = PageSizeLimits::new(25, 50, PageSizePolicy::Reject);
+        assert_eq!(50, limits.resolve(50).unwrap());
+    }
+
+    #[test]
+    fn test_resolve_negative_is_rejected() {
+        let limits = PageSizeLimits::new(25, 50, PageSizePolicy::Clamp);
+        assert!(matches!(
+            limits.resolve(-1),
+            Err(DataStorerError::InvalidPageSize { page_size: -1, max: 50 })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_above_max_rejected() {
+        let limits = PageSizeLimits::new(25, 50, PageSizePolicy::Reject);
+        assert!(matches!(
+            limits.resolve(10_000_000),
+            Err(DataStorerError::InvalidPageSize { page_size: 10_000_000, max: 50 })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_above_max_clamped() {
+        let limits = PageSizeLimits::new(25, 50, PageSizePolicy::Clamp);
+        assert_eq!(50, limits.resolve(10_000_000).unwrap());
+    }
+
+    #[test]
+    fn test_cursor_serializes_as_plain_string() {
+        let cursor = Cursor::new("abc");
+        assert_eq!("\"abc\"", serde_json::to_string(&cursor).unwrap());
+    }
+
+    #[test]
+    fn test_to_query_params_path_ascending() {
+        let sort = Sort::new(SortBy::Path, SortOrder::Ascending);
+        assert_eq!("sort_by=path&sort_order=asc", sort.to_query_params());
+    }
+
+    #[test]
+    fn test_to_query_params_created_at_descending() {
+        let sort = Sort::new(SortBy::CreatedAt, SortOrder::Descending);
+        assert_eq!("sort_by=created_at&sort_order=desc", sort.to_query_params());
+    }
+
+    fn encrypted(datatype: DataType, keyname: &str) -> DataValue {
+        DataValue::Encrypted(EncryptedDataValue::new(vec![1], datatype, keyname))
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        assert!(DataFilter::new().matches(&Data::new(".a.", DataValue::from(1u64))));
+    }
+
+    #[test]
+    fn test_type_and_encryption() {
+        let unencrypted_strings = DataFilter::new()
+            .with_type(DataType::String)
+            .with_encryption(EncryptionFilter::Unencrypted);
+        assert!(unencrypted_strings.matches(&Data::new(".a.", DataValue::from("x"))));
+        assert!(!unencrypted_strings.matches(&Data::new(".a.", DataValue::from(1u64))));
+        assert!(!unencrypted_strings
+            .matches(&Data::new(".a.", encrypted(DataType::String, "k1"))));
+
+        let strings = DataFilter::new().with_type(DataType::String);
+        assert!(strings.matches(&Data::new(".a.", encrypted(DataType::String, "k1"))));
+    }
+
+    #[test]
+    fn test_encrypted_by_key() {
+        let by_k1 = DataFilter::new()
+            .with_encryption(EncryptionFilter::EncryptedBy("k1".to_owned()));
+        let data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![DataValue::from(1u64), encrypted(DataType::U64, "k1")]),
+        );
+        assert!(by_k1.matches(&data));
+        assert!(!by_k1.matches(&Data::new(".a.", encrypted(DataType::U64, "k2"))));
+        assert!(DataFilter::new()
+            .with_encryption(EncryptionFilter::Encrypted)
+            .matches(&data));
+    }
+}
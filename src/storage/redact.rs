@@ -1,11 +1,134 @@
-use crate::{Data, DataStorer, StorageError, DataStorerError};
+pub mod api;
+pub mod etag;
+pub mod ndjson;
+
+use crate::compat::{to_wire, WireVersion, WIRE_VERSION_HEADER};
+use crate::{
+    Cursor, Data, DataCodec, DataCollection, DataStorer, DataStorerError, Metrics,
+    MetricsSnapshot, OpContext, PageSizeLimits, Sort, StorageError,
+};
+use api::{
+    BulkCreateRequest, BulkCreateResponse, BulkDeleteRequest, BulkDeleteResponse,
+    BulkGetRequest, BulkGetResponse, CollectionQuery, Endpoint, HealthResponse, VersionResponse,
+};
 use async_trait::async_trait;
+use etag::EtagCache;
+use futures::{stream, StreamExt, TryStreamExt};
+use ndjson::{CollectionStream, NDJSON_CONTENT_TYPE, NEXT_CURSOR_HEADER};
+use reqwest::{
+    header::{HeaderMap, ACCEPT, CONTENT_TYPE, ETAG, IF_NONE_MATCH, RETRY_AFTER},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::OnceCell;
+
+/// Connection settings of the HTTP client a `RedactDataStorer` talks to its
+/// server with. The defaults are those of `reqwest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactClientConfig {
+    /// Speaks HTTP/2 without negotiating it first, for servers known to
+    /// support it
+    pub http2_prior_knowledge: bool,
+    /// How long an idle pooled connection is kept before being closed. Set it
+    /// below the server's keep-alive timeout so that requests are not sent on
+    /// connections the server has already closed.
+    pub pool_idle_timeout: Option<Duration>,
+    /// The most idle connections kept open to the server; `reqwest` does not
+    /// cap the number of connections in use
+    pub pool_max_idle_per_host: usize,
+    /// Interval of TCP keepalive probes on open connections, if any
+    pub tcp_keepalive: Option<Duration>,
+    /// How long establishing a connection may take, if bounded
+    pub connect_timeout: Option<Duration>,
+}
+
+impl Default for RedactClientConfig {
+    fn default() -> Self {
+        RedactClientConfig {
+            http2_prior_knowledge: false,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            connect_timeout: None,
+        }
+    }
+}
+
+impl RedactClientConfig {
+    /// Builds an HTTP client with the configured settings
+    pub fn build(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder()
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .tcp_keepalive(self.tcp_keepalive);
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        builder.build()
+    }
+}
 
 /// Stores an instance of a redact-backed data storer.
 /// The redact-store server is an example implementation of a redact storage backing.
+/// The wire version data is written with is negotiated with the server on
+/// first use, so that servers still speaking a previous version can be
+/// talked to during rolling upgrades.
+///
+/// Besides the `DataStorer` operations, it exposes the rest of the redact-store
+/// API, such as health checks and bulk operations, whose typed requests and
+/// responses are found in the `api` module.
+///
+/// Collections can be streamed as newline-delimited JSON, see
+/// `with_streamed_collections`, so that large pages are decoded entry by
+/// entry as they arrive rather than buffered whole.
 #[derive(Clone)]
 pub struct RedactDataStorer {
     url: String,
+    client: reqwest::Client,
+    page_size_limits: PageSizeLimits,
+    context: OpContext,
+    wire_version: Arc<OnceCell<WireVersion>>,
+    codec: DataCodec,
+    stream_collections: bool,
+    retry_budget: Duration,
+    etags: Option<Arc<EtagCache>>,
+    metrics: Arc<Metrics>,
+}
+
+/// Returns whether the server shed the request: 429 Too Many Requests, or 503
+/// Service Unavailable when it says when to retry
+fn is_rate_limited(response: &reqwest::Response) -> bool {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::SERVICE_UNAVAILABLE => response.headers().contains_key(RETRY_AFTER),
+        _ => false,
+    }
+}
+
+/// Returns how long a `Retry-After` header asks to wait from now, given as
+/// either a number of seconds or an HTTP date
+pub(crate) fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+fn internal_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> DataStorerError {
+    DataStorerError::StorageError {
+        source: StorageError::InternalError {
+            source: Box::new(e),
+        },
+    }
 }
 
 impl RedactDataStorer {
@@ -13,43 +136,535 @@ impl RedactDataStorer {
     pub fn new(url: &str) -> RedactDataStorer {
         RedactDataStorer {
             url: url.to_owned(),
+            client: reqwest::Client::new(),
+            page_size_limits: PageSizeLimits::default(),
+            context: OpContext::default(),
+            wire_version: Arc::new(OnceCell::new()),
+            codec: DataCodec::default(),
+            stream_collections: false,
+            retry_budget: Duration::ZERO,
+            etags: None,
+            metrics: Arc::new(Metrics::new()),
         }
     }
+
+    /// Keeps up to `capacity` of the entries last fetched with `get` along
+    /// with their ETags, and revalidates them with `If-None-Match` when they
+    /// are fetched again, so that unchanged entries cost a 304 Not Modified
+    /// instead of their body. Clones of the storer share the entries.
+    pub fn with_revalidation(mut self, capacity: usize) -> Self {
+        self.etags = Some(Arc::new(EtagCache::new(capacity)));
+        self
+    }
+
+    /// Retries requests the server rejects with 429 Too Many Requests, or 503
+    /// Service Unavailable with a `Retry-After`, once the time it asks for has
+    /// passed, for as long as the total time waited stays within the budget
+    /// and the context's deadline. Without a budget, or once it is spent, such
+    /// requests fail with `DataStorerError::RateLimited`.
+    pub fn with_retry_budget(mut self, retry_budget: Duration) -> Self {
+        self.retry_budget = retry_budget;
+        self
+    }
+
+    /// Returns a snapshot of the requests sent to the server: each attempt is
+    /// recorded as a `request` operation, each retry of a rate limited request
+    /// counted as `rate_limited_retries`, and each request failed as rate
+    /// limited counted as `rate_limited`
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Replaces the HTTP client with one built from the given connection
+    /// settings. Clones of the storer share their client's connection pool.
+    pub fn with_client_config(
+        mut self,
+        config: &RedactClientConfig,
+    ) -> Result<Self, DataStorerError> {
+        self.client = config.build().map_err(internal_error)?;
+        Ok(self)
+    }
+
+    /// Sends request bodies with the given codec instead of JSON and asks the
+    /// server to respond with it. Responses are decoded with the codec named
+    /// by their content type, so servers which ignore the request still work.
+    pub fn with_codec(mut self, codec: DataCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Pins the wire version spoken with the server instead of negotiating it
+    pub fn with_wire_version(mut self, wire_version: WireVersion) -> Self {
+        self.wire_version = Arc::new(OnceCell::new_with(Some(wire_version)));
+        self
+    }
+
+    /// Returns the wire version spoken with the server, asking the server for
+    /// the newest version it speaks on first use. Servers which predate
+    /// negotiation do not serve their version and are spoken to with `V1`.
+    pub async fn wire_version(&self) -> Result<WireVersion, DataStorerError> {
+        self.wire_version
+            .get_or_try_init(|| async {
+                let response = self.send(self.request(&Endpoint::Version)).await?;
+                if !response.status().is_success() {
+                    return Ok(WireVersion::V1);
+                }
+                Ok(Self::decode::<VersionResponse>(response)
+                    .await
+                    .ok()
+                    .and_then(|version| WireVersion::negotiate(version.wire_version))
+                    .unwrap_or(WireVersion::V1))
+            })
+            .await
+            .copied()
+    }
+
+    /// Asks the server to stream collections as newline-delimited JSON, which
+    /// `get_collection`, `get_collection_page`, and `list` then decode as the
+    /// body arrives. Servers which ignore the request still work.
+    pub fn with_streamed_collections(mut self, stream_collections: bool) -> Self {
+        self.stream_collections = stream_collections;
+        self
+    }
+
+    /// Replaces the default page size limits applied to collection queries
+    /// before they are sent to the server.
+    pub fn with_page_size_limits(mut self, page_size_limits: PageSizeLimits) -> Self {
+        self.page_size_limits = page_size_limits;
+        self
+    }
+
+    /// Returns the health reported by the server, whether healthy or not
+    pub async fn health(&self) -> Result<HealthResponse, DataStorerError> {
+        let response = self.send(self.request(&Endpoint::Health)).await?;
+        Self::decode(response).await
+    }
+
+    /// Returns a page of the entries under a path
+    pub async fn list(&self, query: &CollectionQuery) -> Result<DataCollection, DataStorerError> {
+        let query = CollectionQuery {
+            page_size: self.page_size_limits.resolve(query.page_size)?,
+            ..query.clone()
+        };
+        if self.stream_collections {
+            return self.collect_stream(&query).await;
+        }
+        self.call::<(), _>(Endpoint::List(&query), None).await
+    }
+
+    /// Returns a page of the entries under a path, yielding each entry as
+    /// soon as it is received when the server streams the collection as
+    /// newline-delimited JSON, and once the whole page is received otherwise
+    pub async fn list_stream(
+        &self,
+        query: &CollectionQuery,
+    ) -> Result<CollectionStream, DataStorerError> {
+        let query = CollectionQuery {
+            page_size: self.page_size_limits.resolve(query.page_size)?,
+            ..query.clone()
+        };
+        let accept = format!("{}, {};q=0.9", NDJSON_CONTENT_TYPE, self.codec.content_type());
+        let response = self
+            .send_accepting(self.request(&Endpoint::List(&query)), &accept)
+            .await?
+            .error_for_status()
+            .map_err(internal_error)?;
+        let streamed = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with(NDJSON_CONTENT_TYPE));
+        if !streamed {
+            let collection = Self::decode::<DataCollection>(response).await?;
+            return Ok(CollectionStream {
+                next_cursor: collection.next_cursor,
+                data: stream::iter(collection.data.into_iter().map(Ok)).boxed(),
+            });
+        }
+        let next_cursor = response
+            .headers()
+            .get(NEXT_CURSOR_HEADER)
+            .and_then(|cursor| cursor.to_str().ok())
+            .map(Cursor::new);
+        Ok(CollectionStream {
+            next_cursor,
+            data: ndjson::data_stream(response),
+        })
+    }
+
+    /// Fetches a page with `list_stream` and collects its entries
+    async fn collect_stream(&self, query: &CollectionQuery) -> Result<DataCollection, DataStorerError> {
+        let page = self.list_stream(query).await?;
+        Ok(DataCollection {
+            data: page.data.try_collect().await?,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Fetches the entries at the given paths in a single request, omitting
+    /// paths holding no entry
+    pub async fn get_many(&self, paths: &[&str]) -> Result<Vec<Data>, DataStorerError> {
+        let request = BulkGetRequest {
+            paths: paths.iter().map(|path| (*path).to_owned()).collect(),
+        };
+        self.call::<_, BulkGetResponse>(Endpoint::BulkGet, Some(&request))
+            .await
+            .map(|collection| collection.data)
+    }
+
+    /// Deletes the entries at the given paths in a single request, returning
+    /// how many paths held an entry
+    pub async fn delete_many(&self, paths: &[&str]) -> Result<u64, DataStorerError> {
+        let request = BulkDeleteRequest {
+            paths: paths.iter().map(|path| (*path).to_owned()).collect(),
+        };
+        self.call::<_, BulkDeleteResponse>(Endpoint::BulkDelete, Some(&request))
+            .await
+            .map(|response| response.deleted)
+    }
+
+    /// Builds a request to an endpoint of the server
+    fn request(&self, endpoint: &Endpoint<'_>) -> reqwest::RequestBuilder {
+        self.client.request(endpoint.method(), endpoint.url(&self.url))
+    }
+
+    /// Calls an endpoint with a body encoded with the storer's codec, failing
+    /// on error statuses, and decodes its response
+    async fn call<B: Serialize, T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint<'_>,
+        body: Option<&B>,
+    ) -> Result<T, DataStorerError> {
+        let mut request = self.request(&endpoint);
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, self.codec.content_type())
+                .body(self.codec.encode(body).map_err(internal_error)?);
+        }
+        let response = self
+            .send(request)
+            .await?
+            .error_for_status()
+            .map_err(internal_error)?;
+        Self::decode(response).await
+    }
+
+    /// Sends a request carrying the storer's operation context as headers,
+    /// timing out at the context's deadline
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, DataStorerError> {
+        self.send_accepting(request, self.codec.content_type()).await
+    }
+
+    /// Sends a request as `send` does, asking for the given content types.
+    /// Rate limited requests are retried within the storer's retry budget.
+    async fn send_accepting(
+        &self,
+        request: reqwest::RequestBuilder,
+        accept: &str,
+    ) -> Result<reqwest::Response, DataStorerError> {
+        let mut request = self
+            .context
+            .headers()
+            .into_iter()
+            .fold(request, |request, (name, value)| request.header(name, value))
+            .header(WIRE_VERSION_HEADER, WireVersion::CURRENT.number())
+            .header(ACCEPT, accept);
+        let mut waited = Duration::ZERO;
+        loop {
+            // Requests with streamed bodies cannot be cloned, nor retried
+            let retry = request.try_clone();
+            let response = self.metrics.measure("request", self.send_once(request)).await?;
+            if !is_rate_limited(&response) {
+                return Ok(response);
+            }
+            let retry_after = retry_after(response.headers(), SystemTime::now());
+            request = match (retry_after, retry) {
+                (Some(wait), Some(retry))
+                    if waited + wait <= self.retry_budget
+                        && self.context.remaining().is_none_or(|r| r > wait) =>
+                {
+                    self.metrics.increment("rate_limited_retries");
+                    tokio::time::sleep(wait).await;
+                    waited += wait;
+                    retry
+                }
+                _ => {
+                    self.metrics.increment("rate_limited");
+                    return Err(DataStorerError::RateLimited { retry_after });
+                }
+            };
+        }
+    }
+
+    /// Sends a request once, timing out at the context's deadline
+    async fn send_once(
+        &self,
+        mut request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, DataStorerError> {
+        if let Some(remaining) = self.context.remaining() {
+            if remaining.is_zero() {
+                return Err(DataStorerError::DeadlineExceeded);
+            }
+            request = request.timeout(remaining);
+        }
+        request.send().await.map_err(|e| {
+            if e.is_timeout() && self.context.deadline().is_some() {
+                DataStorerError::DeadlineExceeded
+            } else {
+                internal_error(e)
+            }
+        })
+    }
+
+    /// Decodes a response body with the codec named by its content type,
+    /// falling back to JSON
+    async fn decode<T: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<T, DataStorerError> {
+        let codec = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(DataCodec::from_content_type)
+            .unwrap_or_default();
+        let bytes = response.bytes().await.map_err(internal_error)?;
+        codec.decode(&bytes).map_err(internal_error)
+    }
 }
 
 #[async_trait]
 impl DataStorer for RedactDataStorer {
     async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
-        match reqwest::get(&format!("{}/data/{}", self.url, path)).await {
-            Ok(r) => Ok(r
-                .json::<Data>()
-                .await
-                .map_err(|source| DataStorerError::StorageError {
-                    source: StorageError::InternalError {
-                        source: Box::new(source),
-                    }
-                })?),
-            Err(e) => Err(DataStorerError::StorageError {
-                source: StorageError::InternalError {
-                    source: Box::new(e)
+        let mut request = self.request(&Endpoint::Get(path));
+        let held = self.etags.as_ref().and_then(|etags| etags.get(path));
+        if let Some((ref etag, _)) = held {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        let r = self.send(request).await?;
+        match (r.status(), held) {
+            (StatusCode::NOT_MODIFIED, Some((_, data))) => return Ok(data),
+            (StatusCode::NOT_FOUND, _) => {
+                if let Some(ref etags) = self.etags {
+                    etags.remove(path);
                 }
-            }),
+                return Err(DataStorerError::StorageError {
+                    source: StorageError::NotFound,
+                });
+            }
+            _ => (),
+        }
+        let r = r.error_for_status().map_err(internal_error)?;
+        let etag = r
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_owned);
+        let data = Self::decode::<Data>(r).await?;
+        if let (Some(etags), Some(etag)) = (self.etags.as_ref(), etag) {
+            etags.insert(path, etag, data.clone());
+        }
+        Ok(data)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut query =
+            CollectionQuery::new(path, self.page_size_limits.resolve(page_size)?).with_skip(skip);
+        query.sort = sort;
+        if self.stream_collections {
+            return self.collect_stream(&query).await;
         }
+
+        let r = self.send(self.request(&Endpoint::List(&query))).await?;
+        Self::decode::<DataCollection>(r).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let mut query = CollectionQuery::new(path, self.page_size_limits.resolve(page_size)?);
+        query.cursor = cursor;
+        if self.stream_collections {
+            return self.collect_stream(&query).await;
+        }
+
+        let r = self.send(self.request(&Endpoint::List(&query))).await?;
+        Self::decode::<DataCollection>(r).await
     }
 
     async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
-        match reqwest::Client::new()
-            .post(&format!("{}/data?path={}", self.url, data.path()))
-            .json(&data)
-            .send()
+        let body = to_wire(&data, self.wire_version().await?)?;
+        let body = self.codec.encode(&body).map_err(internal_error)?;
+        self.send(
+            self.request(&Endpoint::Create(&data.path()))
+                .header(CONTENT_TYPE, self.codec.content_type())
+                .body(body),
+        )
+        .await?
+        .error_for_status()
+        .map_err(internal_error)?;
+        Ok(true)
+    }
+
+    /// Creates the entries in a single request to the bulk endpoint
+    async fn create_many(&self, data: Vec<Data>) -> Result<u64, DataStorerError> {
+        let wire_version = self.wire_version().await?;
+        let request = BulkCreateRequest {
+            data: data
+                .iter()
+                .map(|data| to_wire(data, wire_version))
+                .collect::<Result<_, _>>()?,
+        };
+        self.call::<_, BulkCreateResponse>(Endpoint::BulkCreate, Some(&request))
             .await
-        {
-            Ok(_) => Ok(true),
-            Err(e) => Err(DataStorerError::StorageError {
-                source: StorageError::InternalError {
-                    source: Box::new(e)
-                }
-            }),
+            .map(|response| response.created)
+    }
+
+    /// Creates the default with the conditional create endpoint, which returns
+    /// whatever is stored at the path afterwards
+    async fn get_or_create(&self, path: &str, mut default: Data) -> Result<Data, DataStorerError> {
+        default.set_path(path);
+        let body = to_wire(&default, self.wire_version().await?)?;
+        self.call(Endpoint::CreateIfAbsent(path), Some(&body)).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        match self.send(self.request(&Endpoint::Delete(path))).await? {
+            r if r.status() == reqwest::StatusCode::NOT_FOUND => Ok(false),
+            r => r.error_for_status().map(|_| true).map_err(internal_error),
         }
     }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        RedactDataStorer {
+            context,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_after, RedactClientConfig, RedactDataStorer};
+    use crate::{Data, DataStorer, DataStorerError, DataValue, OpContext, StorageError};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    /// Serves every request with an empty response of the given status,
+    /// returning the URL of the server
+    fn respond_with(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                    let lower = line.to_ascii_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap_or(0);
+                    }
+                    line.clear();
+                }
+                let _ = reader.read_exact(&mut vec![0; length]);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_with_client_config() {
+        let config = RedactClientConfig {
+            http2_prior_knowledge: true,
+            pool_idle_timeout: Some(Duration::from_secs(5)),
+            pool_max_idle_per_host: 4,
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            connect_timeout: Some(Duration::from_secs(1)),
+        };
+        assert!(RedactDataStorer::new("http://localhost")
+            .with_client_config(&config)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fails_fast_past_deadline() {
+        let storer = RedactDataStorer::new("http://localhost:1")
+            .with_context(OpContext::new().with_deadline(UNIX_EPOCH));
+        assert!(matches!(
+            storer.get(".a.").await,
+            Err(DataStorerError::DeadlineExceeded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_maps_missing_entries_to_not_found() {
+        let storer = RedactDataStorer::new(&respond_with("404 Not Found"));
+        assert!(matches!(
+            storer.get(".a.").await,
+            Err(DataStorerError::StorageError {
+                source: StorageError::NotFound
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fails_on_error_statuses() {
+        let storer = RedactDataStorer::new(&respond_with("500 Internal Server Error"));
+        assert!(matches!(
+            storer.get(".a.").await,
+            Err(DataStorerError::StorageError {
+                source: StorageError::InternalError { .. }
+            })
+        ));
+        assert!(storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        let headers = |value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(
+            Some(Duration::from_secs(120)),
+            retry_after(&headers("120"), now)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            retry_after(&headers("Sun, 06 Nov 1994 08:50:37 GMT"), now)
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            retry_after(&headers("Sun, 06 Nov 1994 08:48:37 GMT"), now)
+        );
+        assert_eq!(None, retry_after(&headers("soon"), now));
+        assert_eq!(None, retry_after(&HeaderMap::new(), now));
+    }
 }
@@ -0,0 +1,229 @@
+use crate::{Cursor, DataCollection, Sort};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The endpoints of the redact-store HTTP API
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint<'a> {
+    /// `GET /health`, answered with a `HealthResponse`
+    Health,
+    /// `GET /version`, answered with a `VersionResponse`
+    Version,
+    /// `GET /data/{path}`, answered with the `Data` at the path
+    Get(&'a str),
+    /// `GET /data/{path}?{query}`, answered with a `DataCollection`, or with
+    /// its entries as newline-delimited JSON if asked to, see the `ndjson`
+    /// module
+    List(&'a CollectionQuery),
+    /// `POST /data?path={path}` with a `Data` body
+    Create(&'a str),
+    /// `POST /data?path={path}&if_absent=true` with a `Data` body, only
+    /// written if nothing is stored at the path, answered with the `Data`
+    /// stored there afterwards
+    CreateIfAbsent(&'a str),
+    /// `DELETE /data/{path}`, answered with 404 if there was nothing to delete
+    Delete(&'a str),
+    /// `POST /data/bulk/get` with a `BulkGetRequest`, answered with a
+    /// `DataCollection` of the entries found
+    BulkGet,
+    /// `POST /data/bulk` with a `BulkCreateRequest`, answered with a
+    /// `BulkCreateResponse`
+    BulkCreate,
+    /// `POST /data/bulk/delete` with a `BulkDeleteRequest`, answered with a
+    /// `BulkDeleteResponse`
+    BulkDelete,
+}
+
+impl<'a> Endpoint<'a> {
+    /// Returns the HTTP method the endpoint is called with
+    pub fn method(&self) -> Method {
+        match *self {
+            Endpoint::Health | Endpoint::Version | Endpoint::Get(_) | Endpoint::List(_) => {
+                Method::GET
+            }
+            Endpoint::Delete(_) => Method::DELETE,
+            Endpoint::Create(_)
+            | Endpoint::CreateIfAbsent(_)
+            | Endpoint::BulkGet
+            | Endpoint::BulkCreate
+            | Endpoint::BulkDelete => Method::POST,
+        }
+    }
+
+    /// Returns the URL of the endpoint on the server at the given base URL
+    pub fn url(&self, base: &str) -> String {
+        match *self {
+            Endpoint::Health => format!("{}/health", base),
+            Endpoint::Version => format!("{}/version", base),
+            Endpoint::Get(path) | Endpoint::Delete(path) => format!("{}/data/{}", base, path),
+            Endpoint::List(query) => {
+                format!("{}/data/{}?{}", base, query.path, query.query_string())
+            }
+            Endpoint::Create(path) => format!("{}/data?path={}", base, path),
+            Endpoint::CreateIfAbsent(path) => {
+                format!("{}/data?path={}&if_absent=true", base, path)
+            }
+            Endpoint::BulkGet => format!("{}/data/bulk/get", base),
+            Endpoint::BulkCreate => format!("{}/data/bulk", base),
+            Endpoint::BulkDelete => format!("{}/data/bulk/delete", base),
+        }
+    }
+}
+
+/// A query for a page of the entries under a path, starting either at an
+/// offset or at a cursor returned with a previous page
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionQuery {
+    pub path: String,
+    pub page_size: i64,
+    pub skip: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub sort: Option<Sort>,
+}
+
+impl CollectionQuery {
+    /// Builds a query for the first page of the entries under a path
+    pub fn new(path: &str, page_size: i64) -> Self {
+        CollectionQuery {
+            path: path.to_owned(),
+            page_size,
+            skip: None,
+            cursor: None,
+            sort: None,
+        }
+    }
+
+    /// Starts the page after the given number of entries
+    pub fn with_skip(mut self, skip: i64) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Starts the page at a cursor returned with a previous page
+    pub fn with_cursor(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Orders the entries
+    pub fn with_sort(mut self, sort: Sort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Returns the query parameters the query is sent with
+    pub fn query_string(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(skip) = self.skip {
+            params.push(format!("skip={}", skip));
+        }
+        params.push(format!("page_size={}", self.page_size));
+        if let Some(ref cursor) = self.cursor {
+            params.push(format!("cursor={}", cursor));
+        }
+        if let Some(ref sort) = self.sort {
+            params.push(sort.to_query_params());
+        }
+        params.join("&")
+    }
+}
+
+/// The body of the health endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HealthResponse {
+    /// `ok` when the server and its backing store are available
+    pub status: String,
+}
+
+impl HealthResponse {
+    /// Returns whether the server reported itself healthy
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+/// The body of the version endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionResponse {
+    /// The newest wire version the server speaks, see `WireVersion::negotiate`
+    pub wire_version: u32,
+}
+
+/// The body of the bulk get endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BulkGetRequest {
+    pub paths: Vec<String>,
+}
+
+/// The response of the bulk get endpoint
+pub type BulkGetResponse = DataCollection;
+
+/// The body of the bulk create endpoint, holding data encoded for the wire
+/// version spoken with the server, see `to_wire`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkCreateRequest {
+    pub data: Vec<Value>,
+}
+
+/// The body of the bulk create endpoint's response
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkCreateResponse {
+    pub created: u64,
+}
+
+/// The body of the bulk delete endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BulkDeleteRequest {
+    pub paths: Vec<String>,
+}
+
+/// The body of the bulk delete endpoint's response, counting the paths which
+/// held an entry
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkDeleteResponse {
+    pub deleted: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CollectionQuery, Endpoint};
+    use crate::{Cursor, Sort, SortBy, SortOrder};
+    use reqwest::Method;
+
+    #[test]
+    fn test_urls() {
+        let base = "http://store";
+        assert_eq!("http://store/health", Endpoint::Health.url(base));
+        assert_eq!("http://store/data/.a.", Endpoint::Get(".a.").url(base));
+        assert_eq!(Method::DELETE, Endpoint::Delete(".a.").method());
+        assert_eq!(
+            "http://store/data?path=.a.",
+            Endpoint::Create(".a.").url(base)
+        );
+        assert_eq!(
+            "http://store/data?path=.a.&if_absent=true",
+            Endpoint::CreateIfAbsent(".a.").url(base)
+        );
+        assert_eq!(Method::POST, Endpoint::BulkGet.method());
+
+        let query = CollectionQuery::new(".a.", 10).with_skip(5);
+        assert_eq!(
+            "http://store/data/.a.?skip=5&page_size=10",
+            Endpoint::List(&query).url(base)
+        );
+        let query = CollectionQuery::new(".a.", 10)
+            .with_cursor(Cursor::new("c"))
+            .with_sort(Sort {
+                by: SortBy::Path,
+                order: SortOrder::Descending,
+            });
+        assert_eq!(
+            format!(
+                "page_size=10&cursor=c&{}",
+                query.sort.as_ref().unwrap().to_query_params()
+            ),
+            query.query_string()
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::Data;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+/// The entries last fetched from the server along with their ETags, so that
+/// fetching them again can be answered with 304 Not Modified instead of the
+/// entry. Holds at most `capacity` entries, evicting the oldest first.
+#[derive(Debug)]
+pub struct EtagCache {
+    capacity: usize,
+    state: Mutex<EtagCacheState>,
+}
+
+#[derive(Debug, Default)]
+struct EtagCacheState {
+    entries: HashMap<String, (String, Data)>,
+    order: VecDeque<String>,
+}
+
+impl EtagCache {
+    pub fn new(capacity: usize) -> Self {
+        EtagCache {
+            capacity,
+            state: Mutex::new(EtagCacheState::default()),
+        }
+    }
+
+    /// Returns the ETag and entry last fetched from a path, if still held
+    pub fn get(&self, path: &str) -> Option<(String, Data)> {
+        self.state.lock().unwrap().entries.get(path).cloned()
+    }
+
+    /// Holds an entry fetched from a path along with its ETag
+    pub fn insert(&self, path: &str, etag: String, data: Data) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state
+            .entries
+            .insert(path.to_owned(), (etag, data))
+            .is_none()
+        {
+            state.order.push_back(path.to_owned());
+        }
+        while state.entries.len() > self.capacity {
+            match state.order.pop_front() {
+                Some(oldest) => {
+                    state.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Forgets the entry fetched from a path
+    pub fn remove(&self, path: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.entries.remove(path).is_some() {
+            state.order.retain(|held| held != path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EtagCache;
+    use crate::{Data, DataValue};
+
+    #[test]
+    fn test_evicts_oldest_entries() {
+        let cache = EtagCache::new(2);
+        let data = |path| Data::new(path, DataValue::from(1u64));
+        cache.insert(".a.", "\"1\"".to_owned(), data(".a."));
+        cache.insert(".b.", "\"2\"".to_owned(), data(".b."));
+        cache.insert(".a.", "\"3\"".to_owned(), data(".a."));
+        cache.insert(".c.", "\"4\"".to_owned(), data(".c."));
+
+        assert!(cache.get(".a.").is_none());
+        assert_eq!("\"2\"", cache.get(".b.").unwrap().0);
+        assert_eq!(data(".c."), cache.get(".c.").unwrap().1);
+        cache.remove(".b.");
+        assert!(cache.get(".b.").is_none());
+        cache.insert(".d.", "\"5\"".to_owned(), data(".d."));
+        assert!(cache.get(".c.").is_some());
+    }
+}
@@ -0,0 +1,120 @@
+use super::internal_error;
+use crate::{Cursor, Data, DataStorerError};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::mem;
+
+/// Content type of collections streamed as newline-delimited JSON, one `Data`
+/// per line
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Header through which the server returns the cursor of the next page of a
+/// streamed collection, since the body holds nothing but entries
+pub const NEXT_CURSOR_HEADER: &str = "X-Redact-Next-Cursor";
+
+/// A page of a collection whose entries are decoded as they arrive, see
+/// `RedactDataStorer::list_stream`
+pub struct CollectionStream {
+    /// Where the following page begins, if anywhere
+    pub next_cursor: Option<Cursor>,
+    pub data: BoxStream<'static, Result<Data, DataStorerError>>,
+}
+
+/// Splits newline-delimited JSON into entries as chunks of it arrive,
+/// holding only the incomplete line at the end of the chunks pushed so far
+#[derive(Debug, Default)]
+pub struct NdJsonDecoder {
+    buf: Vec<u8>,
+}
+
+impl NdJsonDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a chunk of the body
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Decodes the next complete line, if any, skipping blank lines
+    pub fn next_data(&mut self) -> Option<Result<Data, serde_json::Error>> {
+        while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            if !is_blank(&line) {
+                return Some(serde_json::from_slice(&line));
+            }
+        }
+        None
+    }
+
+    /// Decodes whatever follows the last newline once the body has ended
+    pub fn finish(&mut self) -> Option<Result<Data, serde_json::Error>> {
+        let rest = mem::take(&mut self.buf);
+        match is_blank(&rest) {
+            true => None,
+            false => Some(serde_json::from_slice(&rest)),
+        }
+    }
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace)
+}
+
+/// Streams the entries of a newline-delimited JSON response as its body
+/// arrives. The stream ends after yielding the first error encountered.
+pub(crate) fn data_stream(
+    response: reqwest::Response,
+) -> BoxStream<'static, Result<Data, DataStorerError>> {
+    stream::unfold(Some((response, NdJsonDecoder::new())), |state| async move {
+        let (mut response, mut decoder) = state?;
+        loop {
+            if let Some(data) = decoder.next_data() {
+                return match data {
+                    Ok(data) => Some((Ok(data), Some((response, decoder)))),
+                    Err(e) => Some((Err(internal_error(e)), None)),
+                };
+            }
+            match response.chunk().await {
+                Ok(Some(chunk)) => decoder.push(&chunk),
+                Ok(None) => {
+                    return decoder
+                        .finish()
+                        .map(|data| (data.map_err(internal_error), None))
+                }
+                Err(e) => return Some((Err(internal_error(e)), None)),
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NdJsonDecoder;
+    use crate::{Data, DataValue};
+
+    #[test]
+    fn test_decodes_lines_split_across_chunks() {
+        let a = serde_json::to_string(&Data::new(".a.", DataValue::from(1u64))).unwrap();
+        let b = serde_json::to_string(&Data::new(".b.", DataValue::from("x"))).unwrap();
+        let body = format!("{}\n\n{}", a, b);
+        let (first, second) = body.as_bytes().split_at(a.len() / 2);
+
+        let mut decoder = NdJsonDecoder::new();
+        decoder.push(first);
+        assert!(decoder.next_data().is_none());
+        decoder.push(second);
+        assert_eq!(".a.", decoder.next_data().unwrap().unwrap().path());
+        assert!(decoder.next_data().is_none());
+        assert_eq!(".b.", decoder.finish().unwrap().unwrap().path());
+        assert!(decoder.finish().is_none());
+    }
+
+    #[test]
+    fn test_reports_malformed_lines() {
+        let mut decoder = NdJsonDecoder::new();
+        decoder.push(b"{\"path\":\n");
+        assert!(decoder.next_data().unwrap().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::storage::versioning::{now_millis, versioned_path};
+use crate::{Data, DataPath, DataStorer, DataStorerError};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+/// How much of the data matching a policy is retained
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionRule {
+    /// Expires entries and versions written longer ago than the given duration.
+    /// Data without a `written_at` time never expires under this rule.
+    MaxAge(Duration),
+    /// Expires all but the given number of most recent versions of each entry
+    MaxVersions(usize),
+}
+
+/// Applies a rule to every entry whose path matches a pattern. Patterns are
+/// paths in which a `*` segment matches any single segment, e.g.
+/// `.sessions.*.`, and cover the whole subtree beneath any matched path.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub pattern: String,
+    pub rule: RetentionRule,
+}
+
+impl RetentionPolicy {
+    /// Returns whether the policy applies to the entry at the given path
+    pub fn matches(&self, path: &str) -> bool {
+        DataPath::from(path).matches(&self.pattern)
+    }
+
+    /// Returns the longest prefix of the pattern without wildcards, under
+    /// which every matching entry is stored
+    fn literal_prefix(&self) -> String {
+        let literal: Vec<_> = self
+            .pattern
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .take_while(|s| *s != "*")
+            .collect();
+        DataPath::from(literal.join(".").as_str()).to_string()
+    }
+}
+
+/// A registry of the retention policies to enforce
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RetentionPolicies {
+    policies: Vec<RetentionPolicy>,
+}
+
+impl RetentionPolicies {
+    /// Builds an empty registry
+    pub fn new() -> Self {
+        RetentionPolicies::default()
+    }
+
+    /// Registers a rule for every entry matching the path pattern
+    pub fn register(mut self, pattern: &str, rule: RetentionRule) -> Self {
+        self.policies.push(RetentionPolicy {
+            pattern: DataPath::from(pattern).to_string(),
+            rule,
+        });
+        self
+    }
+
+    /// Returns the registered policies
+    pub fn policies(&self) -> &[RetentionPolicy] {
+        &self.policies
+    }
+}
+
+/// Whether a retention run removes expired data or only reports it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionMode {
+    DryRun,
+    Enforce,
+}
+
+/// Describes the outcome of a retention run
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RetentionReport {
+    /// The paths of the expired entries and versions, which were removed
+    /// unless this was a dry run
+    pub expired: Vec<String>,
+    /// The number of expired entries copied to the archive before removal
+    pub archived: usize,
+    /// The number of matching entries whose age is unknown
+    pub unknown_age: usize,
+    pub dry_run: bool,
+}
+
+/// Deletes the data expired under the given policies.
+///
+/// Versions are only visible to retention through the storer a
+/// `VersionedDataStorer` wraps, so pass that storer to enforce
+/// `RetentionRule::MaxVersions`.
+pub async fn run_retention<S: DataStorer>(
+    storer: &S,
+    policies: &RetentionPolicies,
+    mode: RetentionMode,
+) -> Result<RetentionReport, DataStorerError> {
+    run(storer, None::<&S>, policies, mode).await
+}
+
+/// Moves the data expired under the given policies into an archive storer,
+/// at the same paths, before deleting it; see `run_retention`.
+pub async fn run_retention_with_archive<S: DataStorer, A: DataStorer>(
+    storer: &S,
+    archive: &A,
+    policies: &RetentionPolicies,
+    mode: RetentionMode,
+) -> Result<RetentionReport, DataStorerError> {
+    run(storer, Some(archive), policies, mode).await
+}
+
+async fn run<S: DataStorer, A: DataStorer>(
+    storer: &S,
+    archive: Option<&A>,
+    policies: &RetentionPolicies,
+    mode: RetentionMode,
+) -> Result<RetentionReport, DataStorerError> {
+    let now = now_millis();
+    let mut expired = BTreeMap::<String, Data>::new();
+    let mut unknown_age = BTreeSet::<String>::new();
+
+    for policy in policies.policies() {
+        let mut matching = Vec::new();
+        let prefix = policy.literal_prefix();
+        let mut entries = storer.iter_collection(&prefix);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let path = entry.path();
+            if policy.matches(versioned_path(&path).unwrap_or(&path)) {
+                matching.push(entry);
+            }
+        }
+        drop(entries);
+
+        match policy.rule {
+            RetentionRule::MaxAge(max_age) => {
+                let cutoff = now.saturating_sub(max_age.as_millis() as u64);
+                for entry in matching {
+                    match entry.written_at() {
+                        Some(written_at) if written_at < cutoff => {
+                            expired.insert(entry.path(), entry);
+                        }
+                        Some(_) => (),
+                        None => {
+                            unknown_age.insert(entry.path());
+                        }
+                    }
+                }
+            }
+            RetentionRule::MaxVersions(keep) => {
+                let mut versions = BTreeMap::<String, Vec<Data>>::new();
+                for entry in matching {
+                    if let Some(owner) = versioned_path(&entry.path()) {
+                        versions.entry(owner.to_owned()).or_default().push(entry);
+                    }
+                }
+                for (_, mut versions) in versions {
+                    versions.sort_by_key(|v| v.path());
+                    let excess = versions.len().saturating_sub(keep);
+                    for version in versions.into_iter().take(excess) {
+                        expired.insert(version.path(), version);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut report = RetentionReport {
+        expired: expired.keys().cloned().collect(),
+        archived: 0,
+        unknown_age: unknown_age.len(),
+        dry_run: mode == RetentionMode::DryRun,
+    };
+    if mode == RetentionMode::Enforce {
+        for (path, data) in expired {
+            if let Some(archive) = archive {
+                archive.create(data).await?;
+                report.archived += 1;
+            }
+            storer.delete(&path).await?;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::storage::versioning::{now_millis, version_path};
+    use crate::{
+        run_retention, run_retention_with_archive, Data, DataStorer, DataValue, RetentionMode,
+        RetentionPolicies, RetentionPolicy, RetentionRule,
+    };
+    use std::time::Duration;
+
+    fn written(path: &str, written_at: u64) -> Data {
+        let mut data = Data::new(path, DataValue::from(1u64));
+        data.set_written_at(Some(written_at));
+        data
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        let policy = RetentionPolicy {
+            pattern: ".sessions.*.tokens.".to_owned(),
+            rule: RetentionRule::MaxVersions(1),
+        };
+        assert!(policy.matches(".sessions.abc.tokens."));
+        assert!(policy.matches(".sessions.abc.tokens.1."));
+        assert!(!policy.matches(".sessions.abc.profile."));
+        assert!(!policy.matches(".sessions.abc."));
+        assert_eq!(".sessions.", policy.literal_prefix());
+    }
+
+    #[tokio::test]
+    async fn test_max_age_dry_run_and_enforce() {
+        let (storer, entries) = memory_storer();
+        let now = now_millis();
+        storer
+            .create(written(".sessions.old.", now - 10_000))
+            .await
+            .unwrap();
+        storer.create(written(".sessions.new.", now)).await.unwrap();
+        storer
+            .create(Data::new(".sessions.unknown.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        let policies = RetentionPolicies::new().register(
+            ".sessions.*.",
+            RetentionRule::MaxAge(Duration::from_secs(5)),
+        );
+
+        let report = run_retention(&storer, &policies, RetentionMode::DryRun)
+            .await
+            .unwrap();
+        assert_eq!(vec![".sessions.old."], report.expired);
+        assert_eq!(1, report.unknown_age);
+        assert_eq!(3, entries.lock().unwrap().len());
+
+        run_retention(&storer, &policies, RetentionMode::Enforce)
+            .await
+            .unwrap();
+        assert!(!entries.lock().unwrap().contains_key(".sessions.old."));
+        assert_eq!(2, entries.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_max_versions_archives_old_versions() {
+        let (storer, entries) = memory_storer();
+        let (archive, archived) = memory_storer();
+        storer.create(written(".a.", 3)).await.unwrap();
+        for t in 1..=3 {
+            storer
+                .create(written(&version_path(".a.", t), t))
+                .await
+                .unwrap();
+        }
+        let policies = RetentionPolicies::new().register(".a.", RetentionRule::MaxVersions(1));
+
+        let report =
+            run_retention_with_archive(&storer, &archive, &policies, RetentionMode::Enforce)
+                .await
+                .unwrap();
+
+        assert_eq!(
+            vec![version_path(".a.", 1), version_path(".a.", 2)],
+            report.expired
+        );
+        assert_eq!(2, report.archived);
+        assert_eq!(2, archived.lock().unwrap().len());
+        let remaining: Vec<_> = entries.lock().unwrap().keys().cloned().collect();
+        assert_eq!(vec![".a.".to_owned(), version_path(".a.", 3)], remaining);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataPath, DataStats, DataStorer,
+    DataStorerError, DataValue, EraseReport, OpContext, Sort,
+};
+use async_trait::async_trait;
+
+/// A view of a storer in which every path is relative to a fixed prefix, so
+/// that code handed the view cannot read or write outside of the prefix.
+/// Data returned by the wrapped storer from outside the prefix fails the
+/// operation with `DataStorerError::ScopeViolation`.
+#[derive(Clone)]
+pub struct ScopedDataStorer<S: DataStorer> {
+    storer: S,
+    paths: PathScope,
+}
+
+impl<S: DataStorer> ScopedDataStorer<S> {
+    /// Instantiates a view of an existing storer rooted at the given prefix
+    pub fn new(storer: S, prefix: &str) -> Self {
+        ScopedDataStorer {
+            storer,
+            paths: PathScope::new(prefix),
+        }
+    }
+
+    /// Returns the prefix all paths in this view are relative to
+    pub fn prefix(&self) -> &str {
+        &self.paths.prefix
+    }
+}
+
+/// Maps paths relative to a prefix to their location in a wrapped storer and
+/// back, for the views which confine a storer under a prefix. Paths read back
+/// from outside the prefix are a `DataStorerError::ScopeViolation`, or a
+/// `DataStorerError::TenantViolation` if the prefix belongs to a tenant.
+#[derive(Clone)]
+pub(crate) struct PathScope {
+    prefix: String,
+    tenant: Option<String>,
+}
+
+impl PathScope {
+    /// Instantiates a mapping rooted at the given prefix
+    pub(crate) fn new(prefix: &str) -> Self {
+        PathScope {
+            prefix: DataPath::from(prefix).to_string(),
+            tenant: None,
+        }
+    }
+
+    /// Instantiates a mapping rooted at the given prefix, which holds the data
+    /// of a tenant
+    pub(crate) fn with_tenant(prefix: &str, tenant: &str) -> Self {
+        PathScope {
+            tenant: Some(tenant.to_owned()),
+            ..PathScope::new(prefix)
+        }
+    }
+
+    /// Maps a path relative to the prefix to its location in the wrapped storer
+    pub(crate) fn scope(&self, path: &str) -> String {
+        let path = DataPath::from(path).to_string();
+        format!("{}{}", self.prefix, &path[1..])
+    }
+
+    /// Maps a path in the wrapped storer back to its relative path
+    pub(crate) fn unscope_path(&self, path: String) -> Result<String, DataStorerError> {
+        match path.strip_prefix(&self.prefix[..self.prefix.len() - 1]) {
+            Some(relative) if path.starts_with(&self.prefix) => Ok(relative.to_owned()),
+            _ => Err(match self.tenant {
+                Some(ref tenant) => DataStorerError::TenantViolation {
+                    tenant: tenant.clone(),
+                    path,
+                },
+                None => DataStorerError::ScopeViolation {
+                    prefix: self.prefix.clone(),
+                    path,
+                },
+            }),
+        }
+    }
+
+    /// Maps data read from the wrapped storer back to its relative path
+    pub(crate) fn unscope(&self, mut data: Data) -> Result<Data, DataStorerError> {
+        let path = self.unscope_path(data.path())?;
+        data.set_path(&path);
+        Ok(data)
+    }
+
+    pub(crate) fn unscope_page(
+        &self,
+        page: DataCollection,
+    ) -> Result<DataCollection, DataStorerError> {
+        Ok(DataCollection {
+            data: page
+                .data
+                .into_iter()
+                .map(|d| self.unscope(d))
+                .collect::<Result<_, _>>()?,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Maps the paths an erasure in the wrapped storer reports back to their
+    /// relative paths
+    pub(crate) fn unscope_report(
+        &self,
+        report: EraseReport,
+    ) -> Result<EraseReport, DataStorerError> {
+        Ok(EraseReport {
+            erased: report
+                .erased
+                .into_iter()
+                .map(|path| self.unscope_path(path))
+                .collect::<Result<_, _>>()?,
+            ..report
+        })
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for ScopedDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(&self.paths.scope(path)).await?;
+        self.paths.unscope(data)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(&self.paths.scope(path), skip, page_size, sort)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_filtered_collection(&self.paths.scope(path), filter, skip, page_size, sort)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(&self.paths.scope(path), cursor, page_size)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn create(&self, mut data: Data) -> Result<bool, DataStorerError> {
+        data.set_path(&self.paths.scope(&data.path()));
+        self.storer.create(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        let scoped = self.paths.scope(path);
+        let data = self.storer.get_or_create(&scoped, default).await?;
+        self.paths.unscope(data)
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.storer.increment(&self.paths.scope(path), delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.storer
+            .compare_and_swap(&self.paths.scope(path), expected, new)
+            .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(&self.paths.scope(path)).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let report = self.storer.erase(&self.paths.scope(prefix)).await?;
+        self.paths.unscope_report(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer
+            .aggregate(&self.paths.scope(path), aggregation)
+            .await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.storer.stats(&self.paths.scope(prefix)).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataStorerError, DataValue, ScopedDataStorer};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_paths_are_relative_to_prefix() {
+        let (inner, entries) = memory_storer();
+        let storer = ScopedDataStorer::new(inner, "myservice");
+
+        storer
+            .create(Data::new(".config.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(entries.lock().unwrap().contains_key(".myservice.config."));
+        assert_eq!(".config.", storer.get("config").await.unwrap().path());
+    }
+
+    #[tokio::test]
+    async fn test_sibling_trees_are_unreachable() {
+        let inner = Arc::new(memory_storer().0);
+        inner
+            .create(Data::new(".other.config.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        let storer = inner.scoped(".myservice.");
+
+        assert!(storer.get(".other.config.").await.is_err());
+        let page = storer.get_collection(".", 0, 100, None).await.unwrap();
+        assert!(page.data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_views_nest() {
+        let (inner, entries) = memory_storer();
+        let storer = ScopedDataStorer::new(ScopedDataStorer::new(inner, ".a."), ".b.");
+
+        storer
+            .create(Data::new(".c.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(entries.lock().unwrap().contains_key(".a.b.c."));
+        let page = storer.get_collection(".", 0, 100, None).await.unwrap();
+        assert_eq!(".c.", page.data[0].path());
+    }
+
+    #[tokio::test]
+    async fn test_erase_reports_relative_paths() {
+        let (inner, entries) = memory_storer();
+        let storer = ScopedDataStorer::new(inner, ".myservice.");
+
+        storer
+            .create(Data::new(".users.1.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let report = storer.erase(".users.").await.unwrap();
+        assert_eq!(vec![".users.1."], report.erased);
+        assert!(entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_data_outside_prefix_is_refused() {
+        let (mut inner, _) = memory_storer();
+        inner.checkpoint();
+        inner
+            .expect_get()
+            .returning(|_| Ok(Data::new(".other.config.", DataValue::from(1u64))));
+        let storer = ScopedDataStorer::new(inner, ".myservice.");
+
+        let result = storer.get(".config.").await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::ScopeViolation { ref path, .. }) if path == ".other.config."
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataSignature, DataStorer, DataStorerError,
+    EraseReport, OpContext, SigningError, Sort,
+};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Produces detached signatures over the canonical encoding of `Data`.
+#[async_trait]
+pub trait DataSigner: Clone + Send + Sync {
+    /// Signs the given message, returning the signature and the key it was made with
+    async fn sign(&self, message: &[u8]) -> Result<DataSignature, SigningError>;
+}
+
+/// Checks detached signatures over the canonical encoding of `Data`.
+#[async_trait]
+pub trait DataVerifier: Clone + Send + Sync {
+    /// Returns whether the signature is valid for the given message
+    async fn verify(&self, message: &[u8], signature: &DataSignature)
+        -> Result<bool, SigningError>;
+}
+
+/// Signs and verifies data with HMAC-SHA256 using a single shared key.
+/// Because the key is symmetric, every party able to verify is also able to
+/// sign; use an asymmetric implementation when verifiers must not be producers.
+#[derive(Clone)]
+pub struct HmacDataSigner {
+    keyid: String,
+    key: Vec<u8>,
+}
+
+impl HmacDataSigner {
+    /// The algorithm identifier recorded in signatures made by this signer
+    pub const ALGORITHM: &'static str = "HS256";
+
+    /// Instantiates a signer using the given key, identified by `keyid`
+    pub fn new(keyid: &str, key: &[u8]) -> Self {
+        HmacDataSigner {
+            keyid: keyid.to_owned(),
+            key: key.to_vec(),
+        }
+    }
+
+    fn mac(&self, message: &[u8]) -> Result<Hmac<Sha256>, SigningError> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).map_err(|e| SigningError::InternalError {
+                source: Box::new(e),
+            })?;
+        mac.update(message);
+        Ok(mac)
+    }
+}
+
+#[async_trait]
+impl DataSigner for HmacDataSigner {
+    async fn sign(&self, message: &[u8]) -> Result<DataSignature, SigningError> {
+        Ok(DataSignature {
+            algorithm: HmacDataSigner::ALGORITHM.to_owned(),
+            keyid: self.keyid.clone(),
+            signature: self.mac(message)?.finalize().into_bytes().to_vec(),
+        })
+    }
+}
+
+#[async_trait]
+impl DataVerifier for HmacDataSigner {
+    async fn verify(
+        &self,
+        message: &[u8],
+        signature: &DataSignature,
+    ) -> Result<bool, SigningError> {
+        if signature.keyid != self.keyid {
+            return Err(SigningError::UnknownKey {
+                keyid: signature.keyid.clone(),
+            });
+        }
+        if signature.algorithm != HmacDataSigner::ALGORITHM {
+            return Ok(false);
+        }
+        Ok(self
+            .mac(message)?
+            .verify_slice(&signature.signature)
+            .is_ok())
+    }
+}
+
+/// Wraps a storer to sign every written `Data` and verify the signature of
+/// every `Data` read back, failing reads of unsigned or invalidly signed data
+/// with `DataStorerError::InvalidSignature`.
+#[derive(Clone)]
+pub struct SigningDataStorer<S: DataStorer, G: DataSigner, V: DataVerifier> {
+    storer: S,
+    signer: G,
+    verifier: V,
+}
+
+impl<S: DataStorer, G: DataSigner, V: DataVerifier> SigningDataStorer<S, G, V> {
+    /// Instantiates a signing storer around an existing storer
+    pub fn new(storer: S, signer: G, verifier: V) -> Self {
+        SigningDataStorer {
+            storer,
+            signer,
+            verifier,
+        }
+    }
+
+    async fn verify(&self, data: Data) -> Result<Data, DataStorerError> {
+        let valid = match data.signature() {
+            Some(signature) => {
+                self.verifier
+                    .verify(&data.canonical_bytes(), signature)
+                    .await?
+            }
+            None => false,
+        };
+        if valid {
+            Ok(data)
+        } else {
+            Err(DataStorerError::InvalidSignature { path: data.path() })
+        }
+    }
+
+    async fn verify_page(&self, page: DataCollection) -> Result<DataCollection, DataStorerError> {
+        let mut data = Vec::with_capacity(page.data.len());
+        for d in page.data {
+            data.push(self.verify(d).await?);
+        }
+        Ok(DataCollection {
+            data,
+            next_cursor: page.next_cursor,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer, G: DataSigner, V: DataVerifier> DataStorer for SigningDataStorer<S, G, V> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(path).await?;
+        self.verify(data).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(path, skip, page_size, sort)
+            .await?;
+        self.verify_page(page).await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(path, cursor, page_size)
+            .await?;
+        self.verify_page(page).await
+    }
+
+    async fn create(&self, mut data: Data) -> Result<bool, DataStorerError> {
+        let signature = self.signer.sign(&data.canonical_bytes()).await?;
+        data.set_signature(Some(signature));
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(prefix).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        Data, DataSigner, DataStorer, DataStorerError, DataValue, DataVerifier, HmacDataSigner,
+        SigningDataStorer, SigningError,
+    };
+
+    #[tokio::test]
+    async fn test_hmac_round_trip() {
+        let signer = HmacDataSigner::new("k1", b"secret");
+        let signature = signer.sign(b"message").await.unwrap();
+        assert_eq!("HS256", signature.algorithm);
+        assert!(signer.verify(b"message", &signature).await.unwrap());
+        assert!(!signer.verify(b"other", &signature).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_hmac_unknown_key() {
+        let signature = HmacDataSigner::new("k1", b"secret")
+            .sign(b"message")
+            .await
+            .unwrap();
+        let result = HmacDataSigner::new("k2", b"secret")
+            .verify(b"message", &signature)
+            .await;
+        assert!(matches!(result, Err(SigningError::UnknownKey { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_storer_signs_and_verifies() {
+        let (inner, entries) = memory_storer();
+        let signer = HmacDataSigner::new("k1", b"secret");
+        let storer = SigningDataStorer::new(inner, signer.clone(), signer);
+
+        storer
+            .create(Data::new(".path.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(entries.lock().unwrap()[".path."].signature().is_some());
+        assert_eq!("1", storer.get(".path.").await.unwrap().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_storer_rejects_unsigned_data() {
+        let (inner, entries) = memory_storer();
+        let signer = HmacDataSigner::new("k1", b"secret");
+        let storer = SigningDataStorer::new(inner, signer.clone(), signer);
+
+        entries.lock().unwrap().insert(
+            ".path.".to_owned(),
+            Data::new(".path.", DataValue::from(1u64)),
+        );
+
+        let result = storer.get(".path.").await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::InvalidSignature { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_storer_rejects_data_signed_by_another_producer() {
+        let (inner, _) = memory_storer();
+        let storer = SigningDataStorer::new(
+            inner,
+            HmacDataSigner::new("k1", b"forged"),
+            HmacDataSigner::new("k1", b"secret"),
+        );
+
+        storer
+            .create(Data::new(".path.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        let result = storer.get(".path.").await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::InvalidSignature { .. })
+        ));
+    }
+}
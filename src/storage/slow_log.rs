@@ -0,0 +1,233 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataPatch, DataStats, DataStorer,
+    DataStorerError, DataValue, EraseReport, MergeStrategy, OpContext, Sort,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Runs an operation, logging a warning with the backend, operation, path, and
+/// elapsed time if it took longer than the threshold, whether it succeeded or
+/// not
+pub(crate) async fn log_if_slow<F: Future>(
+    backend: &str,
+    operation: &str,
+    path: &str,
+    threshold: Duration,
+    f: F,
+) -> F::Output {
+    let started = Instant::now();
+    let output = f.await;
+    let elapsed = started.elapsed();
+    if elapsed > threshold {
+        tracing::warn!(
+            backend,
+            operation,
+            path,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "slow operation"
+        );
+    }
+    output
+}
+
+/// Wraps a storer to log every operation taking longer than a threshold, so
+/// that tail latencies can be traced back to paths without instrumenting
+/// every call site. The backend name distinguishes storers in the logs.
+#[derive(Clone)]
+pub struct SlowLogDataStorer<S: DataStorer> {
+    storer: S,
+    backend: String,
+    threshold: Duration,
+}
+
+impl<S: DataStorer> SlowLogDataStorer<S> {
+    /// Instantiates a storer logging the operations of an existing storer
+    /// which take longer than `threshold`
+    pub fn new(storer: S, backend: &str, threshold: Duration) -> Self {
+        SlowLogDataStorer {
+            storer,
+            backend: backend.to_owned(),
+            threshold,
+        }
+    }
+
+    async fn log<F: Future>(&self, operation: &str, path: &str, f: F) -> F::Output {
+        log_if_slow(&self.backend, operation, path, self.threshold, f).await
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for SlowLogDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.log("get", path, self.storer.get(path)).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.log(
+            "get_collection",
+            path,
+            self.storer.get_collection(path, skip, page_size, sort),
+        )
+        .await
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.log(
+            "get_filtered_collection",
+            path,
+            self.storer
+                .get_filtered_collection(path, filter, skip, page_size, sort),
+        )
+        .await
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        self.log(
+            "get_collection_page",
+            path,
+            self.storer.get_collection_page(path, cursor, page_size),
+        )
+        .await
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        let path = data.path();
+        self.log("create", &path, self.storer.create(data)).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        self.log(
+            "get_or_create",
+            path,
+            self.storer.get_or_create(path, default),
+        )
+        .await
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.log("increment", path, self.storer.increment(path, delta))
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.log(
+            "compare_and_swap",
+            path,
+            self.storer.compare_and_swap(path, expected, new),
+        )
+        .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.log("delete", path, self.storer.delete(path)).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.log("erase", prefix, self.storer.erase(prefix)).await
+    }
+
+    async fn apply_patch(&self, prefix: &str, patch: &DataPatch) -> Result<(), DataStorerError> {
+        self.log(
+            "apply_patch",
+            prefix,
+            self.storer.apply_patch(prefix, patch),
+        )
+        .await
+    }
+
+    async fn put_subtree(
+        &self,
+        prefix: &str,
+        value: Value,
+        strategy: MergeStrategy,
+    ) -> Result<DataPatch, DataStorerError> {
+        self.log(
+            "put_subtree",
+            prefix,
+            self.storer.put_subtree(prefix, value, strategy),
+        )
+        .await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.log("aggregate", path, self.storer.aggregate(path, aggregation))
+            .await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.log("stats", prefix, self.storer.stats(prefix)).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log_if_slow, SlowLogDataStorer};
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataValue};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_forwards_operations() {
+        let (inner, _) = memory_storer();
+        let storer = SlowLogDataStorer::new(inner, "memory", Duration::from_secs(0));
+        assert!(storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap());
+        assert_eq!(
+            Data::new(".a.", DataValue::from(1u64)),
+            storer.get(".a.").await.unwrap()
+        );
+        assert!(storer.delete(".a.").await.unwrap());
+        assert!(storer.get(".a.").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_log_if_slow_returns_output() {
+        let slow = async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            7
+        };
+        assert_eq!(
+            7,
+            log_if_slow("memory", "get", ".a.", Duration::from_millis(1), slow).await
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{Data, DataType, DataValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Usage statistics of the data stored under a prefix. Sizes are those of the
+/// backend's own encoding where it can report them, and of the JSON encoding
+/// of each entry otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DataStats {
+    pub entries: u64,
+    pub total_size_bytes: u64,
+    pub values: u64,
+    pub encrypted_values: u64,
+    /// Number of values of each type, keyed by the type's name, e.g. `string`
+    pub types: BTreeMap<String, u64>,
+}
+
+impl DataStats {
+    /// Creates empty statistics
+    pub fn new() -> Self {
+        DataStats::default()
+    }
+
+    /// Adds an entry, and each of its values, to the statistics
+    pub fn add_data(&mut self, data: &Data) {
+        self.entries += 1;
+        self.total_size_bytes += serde_json::to_vec(data).map_or(0, |b| b.len() as u64);
+        for value in data.value().0.iter() {
+            self.add_value(
+                &value.datatype(),
+                matches!(value, DataValue::Encrypted(_)),
+                1,
+            );
+        }
+    }
+
+    /// Adds `count` values of a type and encryption state to the statistics
+    pub fn add_value(&mut self, datatype: &DataType, encrypted: bool, count: u64) {
+        self.values += count;
+        if encrypted {
+            self.encrypted_values += count;
+        }
+        *self.types.entry(datatype.to_string()).or_insert(0) += count;
+    }
+
+    /// Returns the share of values which are encrypted, from 0 to 1, or `None`
+    /// if there are no values
+    pub fn encrypted_ratio(&self) -> Option<f64> {
+        match self.values {
+            0 => None,
+            values => Some(self.encrypted_values as f64 / values as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Data, DataStats, DataType, DataValue, DataValueCollection, EncryptedDataValue};
+
+    #[test]
+    fn test_add_data() {
+        let mut stats = DataStats::new();
+        assert_eq!(None, stats.encrypted_ratio());
+
+        let data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from("x"),
+                DataValue::from(1u64),
+                DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::String, "k1")),
+            ]),
+        );
+        stats.add_data(&data);
+        stats.add_data(&Data::new(".b.", DataValue::from("y")));
+
+        assert_eq!(2, stats.entries);
+        assert_eq!(4, stats.values);
+        assert_eq!(Some(0.25), stats.encrypted_ratio());
+        assert_eq!(Some(&3), stats.types.get("string"));
+        assert_eq!(Some(&1), stats.types.get("u64"));
+        assert_eq!(
+            (serde_json::to_vec(&data).unwrap().len()
+                + serde_json::to_vec(&Data::new(".b.", DataValue::from("y")))
+                    .unwrap()
+                    .len()) as u64,
+            stats.total_size_bytes
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{Conflict, ConflictResolver, Data, DataStorer, DataStorerError, Resolution};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The checkpoint of the syncs between two storers: the digest each entry had
+/// on both sides when it was last synced, telling which side changed it
+/// since, and how far an interrupted sync got. Keep it alongside the storers,
+/// e.g. serialized to a file, and pass the same checkpoint to every sync of
+/// the same prefix between them.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncState {
+    synced: BTreeMap<String, String>,
+    resume_after: Option<String>,
+}
+
+impl SyncState {
+    /// Creates the checkpoint of two storers never synced before
+    pub fn new() -> Self {
+        SyncState::default()
+    }
+
+    /// Returns the digest an entry had on both sides when it was last synced
+    pub fn synced_digest(&self, path: &str) -> Option<&str> {
+        self.synced.get(path).map(String::as_str)
+    }
+
+    /// Returns whether the last sync was interrupted, so that the next one
+    /// only reconciles the paths it did not reach
+    pub fn is_resuming(&self) -> bool {
+        self.resume_after.is_some()
+    }
+}
+
+/// Describes the outcome of a sync
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The paths written or deleted on the local side
+    pub pulled: Vec<String>,
+    /// The paths written or deleted on the remote side
+    pub pushed: Vec<String>,
+    /// The paths changed on both sides since they were last synced, which
+    /// were resolved by the conflict resolver
+    pub conflicts: Vec<String>,
+}
+
+/// Which side of a sync a path is copied to
+enum Transfer {
+    None,
+    Pull,
+    Push,
+    Merge(Box<Data>),
+}
+
+/// Reconciles the entries under a prefix of two storers, e.g. an edge store
+/// and a central one, copying each entry changed on one side since the last
+/// sync to the other. Entries are compared by the digest of their path and
+/// values, so only changed entries are written, and deletions are carried
+/// over too. Entries changed on both sides are settled by the conflict
+/// resolver, except that an entry changed locally and deleted remotely is
+/// always kept.
+///
+/// Paths are reconciled in order, recording each in the checkpoint, so that
+/// a sync which fails is resumed by the next after the last path reconciled.
+pub async fn run_sync<L: DataStorer, R: DataStorer>(
+    local: &L,
+    remote: &R,
+    prefix: &str,
+    resolver: &dyn ConflictResolver,
+    state: &mut SyncState,
+) -> Result<SyncReport, DataStorerError> {
+    let local_entries = entries(local, prefix).await?;
+    let remote_entries = entries(remote, prefix).await?;
+    let paths: BTreeSet<&String> = local_entries
+        .keys()
+        .chain(remote_entries.keys())
+        .chain(state.synced.keys().filter(|path| path.starts_with(prefix)))
+        .collect();
+    let paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| {
+            state
+                .resume_after
+                .as_ref()
+                .is_none_or(|after| *path > after)
+        })
+        .cloned()
+        .collect();
+
+    let mut report = SyncReport::default();
+    for path in paths {
+        let l = local_entries.get(&path);
+        let r = remote_entries.get(&path);
+        let (local_digest, remote_digest) = (digest(l), digest(r));
+        let synced = state.synced.get(&path).cloned();
+        let transfer = if local_digest == remote_digest {
+            Transfer::None
+        } else if local_digest == synced {
+            Transfer::Pull
+        } else if remote_digest == synced {
+            Transfer::Push
+        } else {
+            report.conflicts.push(path.clone());
+            match r {
+                None => Transfer::Push,
+                Some(r) => {
+                    let conflict = Conflict {
+                        path: &path,
+                        local: l,
+                        local_written_at: l.and_then(Data::written_at),
+                        remote: r,
+                    };
+                    match resolver.resolve(&conflict) {
+                        Resolution::Local => Transfer::Push,
+                        Resolution::Remote => Transfer::Pull,
+                        Resolution::Merged(data) => Transfer::Merge(data),
+                    }
+                }
+            }
+        };
+
+        let digest = match transfer {
+            Transfer::None => local_digest,
+            Transfer::Pull => {
+                copy(r, local, &path).await?;
+                report.pulled.push(path.clone());
+                remote_digest
+            }
+            Transfer::Push => {
+                copy(l, remote, &path).await?;
+                report.pushed.push(path.clone());
+                local_digest
+            }
+            Transfer::Merge(data) => {
+                let digest = Some(data.compute_integrity());
+                local.create((*data).clone()).await?;
+                report.pulled.push(path.clone());
+                remote.create(*data).await?;
+                report.pushed.push(path.clone());
+                digest
+            }
+        };
+        match digest {
+            Some(digest) => state.synced.insert(path.clone(), digest),
+            None => state.synced.remove(&path),
+        };
+        state.resume_after = Some(path);
+    }
+    state.resume_after = None;
+    Ok(report)
+}
+
+async fn entries<S: DataStorer>(
+    storer: &S,
+    prefix: &str,
+) -> Result<BTreeMap<String, Data>, DataStorerError> {
+    let mut entries = BTreeMap::new();
+    let mut stream = storer.iter_collection(prefix);
+    while let Some(data) = stream.next().await {
+        let data = data?;
+        entries.insert(data.path(), data);
+    }
+    Ok(entries)
+}
+
+fn digest(data: Option<&Data>) -> Option<String> {
+    data.map(Data::compute_integrity)
+}
+
+/// Writes the given version of an entry to a storer, deleting the entry if
+/// there is none
+async fn copy<S: DataStorer>(
+    data: Option<&Data>,
+    storer: &S,
+    path: &str,
+) -> Result<(), DataStorerError> {
+    match data {
+        Some(data) => storer.create(data.clone()).await.map(|_| ()),
+        None => storer.delete(path).await.map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_sync, SyncReport, SyncState};
+    use crate::{Data, DataStorer, DataValue, LastWriteWins, MemoryDataStorer, PreferRemote};
+
+    async fn value(storer: &MemoryDataStorer, path: &str) -> Option<String> {
+        storer
+            .get(path)
+            .await
+            .ok()
+            .map(|data| data.value().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_sync_exchanges_changed_entries() {
+        let (local, remote) = (MemoryDataStorer::new(), MemoryDataStorer::new());
+        let mut state = SyncState::new();
+        for (path, value) in [(".a.", 1u64), (".b.", 2)] {
+            local
+                .create(Data::new(path, DataValue::from(value)))
+                .await
+                .unwrap();
+        }
+        for (path, value) in [(".b.", 2u64), (".c.", 3)] {
+            remote
+                .create(Data::new(path, DataValue::from(value)))
+                .await
+                .unwrap();
+        }
+
+        let report = run_sync(&local, &remote, ".", &LastWriteWins, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec![".c."], report.pulled);
+        assert_eq!(vec![".a."], report.pushed);
+        assert!(!state.is_resuming());
+        assert!(state.synced_digest(".b.").is_some());
+
+        remote
+            .create(Data::new(".a.", DataValue::from(10u64)))
+            .await
+            .unwrap();
+        local.delete(".c.").await.unwrap();
+        let report = run_sync(&local, &remote, ".", &LastWriteWins, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec![".a."], report.pulled);
+        assert_eq!(vec![".c."], report.pushed);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(Some("10".to_owned()), value(&local, ".a.").await);
+        assert_eq!(None, value(&remote, ".c.").await);
+        assert_eq!(None, state.synced_digest(".c."));
+
+        local
+            .create(Data::new(".b.", DataValue::from(20u64)))
+            .await
+            .unwrap();
+        remote
+            .create(Data::new(".b.", DataValue::from(200u64)))
+            .await
+            .unwrap();
+        let report = run_sync(&local, &remote, ".", &PreferRemote, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec![".b."], report.conflicts);
+        assert_eq!(Some("200".to_owned()), value(&local, ".b.").await);
+        let report = run_sync(&local, &remote, ".", &PreferRemote, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(SyncReport::default(), report);
+    }
+
+    #[tokio::test]
+    async fn test_sync_resumes_after_last_path_reconciled() {
+        let (local, remote) = (MemoryDataStorer::new(), MemoryDataStorer::new());
+        for path in [".a.", ".b.", ".c."] {
+            local
+                .create(Data::new(path, DataValue::from(1u64)))
+                .await
+                .unwrap();
+        }
+        let mut state = SyncState::new();
+        state.resume_after = Some(".a.".to_owned());
+        assert!(state.is_resuming());
+
+        let report = run_sync(&local, &remote, ".", &LastWriteWins, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec![".b.", ".c."], report.pushed);
+        assert!(!state.is_resuming());
+        let report = run_sync(&local, &remote, ".", &LastWriteWins, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec![".a."], report.pushed);
+    }
+}
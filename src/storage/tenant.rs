@@ -0,0 +1,271 @@
+use super::scoped::PathScope;
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataStats, DataStorer, DataStorerError,
+    DataValue, EraseReport, OpContext, Sort,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Determines how a `TenantScopedDataStorer` keeps tenants apart in the
+/// storer it wraps.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantStrategy {
+    /// Stores each tenant's data under a `.tenant.` path prefix
+    PathPrefix,
+    /// Stores each tenant's data in its own partition of the storer, such as
+    /// a collection per tenant; see `TenantPartitioned`
+    Partition,
+}
+
+/// A storer which can hand out an instance confined to a single tenant's
+/// partition of the underlying storage.
+pub trait TenantPartitioned: DataStorer {
+    /// Returns a storer whose operations only touch the given tenant's partition
+    fn for_tenant(&self, tenant: &str) -> Self;
+}
+
+/// Wraps a storer to confine every operation to a single tenant. Callers use
+/// paths relative to the tenant, and data read back is returned at those same
+/// relative paths. With the path prefix strategy, any data the wrapped storer
+/// returns from outside the tenant's prefix fails the operation with
+/// `DataStorerError::TenantViolation` rather than being handed to the caller.
+#[derive(Clone)]
+pub struct TenantScopedDataStorer<S: DataStorer> {
+    storer: S,
+    tenant: String,
+    strategy: TenantStrategy,
+    paths: PathScope,
+}
+
+impl<S: DataStorer> TenantScopedDataStorer<S> {
+    /// Instantiates a storer scoping all operations on an existing storer to
+    /// the given tenant by prefixing paths with `.tenant.`
+    pub fn with_path_prefix(storer: S, tenant: &str) -> Result<Self, DataStorerError> {
+        validate_tenant(tenant)?;
+        Ok(Self::scoped(storer, tenant, TenantStrategy::PathPrefix))
+    }
+
+    /// Returns the id of the tenant this storer is scoped to
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Returns the strategy used to keep this tenant's data apart
+    pub fn strategy(&self) -> TenantStrategy {
+        self.strategy
+    }
+
+    /// Wraps a storer already confined to the tenant's partition, if the
+    /// strategy has one, mapping paths under the tenant's prefix otherwise
+    fn scoped(storer: S, tenant: &str, strategy: TenantStrategy) -> Self {
+        let prefix = match strategy {
+            TenantStrategy::PathPrefix => format!(".{}.", tenant),
+            TenantStrategy::Partition => ".".to_owned(),
+        };
+        TenantScopedDataStorer {
+            storer,
+            tenant: tenant.to_owned(),
+            strategy,
+            paths: PathScope::with_tenant(&prefix, tenant),
+        }
+    }
+}
+
+impl<S: TenantPartitioned> TenantScopedDataStorer<S> {
+    /// Instantiates a storer scoping all operations on an existing storer to
+    /// the given tenant using the given strategy
+    pub fn new(storer: S, tenant: &str, strategy: TenantStrategy) -> Result<Self, DataStorerError> {
+        validate_tenant(tenant)?;
+        let storer = match strategy {
+            TenantStrategy::PathPrefix => storer,
+            TenantStrategy::Partition => storer.for_tenant(tenant),
+        };
+        Ok(Self::scoped(storer, tenant, strategy))
+    }
+}
+
+/// Ensures a tenant id can be used as a single path segment or partition name
+fn validate_tenant(tenant: &str) -> Result<(), DataStorerError> {
+    let valid = !tenant.is_empty()
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(DataStorerError::InvalidTenant {
+            tenant: tenant.to_owned(),
+        })
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for TenantScopedDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(&self.paths.scope(path)).await?;
+        self.paths.unscope(data)
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(&self.paths.scope(path), skip, page_size, sort)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_filtered_collection(&self.paths.scope(path), filter, skip, page_size, sort)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(&self.paths.scope(path), cursor, page_size)
+            .await?;
+        self.paths.unscope_page(page)
+    }
+
+    async fn create(&self, mut data: Data) -> Result<bool, DataStorerError> {
+        data.set_path(&self.paths.scope(&data.path()));
+        self.storer.create(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        let scoped = self.paths.scope(path);
+        let data = self.storer.get_or_create(&scoped, default).await?;
+        self.paths.unscope(data)
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.storer.increment(&self.paths.scope(path), delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.storer
+            .compare_and_swap(&self.paths.scope(path), expected, new)
+            .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(&self.paths.scope(path)).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let report = self.storer.erase(&self.paths.scope(prefix)).await?;
+        self.paths.unscope_report(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer
+            .aggregate(&self.paths.scope(path), aggregation)
+            .await
+    }
+
+    async fn stats(&self, prefix: &str) -> Result<DataStats, DataStorerError> {
+        self.storer.stats(&self.paths.scope(prefix)).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{Data, DataStorer, DataStorerError, DataValue, TenantScopedDataStorer};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_paths_are_prefixed_with_tenant() {
+        let (inner, entries) = memory_storer();
+        let storer = TenantScopedDataStorer::with_path_prefix(inner, "acme").unwrap();
+
+        storer
+            .create(Data::new(".users.1.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(entries.lock().unwrap().contains_key(".acme.users.1."));
+        assert_eq!(".users.1.", storer.get(".users.1.").await.unwrap().path());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_are_isolated() {
+        let inner = Arc::new(memory_storer().0);
+        let acme = TenantScopedDataStorer::with_path_prefix(inner.clone(), "acme").unwrap();
+        let globex = TenantScopedDataStorer::with_path_prefix(inner, "globex").unwrap();
+
+        acme.create(Data::new(".users.1.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+
+        assert!(globex.get(".users.1.").await.is_err());
+        let page = globex.get_collection(".", 0, 100, None).await.unwrap();
+        assert!(page.data.is_empty());
+        let page = acme.get_collection(".", 0, 100, None).await.unwrap();
+        assert_eq!(
+            vec![".users.1.".to_owned()],
+            page.data.iter().map(Data::path).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_data_outside_tenant_is_refused() {
+        let (mut inner, _) = memory_storer();
+        inner.checkpoint();
+        inner
+            .expect_get()
+            .returning(|_| Ok(Data::new(".globex.users.1.", DataValue::from(1u64))));
+        let storer = TenantScopedDataStorer::with_path_prefix(inner, "acme").unwrap();
+
+        let result = storer.get(".users.1.").await;
+        assert!(matches!(
+            result,
+            Err(DataStorerError::TenantViolation { ref path, .. }) if path == ".globex.users.1."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tenant_ids_are_rejected() {
+        for tenant in &["", "a.b", "a/b", "*"] {
+            let (inner, _) = memory_storer();
+            let result = TenantScopedDataStorer::with_path_prefix(inner, tenant);
+            assert!(matches!(result, Err(DataStorerError::InvalidTenant { .. })));
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataFilter, DataStorer, DataStorerError, DataType,
+    DataValue, EraseReport, OpContext, Sort, UnencryptedDataValue,
+};
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// Converts a plaintext value to another type, returning `None` if it is
+/// already of that type or cannot be represented as it. Strings are parsed,
+/// numbers convert between each other when they fit, booleans convert to and
+/// from 0 and 1, and strings to and from UTF-8 bytes.
+fn coerce(value: &UnencryptedDataValue, to: &DataType) -> Option<UnencryptedDataValue> {
+    if value.datatype() == *to {
+        return None;
+    }
+    let plain = || DataValue::Unencrypted(value.clone());
+    match *to {
+        DataType::U64 => match *value {
+            UnencryptedDataValue::String(ref s) => s.trim().parse().ok(),
+            UnencryptedDataValue::Bool(b) => Some(u64::from(b)),
+            UnencryptedDataValue::F64(n) if n.fract() == 0.0 && n >= 0.0 && n < u64::MAX as f64 => {
+                Some(n as u64)
+            }
+            _ => u64::try_from(plain()).ok(),
+        }
+        .map(UnencryptedDataValue::U64),
+        DataType::I64 => match *value {
+            UnencryptedDataValue::String(ref s) => s.trim().parse().ok(),
+            UnencryptedDataValue::Bool(b) => Some(i64::from(b)),
+            UnencryptedDataValue::F64(n)
+                if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 =>
+            {
+                Some(n as i64)
+            }
+            _ => i64::try_from(plain()).ok(),
+        }
+        .map(UnencryptedDataValue::I64),
+        DataType::F64 => match *value {
+            UnencryptedDataValue::String(ref s) => s.trim().parse().ok(),
+            UnencryptedDataValue::Bool(b) => Some(f64::from(u8::from(b))),
+            _ => f64::try_from(plain()).ok(),
+        }
+        .map(UnencryptedDataValue::F64),
+        DataType::String => match *value {
+            UnencryptedDataValue::Bytes(ref b) => String::from_utf8(b.clone()).ok(),
+            _ => Some(value.to_string()),
+        }
+        .map(UnencryptedDataValue::String),
+        DataType::Bool => match *value {
+            UnencryptedDataValue::String(ref s) => s.trim().parse().ok(),
+            UnencryptedDataValue::U64(n) if n <= 1 => Some(n == 1),
+            UnencryptedDataValue::I64(n) if n == 0 || n == 1 => Some(n == 1),
+            _ => None,
+        }
+        .map(UnencryptedDataValue::Bool),
+        DataType::Bytes => match *value {
+            UnencryptedDataValue::String(ref s) => Some(s.clone().into_bytes()),
+            _ => None,
+        }
+        .map(UnencryptedDataValue::Bytes),
+    }
+}
+
+/// Wraps a storer holding data stored with the wrong `DataType`, converting
+/// the values under configured paths to the type they should have had as
+/// they are read, and as they are written. Historic entries are thereby
+/// served as the new type at once, and stored as it the next time they are
+/// written, with no backfill of the whole store.
+///
+/// Values which cannot be represented as the new type, such as a string which
+/// is not a number, and encrypted values, whose plaintext is out of reach,
+/// are left as they are. Signed data is left as it is too, as its signature
+/// covers its values; data carrying an integrity checksum is resealed.
+/// Collection filters and aggregations are applied by the wrapped storer, to
+/// the values as stored.
+#[derive(Clone)]
+pub struct TypeMigratingDataStorer<S: DataStorer> {
+    storer: S,
+    coercions: Vec<(String, DataType)>,
+}
+
+impl<S: DataStorer> TypeMigratingDataStorer<S> {
+    /// Instantiates a storer migrating no paths in front of an existing storer
+    pub fn new(storer: S) -> Self {
+        TypeMigratingDataStorer {
+            storer,
+            coercions: vec![],
+        }
+    }
+
+    /// Converts the values of the paths matching a pattern, in which a `*`
+    /// segment matches any single segment, e.g. `.users.*.age.`, to the given
+    /// type. Paths matching several patterns are converted as the first added.
+    pub fn with_coercion(mut self, pattern: &str, to: DataType) -> Self {
+        self.coercions.push((pattern.to_owned(), to));
+        self
+    }
+
+    fn migrate(&self, mut data: Data) -> Data {
+        let to = match self
+            .coercions
+            .iter()
+            .find(|(pattern, _)| data.data_path().matches(pattern))
+        {
+            Some((_, to)) => to,
+            None => return data,
+        };
+        if data.signature().is_some() {
+            return data;
+        }
+        let mut migrated = false;
+        for value in data.values_mut().0.iter_mut() {
+            if let DataValue::Unencrypted(ref mut plain) = *value {
+                if let Some(coerced) = coerce(plain, to) {
+                    *plain = coerced;
+                    migrated = true;
+                }
+            }
+        }
+        if migrated && data.integrity().is_some() {
+            data.seal_integrity();
+        }
+        data
+    }
+
+    fn migrate_collection(&self, mut collection: DataCollection) -> DataCollection {
+        collection.data = collection
+            .data
+            .into_iter()
+            .map(|data| self.migrate(data))
+            .collect();
+        collection
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for TypeMigratingDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        Ok(self.migrate(self.storer.get(path).await?))
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let collection = self
+            .storer
+            .get_collection(path, skip, page_size, sort)
+            .await?;
+        Ok(self.migrate_collection(collection))
+    }
+
+    async fn get_filtered_collection(
+        &self,
+        path: &str,
+        filter: &DataFilter,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let collection = self
+            .storer
+            .get_filtered_collection(path, filter, skip, page_size, sort)
+            .await?;
+        Ok(self.migrate_collection(collection))
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let collection = self
+            .storer
+            .get_collection_page(path, cursor, page_size)
+            .await?;
+        Ok(self.migrate_collection(collection))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.storer.create(self.migrate(data)).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn delete_collection(
+        &self,
+        path: &str,
+        filter: Option<&DataFilter>,
+    ) -> Result<u64, DataStorerError> {
+        self.storer.delete_collection(path, filter).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(prefix).await
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coerce, TypeMigratingDataStorer};
+    use crate::{Data, DataStorer, DataType, DataValue, MemoryDataStorer, UnencryptedDataValue};
+
+    #[test]
+    fn test_coerce() {
+        let string = |s: &str| UnencryptedDataValue::String(s.to_owned());
+        assert_eq!(
+            Some(UnencryptedDataValue::U64(42)),
+            coerce(&string(" 42 "), &DataType::U64)
+        );
+        assert_eq!(None, coerce(&string("-1"), &DataType::U64));
+        assert_eq!(
+            Some(UnencryptedDataValue::I64(-3)),
+            coerce(&UnencryptedDataValue::F64(-3.0), &DataType::I64)
+        );
+        assert_eq!(
+            None,
+            coerce(&UnencryptedDataValue::F64(0.5), &DataType::I64)
+        );
+        assert_eq!(
+            Some(string("7")),
+            coerce(&UnencryptedDataValue::U64(7), &DataType::String)
+        );
+        assert_eq!(
+            Some(UnencryptedDataValue::Bool(true)),
+            coerce(&UnencryptedDataValue::U64(1), &DataType::Bool)
+        );
+        assert_eq!(None, coerce(&string("x"), &DataType::String));
+    }
+
+    #[tokio::test]
+    async fn test_migrates_on_read_and_write() {
+        let inner = MemoryDataStorer::new();
+        for (path, value) in [
+            (".counts.a.", "42"),
+            (".counts.b.", "n/a"),
+            (".names.a.", "42"),
+        ] {
+            inner
+                .create(Data::new(path, DataValue::from(value)))
+                .await
+                .unwrap();
+        }
+        let storer =
+            TypeMigratingDataStorer::new(inner.clone()).with_coercion(".counts.*.", DataType::U64);
+
+        let a = storer.get(".counts.a.").await.unwrap();
+        assert_eq!(Ok(42u64), a.value_as::<u64>());
+        assert_eq!(
+            DataType::String,
+            inner.get(".counts.a.").await.unwrap().value().0[0].datatype()
+        );
+        assert!(storer
+            .get(".counts.b.")
+            .await
+            .unwrap()
+            .value_as::<String>()
+            .is_ok());
+        let collection = storer.get_collection(".", 0, 10, None).await.unwrap();
+        let types: Vec<_> = collection
+            .data
+            .iter()
+            .map(|data| data.value().0[0].datatype())
+            .collect();
+        assert_eq!(2, types.iter().filter(|t| **t == DataType::String).count());
+
+        storer
+            .create(Data::new(".counts.a.", DataValue::from("43")))
+            .await
+            .unwrap();
+        assert_eq!(
+            Ok(43u64),
+            inner.get(".counts.a.").await.unwrap().value_as::<u64>()
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use crate::{
+    Aggregation, Cursor, Data, DataCollection, DataPath, DataStorer, DataStorerError,
+    DataValueCollection, EraseReport, OpContext, RetentionMode, Sort, StorageError,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The path segment under which the versions of an entry are stored
+const VERSION_SEGMENT: &str = "_version";
+
+/// Returns the path the version of an entry written at the given time is
+/// stored at; times are zero-padded so versions sort chronologically by path.
+pub fn version_path(path: &str, written_at: u64) -> String {
+    format!(
+        "{}{}.{:020}.",
+        DataPath::from(path),
+        VERSION_SEGMENT,
+        written_at
+    )
+}
+
+/// Returns the path under which every version of an entry is stored
+pub fn versions_prefix(path: &str) -> String {
+    format!("{}{}.", DataPath::from(path), VERSION_SEGMENT)
+}
+
+/// Returns the path of the entry a version belongs to, or `None` if the path
+/// is not that of a version
+pub fn versioned_path(path: &str) -> Option<&str> {
+    let index = path.rfind(&format!(".{}.", VERSION_SEGMENT))?;
+    Some(&path[..index + 1])
+}
+
+/// Returns the time a version was written at, as recorded in its path
+fn version_time(version: &Data) -> Option<u64> {
+    let path = version.path();
+    let segment = path.trim_end_matches('.').rsplit('.').next()?;
+    segment.parse().ok()
+}
+
+/// Returns whether a version records the deletion of its entry rather than
+/// a write
+pub fn is_tombstone(version: &Data) -> bool {
+    version.value().0.is_empty()
+}
+
+/// Returns the current time in milliseconds since the unix epoch
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Which versions of each entry a compaction keeps
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionRule {
+    /// Keeps only the given number of most recent versions of each entry
+    KeepLast(usize),
+    /// Prunes versions written longer ago than the given duration
+    OlderThan(Duration),
+}
+
+/// Describes the outcome of a compaction
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// The paths of the pruned versions, which were removed unless this was
+    /// a dry run
+    pub pruned: Vec<String>,
+    /// The number of pruned versions that were tombstones
+    pub tombstones: usize,
+    pub dry_run: bool,
+}
+
+/// Wraps a storer to keep the history of every entry written through it.
+///
+/// Each write is stamped with its `written_at` time and also stored as a
+/// version at `.path._version.TIME.`. Deleting an entry removes it but records
+/// a tombstone version holding no values, so its history is kept; erasing a
+/// prefix removes the history along with the entries. Versions are hidden
+/// from collection reads.
+#[derive(Clone)]
+pub struct VersionedDataStorer<S: DataStorer> {
+    storer: S,
+}
+
+impl<S: DataStorer> VersionedDataStorer<S> {
+    /// Instantiates a history-keeping storer around an existing storer
+    pub fn new(storer: S) -> Self {
+        VersionedDataStorer { storer }
+    }
+
+    /// Returns every version of the entry at the path, oldest first
+    pub async fn versions(&self, path: &str) -> Result<Vec<Data>, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        let prefix = versions_prefix(&path);
+        let mut versions = Vec::new();
+        let mut entries = self.storer.iter_collection(&prefix);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if versioned_path(&entry.path()) == Some(path.as_str()) {
+                versions.push(entry);
+            }
+        }
+        versions.sort_by_key(|v| v.path());
+        Ok(versions)
+    }
+
+    /// Returns the entry at the path as it was at the given time, in
+    /// milliseconds since the unix epoch. Fails with `NotFound` if the entry
+    /// had not been written yet or was deleted at that time.
+    pub async fn get_as_of(&self, path: &str, timestamp: u64) -> Result<Data, DataStorerError> {
+        let path = DataPath::from(path).to_string();
+        let current = self
+            .versions(&path)
+            .await?
+            .into_iter()
+            .take_while(|v| version_time(v).is_some_and(|at| at <= timestamp))
+            .last();
+        match current {
+            Some(mut version) if !is_tombstone(&version) => {
+                version.set_path(&path);
+                Ok(version)
+            }
+            _ => Err(DataStorerError::StorageError {
+                source: StorageError::NotFound,
+            }),
+        }
+    }
+
+    /// Prunes the history of every entry under the prefix according to the
+    /// rule. The most recent version of an entry is always kept, unless it is
+    /// a tombstone with no earlier versions left, in which case the deleted
+    /// entry's history is dropped entirely.
+    pub async fn compact(
+        &self,
+        prefix: &str,
+        rule: CompactionRule,
+        mode: RetentionMode,
+    ) -> Result<CompactionReport, DataStorerError> {
+        let mut histories = BTreeMap::<String, Vec<Data>>::new();
+        let mut entries = self.storer.iter_collection(prefix);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if let Some(owner) = versioned_path(&entry.path()) {
+                histories.entry(owner.to_owned()).or_default().push(entry);
+            }
+        }
+        drop(entries);
+
+        let cutoff = match rule {
+            CompactionRule::OlderThan(age) => now_millis().saturating_sub(age.as_millis() as u64),
+            CompactionRule::KeepLast(_) => 0,
+        };
+        let mut report = CompactionReport {
+            dry_run: mode == RetentionMode::DryRun,
+            ..CompactionReport::default()
+        };
+        for mut versions in histories.into_values() {
+            versions.sort_by_key(|v| v.path());
+            let mut pruned = match rule {
+                CompactionRule::KeepLast(keep) => versions.len().saturating_sub(keep.max(1)),
+                CompactionRule::OlderThan(_) => versions
+                    .iter()
+                    .take(versions.len() - 1)
+                    .take_while(|v| v.written_at().is_some_and(|at| at < cutoff))
+                    .count(),
+            };
+            while versions.get(pruned).is_some_and(is_tombstone) {
+                pruned += 1;
+            }
+            for version in versions.into_iter().take(pruned) {
+                if is_tombstone(&version) {
+                    report.tombstones += 1;
+                }
+                report.pruned.push(version.path());
+            }
+        }
+
+        if mode == RetentionMode::Enforce {
+            for path in &report.pruned {
+                self.storer.delete(path).await?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn hide_versions(page: DataCollection) -> DataCollection {
+        DataCollection {
+            data: page
+                .data
+                .into_iter()
+                .filter(|d| versioned_path(&d.path()).is_none())
+                .collect(),
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for VersionedDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        self.storer.get(path).await
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(path, skip, page_size, sort)
+            .await?;
+        Ok(Self::hide_versions(page))
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(path, cursor, page_size)
+            .await?;
+        Ok(Self::hide_versions(page))
+    }
+
+    async fn create(&self, mut data: Data) -> Result<bool, DataStorerError> {
+        let written_at = now_millis();
+        data.set_written_at(Some(written_at));
+        let mut version = data.clone();
+        version.set_path(&version_path(&data.path(), written_at));
+        self.storer.create(version).await?;
+        self.storer.create(data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        let written_at = now_millis();
+        let mut tombstone = Data::with_values(
+            &version_path(path, written_at),
+            DataValueCollection::default(),
+        );
+        tombstone.set_written_at(Some(written_at));
+        self.storer.create(tombstone).await?;
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        let mut report = self.storer.erase(prefix).await?;
+        report.erased.retain(|path| versioned_path(path).is_none());
+        Ok(report)
+    }
+
+    async fn aggregate(
+        &self,
+        path: &str,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, DataStorerError> {
+        self.storer.aggregate(path, aggregation).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        VersionedDataStorer {
+            storer: self.storer.with_context(context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_tombstone, now_millis, version_path, version_time, versioned_path};
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        CompactionRule, Data, DataStorer, DataValue, DataValueCollection, RetentionMode,
+        VersionedDataStorer,
+    };
+    use std::time::Duration;
+
+    fn version(path: &str, written_at: u64, value: Option<u64>) -> Data {
+        let mut data = match value {
+            Some(value) => Data::new(&version_path(path, written_at), DataValue::from(value)),
+            None => Data::with_values(
+                &version_path(path, written_at),
+                DataValueCollection::default(),
+            ),
+        };
+        data.set_written_at(Some(written_at));
+        data
+    }
+
+    #[test]
+    fn test_versioned_path() {
+        let path = version_path(".a.b.", 12);
+        assert_eq!(".a.b._version.00000000000000000012.", path);
+        assert_eq!(Some(".a.b."), versioned_path(&path));
+        assert_eq!(None, versioned_path(".a.b."));
+        assert_eq!(
+            Some(12),
+            version_time(&Data::new(&path, DataValue::from(1u64)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_kept_as_versions() {
+        let (inner, _) = memory_storer();
+        let storer = VersionedDataStorer::new(inner);
+
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        storer
+            .create(Data::new(".a.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+
+        let current = storer.get(".a.").await.unwrap();
+        assert_eq!("2", current.to_string());
+        assert!(current.written_at().is_some());
+        let versions = storer.versions(".a.").await.unwrap();
+        let values: Vec<_> = versions.iter().map(|v| v.to_string()).collect();
+        assert_eq!(vec!["1", "2"], values);
+    }
+
+    #[tokio::test]
+    async fn test_delete_records_tombstone() {
+        let (inner, _) = memory_storer();
+        let storer = VersionedDataStorer::new(inner);
+
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        assert!(storer.delete(".a.").await.unwrap());
+
+        assert!(storer.get(".a.").await.is_err());
+        let versions = storer.versions(".a.").await.unwrap();
+        assert_eq!(2, versions.len());
+        assert!(is_tombstone(&versions[1]));
+    }
+
+    #[tokio::test]
+    async fn test_collections_hide_versions_and_nested_history() {
+        let (inner, entries) = memory_storer();
+        let storer = VersionedDataStorer::new(inner);
+
+        storer
+            .create(Data::new(".a.", DataValue::from(1u64)))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".a.b.", DataValue::from(2u64)))
+            .await
+            .unwrap();
+
+        let page = storer.get_collection(".a.", 0, 100, None).await.unwrap();
+        assert_eq!(2, page.data.len());
+        assert_eq!(1, storer.versions(".a.").await.unwrap().len());
+
+        let report = storer.erase(".a.").await.unwrap();
+        assert_eq!(vec![".a.", ".a.b."], report.erased);
+        assert!(entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compact_keep_last() {
+        let (inner, entries) = memory_storer();
+        for (at, value) in [(1, Some(1)), (2, None), (3, Some(3)), (4, Some(4))] {
+            inner.create(version(".a.", at, value)).await.unwrap();
+        }
+        let storer = VersionedDataStorer::new(inner);
+
+        let report = storer
+            .compact(".", CompactionRule::KeepLast(3), RetentionMode::DryRun)
+            .await
+            .unwrap();
+        assert!(report.dry_run);
+        assert_eq!(
+            vec![version_path(".a.", 1), version_path(".a.", 2)],
+            report.pruned
+        );
+        assert_eq!(1, report.tombstones);
+        assert_eq!(4, entries.lock().unwrap().len());
+
+        storer
+            .compact(".", CompactionRule::KeepLast(3), RetentionMode::Enforce)
+            .await
+            .unwrap();
+        let values: Vec<_> = storer
+            .versions(".a.")
+            .await
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(vec!["3", "4"], values);
+    }
+
+    #[tokio::test]
+    async fn test_compact_older_than_keeps_latest_version() {
+        let (inner, _) = memory_storer();
+        let old = now_millis() - 60_000;
+        for (at, value) in [(old, Some(1)), (old + 1, Some(2))] {
+            inner.create(version(".live.", at, value)).await.unwrap();
+        }
+        for (at, value) in [(old, Some(1)), (old + 1, None)] {
+            inner.create(version(".gone.", at, value)).await.unwrap();
+        }
+        let storer = VersionedDataStorer::new(inner);
+
+        let report = storer
+            .compact(
+                ".",
+                CompactionRule::OlderThan(Duration::from_secs(1)),
+                RetentionMode::Enforce,
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, report.pruned.len());
+        assert_eq!(1, report.tombstones);
+        assert_eq!(1, storer.versions(".live.").await.unwrap().len());
+        assert!(storer.versions(".gone.").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_as_of() {
+        let (inner, _) = memory_storer();
+        for (at, value) in [(10, Some(1)), (20, Some(2)), (30, None), (40, Some(4))] {
+            inner.create(version(".a.", at, value)).await.unwrap();
+        }
+        let storer = VersionedDataStorer::new(inner);
+
+        assert!(storer.get_as_of(".a.", 9).await.is_err());
+        let data = storer.get_as_of(".a.", 10).await.unwrap();
+        assert_eq!(".a.", data.path());
+        assert_eq!("1", data.to_string());
+        assert_eq!("2", storer.get_as_of("a", 29).await.unwrap().to_string());
+        assert!(storer.get_as_of(".a.", 35).await.is_err());
+        assert_eq!("4", storer.get_as_of(".a.", 50).await.unwrap().to_string());
+    }
+}
@@ -0,0 +1,355 @@
+use crate::storage::query::Cursor;
+use crate::{
+    Data, DataCollection, DataPath, DataSignature, DataType, DataValue, DataValueCollection,
+    EncryptedDataValue, KeyName, UnencryptedDataValue,
+};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Returned when deserialized data is well-formed but inconsistent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrictDataError {
+    /// The data holds no values
+    NoValues { path: String },
+    /// An encrypted value has no ciphertext
+    EmptyCiphertext { path: String, index: usize },
+    /// An encrypted value names an invalid key
+    InvalidKeyName { path: String, keyname: String },
+    /// The data's integrity checksum does not match its path and values
+    IntegrityMismatch { path: String },
+}
+
+impl Error for StrictDataError {}
+
+impl Display for StrictDataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            StrictDataError::NoValues { ref path } => write!(f, "Data at {} has no values", path),
+            StrictDataError::EmptyCiphertext { ref path, index } => write!(
+                f,
+                "Encrypted value {} of the data at {} has no ciphertext",
+                index, path
+            ),
+            StrictDataError::InvalidKeyName {
+                ref path,
+                ref keyname,
+            } => write!(
+                f,
+                "An encrypted value of the data at {} names invalid key {:?}",
+                path, keyname
+            ),
+            StrictDataError::IntegrityMismatch { ref path } => {
+                write!(f, "Data at {} does not match its integrity checksum", path)
+            }
+        }
+    }
+}
+
+/// Checks that data is internally consistent: it holds at least one value,
+/// its encrypted values carry ciphertext and valid key names, and its
+/// integrity checksum, if any, matches its path and values
+pub fn validate(data: &Data) -> Result<(), StrictDataError> {
+    let path = data.path();
+    if data.value().0.is_empty() {
+        return Err(StrictDataError::NoValues { path });
+    }
+    for (index, value) in data.value().0.iter().enumerate() {
+        if let DataValue::Encrypted(ref e) = *value {
+            if e.value().is_empty() {
+                return Err(StrictDataError::EmptyCiphertext { path, index });
+            }
+            if KeyName::new(e.keyname()).is_err() {
+                return Err(StrictDataError::InvalidKeyName {
+                    path,
+                    keyname: e.keyname().to_owned(),
+                });
+            }
+        }
+    }
+    if data.verify_integrity() == Some(false) {
+        return Err(StrictDataError::IntegrityMismatch { path });
+    }
+    Ok(())
+}
+
+/// Deserializes data from untrusted sources strictly, for use where a
+/// corrupted or hostile payload should fail loudly rather than be repaired.
+/// Unlike plain `Data` deserialization, `Strict<Data>` and
+/// `Strict<DataCollection>`:
+/// - reject unknown fields, at every level
+/// - reject paths not already in the `.my.json.path.` form, see
+///   `DataPath::parse_strict`
+/// - only accept values in the current tagged encoding, a list of
+///   `{"Unencrypted": {"U64": 1}}` or `{"Encrypted": {...}}`, rather than the
+///   encodings of previous wire versions or plain JSON scalars whose type
+///   would have to be inferred
+/// - check the decoded data with `validate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strict<T>(pub T);
+
+impl<T> Strict<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictEncryptedDataValue {
+    value: Vec<u8>,
+    datatype: DataType,
+    keyname: String,
+    #[serde(default)]
+    nonce: Option<Vec<u8>>,
+    #[serde(default)]
+    aad: Option<Vec<u8>>,
+    #[serde(default)]
+    algorithm: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum StrictDataValue {
+    Encrypted(StrictEncryptedDataValue),
+    Unencrypted(UnencryptedDataValue),
+}
+
+impl From<StrictDataValue> for DataValue {
+    fn from(value: StrictDataValue) -> Self {
+        match value {
+            StrictDataValue::Encrypted(e) => {
+                let mut encrypted = EncryptedDataValue::new(e.value, e.datatype, &e.keyname);
+                if let Some(nonce) = e.nonce {
+                    encrypted = encrypted.with_nonce(nonce);
+                }
+                if let Some(aad) = e.aad {
+                    encrypted = encrypted.with_aad(aad);
+                }
+                if let Some(algorithm) = e.algorithm {
+                    encrypted = encrypted.with_algorithm(&algorithm);
+                }
+                DataValue::Encrypted(encrypted)
+            }
+            StrictDataValue::Unencrypted(u) => DataValue::Unencrypted(u),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictDataSignature {
+    algorithm: String,
+    keyid: String,
+    signature: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictData {
+    #[serde(deserialize_with = "DataPath::deserialize_strict")]
+    path: DataPath,
+    value: Vec<StrictDataValue>,
+    #[serde(default)]
+    integrity: Option<String>,
+    #[serde(default)]
+    signature: Option<StrictDataSignature>,
+    #[serde(default)]
+    written_at: Option<u64>,
+    #[serde(default)]
+    encryptedby: Vec<KeyName>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    expires_at: Option<u64>,
+    #[serde(default)]
+    recipients: BTreeMap<KeyName, Vec<u8>>,
+    #[serde(default)]
+    links: Vec<StrictDataLink>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictDataLink {
+    link_type: String,
+    #[serde(deserialize_with = "DataPath::deserialize_strict")]
+    target: DataPath,
+}
+
+impl From<StrictData> for Data {
+    fn from(strict: StrictData) -> Self {
+        let values = strict.value.into_iter().map(DataValue::from).collect();
+        let mut data = Data::with_values(&strict.path.to_string(), DataValueCollection(values));
+        data.set_integrity(strict.integrity);
+        data.set_signature(strict.signature.map(|s| DataSignature {
+            algorithm: s.algorithm,
+            keyid: s.keyid,
+            signature: s.signature,
+        }));
+        data.set_written_at(strict.written_at);
+        for keyname in strict.encryptedby {
+            data.add_encrypted_by(keyname);
+        }
+        for (key, value) in strict.labels {
+            data.set_label(&key, &value);
+        }
+        data.set_expires_at(strict.expires_at);
+        for (keyname, wrapped_key) in strict.recipients {
+            data.add_recipient(keyname, wrapped_key);
+        }
+        for link in strict.links {
+            data.add_link(&link.link_type, link.target.as_str());
+        }
+        data
+    }
+}
+
+impl<'de> Deserialize<'de> for Strict<Data> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Data::from(StrictData::deserialize(deserializer)?);
+        validate(&data).map_err(D::Error::custom)?;
+        Ok(Strict(data))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictDataCollection {
+    data: Vec<Strict<Data>>,
+    #[serde(default)]
+    next_cursor: Option<Cursor>,
+}
+
+impl<'de> Deserialize<'de> for Strict<DataCollection> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let strict = StrictDataCollection::deserialize(deserializer)?;
+        Ok(Strict(DataCollection {
+            data: strict.data.into_iter().map(Strict::into_inner).collect(),
+            next_cursor: strict.next_cursor,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Strict, StrictDataError};
+    use crate::{
+        Data, DataCollection, DataType, DataValue, DataValueCollection, EncryptedDataValue,
+        KeyName,
+    };
+    use serde_json::json;
+
+    fn strict_data(value: serde_json::Value) -> Result<Data, serde_json::Error> {
+        serde_json::from_value::<Strict<Data>>(value).map(Strict::into_inner)
+    }
+
+    #[test]
+    fn test_accepts_current_encoding() {
+        let mut data = Data::with_values(
+            ".a.",
+            DataValueCollection::from(vec![
+                DataValue::from(1u64),
+                DataValue::Encrypted(
+                    EncryptedDataValue::new(vec![2], DataType::U64, "k")
+                        .with_nonce(vec![3; 12])
+                        .with_algorithm("AES-256-GCM"),
+                ),
+            ]),
+        );
+        data.set_label("team", "billing");
+        data.set_written_at(Some(5));
+        data.add_recipient(KeyName::new("k").unwrap(), vec![4]);
+        data.add_link("owner", ".users.1.");
+        data.seal_integrity();
+        let collection = DataCollection::new(vec![data.clone()]);
+        assert_eq!(
+            data,
+            strict_data(serde_json::to_value(&data).unwrap()).unwrap()
+        );
+        assert_eq!(
+            collection,
+            serde_json::from_value::<Strict<DataCollection>>(
+                serde_json::to_value(&collection).unwrap()
+            )
+            .unwrap()
+            .into_inner()
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        assert!(strict_data(json!({
+            "path": ".a.",
+            "value": [{"Unencrypted": {"U64": 1}}],
+            "extra": true,
+        }))
+        .is_err());
+        assert!(strict_data(json!({
+            "path": ".a.",
+            "value": [{"Encrypted": {"value": [1], "datatype": "U64", "keyname": "k", "iv": []}}],
+        }))
+        .is_err());
+        assert!(serde_json::from_value::<Strict<DataCollection>>(json!({
+            "data": [],
+            "total": 0,
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_rejects_tolerated_encodings() {
+        for value in [
+            json!({"path": "a", "value": [{"Unencrypted": {"U64": 1}}]}),
+            json!({"path": ".a.", "value": {"Unencrypted": {"U64": 1}}}),
+            json!({"path": ".a.", "value": [1]}),
+            json!({"path": ".a.", "value": [{"U64": 1}]}),
+            json!({"path": ".a.", "values": [{"Unencrypted": {"U64": 1}}]}),
+        ] {
+            assert!(serde_json::from_value::<Data>(value.clone()).is_ok());
+            assert!(strict_data(value).is_err());
+        }
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_data() {
+        assert!(strict_data(json!({"path": ".a.", "value": []})).is_err());
+        assert!(strict_data(json!({
+            "path": ".a.",
+            "value": [{"Encrypted": {"value": [], "datatype": "U64", "keyname": "k"}}],
+        }))
+        .is_err());
+        assert!(strict_data(json!({
+            "path": ".a.",
+            "value": [{"Unencrypted": {"U64": 1}}],
+            "integrity": "00",
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let encrypted =
+            DataValue::Encrypted(EncryptedDataValue::new(vec![1], DataType::U64, "k 1"));
+        assert_eq!(
+            Err(StrictDataError::InvalidKeyName {
+                path: ".a.".to_owned(),
+                keyname: "k 1".to_owned()
+            }),
+            validate(&Data::new(".a.", encrypted))
+        );
+        assert_eq!(Ok(()), validate(&Data::new(".a.", DataValue::from(1u64))));
+    }
+
+    #[test]
+    fn test_to_string_empty_ciphertext() {
+        let s = StrictDataError::EmptyCiphertext {
+            path: ".a.".to_owned(),
+            index: 0,
+        }
+        .to_string();
+        assert_eq!(s, "Encrypted value 0 of the data at .a. has no ciphertext");
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{Data, DataStorer, DataStorerError, DataType, DataValue, EncryptedDataValue, KeyName};
+use std::convert::TryFrom;
+
+/// The fields generated for each user, and the type of their values. Users
+/// with more fields than listed here repeat them with a numeric suffix, e.g.
+/// `name1`.
+const FIELDS: [(&str, DataType); 8] = [
+    ("name", DataType::String),
+    ("email", DataType::String),
+    ("age", DataType::U64),
+    ("balance", DataType::F64),
+    ("verified", DataType::Bool),
+    ("timezone_offset", DataType::I64),
+    ("avatar", DataType::Bytes),
+    ("address", DataType::String),
+];
+
+const FIRST_NAMES: [&str; 8] = [
+    "Ada", "Grace", "Alan", "Edsger", "Barbara", "Ken", "Margaret", "Dennis",
+];
+
+const LAST_NAMES: [&str; 8] = [
+    "Lovelace", "Hopper", "Turing", "Dijkstra", "Liskov", "Thompson", "Hamilton", "Ritchie",
+];
+
+const STREETS: [&str; 4] = ["Main St", "Elm St", "Oak Ave", "Pine Rd"];
+
+/// A small deterministic generator, so that fixtures are reproducible from
+/// their seed without depending on a random number crate
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// Generates a realistic tree of user records for tests, one `Data` per
+/// field of each user, at paths such as `.users.3.email.`. The same seed
+/// always generates the same tree.
+///
+/// ```
+/// # use redact_data::test_util::Fixture;
+/// let data = Fixture::new().users(10).fields(4).encrypted(0.5).seed(7).build();
+/// assert_eq!(40, data.len());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    root: String,
+    users: usize,
+    fields: usize,
+    encrypted: f64,
+    keyname: KeyName,
+    seed: u64,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Fixture {
+            root: ".users.".to_owned(),
+            users: 10,
+            fields: 4,
+            encrypted: 0.0,
+            keyname: KeyName::new("fixture-key").unwrap(),
+            seed: 0,
+        }
+    }
+}
+
+impl Fixture {
+    /// Starts a fixture of 10 unencrypted users with 4 fields each under
+    /// `.users.`, generated with seed 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path the users are generated under
+    pub fn root(mut self, root: &str) -> Self {
+        self.root = crate::DataPath::new(root).to_string();
+        self
+    }
+
+    /// Sets how many users are generated
+    pub fn users(mut self, users: usize) -> Self {
+        self.users = users;
+        self
+    }
+
+    /// Sets how many fields each user has
+    pub fn fields(mut self, fields: usize) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Sets the fraction of values, from 0 to 1, generated as encrypted
+    /// values, whose ciphertext is random bytes
+    pub fn encrypted(mut self, fraction: f64) -> Self {
+        self.encrypted = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the name of the key encrypted values claim to be encrypted by
+    pub fn keyname(mut self, keyname: KeyName) -> Self {
+        self.keyname = keyname;
+        self
+    }
+
+    /// Sets the seed the tree is generated from
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generates the tree, ordered by user and then by field
+    pub fn build(&self) -> Vec<Data> {
+        let mut rng = SplitMix64(self.seed);
+        let mut data = Vec::with_capacity(self.users * self.fields);
+        for user in 0..self.users {
+            let first = rng.pick(&FIRST_NAMES);
+            let last = rng.pick(&LAST_NAMES);
+            for field in 0..self.fields {
+                let (name, datatype) = &FIELDS[field % FIELDS.len()];
+                let name = match field / FIELDS.len() {
+                    0 => name.to_string(),
+                    n => format!("{}{}", name, n),
+                };
+                let path = format!("{}{}.{}.", self.root, user, name);
+                let value = match (rng.next() as f64 / u64::MAX as f64) < self.encrypted {
+                    true => DataValue::Encrypted(EncryptedDataValue::new(
+                        rng.bytes(16),
+                        datatype.clone(),
+                        self.keyname.as_str(),
+                    )),
+                    false => self.value(&mut rng, &name, datatype, first, last),
+                };
+                data.push(Data::new(&path, value));
+            }
+        }
+        data
+    }
+
+    /// Generates the tree and writes it to a storer, returning what was
+    /// written
+    pub async fn populate<S: DataStorer>(&self, storer: &S) -> Result<Vec<Data>, DataStorerError> {
+        let data = self.build();
+        storer.create_many(data.clone()).await?;
+        Ok(data)
+    }
+
+    fn value(
+        &self,
+        rng: &mut SplitMix64,
+        name: &str,
+        datatype: &DataType,
+        first: &str,
+        last: &str,
+    ) -> DataValue {
+        match *datatype {
+            DataType::String if name.starts_with("name") => format!("{} {}", first, last).into(),
+            DataType::String if name.starts_with("email") => format!(
+                "{}.{}{}@example.com",
+                first.to_lowercase(),
+                last.to_lowercase(),
+                rng.below(100)
+            )
+            .into(),
+            DataType::String => format!("{} {}", 1 + rng.below(9999), rng.pick(&STREETS)).into(),
+            DataType::U64 => (18 + rng.below(72)).into(),
+            DataType::I64 => (i64::try_from(rng.below(27)).unwrap() - 12).into(),
+            DataType::F64 => (rng.below(10_000_000) as f64 / 100.0).into(),
+            DataType::Bool => (rng.below(2) == 1).into(),
+            DataType::Bytes => rng.bytes(32).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fixture;
+    use crate::storage::tests::memory_storer;
+    use crate::DataValue;
+
+    #[test]
+    fn test_generates_users_and_fields() {
+        let data = Fixture::new().users(3).fields(10).build();
+        assert_eq!(30, data.len());
+        assert_eq!(".users.0.name.", data[0].path());
+        assert_eq!(".users.2.email1.", data[29].path());
+        assert!(data
+            .iter()
+            .all(|d| matches!(d.value().0[0], DataValue::Unencrypted(_))));
+    }
+
+    #[test]
+    fn test_same_seed_generates_same_tree() {
+        let fixture = Fixture::new().root(".people").encrypted(0.5).seed(42);
+        assert_eq!(fixture.build(), fixture.build());
+        assert_ne!(fixture.build(), fixture.clone().seed(43).build());
+        assert!(fixture.build()[0].path().starts_with(".people.0."));
+    }
+
+    #[test]
+    fn test_encrypts_fraction_of_values() {
+        let data = Fixture::new().users(50).encrypted(1.0).build();
+        assert!(data
+            .iter()
+            .all(|d| matches!(d.value().0[0], DataValue::Encrypted(_))));
+        let data = Fixture::new().users(50).encrypted(0.5).build();
+        let encrypted = data
+            .iter()
+            .filter(|d| matches!(d.value().0[0], DataValue::Encrypted(_)))
+            .count();
+        assert!(encrypted > 50 && encrypted < 150);
+    }
+
+    #[tokio::test]
+    async fn test_populates_storer() {
+        let (storer, entries) = memory_storer();
+        let data = Fixture::new().users(2).populate(&storer).await.unwrap();
+        assert_eq!(8, data.len());
+        assert_eq!(8, entries.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,437 @@
+use crate::{
+    Cursor, Data, DataCollection, DataPath, DataStorer, DataStorerError, DataValue,
+    DataValueCollection, EraseReport, OpContext, Sort, UnencryptedDataValue,
+};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// A transformation of individual values, used to produce analytics-safe
+/// copies of data. Encrypted values cannot be inspected and should be
+/// returned unchanged.
+pub trait ValueTransform: Send + Sync {
+    fn apply(&self, value: &DataValue) -> DataValue;
+}
+
+/// Pseudonymizes values by replacing them with the hex-encoded SHA-256 digest
+/// of a secret salt followed by the value's canonical encoding. Equal values
+/// map to equal pseudonyms, so joins and counts survive the transform.
+#[derive(Debug, Clone)]
+pub struct SaltedHash {
+    salt: Vec<u8>,
+}
+
+impl SaltedHash {
+    pub fn new(salt: &[u8]) -> Self {
+        SaltedHash {
+            salt: salt.to_vec(),
+        }
+    }
+}
+
+impl ValueTransform for SaltedHash {
+    fn apply(&self, value: &DataValue) -> DataValue {
+        if let DataValue::Encrypted(_) = value {
+            return value.clone();
+        }
+        let canonical = Data::new(".", value.clone()).canonical_bytes();
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(&canonical);
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        DataValue::from(digest)
+    }
+}
+
+/// Masks strings while preserving their format: digits become `0`, letters
+/// become `X` or `x` according to their case, and every other character is
+/// kept, so masked values still pass format validation. The last `keep_last`
+/// letters and digits are left unmasked. Non-string values are unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct Mask {
+    keep_last: usize,
+}
+
+impl Mask {
+    pub fn new(keep_last: usize) -> Self {
+        Mask { keep_last }
+    }
+}
+
+impl ValueTransform for Mask {
+    fn apply(&self, value: &DataValue) -> DataValue {
+        let s = match value {
+            DataValue::Unencrypted(UnencryptedDataValue::String(s)) => s,
+            _ => return value.clone(),
+        };
+        let maskable = s.chars().filter(|c| c.is_alphanumeric()).count();
+        let mut masked = 0;
+        let out: String = s
+            .chars()
+            .map(|c| {
+                if !c.is_alphanumeric() {
+                    return c;
+                }
+                masked += 1;
+                if masked + self.keep_last > maskable {
+                    c
+                } else if c.is_numeric() {
+                    '0'
+                } else if c.is_lowercase() {
+                    'x'
+                } else {
+                    'X'
+                }
+            })
+            .collect();
+        DataValue::from(out)
+    }
+}
+
+/// Generalizes numeric values into ranges, replacing each with a string label
+/// `lo..hi` of the bucket it falls in. Non-numeric values are unchanged.
+#[derive(Debug, Clone)]
+pub struct Bucketing {
+    /// Ascending lower bounds of every bucket but the first
+    bounds: Vec<f64>,
+    width: Option<f64>,
+}
+
+impl Bucketing {
+    /// Buckets values into fixed-width ranges starting at multiples of `width`
+    pub fn fixed(width: f64) -> Self {
+        Bucketing {
+            bounds: Vec::new(),
+            width: Some(width),
+        }
+    }
+
+    /// Fits buckets to a sample of values such that every bucket holds at
+    /// least `k` of them, the basis of k-anonymity for numeric attributes.
+    /// Equal values always share a bucket, so buckets may hold more than `k`.
+    pub fn k_anonymous(values: &[f64], k: usize) -> Self {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let k = k.max(1);
+
+        // Cut before position i only when both sides keep at least k values
+        let mut bounds = Vec::new();
+        let mut i = k;
+        while i + k <= sorted.len() {
+            if sorted[i] != sorted[i - 1] {
+                bounds.push(sorted[i]);
+                i += k;
+            } else {
+                i += 1;
+            }
+        }
+        Bucketing {
+            bounds,
+            width: None,
+        }
+    }
+
+    fn label(&self, n: f64) -> String {
+        if let Some(width) = self.width {
+            let lo = (n / width).floor() * width;
+            return format!("{}..{}", lo, lo + width);
+        }
+        let index = self.bounds.iter().take_while(|b| **b <= n).count();
+        match (
+            index.checked_sub(1).map(|i| self.bounds[i]),
+            self.bounds.get(index),
+        ) {
+            (None, None) => "..".to_owned(),
+            (None, Some(hi)) => format!("..{}", hi),
+            (Some(lo), None) => format!("{}..", lo),
+            (Some(lo), Some(hi)) => format!("{}..{}", lo, hi),
+        }
+    }
+}
+
+impl ValueTransform for Bucketing {
+    fn apply(&self, value: &DataValue) -> DataValue {
+        match value.as_f64() {
+            Some(n) => DataValue::from(self.label(n)),
+            None => value.clone(),
+        }
+    }
+}
+
+/// A set of transforms, each applied to the values of entries whose path
+/// matches its pattern; see `DataPath::matches`. Only the first matching
+/// transform is applied to an entry.
+#[derive(Clone, Default)]
+pub struct Transforms {
+    transforms: Vec<(String, Arc<dyn ValueTransform>)>,
+}
+
+impl Transforms {
+    /// Builds an empty set of transforms
+    pub fn new() -> Self {
+        Transforms::default()
+    }
+
+    /// Adds a transform for the entries matching the path pattern
+    pub fn add<T: ValueTransform + 'static>(mut self, pattern: &str, transform: T) -> Self {
+        self.transforms
+            .push((DataPath::from(pattern).to_string(), Arc::new(transform)));
+        self
+    }
+
+    /// Returns a transformed copy of the data; integrity checksums and
+    /// signatures are dropped as they no longer match the values
+    pub fn apply(&self, data: &Data) -> Data {
+        let path = DataPath::from(data.path().as_str());
+        match self
+            .transforms
+            .iter()
+            .find(|(pattern, _)| path.matches(pattern))
+        {
+            Some((_, transform)) => Data::with_values(
+                &data.path(),
+                DataValueCollection(data.value().0.iter().map(|v| transform.apply(v)).collect()),
+            ),
+            None => data.clone(),
+        }
+    }
+
+    /// Returns transformed copies of every entry, e.g. for an export
+    pub fn apply_all(&self, data: &[Data]) -> Vec<Data> {
+        data.iter().map(|d| self.apply(d)).collect()
+    }
+}
+
+/// Wraps a storer to apply transforms to every entry read through it, for
+/// handing analytics consumers a view which never exposes raw values.
+/// Aggregations are computed client-side over the transformed values, so a
+/// transformed path only counts towards them if its transform keeps it
+/// numeric. Writes, counters and compare-and-swap pass through to the wrapped
+/// storer unchanged, as do the values they compare and update.
+#[derive(Clone)]
+pub struct TransformingDataStorer<S: DataStorer> {
+    storer: S,
+    transforms: Transforms,
+}
+
+impl<S: DataStorer> TransformingDataStorer<S> {
+    /// Instantiates a transforming view of an existing storer
+    pub fn new(storer: S, transforms: Transforms) -> Self {
+        TransformingDataStorer { storer, transforms }
+    }
+
+    fn transform_page(&self, page: DataCollection) -> DataCollection {
+        DataCollection {
+            data: self.transforms.apply_all(&page.data),
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: DataStorer> DataStorer for TransformingDataStorer<S> {
+    async fn get(&self, path: &str) -> Result<Data, DataStorerError> {
+        let data = self.storer.get(path).await?;
+        Ok(self.transforms.apply(&data))
+    }
+
+    async fn get_collection(
+        &self,
+        path: &str,
+        skip: i64,
+        page_size: i64,
+        sort: Option<Sort>,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection(path, skip, page_size, sort)
+            .await?;
+        Ok(self.transform_page(page))
+    }
+
+    async fn get_collection_page(
+        &self,
+        path: &str,
+        cursor: Option<Cursor>,
+        page_size: i64,
+    ) -> Result<DataCollection, DataStorerError> {
+        let page = self
+            .storer
+            .get_collection_page(path, cursor, page_size)
+            .await?;
+        Ok(self.transform_page(page))
+    }
+
+    async fn create(&self, data: Data) -> Result<bool, DataStorerError> {
+        self.storer.create(data).await
+    }
+
+    async fn get_or_create(&self, path: &str, default: Data) -> Result<Data, DataStorerError> {
+        let data = self.storer.get_or_create(path, default).await?;
+        Ok(self.transforms.apply(&data))
+    }
+
+    async fn increment(&self, path: &str, delta: i64) -> Result<i64, DataStorerError> {
+        self.storer.increment(path, delta).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        path: &str,
+        expected: DataValue,
+        new: DataValue,
+    ) -> Result<bool, DataStorerError> {
+        self.storer.compare_and_swap(path, expected, new).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool, DataStorerError> {
+        self.storer.delete(path).await
+    }
+
+    async fn erase(&self, prefix: &str) -> Result<EraseReport, DataStorerError> {
+        self.storer.erase(prefix).await
+    }
+
+    fn with_context(&self, context: OpContext) -> Self {
+        let mut storer = self.clone();
+        storer.storer = self.storer.with_context(context);
+        storer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::memory_storer;
+    use crate::{
+        Aggregation, Bucketing, Data, DataStorer, DataValue, Mask, SaltedHash,
+        TransformingDataStorer, Transforms, ValueTransform,
+    };
+
+    #[test]
+    fn test_salted_hash_is_deterministic_per_salt() {
+        let a = SaltedHash::new(b"salt-a");
+        let b = SaltedHash::new(b"salt-b");
+        let value = DataValue::from("alice@example.com");
+        assert_eq!(a.apply(&value), a.apply(&value));
+        assert_ne!(a.apply(&value), b.apply(&value));
+        assert_eq!(64, a.apply(&value).to_string().len());
+    }
+
+    #[test]
+    fn test_mask_preserves_format() {
+        let mask = Mask::new(4);
+        assert_eq!(
+            "0000-0000-0000-1234",
+            mask.apply(&DataValue::from("4111-1111-1111-1234"))
+                .to_string()
+        );
+        assert_eq!(
+            "Xxxxx",
+            Mask::new(0).apply(&DataValue::from("Alice")).to_string()
+        );
+        assert_eq!("5", mask.apply(&DataValue::from(5u64)).to_string());
+    }
+
+    #[test]
+    fn test_fixed_bucketing() {
+        let buckets = Bucketing::fixed(10.0);
+        assert_eq!("30..40", buckets.apply(&DataValue::from(37u64)).to_string());
+        assert_eq!("-10..0", buckets.apply(&DataValue::from(-3i64)).to_string());
+    }
+
+    #[test]
+    fn test_k_anonymous_buckets_hold_at_least_k_values() {
+        let ages = [21.0, 22.0, 22.0, 25.0, 31.0, 33.0, 40.0, 41.0, 58.0];
+        let buckets = Bucketing::k_anonymous(&ages, 3);
+        let labels: Vec<_> = ages
+            .iter()
+            .map(|a| buckets.apply(&DataValue::from(*a)).to_string())
+            .collect();
+        for label in &labels {
+            assert!(
+                labels.iter().filter(|l| *l == label).count() >= 3,
+                "{:?}",
+                labels
+            );
+        }
+        assert_eq!("..25", labels[0]);
+    }
+
+    #[tokio::test]
+    async fn test_transforming_storer_applies_matching_transforms() {
+        let (inner, _) = memory_storer();
+        let storer = TransformingDataStorer::new(
+            inner,
+            Transforms::new().add(".users.*.card.", Mask::new(2)),
+        );
+
+        storer
+            .create(Data::new(".users.1.card.", DataValue::from("1234")))
+            .await
+            .unwrap();
+        storer
+            .create(Data::new(".users.1.name.", DataValue::from("Al")))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            "0034",
+            storer.get(".users.1.card.").await.unwrap().to_string()
+        );
+        assert_eq!(
+            "Al",
+            storer.get(".users.1.name.").await.unwrap().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_aggregations_see_transformed_values() {
+        let (inner, _) = memory_storer();
+        let storer = TransformingDataStorer::new(
+            inner,
+            Transforms::new().add(".salaries.*.", Bucketing::fixed(10_000.0)),
+        );
+        for (path, value) in [(".salaries.a.", 41_234u64), (".salaries.b.", 97_000)] {
+            storer
+                .create(Data::new(path, DataValue::from(value)))
+                .await
+                .unwrap();
+        }
+
+        for (aggregation, expected) in [
+            (Aggregation::Sum, Some(0.0)),
+            (Aggregation::Min, None),
+            (Aggregation::Max, None),
+        ] {
+            assert_eq!(
+                expected,
+                storer.aggregate(".salaries.", aggregation).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_modify_writes_use_raw_values() {
+        let (inner, _) = memory_storer();
+        let storer = TransformingDataStorer::new(
+            inner,
+            Transforms::new().add(".counter.", Bucketing::fixed(10.0)),
+        );
+
+        assert_eq!(5, storer.increment(".counter.", 5).await.unwrap());
+        assert_eq!(12, storer.increment(".counter.", 7).await.unwrap());
+        assert!(storer
+            .compare_and_swap(".counter.", DataValue::from(12i64), DataValue::from(20i64))
+            .await
+            .unwrap());
+        let data = storer
+            .get_or_create(".counter.", Data::new(".counter.", DataValue::from(0i64)))
+            .await
+            .unwrap();
+        assert_eq!("20..30", data.to_string());
+    }
+}